@@ -0,0 +1,154 @@
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use raug_fft::prelude::*;
+
+const FFT_LENGTHS: [usize; 8] = [64, 128, 256, 512, 1024, 2048, 4096, 8192];
+const SAMPLE_RATE: f32 = 48_000.0;
+
+fn signal(length: usize) -> Vec<f32> {
+    (0..length)
+        .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / SAMPLE_RATE).sin())
+        .collect()
+}
+
+fn transforms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transforms");
+    for fft_length in FFT_LENGTHS {
+        let mut transform = RealFftTransform::new(fft_length);
+        let input = signal(fft_length);
+        let mut time_domain = input.clone();
+        let mut spectrum = vec![Complex32::ZERO; fft_length / 2 + 1];
+
+        group.throughput(Throughput::Elements(fft_length as u64));
+        group.bench_with_input(
+            BenchmarkId::new("forward", fft_length),
+            &fft_length,
+            |b, _| {
+                b.iter(|| {
+                    time_domain.copy_from_slice(&input);
+                    transform
+                        .forward(black_box(&mut time_domain), &mut spectrum)
+                        .unwrap();
+                })
+            },
+        );
+
+        transform.forward(&mut time_domain, &mut spectrum).unwrap();
+        let reference = spectrum.clone();
+        group.bench_with_input(
+            BenchmarkId::new("inverse", fft_length),
+            &fft_length,
+            |b, _| {
+                b.iter(|| {
+                    spectrum.copy_from_slice(&reference);
+                    transform
+                        .inverse(black_box(&mut spectrum), &mut time_domain)
+                        .unwrap();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn windowing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("windowing");
+    for fft_length in FFT_LENGTHS {
+        let hop_length = fft_length / 4;
+        let window = stft_window(WindowFunction::Hann, fft_length, hop_length);
+        let mut frames = FrameInput::new(fft_length);
+        frames.push(&signal(fft_length));
+        let mut frame = vec![0.0; fft_length];
+
+        group.throughput(Throughput::Elements(fft_length as u64));
+        group.bench_with_input(
+            BenchmarkId::new("read_windowed", fft_length),
+            &fft_length,
+            |b, _| b.iter(|| frames.read_windowed(black_box(&window), &mut frame)),
+        );
+
+        let mut overlap_add = OverlapAdd::new(fft_length);
+        let mut out = vec![0.0; hop_length];
+        group.bench_with_input(
+            BenchmarkId::new("overlap_add", fft_length),
+            &fft_length,
+            |b, _| {
+                b.iter(|| {
+                    overlap_add.add_frame(black_box(&frame), &window, hop_length);
+                    overlap_add.read(&mut out);
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Runs one block per iteration through `graph`, which must have one audio input and output.
+fn bench_graph(
+    group: &mut criterion::BenchmarkGroup<'_, criterion::measurement::WallTime>,
+    id: BenchmarkId,
+    mut graph: Box<dyn DynFftGraph>,
+    block_size: usize,
+) {
+    graph.allocate(SAMPLE_RATE, block_size);
+    let input = signal(block_size);
+    let mut output = vec![0.0; block_size];
+    group.throughput(Throughput::Elements(block_size as u64));
+    group.bench_function(id, |b| {
+        b.iter(|| {
+            graph
+                .process_block(&[black_box(&input)], &mut [&mut output])
+                .unwrap();
+        })
+    });
+}
+
+fn identity_graph(c: &mut Criterion) {
+    let mut group = c.benchmark_group("identity_graph");
+    for fft_length in FFT_LENGTHS {
+        let hop_length = fft_length / 4;
+        let mut graph = dyn_graph(fft_length, hop_length, WindowFunction::Hann).unwrap();
+        let input = graph.add_audio_input();
+        let output = graph.add_audio_output();
+        graph.connect(input, 0, output, 0).unwrap();
+
+        bench_graph(
+            &mut group,
+            BenchmarkId::from_parameter(fft_length),
+            graph,
+            hop_length,
+        );
+    }
+    group.finish();
+}
+
+fn effect_chain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("effect_chain");
+    let fft_length = 2048;
+    for overlap in [2, 4, 8] {
+        let hop_length = fft_length / overlap;
+        let mut graph = dyn_graph(fft_length, hop_length, WindowFunction::Hann).unwrap();
+        let mut node = graph.add_audio_input();
+        for name in ["linear_phase_eq", "spectral_tilt", "exciter", "freq_shift"] {
+            let next = graph
+                .add_builtin(name, &ProcessorParams::new().with("shift_hz", 5.0))
+                .unwrap();
+            graph.connect(node, 0, next, 0).unwrap();
+            node = next;
+        }
+        let output = graph.add_audio_output();
+        graph.connect(node, 0, output, 0).unwrap();
+
+        bench_graph(
+            &mut group,
+            BenchmarkId::new("hop", hop_length),
+            graph,
+            hop_length,
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, transforms, windowing, identity_graph, effect_chain);
+criterion_main!(benches);
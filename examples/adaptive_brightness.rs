@@ -0,0 +1,33 @@
+use raug::prelude::*;
+use raug_ext::prelude::*;
+use raug_fft::prelude::*;
+
+fn main() {
+    env_logger::init();
+
+    let patch = PatchDescription::from_toml(include_str!("adaptive_brightness.toml"))
+        .unwrap()
+        .build::<Fft1024>(&ProcessorRegistry::default())
+        .unwrap();
+
+    let graph = Graph::new(0, 2);
+
+    let saw = BlSawOscillator::default().node(&graph, 110.0);
+
+    let fft = graph.node(patch.graph);
+
+    fft.input(0).connect(saw.output(0));
+
+    let fft = fft * 0.2;
+
+    graph.dac((&fft, &fft));
+
+    graph
+        .play(
+            CpalOut::spawn(&AudioBackend::Default, &AudioDevice::Default)
+                .record_to_wav("adaptive_brightness.wav"),
+        )
+        .unwrap()
+        .run_for(Duration::from_secs(10))
+        .unwrap();
+}
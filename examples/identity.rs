@@ -11,21 +11,13 @@ fn main() {
 
     let fft = graph.node({
         let fft_graph = FftGraphBuilder::<Fft1024>::new(256, raug_fft::WindowFunction::Hann);
-        let fft_input = fft_graph.add_audio_input();
-        let fft_output = fft_graph.add_audio_output();
-
-        fft_output
-            .input(0)
-            .unwrap()
-            .connect(fft_input.output(0).unwrap())
-            .unwrap();
+        let output = fft_graph.input().output().unwrap();
+        fft_graph.set_output_gain(&output, 0.2);
         fft_graph
     });
 
     fft.input(0).connect(sine.output(0));
 
-    let fft = fft * 0.2;
-
     graph.dac((&fft, &fft));
 
     graph
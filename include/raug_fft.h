@@ -0,0 +1,67 @@
+#ifndef RAUG_FFT_H
+#define RAUG_FFT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum RaugFftStatus {
+    RAUG_FFT_OK = 0,
+    RAUG_FFT_NULL_POINTER = -1,
+    RAUG_FFT_INVALID_ARGUMENT = -2,
+    RAUG_FFT_UNKNOWN_PROCESSOR = -3,
+    RAUG_FFT_CONNECTION_FAILED = -4,
+    RAUG_FFT_BLOCK_TOO_LARGE = -5,
+    RAUG_FFT_PROCESS_FAILED = -6,
+} RaugFftStatus;
+
+typedef struct RaugFftGraph RaugFftGraph;
+
+/* Returns NULL if the FFT length or window name is unsupported or the hop length is zero or
+ * longer than the FFT. `window` may be NULL. */
+RaugFftGraph *raug_fft_graph_new(size_t fft_length, size_t hop_length, const char *window);
+
+void raug_fft_graph_free(RaugFftGraph *graph);
+
+RaugFftStatus raug_fft_graph_add_audio_input(RaugFftGraph *graph, size_t *out_node);
+
+RaugFftStatus raug_fft_graph_add_audio_output(RaugFftGraph *graph, size_t *out_node);
+
+RaugFftStatus raug_fft_graph_add_processor(RaugFftGraph *graph,
+                                           const char *name,
+                                           const char *const *param_names,
+                                           const float *param_values,
+                                           size_t num_params,
+                                           size_t *out_node);
+
+RaugFftStatus raug_fft_graph_connect(RaugFftGraph *graph,
+                                     size_t source,
+                                     uint32_t source_output,
+                                     size_t target,
+                                     uint32_t target_input);
+
+/* Call after adding the graph's audio inputs and outputs. */
+RaugFftStatus raug_fft_graph_allocate(RaugFftGraph *graph, float sample_rate, size_t block_size);
+
+RaugFftStatus raug_fft_graph_set_transport(RaugFftGraph *graph, float bpm, double beat, bool playing);
+
+/* The same seed gives the same output from processors drawing random numbers. */
+RaugFftStatus raug_fft_graph_set_seed(RaugFftGraph *graph, uint64_t seed);
+
+size_t raug_fft_graph_latency_samples(const RaugFftGraph *graph);
+
+/* `input` and `output` interleave one channel per audio input and output, respectively. */
+RaugFftStatus raug_fft_graph_process_interleaved(RaugFftGraph *graph,
+                                                 const float *input,
+                                                 float *output,
+                                                 size_t frames);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RAUG_FFT_H */
@@ -0,0 +1,60 @@
+//! Two-sided spectra and analytic signals.
+//!
+//! A real signal's spectrum is conjugate-symmetric, so the real FFT only keeps the `N / 2 + 1`
+//! bins from DC to Nyquist. Complex-domain algorithms (frequency shifting, single-sideband
+//! modulation, envelopes from the Hilbert transform) work on all `N` bins instead, ordered from
+//! DC up to Nyquist and then on through the negative frequencies back to just below DC, as a
+//! complex FFT outputs them.
+//!
+//! The analytic signal `x + i H(x)` of a real signal `x`, where `H` is the Hilbert transform, has
+//! the same positive frequencies (doubled) and no negative ones. Its magnitude is the
+//! instantaneous amplitude of `x` and the rate of change of its phase the instantaneous frequency.
+
+use crate::core::Complex32;
+
+/// Extends the one-sided spectrum `real` of a real signal (`N / 2 + 1` bins) to all `N` bins of
+/// `out`, mirroring the conjugates of the positive frequencies into the negative ones.
+pub fn two_sided_spectrum(real: &[Complex32], out: &mut [Complex32]) {
+    let n = out.len();
+    out.fill(Complex32::ZERO);
+    for (bin, value) in real.iter().take(n / 2 + 1).enumerate() {
+        out[bin] = *value;
+        if bin > 0 && bin < n - bin {
+            out[n - bin] = value.conj();
+        }
+    }
+}
+
+/// Writes the spectrum of the analytic signal of a real signal with the one-sided spectrum `real`
+/// to all `N` bins of `out`: DC and Nyquist as they are, the other positive frequencies doubled
+/// and the negative frequencies zero.
+pub fn analytic_spectrum(real: &[Complex32], out: &mut [Complex32]) {
+    let n = out.len();
+    out.fill(Complex32::ZERO);
+    for (bin, value) in real.iter().take(n / 2 + 1).enumerate() {
+        out[bin] = if bin == 0 || 2 * bin == n {
+            *value
+        } else {
+            value * 2.0
+        };
+    }
+}
+
+/// Writes the one-sided spectrum (`N / 2 + 1` bins) of the real part of a complex signal with the
+/// two-sided spectrum `complex` to `out`, the inverse of [`two_sided_spectrum`] and
+/// [`analytic_spectrum`].
+pub fn one_sided_spectrum(complex: &[Complex32], out: &mut [Complex32]) {
+    let n = complex.len();
+    if n == 0 {
+        out.fill(Complex32::ZERO);
+        return;
+    }
+    for (bin, value) in out.iter_mut().enumerate() {
+        *value = if bin > n / 2 {
+            Complex32::ZERO
+        } else {
+            // the real part of a signal averages each frequency with the conjugate of its mirror
+            (complex[bin] + complex[(n - bin) % n].conj()) * 0.5
+        };
+    }
+}
@@ -0,0 +1,469 @@
+//! Grouping the bins of a real spectrum into frequency bands.
+//!
+//! Band-wise processors (dynamics, masking, meters) work on a handful of bands instead of every
+//! bin. A [`Bands`] layout maps each band to a contiguous range of bins and sums spectral power
+//! over them. A [`MelFilterbank`] instead weights bins with overlapping triangular filters, as
+//! used for mel spectrograms, and [`TriangularBands`] splits every bin between the two nearest
+//! band centers, as RNNoise does. [`OctaveBands`] follow the fractional-octave bands of acoustic
+//! measurements (IEC 61260).
+
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+use crate::core::{Complex32, math};
+
+/// The upper edges of the Bark critical bands (Zwicker), in Hz. The last band extends to
+/// Nyquist.
+pub const BARK_EDGES_HZ: [f32; 24] = [
+    100.0, 200.0, 300.0, 400.0, 510.0, 630.0, 770.0, 920.0, 1080.0, 1270.0, 1480.0, 1720.0, 2000.0,
+    2320.0, 2700.0, 3150.0, 3700.0, 4400.0, 5300.0, 6400.0, 7700.0, 9500.0, 12000.0, 15500.0,
+];
+
+/// The band centers used by RNNoise, in Hz. Bins above the last center belong to the last band.
+pub const RNNOISE_CENTERS_HZ: [f32; 22] = [
+    0.0, 200.0, 400.0, 600.0, 800.0, 1000.0, 1200.0, 1400.0, 1600.0, 2000.0, 2400.0, 2800.0,
+    3200.0, 4000.0, 4800.0, 5600.0, 6800.0, 8000.0, 9600.0, 12000.0, 15600.0, 20000.0,
+];
+
+/// Contiguous, non-empty ranges of bins covering a whole real spectrum from DC to Nyquist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bands {
+    // band `i` covers bins `starts[i]..starts[i + 1]`, the last entry is the number of bins
+    starts: Vec<usize>,
+}
+
+impl Bands {
+    /// Splits the spectrum at the given ascending frequencies.
+    ///
+    /// Edges are rounded to the nearest bin. Bands that would be empty, because their edges round
+    /// to the same bin or lie beyond Nyquist, are dropped, so there may be fewer than
+    /// `edges_hz.len() + 1` bands.
+    pub fn from_edges_hz(edges_hz: &[f32], sample_rate: f32, fft_length: usize) -> Self {
+        let num_bins = fft_length / 2 + 1;
+        let mut starts = Vec::with_capacity(edges_hz.len() + 2);
+        starts.push(0);
+        for &edge_hz in edges_hz {
+            let bin = (edge_hz * fft_length as f32 / sample_rate + 0.5) as usize;
+            if bin > *starts.last().unwrap() && bin < num_bins {
+                starts.push(bin);
+            }
+        }
+        starts.push(num_bins);
+        Self { starts }
+    }
+
+    /// Splits the spectrum into the Bark critical bands, see [`BARK_EDGES_HZ`].
+    pub fn bark(sample_rate: f32, fft_length: usize) -> Self {
+        Self::from_edges_hz(&BARK_EDGES_HZ, sample_rate, fft_length)
+    }
+
+    /// Returns the number of bands.
+    pub fn len(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bins covered by all bands.
+    pub fn num_bins(&self) -> usize {
+        *self.starts.last().unwrap()
+    }
+
+    /// Returns the bins of band `band`.
+    pub fn range(&self, band: usize) -> Range<usize> {
+        self.starts[band]..self.starts[band + 1]
+    }
+
+    /// Returns the bin ranges of all bands in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.starts.windows(2).map(|pair| pair[0]..pair[1])
+    }
+
+    /// Returns the band containing `bin`, or the last band if `bin` lies beyond it.
+    pub fn band_of(&self, bin: usize) -> usize {
+        self.starts[1..]
+            .partition_point(|&end| end <= bin)
+            .min(self.len() - 1)
+    }
+
+    /// Writes the power of every band of `spectrum` to `out`.
+    ///
+    /// Bins other than DC and Nyquist count twice, as they stand for both halves of the full
+    /// spectrum, so the sum over all bands is the energy of the frame times the FFT length.
+    pub fn powers(&self, spectrum: &[Complex32], out: &mut [f32]) {
+        let last = self.num_bins() - 1;
+        for (out, range) in out.iter_mut().zip(self.iter()) {
+            *out = spectrum[range.clone()]
+                .iter()
+                .zip(range)
+                .map(|(value, bin)| {
+                    let weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+                    weight * value.norm_sqr()
+                })
+                .sum();
+        }
+    }
+}
+
+/// Converts a frequency in Hz to mels (the HTK formula).
+pub fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * math::log10f(1.0 + hz / 700.0)
+}
+
+/// Converts mels to a frequency in Hz (the HTK formula).
+pub fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (math::powf(10.0, mel / 2595.0) - 1.0)
+}
+
+/// Triangular filters evenly spaced on the mel scale, reducing a spectrum to a mel spectrum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MelFilterbank {
+    // the first bin and the weights of every filter
+    filters: Vec<(usize, Vec<f32>)>,
+    // the summed weights of all filters at every bin
+    weight_sums: Vec<f32>,
+    num_bins: usize,
+}
+
+impl MelFilterbank {
+    /// Creates `num_filters` filters between `min_hz` and `max_hz`, each peaking with a weight of
+    /// 1 at its center and reaching 0 at the centers of its neighbors.
+    pub fn new(
+        num_filters: usize,
+        min_hz: f32,
+        max_hz: f32,
+        sample_rate: f32,
+        fft_length: usize,
+    ) -> Self {
+        let num_bins = fft_length / 2 + 1;
+        let bin_hz = sample_rate / fft_length as f32;
+        let (min_mel, max_mel) = (hz_to_mel(min_hz), hz_to_mel(max_hz.min(sample_rate / 2.0)));
+        let edge_hz = |index: usize| {
+            mel_to_hz(min_mel + (max_mel - min_mel) * index as f32 / (num_filters + 1) as f32)
+        };
+
+        let filters: Vec<(usize, Vec<f32>)> = (0..num_filters)
+            .map(|filter| {
+                let (low, center, high) =
+                    (edge_hz(filter), edge_hz(filter + 1), edge_hz(filter + 2));
+                let start = ((low / bin_hz) as usize + 1).min(num_bins);
+                let end = ((high / bin_hz) as usize + 1).clamp(start, num_bins);
+                let weights = (start..end)
+                    .map(|bin| {
+                        let hz = bin as f32 * bin_hz;
+                        if hz <= center {
+                            (hz - low) / (center - low)
+                        } else {
+                            (high - hz) / (high - center)
+                        }
+                        .max(0.0)
+                    })
+                    .collect();
+                (start, weights)
+            })
+            .collect();
+
+        let mut weight_sums = vec![0.0; num_bins];
+        for (start, weights) in &filters {
+            for (sum, weight) in weight_sums[*start..].iter_mut().zip(weights) {
+                *sum += weight;
+            }
+        }
+
+        Self {
+            filters,
+            weight_sums,
+            num_bins,
+        }
+    }
+
+    /// Returns the number of filters.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Returns the number of bins of the spectra the filters apply to.
+    pub fn num_bins(&self) -> usize {
+        self.num_bins
+    }
+
+    /// Writes the weighted sum of `values` (one per bin, e.g. magnitudes or powers) under every
+    /// filter to `out`.
+    pub fn apply(&self, values: &[f32], out: &mut [f32]) {
+        for (out, (start, weights)) in out.iter_mut().zip(&self.filters) {
+            *out = values[*start..]
+                .iter()
+                .zip(weights)
+                .map(|(value, weight)| value * weight)
+                .sum();
+        }
+    }
+
+    /// Spreads one value per filter (e.g. a gain) back over the bins, writing the
+    /// weight-normalized average of the filters covering every bin to `out`. Bins no filter
+    /// covers repeat the bin below them, and take the first filter's value below all filters.
+    pub fn expand(&self, values: &[f32], out: &mut [f32]) {
+        let out = &mut out[..self.num_bins];
+        out.fill(0.0);
+        for ((start, weights), value) in self.filters.iter().zip(values) {
+            for (out, weight) in out[*start..].iter_mut().zip(weights) {
+                *out += weight * value;
+            }
+        }
+
+        let mut previous = values.first().copied().unwrap_or(0.0);
+        for (out, &weight_sum) in out.iter_mut().zip(&self.weight_sums) {
+            if weight_sum > 0.0 {
+                *out /= weight_sum;
+            } else {
+                *out = previous;
+            }
+            previous = *out;
+        }
+    }
+}
+
+/// Bands with triangular responses between ascending center frequencies.
+///
+/// Every bin between two centers is split between their bands in proportion to how close it is
+/// to each, so neighboring bands overlap and the weights of every bin sum to 1. Centers are kept
+/// as fractional bins, so a layout keeps its number of bands at any FFT length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriangularBands {
+    centers: Vec<f32>,
+    num_bins: usize,
+}
+
+impl TriangularBands {
+    /// Places a band at each of the given ascending frequencies, which are clamped to Nyquist.
+    pub fn from_centers_hz(centers_hz: &[f32], sample_rate: f32, fft_length: usize) -> Self {
+        let num_bins = fft_length / 2 + 1;
+        let bins_per_hz = fft_length as f32 / sample_rate;
+        let centers = centers_hz
+            .iter()
+            .map(|hz| (hz * bins_per_hz).clamp(0.0, (num_bins - 1) as f32))
+            .collect();
+        Self { centers, num_bins }
+    }
+
+    /// Places the bands at the RNNoise centers, see [`RNNOISE_CENTERS_HZ`].
+    pub fn rnnoise(sample_rate: f32, fft_length: usize) -> Self {
+        Self::from_centers_hz(&RNNOISE_CENTERS_HZ, sample_rate, fft_length)
+    }
+
+    /// Returns the number of bands.
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    /// Returns the number of bins of the spectra the bands apply to.
+    pub fn num_bins(&self) -> usize {
+        self.num_bins
+    }
+
+    /// Returns the band whose center lies at or below `bin`, the weight of that band at `bin`,
+    /// and the next band (which takes the rest of the weight).
+    fn neighbors(&self, bin: usize) -> (usize, f32, usize) {
+        let last = self.len() - 1;
+        let next = self.centers.partition_point(|&center| center <= bin as f32);
+        if next == 0 {
+            return (0, 1.0, 0);
+        }
+        if next > last {
+            return (last, 1.0, last);
+        }
+        let (low, high) = (self.centers[next - 1], self.centers[next]);
+        let weight = 1.0 - (bin as f32 - low) / (high - low);
+        (next - 1, weight, next)
+    }
+
+    /// Writes the power of every band of `spectrum` to `out`.
+    pub fn powers(&self, spectrum: &[Complex32], out: &mut [f32]) {
+        let out = &mut out[..self.len()];
+        out.fill(0.0);
+        if self.is_empty() {
+            return;
+        }
+        for (bin, value) in spectrum[..self.num_bins].iter().enumerate() {
+            let power = value.norm_sqr();
+            let (low, weight, high) = self.neighbors(bin);
+            out[low] += weight * power;
+            out[high] += (1.0 - weight) * power;
+        }
+    }
+
+    /// Spreads one value per band (e.g. a gain) over the bins, interpolating linearly between
+    /// band centers.
+    pub fn interpolate(&self, values: &[f32], out: &mut [f32]) {
+        if self.is_empty() {
+            return;
+        }
+        for (bin, out) in out[..self.num_bins].iter_mut().enumerate() {
+            let (low, weight, high) = self.neighbors(bin);
+            *out = weight * values[low] + (1.0 - weight) * values[high];
+        }
+    }
+}
+
+/// The width of the bands of an [`OctaveBands`] layout.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OctaveFraction {
+    Octave,
+    #[default]
+    Third,
+    Sixth,
+}
+
+impl OctaveFraction {
+    pub const ALL: [OctaveFraction; 3] = [Self::Octave, Self::Third, Self::Sixth];
+
+    pub fn bands_per_octave(&self) -> usize {
+        match self {
+            Self::Octave => 1,
+            Self::Third => 3,
+            Self::Sixth => 6,
+        }
+    }
+}
+
+/// The frequency ratio of an octave in the base-ten system of IEC 61260, `10^(3/10)`.
+const OCTAVE_RATIO: f32 = 1.995_262_3;
+
+/// Returns the exact mid-band frequencies of the IEC 61260 fractional-octave bands whose
+/// centers lie between `min_hz` and `max_hz`, ascending.
+///
+/// With an odd number of bands per octave, a band is centered on 1 kHz; with an even number, two
+/// bands meet there. The nominal frequencies printed on analyzers (31.5, 63, 125, ...) are these
+/// rounded.
+pub fn octave_band_centers_hz(fraction: OctaveFraction, min_hz: f32, max_hz: f32) -> Vec<f32> {
+    let bands = fraction.bands_per_octave() as f32;
+    let center = |index: i32| {
+        let exponent = if fraction.bands_per_octave() % 2 == 1 {
+            index as f32 / bands
+        } else {
+            (2 * index + 1) as f32 / (2.0 * bands)
+        };
+        1000.0 * math::powf(OCTAVE_RATIO, exponent)
+    };
+
+    let mut centers = Vec::new();
+    if min_hz <= 0.0 || max_hz < min_hz {
+        return centers;
+    }
+    // the index of the band centered closest below `min_hz`
+    let mut index = (math::log10f(min_hz / 1000.0) / math::log10f(OCTAVE_RATIO) * bands) as i32 - 2;
+    loop {
+        let hz = center(index);
+        if hz > max_hz {
+            break;
+        }
+        if hz >= min_hz {
+            centers.push(hz);
+        }
+        index += 1;
+    }
+    centers
+}
+
+/// Fractional-octave bands for measuring a real spectrum, as an analyzer would show them.
+///
+/// Every band spans `2^(±1 / (2 * bands_per_octave))` (in the base-ten ratio) around its center.
+/// Bins are treated as spanning half a bin to either side of their frequency and contribute to
+/// each band in proportion to how much of them it covers, so the bands below the resolution of
+/// the FFT still measure a sensible share of the power. Only bands ending below Nyquist are
+/// kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OctaveBands {
+    centers_hz: Vec<f32>,
+    // the first bin and the weights of every band
+    filters: Vec<(usize, Vec<f32>)>,
+    num_bins: usize,
+}
+
+impl OctaveBands {
+    /// Creates the bands centered between `min_hz` and `max_hz`, see [`octave_band_centers_hz`].
+    pub fn new(
+        fraction: OctaveFraction,
+        min_hz: f32,
+        max_hz: f32,
+        sample_rate: f32,
+        fft_length: usize,
+    ) -> Self {
+        let num_bins = fft_length / 2 + 1;
+        let bins_per_hz = fft_length as f32 / sample_rate;
+        let half_width = math::powf(OCTAVE_RATIO, 0.5 / fraction.bands_per_octave() as f32);
+        let last = (num_bins - 1) as f32;
+
+        let mut centers_hz = Vec::new();
+        let mut filters = Vec::new();
+        for center in octave_band_centers_hz(fraction, min_hz, max_hz) {
+            let low = center / half_width * bins_per_hz;
+            let high = center * half_width * bins_per_hz;
+            if high > last {
+                break;
+            }
+            let first = (low + 0.5) as usize;
+            let weights = (first..=(high + 0.5) as usize)
+                .map(|bin| {
+                    let start = (bin as f32 - 0.5).max(0.0).max(low);
+                    let end = (bin as f32 + 0.5).min(last).min(high);
+                    (end - start).max(0.0)
+                })
+                .collect();
+            centers_hz.push(center);
+            filters.push((first, weights));
+        }
+
+        Self {
+            centers_hz,
+            filters,
+            num_bins,
+        }
+    }
+
+    /// Returns the number of bands.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Returns the number of bins of the spectra the bands apply to.
+    pub fn num_bins(&self) -> usize {
+        self.num_bins
+    }
+
+    /// Returns the exact center frequency of every band, in Hz.
+    pub fn centers_hz(&self) -> &[f32] {
+        &self.centers_hz
+    }
+
+    /// Writes the power of every band of `spectrum` to `out`, counting bins like
+    /// [`Bands::powers`].
+    pub fn powers(&self, spectrum: &[Complex32], out: &mut [f32]) {
+        let last = self.num_bins - 1;
+        for (out, (first, weights)) in out.iter_mut().zip(&self.filters) {
+            *out = weights
+                .iter()
+                .enumerate()
+                .map(|(offset, weight)| {
+                    let bin = first + offset;
+                    let bin_weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+                    weight * bin_weight * spectrum[bin].norm_sqr()
+                })
+                .sum();
+        }
+    }
+}
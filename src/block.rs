@@ -0,0 +1,225 @@
+//! Processing spectra in time×frequency blocks.
+//!
+//! Many spectral algorithms (median-filter HPSS, declipping, models trained on spectrogram
+//! patches) look at several consecutive frames at once. An [`FftBlockProcessor`] receives the
+//! last few frames as a [`FrameBlock`] instead of a single frame, and the graph keeps the sliding
+//! window of frames for it: [`FftGraph::add_block_processor`] wraps it in a [`BlockNode`] with one
+//! spectral input and one spectral output.
+//!
+//! A block processor writes one output frame per hop, corresponding to its
+//! [`output_frame`](FftBlockProcessor::output_frame) within the block. Frames after it in the
+//! block are look-ahead, and are reported as latency.
+//!
+//! The bins of a frame needn't be frequencies: the wavelet transforms
+//! ([`ContinuousWaveletTransform`], [`DiscreteWaveletTransform`]) write one coefficient per scale
+//! every hop, so a block of their frames is a scale×time matrix.
+//!
+//! [`ContinuousWaveletTransform`]: crate::builtins::analysis::ContinuousWaveletTransform
+//! [`DiscreteWaveletTransform`]: crate::builtins::analysis::DiscreteWaveletTransform
+//! [`FftGraph::add_block_processor`]: crate::graph::FftGraph::add_block_processor
+
+use raug::prelude::*;
+
+use crate::{
+    FftError, fft_processor_io,
+    history::FrameHistory,
+    processor::{FftProcessor, FrameInfo},
+    signal::{Complex32, Fft},
+    state::{StateReader, StateWriter},
+};
+
+/// A processor working on blocks of consecutive frames, see the [module docs](self).
+pub trait FftBlockProcessor<F: Fft>
+where
+    Self: Send + 'static,
+{
+    fn name(&self) -> &str {
+        raug::util::interned_short_type_name::<Self>()
+    }
+
+    /// Returns the number of frames in every block. Must be at least 1, and must not change after
+    /// the processor is added to a graph.
+    fn block_frames(&self) -> usize;
+
+    /// Returns the index within the block of the frame the output corresponds to. Defaults to
+    /// the newest frame, adding no latency.
+    fn output_frame(&self) -> usize {
+        self.block_frames() - 1
+    }
+
+    #[allow(unused)]
+    fn allocate(&mut self, sample_rate: f32) {}
+
+    /// See [`FftProcessor::sample_rate_changed`].
+    #[allow(unused)]
+    fn sample_rate_changed(&mut self, old: f32, new: f32) {}
+
+    /// See [`FftProcessor::save_state`]. The frames of the block are saved by the graph.
+    #[allow(unused)]
+    fn save_state(&self, state: &mut StateWriter) {}
+
+    /// See [`FftProcessor::load_state`].
+    #[allow(unused)]
+    fn load_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        Ok(())
+    }
+
+    /// Called before every call to [`process`](FftBlockProcessor::process) with the current
+    /// frame's timing.
+    #[allow(unused)]
+    fn set_frame_info(&mut self, info: &FrameInfo) {}
+
+    /// Processes the block ending at the current frame, writing one output frame.
+    fn process(&mut self, block: FrameBlock<'_, F>, output: &mut F::RealFft) -> ProcResult<()>;
+}
+
+/// The last [`len`](Self::len) frames of a spectrum, as a time×frequency matrix.
+///
+/// Frames are indexed by time from the oldest (0) to the newest (`len() - 1`). Before the graph
+/// has processed enough frames to fill a block, the missing older frames are silent.
+pub struct FrameBlock<'a, F: Fft> {
+    history: &'a FrameHistory<F::RealFft>,
+}
+
+impl<F: Fft> Clone for FrameBlock<'_, F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F: Fft> Copy for FrameBlock<'_, F> {}
+
+impl<'a, F: Fft> FrameBlock<'a, F> {
+    /// Returns the number of frames.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Returns the number of bins of every frame.
+    #[inline]
+    pub fn num_bins(&self) -> usize {
+        F::N_REAL_BINS
+    }
+
+    /// Returns the frame at `time`, counting from the oldest.
+    #[inline]
+    pub fn frame(&self, time: usize) -> &'a F::RealFft {
+        self.history.get(self.len() - 1 - time)
+    }
+
+    #[inline]
+    pub fn newest(&self) -> &'a F::RealFft {
+        self.history.get(0)
+    }
+
+    /// Returns the value of `bin` in the frame at `time`.
+    #[inline]
+    pub fn get(&self, time: usize, bin: usize) -> Complex32 {
+        self.frame(time)[bin]
+    }
+
+    /// Iterates over the frames from oldest to newest.
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &'a F::RealFft> + '_ {
+        (0..self.len()).map(|time| self.frame(time))
+    }
+
+    /// Iterates over the values of `bin` from the oldest frame to the newest.
+    pub fn bin_over_time(&self, bin: usize) -> impl DoubleEndedIterator<Item = Complex32> + '_ {
+        self.frames().map(move |frame| frame[bin])
+    }
+}
+
+/// Runs an [`FftBlockProcessor`] as a node of an [`FftGraph`](crate::graph::FftGraph), keeping
+/// the sliding window of frames it processes.
+pub struct BlockNode<F: Fft, P: FftBlockProcessor<F>> {
+    processor: P,
+    history: FrameHistory<F::RealFft>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft, P: FftBlockProcessor<F>> BlockNode<F, P> {
+    pub fn new(processor: P) -> Self {
+        let block_frames = processor.block_frames().max(1);
+        Self {
+            processor,
+            history: FrameHistory::new(block_frames),
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    pub fn into_inner(self) -> P {
+        self.processor
+    }
+}
+
+impl<F: Fft, P: FftBlockProcessor<F>> FftProcessor for BlockNode<F, P> {
+    fn name(&self) -> &str {
+        self.processor.name()
+    }
+
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft }
+    }
+
+    fn latency_frames(&self) -> usize {
+        let last = self.history.len() - 1;
+        last - self.processor.output_frame().min(last)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.history.clear();
+        self.processor.allocate(sample_rate);
+    }
+
+    fn sample_rate_changed(&mut self, old: f32, new: f32) {
+        self.processor.sample_rate_changed(old, new);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.processor.set_frame_info(info);
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.history);
+        self.processor.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.history)?;
+        self.processor.load_state(state)
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            self.history.push(input);
+            let block = FrameBlock {
+                history: &self.history,
+            };
+            self.processor.process(block, &mut self.out_signal)?;
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+//! Builders for the builtin processors.
+//!
+//! Every builtin with tunable parameters has a `builder()` starting from its defaults, with one
+//! setter per parameter:
+//!
+//! ```ignore
+//! let duck = SpectralDuck::<Fft1024>::builder()
+//!     .threshold_db(-30.0)
+//!     .attack_seconds(0.005)
+//!     .build();
+//! ```
+//!
+//! The same list of parameters backs the builders' `params` method, which sets them by name from
+//! [`ProcessorParams`], so the [`ProcessorRegistry`](crate::registry::ProcessorRegistry) and the
+//! builders can't drift apart. Builders can also start from a processor constructed otherwise,
+//! with `From`. Each parameter is declared with its unit and range, which the processors report
+//! through [`FftProcessor::param_spec`](crate::processor::FftProcessor::param_spec).
+
+use crate::{
+    bands::OctaveFraction,
+    builtins::{control::LfoShape, filter::FeatureCurve},
+    registry::ProcessorParams,
+    wavelet::{ContinuousWavelet, DiscreteWavelet},
+};
+
+/// A parameter type that can be set from the numeric value of a [`ProcessorParams`] entry.
+pub trait ParamValue: Sized {
+    /// Whether the type only takes whole values.
+    const STEPPED: bool = true;
+
+    fn from_param(value: f32) -> Self;
+
+    /// Returns the numeric value [`from_param`](Self::from_param) turns back into `self`.
+    fn to_param(&self) -> f32;
+}
+
+impl ParamValue for f32 {
+    const STEPPED: bool = false;
+
+    fn from_param(value: f32) -> Self {
+        value
+    }
+
+    fn to_param(&self) -> f32 {
+        *self
+    }
+}
+
+/// Any value other than 0 is `true`.
+impl ParamValue for bool {
+    fn from_param(value: f32) -> Self {
+        value != 0.0
+    }
+
+    fn to_param(&self) -> f32 {
+        if *self { 1.0 } else { 0.0 }
+    }
+}
+
+/// Negative values saturate to 0.
+impl ParamValue for usize {
+    fn from_param(value: f32) -> Self {
+        value as usize
+    }
+
+    fn to_param(&self) -> f32 {
+        *self as f32
+    }
+}
+
+/// Negative values saturate to 0.
+impl ParamValue for u32 {
+    fn from_param(value: f32) -> Self {
+        value as u32
+    }
+
+    fn to_param(&self) -> f32 {
+        *self as f32
+    }
+}
+
+/// Setting the parameter makes it `Some`. `None` reads as 0, which the processors taking an
+/// optional parameter treat like `None`.
+impl ParamValue for Option<f32> {
+    const STEPPED: bool = false;
+
+    fn from_param(value: f32) -> Self {
+        Some(value)
+    }
+
+    fn to_param(&self) -> f32 {
+        self.unwrap_or(0.0)
+    }
+}
+
+/// The index into [`LfoShape::ALL`], falling back to the default shape.
+impl ParamValue for LfoShape {
+    fn from_param(value: f32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    fn to_param(&self) -> f32 {
+        Self::ALL
+            .iter()
+            .position(|shape| shape == self)
+            .unwrap_or(0) as f32
+    }
+}
+
+/// The index into [`FeatureCurve::ALL`], falling back to the default curve.
+impl ParamValue for FeatureCurve {
+    fn from_param(value: f32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    fn to_param(&self) -> f32 {
+        Self::ALL
+            .iter()
+            .position(|curve| curve == self)
+            .unwrap_or(0) as f32
+    }
+}
+
+/// The index into [`OctaveFraction::ALL`], falling back to third-octave bands.
+impl ParamValue for OctaveFraction {
+    fn from_param(value: f32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    fn to_param(&self) -> f32 {
+        Self::ALL
+            .iter()
+            .position(|fraction| fraction == self)
+            .unwrap_or(0) as f32
+    }
+}
+
+/// The index into [`ContinuousWavelet::ALL`], falling back to the Morlet wavelet.
+impl ParamValue for ContinuousWavelet {
+    fn from_param(value: f32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    fn to_param(&self) -> f32 {
+        Self::ALL
+            .iter()
+            .position(|wavelet| wavelet == self)
+            .unwrap_or(0) as f32
+    }
+}
+
+/// The index into [`DiscreteWavelet::ALL`], falling back to the Haar wavelet.
+impl ParamValue for DiscreteWavelet {
+    fn from_param(value: f32) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+
+    fn to_param(&self) -> f32 {
+        Self::ALL
+            .iter()
+            .position(|wavelet| wavelet == self)
+            .unwrap_or(0) as f32
+    }
+}
+
+/// Sets `name` from `params` if it's present.
+#[doc(hidden)]
+pub fn set_from_params<T: ParamValue>(target: &mut T, params: &ProcessorParams, name: &str) {
+    if let Some(value) = params.get(name) {
+        *target = T::from_param(value);
+    }
+}
+
+/// Defines a builder for a processor with public parameter fields and a `Default` impl.
+///
+/// Every field is followed by its [`ParamUnit`](crate::processor::ParamUnit), range and
+/// optionally its [`ParamSmoothing`](crate::processor::ParamSmoothing). Fields of a type with
+/// [`ParamValue::STEPPED`] are described as stepped:
+///
+/// ```ignore
+/// processor_builder! {
+///     SpectralDuck => SpectralDuckBuilder {
+///         threshold_db: f32 [Decibels, -100.0, 0.0],
+///         amount: f32 [None, 0.0, 1.0, Internal],
+///     }
+/// }
+/// ```
+macro_rules! processor_builder {
+    ($processor:ident => $builder:ident {
+        $($field:ident: $ty:ty [$unit:ident, $min:expr, $max:expr $(, $smoothing:ident)?]),* $(,)?
+    }) => {
+        #[doc = concat!("Builds a [`", stringify!($processor), "`], see [`builder`](crate::builder).")]
+        pub struct $builder<F: $crate::signal::Fft>($processor<F>);
+
+        impl<F: $crate::signal::Fft> $processor<F> {
+            #[doc = concat!("Returns a builder starting from [`", stringify!($processor), "::default`].")]
+            pub fn builder() -> $builder<F> {
+                $builder(Self::default())
+            }
+        }
+
+        impl<F: $crate::signal::Fft> $builder<F> {
+            /// The names of the parameters the builder sets.
+            pub const PARAMS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            $(
+                pub fn $field(mut self, $field: $ty) -> Self {
+                    self.0.$field = $field;
+                    self
+                }
+            )*
+
+            /// Sets every parameter present in `params` by name, leaving the others unchanged.
+            pub fn params(mut self, params: &$crate::registry::ProcessorParams) -> Self {
+                $(
+                    $crate::builder::set_from_params(
+                        &mut self.0.$field,
+                        params,
+                        stringify!($field),
+                    );
+                )*
+                self
+            }
+
+            /// Describes the parameters the builder sets, with the processor's defaults.
+            pub fn param_spec() -> alloc::vec::Vec<$crate::processor::ParamSpec> {
+                use $crate::builder::ParamValue;
+
+                let defaults = $processor::<F>::default();
+                alloc::vec![$({
+                    let spec = $crate::processor::ParamSpec::new(
+                        stringify!($field),
+                        $crate::processor::ParamUnit::$unit,
+                        $min,
+                        $max,
+                        defaults.$field.to_param(),
+                    );
+                    let spec = if <$ty as ParamValue>::STEPPED { spec.stepped() } else { spec };
+                    spec $(.smoothing($crate::processor::ParamSmoothing::$smoothing))?
+                }),*]
+            }
+
+            /// Sets the parameter `name` of `processor`, returning whether there is one, see
+            /// [`FftProcessor::set_param`](crate::processor::FftProcessor::set_param).
+            pub fn set_param(processor: &mut $processor<F>, name: &str, value: f32) -> bool {
+                use $crate::builder::ParamValue;
+
+                match name {
+                    $(stringify!($field) => {
+                        processor.$field = <$ty as ParamValue>::from_param(value);
+                        true
+                    })*
+                    _ => false,
+                }
+            }
+
+            pub fn build(self) -> $processor<F> {
+                self.0
+            }
+        }
+
+        impl<F: $crate::signal::Fft> From<$processor<F>> for $builder<F> {
+            fn from(processor: $processor<F>) -> Self {
+                Self(processor)
+            }
+        }
+    };
+}
+
+pub(crate) use processor_builder;
@@ -0,0 +1,2071 @@
+//! Processors analyzing spectra into control values and events.
+
+use raug::prelude::*;
+
+use crate::{
+    bands::{OctaveBands, OctaveFraction},
+    builder::processor_builder,
+    chroma::{
+        Chromagram, MAJOR_KEY_PROFILE, MAJOR_TRIAD, MINOR_KEY_PROFILE, MINOR_TRIAD, PITCH_CLASSES,
+        correlate, hz_to_midi, match_template,
+    },
+    denormal::{flush_denormal, flush_real_denormals},
+    fft_processor_io,
+    history::FrameHistory,
+    peaks,
+    processor::{FftProcessor, FrameInfo, ParamSpec, ParamUnit, process_with_own_scratch},
+    processor_clone, processor_state,
+    scratch::{Scratch, ScratchSize},
+    signal::{Complex32, Fft},
+    units,
+    wavelet::{
+        ContinuousWavelet, DiscreteWavelet, cwt_frequencies_hz, cwt_frequency_hz, dwt_step,
+        max_dwt_levels,
+    },
+};
+
+/// Returns the coefficient of a one-pole filter with time constant `seconds`, stepped once per
+/// hop.
+fn one_pole(seconds: f32, hop_seconds: f32) -> f32 {
+    if seconds <= 0.0 || hop_seconds <= 0.0 {
+        0.0
+    } else {
+        (-hop_seconds / seconds).exp()
+    }
+}
+
+/// Returns the value at fractional index `index`, interpolating linearly.
+fn value_at(values: &[f32], index: f32) -> f32 {
+    let whole = index as usize;
+    match (values.get(whole), values.get(whole + 1)) {
+        (Some(low), Some(high)) => low + (high - low) * index.fract(),
+        (Some(low), None) => *low,
+        _ => 0.0,
+    }
+}
+
+/// Detects voice activity, outputting the probability that a frame contains speech.
+///
+/// Three features are measured between `min_hz` and `max_hz`:
+///
+/// - the level above the noise floor, which follows the level down immediately and rises by at
+///   most `floor_rise_db` per second; frames `snr_db` above it score fully,
+/// - the spectral flatness, which is low for voiced speech and high for noise,
+/// - the pitch presence, how much more energy lies on the harmonics of the strongest
+///   fundamental between `min_pitch_hz` and `max_pitch_hz` than on average.
+///
+/// The probability is the level score times the average of the other two, so silence never
+/// counts as speech. The `gate` output opens once the probability exceeds `threshold` and stays
+/// open for `hangover_seconds` after it falls below, so pauses between words don't close it;
+/// `start` and `end` fire when it opens and closes.
+#[derive(Clone)]
+pub struct Vad<F: Fft> {
+    pub min_hz: f32,
+    pub max_hz: f32,
+    pub min_pitch_hz: f32,
+    pub max_pitch_hz: f32,
+    pub snr_db: f32,
+    pub floor_rise_db: f32,
+    pub threshold: f32,
+    pub hangover_seconds: f32,
+    /// The time constant the probability falls with, so single frames don't drop it.
+    pub release_seconds: f32,
+    frame: FrameInfo,
+    floor_db: Option<f32>,
+    probability: f32,
+    open: bool,
+    hangover: f32,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> Vad<F> {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            min_hz: 100.0,
+            max_hz: 4000.0,
+            min_pitch_hz: 70.0,
+            max_pitch_hz: 400.0,
+            snr_db: 12.0,
+            floor_rise_db: 3.0,
+            threshold,
+            hangover_seconds: 0.3,
+            release_seconds: 0.05,
+            frame: FrameInfo::default(),
+            floor_db: None,
+            probability: 0.0,
+            open: false,
+            hangover: 0.0,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the current speech probability.
+    pub fn probability(&self) -> f32 {
+        self.probability
+    }
+
+    /// Returns the current noise floor in dB, or `None` before the first frame.
+    pub fn floor_db(&self) -> Option<f32> {
+        self.floor_db
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Returns the probability of speech in `spectrum`, before smoothing. `magnitudes` is scratch
+    /// space for one value per bin.
+    fn measure(&mut self, spectrum: &[Complex32], magnitudes: &mut [f32]) -> f32 {
+        let bin_hz = F::bin_bandwidth(self.frame.sample_rate);
+        if bin_hz <= 0.0 {
+            return 0.0;
+        }
+        units::magnitudes(spectrum, magnitudes);
+        let last = F::N_REAL_BINS - 1;
+        let start = ((self.min_hz / bin_hz) as usize).clamp(1, last);
+        let end = ((self.max_hz / bin_hz) as usize + 1).clamp(start + 1, F::N_REAL_BINS);
+        let band = &magnitudes[start..end];
+
+        // level above the noise floor
+        let energy: f32 = band.iter().map(|magnitude| magnitude * magnitude).sum();
+        let level_db = 10.0 * (energy / F::N_FFT as f32 + 1e-12).log10();
+        let floor_db = match self.floor_db {
+            Some(floor_db) => {
+                level_db.min(floor_db + self.floor_rise_db * self.frame.hop_seconds())
+            }
+            None => level_db,
+        };
+        self.floor_db = Some(floor_db);
+        let level_score = ((level_db - floor_db) / self.snr_db.max(1e-3)).clamp(0.0, 1.0);
+
+        // flatness: the geometric over the arithmetic mean of the powers
+        let mean_log: f32 = band
+            .iter()
+            .map(|magnitude| (magnitude * magnitude + 1e-12).ln())
+            .sum::<f32>()
+            / band.len() as f32;
+        let mean = energy / band.len() as f32 + 1e-12;
+        let flatness = (mean_log.exp() / mean).clamp(0.0, 1.0);
+
+        // pitch presence: the mean magnitude on the harmonics of the best fundamental relative
+        // to the mean magnitude of the band
+        let mean_magnitude = band.iter().sum::<f32>() / band.len() as f32 + 1e-12;
+        let max_hz = self.max_hz.min(self.frame.sample_rate / 2.0);
+        let mut best = 0.0f32;
+        let mut f0 = self.min_pitch_hz.max(bin_hz);
+        while f0 <= self.max_pitch_hz {
+            let harmonics = ((max_hz / f0) as usize).clamp(1, 8);
+            let sum: f32 = (1..=harmonics)
+                .map(|harmonic| value_at(magnitudes, harmonic as f32 * f0 / bin_hz))
+                .sum();
+            best = best.max(sum / harmonics as f32);
+            // steps of a quarter bin at the highest harmonic
+            f0 += bin_hz / (4.0 * harmonics as f32);
+        }
+        let pitch_score = ((best / mean_magnitude - 1.0) / 2.0).clamp(0.0, 1.0);
+
+        level_score * 0.5 * (1.0 - flatness + pitch_score)
+    }
+}
+
+impl<F: Fft> Default for Vad<F> {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+processor_builder! {
+    Vad => VadBuilder {
+        min_hz: f32 [Hertz, 20.0, 20000.0],
+        max_hz: f32 [Hertz, 20.0, 20000.0],
+        min_pitch_hz: f32 [Hertz, 20.0, 2000.0],
+        max_pitch_hz: f32 [Hertz, 20.0, 2000.0],
+        snr_db: f32 [Decibels, 0.0, 60.0],
+        floor_rise_db: f32 [Decibels, 0.0, 60.0],
+        threshold: f32 [None, 0.0, 1.0],
+        hangover_seconds: f32 [Seconds, 0.0, 2.0],
+        release_seconds: f32 [Seconds, 0.0, 2.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for Vad<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { probability: f32, gate: bool, start: bool, end: bool }
+    }
+
+    processor_state!(floor_db, probability, open, hangover);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        VadBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        VadBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.floor_db = None;
+        self.probability = 0.0;
+        self.open = false;
+        self.hangover = 0.0;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn scratch_size(&self) -> ScratchSize {
+        ScratchSize::new(F::N_REAL_BINS, 0)
+    }
+
+    fn process(&mut self, inputs: ProcessorInputs, outputs: ProcessorOutputs) -> ProcResult<()> {
+        process_with_own_scratch(self, inputs, outputs)
+    }
+
+    fn process_with_scratch(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+        scratch: &mut Scratch,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let hop_seconds = self.frame.hop_seconds();
+        let magnitudes = scratch.reals(F::N_REAL_BINS);
+
+        for (i, spectrum) in input.iter().enumerate() {
+            let probability = self.measure(spectrum, magnitudes);
+            self.probability = if probability > self.probability {
+                probability
+            } else {
+                let coeff = one_pole(self.release_seconds, hop_seconds);
+                probability + (self.probability - probability) * coeff
+            };
+
+            let was_open = self.open;
+            if self.probability > self.threshold {
+                self.open = true;
+                self.hangover = self.hangover_seconds;
+            } else if self.open {
+                self.hangover -= hop_seconds;
+                self.open = self.hangover > 0.0;
+            }
+
+            outputs.set_output_as::<f32>(0, i, &self.probability)?;
+            outputs.set_output_as::<bool>(1, i, &self.open)?;
+            outputs.set_output_as::<bool>(2, i, &(self.open && !was_open))?;
+            outputs.set_output_as::<bool>(3, i, &(!self.open && was_open))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures spectral flux, how much the spectrum grew since the previous frame, and detects
+/// onsets from it.
+///
+/// The flux is the mean over all bins of the rise in log-compressed magnitude
+/// (`ln(1 + compression * magnitude)`), ignoring bins that fell. An `onset` fires when the flux
+/// rises above `ratio` times its recent average (over `average_seconds`) plus `threshold`, at
+/// most once every `min_interval_seconds`.
+#[derive(Clone)]
+pub struct SpectralFlux<F: Fft> {
+    pub compression: f32,
+    pub threshold: f32,
+    pub ratio: f32,
+    pub average_seconds: f32,
+    pub min_interval_seconds: f32,
+    frame: FrameInfo,
+    previous: Vec<f32>,
+    average: f32,
+    above: bool,
+    since_onset: f32,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> SpectralFlux<F> {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            compression: 100.0,
+            threshold,
+            ratio: 1.5,
+            average_seconds: 0.5,
+            min_interval_seconds: 0.05,
+            frame: FrameInfo::default(),
+            previous: vec![0.0; F::N_REAL_BINS],
+            average: 0.0,
+            above: false,
+            since_onset: f32::INFINITY,
+            _f: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<F: Fft> Default for SpectralFlux<F> {
+    fn default() -> Self {
+        Self::new(0.05)
+    }
+}
+
+processor_builder! {
+    SpectralFlux => SpectralFluxBuilder {
+        compression: f32 [None, 0.0, 10000.0],
+        threshold: f32 [None, 0.0, 10.0],
+        ratio: f32 [None, 1.0, 10.0],
+        average_seconds: f32 [Seconds, 0.0, 2.0],
+        min_interval_seconds: f32 [Seconds, 0.0, 1.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralFlux<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { flux: f32, onset: bool }
+    }
+
+    processor_state!(previous, average, above, since_onset);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralFluxBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralFluxBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.previous.fill(0.0);
+        self.average = 0.0;
+        self.above = false;
+        self.since_onset = f32::INFINITY;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let hop_seconds = self.frame.hop_seconds();
+        let coeff = one_pole(self.average_seconds, hop_seconds);
+
+        for (i, spectrum) in input.iter().enumerate() {
+            let mut rise = 0.0;
+            for (previous, value) in self.previous.iter_mut().zip(spectrum.iter()) {
+                let magnitude = (1.0 + self.compression * value.norm()).ln();
+                rise += (magnitude - *previous).max(0.0);
+                *previous = magnitude;
+            }
+            let flux = rise / F::N_REAL_BINS as f32;
+
+            let was_above = self.above;
+            self.above = flux > self.ratio * self.average + self.threshold;
+            self.since_onset += hop_seconds;
+            let onset = self.above && !was_above && self.since_onset >= self.min_interval_seconds;
+            if onset {
+                self.since_onset = 0.0;
+            }
+            self.average = flux + (self.average - flux) * coeff;
+
+            outputs.set_output_as::<f32>(0, i, &flux)?;
+            outputs.set_output_as::<bool>(1, i, &onset)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How much of the difference between the estimated and the running beat phase is corrected
+/// over one beat.
+const PHASE_CORRECTION: f32 = 0.5;
+/// The number of multiples of a lag whose autocorrelation counts towards it.
+const COMB_TEETH: usize = 2;
+/// How much lags within [`TEMPO_JUMP`] of the current period are favored, so the tempo doesn't
+/// flip between similarly strong octaves.
+const TEMPO_INERTIA: f32 = 0.25;
+/// The relative change in period above which the tempo jumps instead of being smoothed.
+const TEMPO_JUMP: f32 = 0.1;
+/// How much offsets close to the running beat phase are favored when estimating the phase.
+const PHASE_INERTIA: f32 = 0.1;
+
+/// Tracks the tempo and beats of an onset detection function, e.g. the `flux` output of a
+/// [`SpectralFlux`].
+///
+/// The last `window_seconds` of the input are kept. Every hop, their autocorrelation is combed
+/// (each lag reinforced by its multiples) and weighted by a log-normal prior around
+/// `prior_bpm`, `prior_octaves` wide, and the best lag between `min_bpm` and `max_bpm` gives the
+/// tempo. Lags close to the current tempo are favored, so it doesn't flip between octaves. Small
+/// tempo changes are smoothed over `tempo_seconds`, while jumps of more than 10% are taken
+/// immediately. The beat phase is found by aligning a pulse train at the tempo with
+/// the input, and a running phase is pulled towards it, so beats keep coming at a steady rate
+/// through breaks.
+///
+/// The outputs are the tempo in BPM, the phase within the current beat from 0 to 1, and a
+/// trigger on every beat. Until the window has filled halfway, the tempo is 0 and no beats fire.
+/// The input should be non-negative, like onset detection functions are.
+#[derive(Clone)]
+pub struct BeatTracker<F: Fft> {
+    pub min_bpm: f32,
+    pub max_bpm: f32,
+    pub prior_bpm: f32,
+    pub prior_octaves: f32,
+    pub window_seconds: f32,
+    pub tempo_seconds: f32,
+    frame: FrameInfo,
+    history: FrameHistory<f32>,
+    frames_seen: usize,
+    autocorrelation: Vec<f32>,
+    // the beat period in hops, or 0 before the first estimate
+    period: f32,
+    phase: f32,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> BeatTracker<F> {
+    pub fn new(min_bpm: f32, max_bpm: f32) -> Self {
+        Self {
+            min_bpm,
+            max_bpm,
+            prior_bpm: 120.0,
+            prior_octaves: 1.0,
+            window_seconds: 6.0,
+            tempo_seconds: 1.0,
+            frame: FrameInfo::default(),
+            history: FrameHistory::new(1),
+            frames_seen: 0,
+            autocorrelation: Vec::new(),
+            period: 0.0,
+            phase: 0.0,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the current tempo in BPM, or 0 before the first estimate.
+    pub fn bpm(&self) -> f32 {
+        if self.period > 0.0 {
+            60.0 * self.frame.frame_rate() / self.period
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the phase within the current beat, from 0 to 1.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// Resizes the window if `window_seconds` or the frame rate changed, which allocates.
+    fn update_window(&mut self) {
+        let frames = (self.window_seconds * self.frame.frame_rate())
+            .ceil()
+            .max(4.0) as usize;
+        if frames != self.history.len() {
+            self.history = FrameHistory::new(frames);
+            self.autocorrelation.resize(frames, 0.0);
+            self.frames_seen = 0;
+            self.period = 0.0;
+        }
+    }
+
+    /// Returns the autocorrelation at fractional `lag`, interpolating linearly.
+    fn autocorrelation_at(&self, lag: f32) -> f32 {
+        value_at(&self.autocorrelation, lag)
+    }
+
+    /// Returns the beat period in hops that best fits the window, or `None` if there's no
+    /// periodicity in range.
+    fn estimate_period(&mut self) -> Option<f32> {
+        let length = self.history.len();
+        let frame_rate = self.frame.frame_rate();
+        let min_lag = ((60.0 * frame_rate / self.max_bpm.max(1.0)).floor() as usize).max(1);
+        let max_lag = ((60.0 * frame_rate / self.min_bpm.max(1.0)).ceil() as usize)
+            .min((length - 2) / COMB_TEETH);
+        if min_lag + 2 > max_lag {
+            return None;
+        }
+
+        let mean = self.history.iter().sum::<f32>() / length as f32;
+        for lag in 0..=(COMB_TEETH * (max_lag + 1)).min(length - 1) {
+            let sum: f32 = (0..length - lag)
+                .map(|age| (self.history.get(age) - mean) * (self.history.get(age + lag) - mean))
+                .sum();
+            // unbiased, so longer lags aren't penalized for overlapping less
+            self.autocorrelation[lag] = sum / (length - lag) as f32;
+        }
+
+        let score = |tracker: &Self, lag: f32| {
+            let bpm = 60.0 * frame_rate / lag;
+            let octaves = (bpm / tracker.prior_bpm).log2() / tracker.prior_octaves.max(1e-3);
+            let prior = (-0.5 * octaves * octaves).exp();
+            let comb: f32 = (1..=COMB_TEETH)
+                .map(|tooth| tracker.autocorrelation_at(tooth as f32 * lag) / tooth as f32)
+                .sum();
+            prior * comb
+        };
+        let (best_lag, _) = (min_lag..=max_lag)
+            .map(|lag| {
+                let mut score = score(self, lag as f32);
+                if (lag as f32 - self.period).abs() <= TEMPO_JUMP * self.period {
+                    score *= 1.0 + TEMPO_INERTIA;
+                }
+                (lag, score)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let best_score = score(self, best_lag as f32);
+        if best_score <= 0.0 {
+            return None;
+        }
+
+        // refine between the neighboring lags with a parabola
+        let (before, after) = (
+            score(self, best_lag as f32 - 1.0),
+            score(self, best_lag as f32 + 1.0),
+        );
+        let curvature = before - 2.0 * best_score + after;
+        let offset = if curvature < 0.0 {
+            (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some(best_lag as f32 + offset)
+    }
+
+    /// Returns how far into its beat the newest frame is, from 0 to 1, by aligning a pulse train
+    /// with the window. Alignments close to the running phase are favored slightly, so the phase
+    /// doesn't flip between equally good alignments (e.g. the on- and off-beats).
+    fn estimate_phase(&self) -> f32 {
+        let length = self.history.len();
+        let offsets = (self.period.ceil() as usize).max(1);
+        let mut best = (0, f32::NEG_INFINITY);
+        for offset in 0..offsets {
+            let mut sum = 0.0;
+            let mut age = offset as f32;
+            while (age.round() as usize) < length {
+                sum += self.history.get(age.round() as usize);
+                age += self.period;
+            }
+            let distance = offset as f32 / self.period - self.phase;
+            let sum = sum * (1.0 + PHASE_INERTIA * (std::f32::consts::TAU * distance).cos());
+            if sum > best.1 {
+                best = (offset, sum);
+            }
+        }
+        best.0 as f32 / self.period
+    }
+}
+
+impl<F: Fft> Default for BeatTracker<F> {
+    fn default() -> Self {
+        Self::new(60.0, 200.0)
+    }
+}
+
+processor_builder! {
+    BeatTracker => BeatTrackerBuilder {
+        min_bpm: f32 [Bpm, 20.0, 400.0],
+        max_bpm: f32 [Bpm, 20.0, 400.0],
+        prior_bpm: f32 [Bpm, 20.0, 400.0],
+        prior_octaves: f32 [Octaves, 0.1, 4.0],
+        window_seconds: f32 [Seconds, 1.0, 20.0],
+        tempo_seconds: f32 [Seconds, 0.0, 20.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for BeatTracker<F> {
+    fft_processor_io! {
+        inputs { input: f32 }
+        outputs { bpm: f32, phase: f32, beat: bool }
+    }
+
+    processor_state!(history, frames_seen, period, phase);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        BeatTrackerBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        BeatTrackerBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.history.clear();
+        self.frames_seen = 0;
+        self.period = 0.0;
+        self.phase = 0.0;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let Some(input) = inputs.input_as::<f32>(0) else {
+            return Ok(());
+        };
+        self.update_window();
+        let coeff = one_pole(self.tempo_seconds, self.frame.hop_seconds());
+
+        for (i, &value) in input.iter().enumerate() {
+            self.history.push(&value);
+            self.frames_seen += 1;
+
+            if self.frames_seen >= self.history.len() / 2
+                && let Some(period) = self.estimate_period()
+            {
+                if self.period <= 0.0 || (period - self.period).abs() > TEMPO_JUMP * self.period {
+                    self.period = period;
+                } else {
+                    self.period = period + (self.period - period) * coeff;
+                }
+            }
+
+            let mut beat = false;
+            if self.period > 0.0 {
+                self.phase += 1.0 / self.period;
+                let error = self.estimate_phase() - self.phase;
+                self.phase += (error - error.round()) * PHASE_CORRECTION / self.period;
+                // corrections never wrap backwards, which would repeat a beat
+                self.phase = self.phase.max(0.0);
+                if self.phase >= 1.0 {
+                    self.phase -= 1.0;
+                    beat = true;
+                }
+            }
+
+            outputs.set_output_as::<f32>(0, i, &self.bpm())?;
+            outputs.set_output_as::<f32>(1, i, &self.phase)?;
+            outputs.set_output_as::<bool>(2, i, &beat)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What a [`KeyDetect`] estimates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDetectMode {
+    /// The key, matching the Krumhansl-Kessler key profiles over a long average.
+    #[default]
+    Key,
+    /// The chord, matching major and minor triads over a short average.
+    Chord,
+}
+
+/// Estimates the key or chord of a spectrum from its chroma.
+///
+/// Chroma vectors between `min_hz` and `max_hz` (see [`Chromagram`]) are normalized, so quiet
+/// passages count as much as loud ones, and averaged over `average_seconds`. The average is
+/// matched against all 24 major and minor keys or triads, see [`match_template`]. The estimate
+/// only changes once another match correlates better by more than `switch_margin`, so it doesn't
+/// flicker between close matches.
+///
+/// The outputs are the index of the estimate (the root for major, 12 plus the root for minor,
+/// see [`template_name`](crate::chroma::template_name)), its correlation as a confidence between
+/// -1 and 1, and a trigger whenever the estimate changes.
+#[derive(Clone)]
+pub struct KeyDetect<F: Fft> {
+    pub mode: KeyDetectMode,
+    pub min_hz: f32,
+    pub max_hz: f32,
+    pub tuning_hz: f32,
+    pub average_seconds: f32,
+    pub switch_margin: f32,
+    frame: FrameInfo,
+    // the range and tuning the chromagram was built for
+    mapped: (f32, f32, f32),
+    chromagram: Option<Chromagram>,
+    frame_chroma: [f32; PITCH_CLASSES],
+    chroma: [f32; PITCH_CLASSES],
+    estimate: usize,
+    confidence: f32,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> KeyDetect<F> {
+    pub fn new(mode: KeyDetectMode) -> Self {
+        Self {
+            mode,
+            min_hz: 60.0,
+            max_hz: 5000.0,
+            tuning_hz: 440.0,
+            average_seconds: match mode {
+                KeyDetectMode::Key => 8.0,
+                KeyDetectMode::Chord => 0.3,
+            },
+            switch_margin: 0.05,
+            frame: FrameInfo::default(),
+            mapped: (0.0, 0.0, 0.0),
+            chromagram: None,
+            frame_chroma: [0.0; PITCH_CLASSES],
+            chroma: [0.0; PITCH_CLASSES],
+            estimate: 0,
+            confidence: 0.0,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the index of the current estimate.
+    pub fn estimate(&self) -> usize {
+        self.estimate
+    }
+
+    /// Returns the correlation of the current estimate.
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Returns the averaged chroma vector, which sums to about 1 once the average has filled.
+    pub fn chroma(&self) -> &[f32; PITCH_CLASSES] {
+        &self.chroma
+    }
+
+    /// Rebuilds the chromagram if the range, tuning or sample rate changed, which allocates.
+    fn update_chromagram(&mut self) {
+        let mapped = (self.min_hz, self.max_hz, self.tuning_hz);
+        if self.frame.sample_rate <= 0.0 || (self.chromagram.is_some() && mapped == self.mapped) {
+            return;
+        }
+        self.mapped = mapped;
+        self.chromagram = Some(Chromagram::new(
+            self.min_hz,
+            self.max_hz,
+            self.tuning_hz,
+            self.frame.sample_rate,
+            F::N_FFT,
+        ));
+    }
+}
+
+impl<F: Fft> Default for KeyDetect<F> {
+    fn default() -> Self {
+        Self::new(KeyDetectMode::Key)
+    }
+}
+
+processor_builder! {
+    KeyDetect => KeyDetectBuilder {
+        min_hz: f32 [Hertz, 20.0, 20000.0],
+        max_hz: f32 [Hertz, 20.0, 20000.0],
+        tuning_hz: f32 [Hertz, 400.0, 480.0],
+        average_seconds: f32 [Seconds, 0.0, 30.0],
+        switch_margin: f32 [None, 0.0, 1.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for KeyDetect<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { key: f32, confidence: f32, changed: bool }
+    }
+
+    processor_state!(chroma, estimate, confidence);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        KeyDetectBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        KeyDetectBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        if sample_rate != self.frame.sample_rate {
+            self.chromagram = None;
+        }
+        self.chroma = [0.0; PITCH_CLASSES];
+        self.estimate = 0;
+        self.confidence = 0.0;
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        // rebuild now rather than on the next frame, which would allocate while processing
+        self.frame.sample_rate = new;
+        self.chromagram = None;
+        self.update_chromagram();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        if info.sample_rate != self.frame.sample_rate {
+            self.chromagram = None;
+        }
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        self.update_chromagram();
+        let coeff = one_pole(self.average_seconds, self.frame.hop_seconds());
+        let (major, minor) = match self.mode {
+            KeyDetectMode::Key => (&MAJOR_KEY_PROFILE, &MINOR_KEY_PROFILE),
+            KeyDetectMode::Chord => (&MAJOR_TRIAD, &MINOR_TRIAD),
+        };
+
+        for (i, spectrum) in input.iter().enumerate() {
+            let mut changed = false;
+            if let Some(chromagram) = &self.chromagram {
+                chromagram.apply(spectrum, &mut self.frame_chroma);
+                let total: f32 = self.frame_chroma.iter().sum();
+                // silent frames leave the average alone
+                if total > 1e-12 {
+                    for (average, value) in self.chroma.iter_mut().zip(&self.frame_chroma) {
+                        let value = value / total;
+                        *average = value + (*average - value) * coeff;
+                    }
+
+                    let (best, correlation) = match_template(&self.chroma, major, minor);
+                    let root = self.estimate % PITCH_CLASSES;
+                    let current = if self.estimate < PITCH_CLASSES {
+                        correlate(&self.chroma, major, root)
+                    } else {
+                        correlate(&self.chroma, minor, root)
+                    };
+                    if best != self.estimate && correlation > current + self.switch_margin {
+                        self.estimate = best;
+                        self.confidence = correlation;
+                        changed = true;
+                    } else {
+                        self.confidence = current;
+                    }
+                }
+            }
+
+            outputs.set_output_as::<f32>(0, i, &(self.estimate as f32))?;
+            outputs.set_output_as::<f32>(1, i, &self.confidence)?;
+            outputs.set_output_as::<bool>(2, i, &changed)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Detects the pitch of a monophonic signal for tuning, with a resolution far finer than a bin.
+///
+/// The fundamental is the lowest spectral peak between `min_hz` and `max_hz` that is at most
+/// `harmonic_db` below the strongest one, so a strong second harmonic isn't mistaken for it. Its
+/// frequency is refined by how far its phase advanced since the previous frame beyond what the
+/// bin's center frequency accounts for, falling back to parabolic interpolation of the log
+/// magnitudes when the peak just moved to another bin. Frames quieter than `min_level_db` (RMS,
+/// measured like [`FrameEnvelope`](crate::builtins::control::FrameEnvelope)) detect nothing.
+///
+/// The outputs are the frequency in Hz (0 when nothing is detected), the nearest MIDI note with A4
+/// at `tuning_hz`, the deviation from it in cents, the deviation smoothed over `display_seconds`
+/// for display, and a gate that is open while a pitch is detected. The note and deviations hold
+/// their last values while nothing is detected.
+#[derive(Clone)]
+pub struct Tuner<F: Fft> {
+    pub min_hz: f32,
+    pub max_hz: f32,
+    pub tuning_hz: f32,
+    pub min_level_db: f32,
+    pub harmonic_db: f32,
+    pub display_seconds: f32,
+    frame: FrameInfo,
+    previous: Box<F::RealFft>,
+    frequency: f32,
+    note: f32,
+    cents: f32,
+    display_cents: f32,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> Tuner<F> {
+    pub fn new(tuning_hz: f32) -> Self {
+        Self {
+            min_hz: 30.0,
+            max_hz: 2000.0,
+            tuning_hz,
+            min_level_db: -60.0,
+            harmonic_db: 12.0,
+            display_seconds: 0.15,
+            frame: FrameInfo::default(),
+            previous: Box::new(F::RealFft::default()),
+            frequency: 0.0,
+            note: 0.0,
+            cents: 0.0,
+            display_cents: 0.0,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the detected frequency in Hz, or 0 if nothing was detected.
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Returns the nearest MIDI note.
+    pub fn note(&self) -> f32 {
+        self.note
+    }
+
+    /// Returns the deviation from the nearest note in cents.
+    pub fn cents(&self) -> f32 {
+        self.cents
+    }
+
+    /// Returns the bin the fundamental of `spectrum` lies in, if any, writing the magnitudes of
+    /// `spectrum` to `magnitudes`.
+    fn find_fundamental(&self, spectrum: &[Complex32], magnitudes: &mut [f32]) -> Option<usize> {
+        let bin_hz = F::bin_bandwidth(self.frame.sample_rate);
+        if bin_hz <= 0.0 {
+            return None;
+        }
+
+        // see `FrameEnvelope` for the level measurement
+        let overlap = F::N_FFT as f32 / self.frame.hop_length.max(1) as f32;
+        let last = F::N_REAL_BINS - 1;
+        let mut energy = 0.0;
+        for (bin, (magnitude, value)) in magnitudes.iter_mut().zip(spectrum).enumerate() {
+            *magnitude = value.norm();
+            let weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+            energy += weight * value.norm_sqr();
+        }
+        let level_db = units::power_to_db(energy * overlap / F::N_FFT as f32);
+        if level_db < self.min_level_db {
+            return None;
+        }
+
+        let start = ((self.min_hz / bin_hz) as usize).max(1);
+        let end = ((self.max_hz / bin_hz) as usize + 1).min(last);
+        if start >= end {
+            return None;
+        }
+        let magnitudes = &*magnitudes;
+        let strongest = (start..end)
+            .filter(|&bin| peaks::is_peak(magnitudes, bin))
+            .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))?;
+        let threshold = magnitudes[strongest] * units::db_to_magnitude(-self.harmonic_db);
+        (start..=strongest)
+            .find(|&bin| peaks::is_peak(magnitudes, bin) && magnitudes[bin] >= threshold)
+    }
+
+    /// Refines the peak at `bin` of `spectrum`, whose magnitudes are `magnitudes`, to a fractional
+    /// bin.
+    fn refine(&self, spectrum: &[Complex32], magnitudes: &[f32], bin: usize) -> f32 {
+        let (interpolated, _) = peaks::interpolate_peak_log(magnitudes, bin);
+
+        // the phase advance over one hop, beyond that of the bin's center frequency
+        let hop = self.frame.hop_length as f32;
+        let expected = std::f32::consts::TAU * bin as f32 * hop / F::N_FFT as f32;
+        let deviation =
+            peaks::wrap_phase(spectrum[bin].arg() - self.previous[bin].arg() - expected);
+        let measured = bin as f32 + deviation * F::N_FFT as f32 / (std::f32::consts::TAU * hop);
+
+        // a peak that just appeared has no meaningful previous phase
+        if self.previous[bin].norm() > f32::EPSILON && (measured - interpolated).abs() < 0.5 {
+            measured
+        } else {
+            interpolated
+        }
+    }
+}
+
+impl<F: Fft> Default for Tuner<F> {
+    fn default() -> Self {
+        Self::new(440.0)
+    }
+}
+
+processor_builder! {
+    Tuner => TunerBuilder {
+        min_hz: f32 [Hertz, 20.0, 20000.0],
+        max_hz: f32 [Hertz, 20.0, 20000.0],
+        tuning_hz: f32 [Hertz, 400.0, 480.0],
+        min_level_db: f32 [Decibels, -120.0, 0.0],
+        harmonic_db: f32 [Decibels, 0.0, 60.0],
+        display_seconds: f32 [Seconds, 0.0, 2.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for Tuner<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { frequency: f32, note: f32, cents: f32, display: f32, detected: bool }
+    }
+
+    processor_state!(previous, frequency, note, cents, display_cents);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        TunerBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        TunerBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.previous.fill(Complex32::ZERO);
+        self.frequency = 0.0;
+        self.note = 0.0;
+        self.cents = 0.0;
+        self.display_cents = 0.0;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn scratch_size(&self) -> ScratchSize {
+        ScratchSize::new(F::N_REAL_BINS, 0)
+    }
+
+    fn process(&mut self, inputs: ProcessorInputs, outputs: ProcessorOutputs) -> ProcResult<()> {
+        process_with_own_scratch(self, inputs, outputs)
+    }
+
+    fn process_with_scratch(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+        scratch: &mut Scratch,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let coeff = one_pole(self.display_seconds, self.frame.hop_seconds());
+        let magnitudes = scratch.reals(F::N_REAL_BINS);
+
+        for (i, spectrum) in input.iter().enumerate() {
+            let fundamental = self.find_fundamental(spectrum, magnitudes);
+            self.frequency = match fundamental {
+                Some(bin) => {
+                    self.refine(spectrum, magnitudes, bin)
+                        * F::bin_bandwidth(self.frame.sample_rate)
+                }
+                None => 0.0,
+            };
+            self.previous.copy_from_slice(spectrum);
+
+            if self.frequency > 0.0 {
+                let pitch = hz_to_midi(self.frequency, self.tuning_hz);
+                let note = pitch.round();
+                self.cents = 100.0 * (pitch - note);
+                if note != self.note {
+                    // jump straight to a new note instead of sliding across the scale
+                    self.note = note;
+                    self.display_cents = self.cents;
+                } else {
+                    self.display_cents = self.cents + (self.display_cents - self.cents) * coeff;
+                }
+            }
+
+            outputs.set_output_as::<f32>(0, i, &self.frequency)?;
+            outputs.set_output_as::<f32>(1, i, &self.note)?;
+            outputs.set_output_as::<f32>(2, i, &self.cents)?;
+            outputs.set_output_as::<f32>(3, i, &self.display_cents)?;
+            outputs.set_output_as::<bool>(4, i, &(self.frequency > 0.0))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures the spectral centroid, the magnitude-weighted mean frequency, as a measure of
+/// brightness.
+///
+/// The `centroid` output is in Hz. The `brightness` output is the centroid's position between
+/// `min_hz` and `max_hz` on a logarithmic scale, from 0 to 1, ready to drive a parameter such as
+/// the feature input of [`AdaptiveGain`](crate::builtins::filter::AdaptiveGain). Both are smoothed
+/// over `smoothing_seconds`. Frames quieter than `min_level_db` (RMS, measured like
+/// [`FrameEnvelope`](crate::builtins::control::FrameEnvelope)) hold the previous values, so
+/// silence doesn't read as dull; before the first loud frame, both outputs are 0.
+#[derive(Clone)]
+pub struct SpectralCentroid<F: Fft> {
+    pub min_hz: f32,
+    pub max_hz: f32,
+    pub min_level_db: f32,
+    pub smoothing_seconds: f32,
+    frame: FrameInfo,
+    centroid: Option<f32>,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> SpectralCentroid<F> {
+    pub fn new(min_hz: f32, max_hz: f32) -> Self {
+        Self {
+            min_hz,
+            max_hz,
+            min_level_db: -60.0,
+            smoothing_seconds: 0.1,
+            frame: FrameInfo::default(),
+            centroid: None,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the current centroid in Hz, or `None` before the first loud frame.
+    pub fn centroid(&self) -> Option<f32> {
+        self.centroid
+    }
+
+    /// Returns the current brightness, see the type docs.
+    pub fn brightness(&self) -> f32 {
+        let Some(centroid) = self.centroid else {
+            return 0.0;
+        };
+        let min_hz = self.min_hz.max(f32::EPSILON);
+        let octaves = (self.max_hz / min_hz).log2();
+        if octaves <= 0.0 {
+            return 0.0;
+        }
+        ((centroid.max(f32::EPSILON) / min_hz).log2() / octaves).clamp(0.0, 1.0)
+    }
+
+    /// Returns the centroid of `spectrum` in Hz, or `None` if it's too quiet.
+    fn measure(&self, spectrum: &[Complex32]) -> Option<f32> {
+        let bin_hz = F::bin_bandwidth(self.frame.sample_rate);
+        if bin_hz <= 0.0 {
+            return None;
+        }
+
+        // see `FrameEnvelope` for the level measurement
+        let overlap = F::N_FFT as f32 / self.frame.hop_length.max(1) as f32;
+        let last = F::N_REAL_BINS - 1;
+        let mut energy = 0.0;
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+        for (bin, value) in spectrum.iter().enumerate() {
+            let weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+            energy += weight * value.norm_sqr();
+            let magnitude = value.norm();
+            weighted += bin as f32 * magnitude;
+            total += magnitude;
+        }
+        let level_db = units::power_to_db(energy * overlap / F::N_FFT as f32);
+        if level_db < self.min_level_db || total <= 0.0 {
+            return None;
+        }
+        Some(weighted / total * bin_hz)
+    }
+}
+
+impl<F: Fft> Default for SpectralCentroid<F> {
+    fn default() -> Self {
+        Self::new(200.0, 8000.0)
+    }
+}
+
+processor_builder! {
+    SpectralCentroid => SpectralCentroidBuilder {
+        min_hz: f32 [Hertz, 20.0, 20000.0],
+        max_hz: f32 [Hertz, 20.0, 20000.0],
+        min_level_db: f32 [Decibels, -120.0, 0.0],
+        smoothing_seconds: f32 [Seconds, 0.0, 2.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralCentroid<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { centroid: f32, brightness: f32 }
+    }
+
+    processor_state!(centroid);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralCentroidBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralCentroidBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.centroid = None;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let coeff = one_pole(self.smoothing_seconds, self.frame.hop_seconds());
+
+        for (i, spectrum) in input.iter().enumerate() {
+            if let Some(centroid) = self.measure(spectrum) {
+                self.centroid = Some(match self.centroid {
+                    Some(previous) => centroid + (previous - centroid) * coeff,
+                    None => centroid,
+                });
+            }
+
+            outputs.set_output_as::<f32>(0, i, &self.centroid.unwrap_or(0.0))?;
+            outputs.set_output_as::<f32>(1, i, &self.brightness())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Estimates the power spectral density of its input with Welch's method, averaging the
+/// periodograms of overlapping segments, e.g. to measure a noise floor or the response of a
+/// system to noise.
+///
+/// Every frame of the graph is a windowed segment. Segments are taken every
+/// `(1 - overlap) * N_FFT` samples (rounded to whole hops, and at least every hop), and the
+/// `psd` output is the mean of the last `averages` of them, or of all taken so far until there
+/// are that many; `ready` is true from then on. The output is held between segments.
+///
+/// The estimate is one-sided, in squared input units per Hz, and carried in the real part of
+/// every bin (the imaginary parts are zero). Since the graph's window is normalized by its
+/// power, summing it over all bins times the bin bandwidth gives the mean square of the input
+/// whatever the window, e.g. white noise of variance `v` reads `2 v / sample_rate` in every bin
+/// but DC and Nyquist.
+#[derive(Clone)]
+pub struct WelchPsd<F: Fft> {
+    pub averages: usize,
+    pub overlap: f32,
+    frame: FrameInfo,
+    history: FrameHistory<Vec<f32>>,
+    // the number of segments in the history
+    segments: usize,
+    // the number of frames since the last segment was taken
+    since_segment: usize,
+    periodogram: Vec<f32>,
+    psd: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> WelchPsd<F> {
+    pub fn new(averages: usize, overlap: f32) -> Self {
+        let mut welch = Self {
+            averages,
+            overlap,
+            frame: FrameInfo::default(),
+            history: FrameHistory::new(1),
+            segments: 0,
+            since_segment: usize::MAX,
+            periodogram: vec![0.0; F::N_REAL_BINS],
+            psd: vec![0.0; F::N_REAL_BINS],
+            out_signal: Box::new(F::RealFft::default()),
+        };
+        welch.update_history();
+        welch
+    }
+
+    /// Returns the current estimate, one value per bin, see the type docs.
+    pub fn psd(&self) -> &[f32] {
+        &self.psd
+    }
+
+    /// Returns whether `averages` segments have been averaged.
+    pub fn is_ready(&self) -> bool {
+        self.segments >= self.history.len()
+    }
+
+    /// Resizes the history if `averages` changed, which allocates.
+    fn update_history(&mut self) {
+        let averages = self.averages.max(1);
+        if averages != self.history.len() {
+            self.history = FrameHistory::new(averages);
+            for age in 0..averages {
+                self.history.get_mut(age).resize(F::N_REAL_BINS, 0.0);
+            }
+            self.segments = 0;
+            self.since_segment = usize::MAX;
+        }
+    }
+
+    /// Returns the number of frames between two segments.
+    fn stride(&self) -> usize {
+        let step = (1.0 - self.overlap.clamp(0.0, 1.0)) * F::N_FFT as f32;
+        (step / self.frame.hop_length.max(1) as f32)
+            .round()
+            .max(1.0) as usize
+    }
+
+    /// Takes `spectrum` as the newest segment and averages the history into the estimate.
+    fn add_segment(&mut self, spectrum: &[Complex32]) {
+        let sample_rate = self.frame.sample_rate;
+        if sample_rate <= 0.0 {
+            return;
+        }
+        // see `FrameEnvelope` for the scaling
+        let overlap = F::N_FFT as f32 / self.frame.hop_length.max(1) as f32;
+        let last = F::N_REAL_BINS - 1;
+
+        for (bin, (power, value)) in self.periodogram.iter_mut().zip(spectrum).enumerate() {
+            let weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+            *power = weight * value.norm_sqr() * overlap / sample_rate;
+        }
+        if self.frame.flush_denormals {
+            flush_real_denormals(&mut self.periodogram);
+        }
+        self.history.push(&self.periodogram);
+
+        self.segments = (self.segments + 1).min(self.history.len());
+        self.psd.fill(0.0);
+        for segment in self.history.iter().take(self.segments) {
+            for (psd, power) in self.psd.iter_mut().zip(segment) {
+                *psd += power;
+            }
+        }
+        let scale = 1.0 / self.segments as f32;
+        for psd in &mut self.psd {
+            *psd *= scale;
+        }
+        if self.frame.flush_denormals {
+            flush_real_denormals(&mut self.psd);
+        }
+    }
+}
+
+impl<F: Fft> Default for WelchPsd<F> {
+    fn default() -> Self {
+        Self::new(8, 0.5)
+    }
+}
+
+processor_builder! {
+    WelchPsd => WelchPsdBuilder {
+        averages: usize [Frames, 1.0, 256.0],
+        overlap: f32 [None, 0.0, 0.95],
+    }
+}
+
+impl<F: Fft> FftProcessor for WelchPsd<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { psd: F::RealFft, ready: bool }
+    }
+
+    processor_state!(history, segments, since_segment, psd);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        WelchPsdBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        WelchPsdBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.update_history();
+        self.history.clear();
+        for age in 0..self.history.len() {
+            self.history.get_mut(age).resize(F::N_REAL_BINS, 0.0);
+        }
+        self.segments = 0;
+        self.since_segment = usize::MAX;
+        self.psd.fill(0.0);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        self.update_history();
+        let stride = self.stride();
+
+        for (i, spectrum) in input.iter().enumerate() {
+            if self.since_segment >= stride {
+                self.add_segment(spectrum);
+                self.since_segment = 0;
+            }
+            self.since_segment = self.since_segment.saturating_add(1);
+
+            for (out, psd) in self.out_signal.iter_mut().zip(&self.psd) {
+                *out = Complex32::new(*psd, 0.0);
+            }
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<bool>(1, i, &self.is_ready())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Averages spectra over time like a real-time analyzer, for displaying a steady spectrum.
+///
+/// Every bin's power follows the input with a one-pole filter, with a time constant of
+/// `attack_ms` while rising and `decay_ms` while falling; a short attack and a long decay show
+/// transients while keeping the display readable. The `average` output carries the magnitude of
+/// the averaged power in the real part of every bin.
+///
+/// With `peak_hold` on, the `peak` output holds the highest average of every bin for `hold_ms`,
+/// then lets it fall with the decay time constant (or keeps it forever if `hold_ms` is 0). With it
+/// off, `peak` is the same as `average`.
+#[derive(Clone)]
+pub struct SpectrumAverage<F: Fft> {
+    pub attack_ms: f32,
+    pub decay_ms: f32,
+    pub peak_hold: bool,
+    pub hold_ms: f32,
+    frame: FrameInfo,
+    average: Vec<f32>,
+    peak: Vec<f32>,
+    // the time each peak has been held for, in seconds
+    held: Vec<f32>,
+    out_average: Box<F::RealFft>,
+    out_peak: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectrumAverage<F> {
+    pub fn new(attack_ms: f32, decay_ms: f32) -> Self {
+        Self {
+            attack_ms,
+            decay_ms,
+            peak_hold: false,
+            hold_ms: 1000.0,
+            frame: FrameInfo::default(),
+            average: vec![0.0; F::N_REAL_BINS],
+            peak: vec![0.0; F::N_REAL_BINS],
+            held: vec![0.0; F::N_REAL_BINS],
+            out_average: Box::new(F::RealFft::default()),
+            out_peak: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Enables peak hold, holding peaks for `hold_ms` (forever if 0).
+    pub fn with_peak_hold(mut self, hold_ms: f32) -> Self {
+        self.peak_hold = true;
+        self.hold_ms = hold_ms;
+        self
+    }
+}
+
+impl<F: Fft> Default for SpectrumAverage<F> {
+    fn default() -> Self {
+        Self::new(100.0, 1000.0)
+    }
+}
+
+processor_builder! {
+    SpectrumAverage => SpectrumAverageBuilder {
+        attack_ms: f32 [Milliseconds, 0.0, 10000.0],
+        decay_ms: f32 [Milliseconds, 0.0, 10000.0],
+        peak_hold: bool [Toggle, 0.0, 1.0],
+        hold_ms: f32 [Milliseconds, 0.0, 10000.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectrumAverage<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { average: F::RealFft, peak: F::RealFft }
+    }
+
+    processor_state!(average, peak, held);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectrumAverageBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectrumAverageBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.average.fill(0.0);
+        self.peak.fill(0.0);
+        self.held.fill(0.0);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let hop_seconds = self.frame.hop_seconds();
+        let attack = one_pole(self.attack_ms / 1000.0, hop_seconds);
+        let decay = one_pole(self.decay_ms / 1000.0, hop_seconds);
+        let hold_seconds = self.hold_ms / 1000.0;
+        let flush_denormals = self.frame.flush_denormals;
+
+        for (i, spectrum) in input.iter().enumerate() {
+            for (bin, value) in spectrum.iter().enumerate() {
+                let power = value.norm_sqr();
+                let average = &mut self.average[bin];
+                let coeff = if power > *average { attack } else { decay };
+                *average = power + (*average - power) * coeff;
+                if flush_denormals {
+                    *average = flush_denormal(*average);
+                }
+
+                let peak = &mut self.peak[bin];
+                let held = &mut self.held[bin];
+                if !self.peak_hold || *average >= *peak {
+                    *peak = *average;
+                    *held = 0.0;
+                } else if hold_seconds > 0.0 {
+                    if *held >= hold_seconds {
+                        *peak = *average + (*peak - *average) * decay;
+                        if flush_denormals {
+                            *peak = flush_denormal(*peak);
+                        }
+                    } else {
+                        *held += hop_seconds;
+                    }
+                }
+
+                self.out_average[bin] = Complex32::new(average.sqrt(), 0.0);
+                self.out_peak[bin] = Complex32::new(peak.sqrt(), 0.0);
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_average)?;
+            outputs.set_output_as::<F::RealFft>(1, i, &*self.out_peak)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures the level of fractional-octave bands like a real-time analyzer, as a higher-level
+/// alternative to individual bins for measurements.
+///
+/// The bands are laid out by [`OctaveBands`] with standard (IEC 61260) center frequencies
+/// between `min_hz` and `max_hz`, in octaves, third octaves or sixth octaves. The layout is
+/// computed when the processor is allocated, and again (allocating) if a parameter changes while
+/// running. Every band's power is smoothed over `smoothing_seconds` (0.125 s is the "fast" time
+/// weighting of sound level meters) and converted to an RMS level in dB, measured like
+/// [`FrameEnvelope`](crate::builtins::control::FrameEnvelope), so a full-scale sine in a band
+/// reads -3 dB.
+///
+/// The `levels` output carries the level of band `i` in the real part of entry `i` of a frame,
+/// and zeros after the last band; bands that don't fit in a frame are left out. The bands' center
+/// frequencies are given by [`centers_hz`](Self::centers_hz).
+#[derive(Clone)]
+pub struct OctaveAnalyzer<F: Fft> {
+    pub fraction: OctaveFraction,
+    pub min_hz: f32,
+    pub max_hz: f32,
+    pub smoothing_seconds: f32,
+    frame: FrameInfo,
+    layout: OctaveBands,
+    // the settings the layout was computed for
+    layout_settings: (OctaveFraction, f32, f32, f32),
+    frame_powers: Vec<f32>,
+    powers: Vec<f32>,
+    levels_db: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> OctaveAnalyzer<F> {
+    pub fn new(fraction: OctaveFraction) -> Self {
+        Self {
+            fraction,
+            min_hz: 20.0,
+            max_hz: 20000.0,
+            smoothing_seconds: 0.125,
+            frame: FrameInfo::default(),
+            // replaced once the sample rate is known
+            layout: OctaveBands::new(fraction, 0.0, 0.0, 48_000.0, F::N_FFT),
+            layout_settings: (fraction, 0.0, 0.0, 0.0),
+            frame_powers: Vec::new(),
+            powers: Vec::new(),
+            levels_db: Vec::new(),
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the exact center frequency of every band, in Hz.
+    pub fn centers_hz(&self) -> &[f32] {
+        self.layout.centers_hz()
+    }
+
+    /// Returns the current level of every band, in dB.
+    pub fn levels_db(&self) -> &[f32] {
+        &self.levels_db
+    }
+
+    /// Recomputes the layout if the parameters or the sample rate changed, which allocates.
+    fn update_layout(&mut self, sample_rate: f32) {
+        let settings = (self.fraction, self.min_hz, self.max_hz, sample_rate);
+        if settings == self.layout_settings || sample_rate <= 0.0 {
+            return;
+        }
+        self.layout = OctaveBands::new(
+            self.fraction,
+            self.min_hz,
+            self.max_hz,
+            sample_rate,
+            F::N_FFT,
+        );
+        self.layout_settings = settings;
+        let bands = self.layout.len();
+        self.frame_powers = vec![0.0; bands];
+        self.powers = vec![0.0; bands];
+        self.levels_db = vec![units::power_to_db(0.0); bands];
+    }
+}
+
+impl<F: Fft> Default for OctaveAnalyzer<F> {
+    fn default() -> Self {
+        Self::new(OctaveFraction::Third)
+    }
+}
+
+processor_builder! {
+    OctaveAnalyzer => OctaveAnalyzerBuilder {
+        fraction: OctaveFraction [Choice, 0.0, 2.0],
+        min_hz: f32 [Hertz, 10.0, 20000.0],
+        max_hz: f32 [Hertz, 10.0, 24000.0],
+        smoothing_seconds: f32 [Seconds, 0.0, 2.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for OctaveAnalyzer<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { levels: F::RealFft }
+    }
+
+    processor_state!(powers, levels_db);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        OctaveAnalyzerBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        OctaveAnalyzerBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.update_layout(sample_rate);
+        self.powers.fill(0.0);
+        self.levels_db.fill(units::power_to_db(0.0));
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        self.update_layout(self.frame.sample_rate);
+        let coeff = one_pole(self.smoothing_seconds, self.frame.hop_seconds());
+        // see `FrameEnvelope` for the level measurement
+        let overlap = F::N_FFT as f32 / self.frame.hop_length.max(1) as f32;
+
+        for (i, spectrum) in input.iter().enumerate() {
+            self.layout.powers(spectrum, &mut self.frame_powers);
+            for ((power, level_db), frame_power) in self
+                .powers
+                .iter_mut()
+                .zip(self.levels_db.iter_mut())
+                .zip(&self.frame_powers)
+            {
+                let frame_power = frame_power * overlap / F::N_FFT as f32;
+                *power = frame_power + (*power - frame_power) * coeff;
+                if self.frame.flush_denormals {
+                    *power = flush_denormal(*power);
+                }
+                *level_db = units::power_to_db(*power);
+            }
+
+            self.out_signal.fill(Complex32::ZERO);
+            for (out, level_db) in self.out_signal.iter_mut().zip(&self.levels_db) {
+                *out = Complex32::new(*level_db, 0.0);
+            }
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The row and column tone frequencies of DTMF (touch-tone) signalling, in Hz.
+pub const DTMF_HZ: [f32; 8] = [697.0, 770.0, 852.0, 941.0, 1209.0, 1336.0, 1477.0, 1633.0];
+
+/// Evaluates the spectrum of time-domain frames at a few arbitrary frequencies with the
+/// Goertzel algorithm, for when a full transform is overkill (DTMF detection, tracking a single
+/// tone).
+///
+/// The input is a frame of audio, such as the windowed frames of an audio input (see
+/// [`FftGraph::audio_input_frames`](crate::graph::FftGraph::audio_input_frames)), which are
+/// windowed by the graph's window. For every frequency in `frequencies_hz`, the outputs
+/// `magnitude{i}` and `phase{i}` carry the magnitude and phase (in radians, relative to the
+/// start of the frame) of the frame's spectrum there. At the center of a bin these match the
+/// bin of an unscaled [`RealFft`](crate::builtins::transforms::RealFft), so thresholds carry
+/// over from spectra, and between bins they don't snap to the nearest one.
+///
+/// Every frequency costs one multiply-add per sample, so a handful of them is cheaper than the
+/// FFT. The frequencies are set by the `freq{i}` parameters.
+#[derive(Clone)]
+pub struct GoertzelBank<F: Fft> {
+    frequencies_hz: Vec<f32>,
+    frame: FrameInfo,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> GoertzelBank<F> {
+    pub fn new(frequencies_hz: Vec<f32>) -> Self {
+        Self {
+            frequencies_hz,
+            frame: FrameInfo::default(),
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates a bank evaluating the eight DTMF frequencies, see [`DTMF_HZ`].
+    pub fn dtmf() -> Self {
+        Self::new(DTMF_HZ.to_vec())
+    }
+
+    pub fn frequencies_hz(&self) -> &[f32] {
+        &self.frequencies_hz
+    }
+
+    /// Returns the spectrum of `frame` at `frequency_hz`.
+    fn evaluate(&self, frame: &[f32], frequency_hz: f32) -> Complex32 {
+        let sample_rate = self.frame.sample_rate;
+        if sample_rate <= 0.0 || frame.is_empty() {
+            return Complex32::ZERO;
+        }
+        let omega = std::f64::consts::TAU * frequency_hz as f64 / sample_rate as f64;
+        let coeff = 2.0 * omega.cos();
+        let (mut s1, mut s2) = (0.0f64, 0.0f64);
+        for &sample in frame {
+            let s0 = sample as f64 + coeff * s1 - s2;
+            s2 = s1;
+            s1 = s0;
+        }
+        // `s1 - e^(-jω) s2` is the spectrum relative to the end of the frame
+        let end = num_complex::Complex64::new(s1 - s2 * omega.cos(), s2 * omega.sin());
+        let start =
+            end * num_complex::Complex64::from_polar(1.0, -omega * (frame.len() - 1) as f64);
+        Complex32::new(start.re as f32, start.im as f32)
+    }
+}
+
+impl<F: Fft> Default for GoertzelBank<F> {
+    fn default() -> Self {
+        Self::dtmf()
+    }
+}
+
+impl<F: Fft> FftProcessor for GoertzelBank<F> {
+    processor_clone!();
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        vec![SignalSpec::new("input", F::AudioBlock::signal_type())]
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        let magnitudes = (0..self.frequencies_hz.len())
+            .map(|i| SignalSpec::new(format!("magnitude{i}"), f32::signal_type()));
+        let phases = (0..self.frequencies_hz.len())
+            .map(|i| SignalSpec::new(format!("phase{i}"), f32::signal_type()));
+        magnitudes.chain(phases).collect()
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        (0..2 * self.frequencies_hz.len())
+            .map(|_| AnyBuffer::zeros::<f32>(size))
+            .collect()
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        self.frequencies_hz
+            .iter()
+            .enumerate()
+            .map(|(i, &hz)| ParamSpec::new(format!("freq{i}"), ParamUnit::Hertz, 0.0, 24000.0, hz))
+            .collect()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let frequency = name
+            .strip_prefix("freq")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| self.frequencies_hz.get_mut(index));
+        match frequency {
+            Some(frequency) => {
+                *frequency = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::AudioBlock>(0).unwrap();
+        let count = self.frequencies_hz.len();
+
+        for (i, frame) in input.iter().enumerate() {
+            for (index, &frequency_hz) in self.frequencies_hz.iter().enumerate() {
+                let value = self.evaluate(frame, frequency_hz);
+                outputs.set_output_as::<f32>(index, i, &value.norm())?;
+                outputs.set_output_as::<f32>(count + index, i, &value.arg())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Analyzes spectra with a continuous wavelet transform, for transients that the STFT's fixed
+/// resolution smears out (see [`wavelet`](crate::wavelet)).
+///
+/// The transform has a fixed number of scales, at most `N / 2 + 1`, whose center frequencies are
+/// spaced logarithmically from `min_hz` to `max_hz` (see [`frequencies_hz`](Self::frequencies_hz)).
+/// Every frame, bin `i` of the `scales` output holds the square root of the energy of the
+/// coefficients of scale `i` over the frame, and the bins after the last scale are zero. The
+/// output is a column of a scale × time matrix, which a [block processor](crate::block) reads as
+/// a [`FrameBlock`](crate::block::FrameBlock) of scales over the last few hops.
+///
+/// The energies are summed over the spectrum, without transforming back to the time domain. They
+/// are twice as high for an analytic `wavelet` as for a real one, whose coefficients are only the
+/// real part of the analytic ones.
+#[derive(Clone)]
+pub struct ContinuousWaveletTransform<F: Fft> {
+    pub wavelet: ContinuousWavelet,
+    pub min_hz: f32,
+    pub max_hz: f32,
+    scales: usize,
+    frame: FrameInfo,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> ContinuousWaveletTransform<F> {
+    /// Creates a transform of `scales` scales, at most one per bin.
+    pub fn new(scales: usize) -> Self {
+        Self {
+            wavelet: ContinuousWavelet::default(),
+            min_hz: 50.0,
+            max_hz: 12800.0,
+            scales: scales.min(F::N_REAL_BINS),
+            frame: FrameInfo::default(),
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn scales(&self) -> usize {
+        self.scales
+    }
+
+    /// Returns the center frequency of every scale, in Hz.
+    pub fn frequencies_hz(&self) -> Vec<f32> {
+        cwt_frequencies_hz(self.scales, self.min_hz, self.max_hz)
+    }
+}
+
+impl<F: Fft> Default for ContinuousWaveletTransform<F> {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+processor_builder! {
+    ContinuousWaveletTransform => ContinuousWaveletTransformBuilder {
+        wavelet: ContinuousWavelet [Choice, 0.0, (ContinuousWavelet::ALL.len() - 1) as f32],
+        min_hz: f32 [Hertz, 10.0, 24000.0],
+        max_hz: f32 [Hertz, 10.0, 24000.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for ContinuousWaveletTransform<F> {
+    processor_clone!();
+
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { scales: F::RealFft }
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        ContinuousWaveletTransformBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        ContinuousWaveletTransformBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let bin_hz = F::bin_bandwidth(self.frame.sample_rate);
+        // by Parseval's theorem, the energy of the coefficients is that of the filtered spectrum,
+        // whose positive frequencies are doubled in the analytic signal, and mirrored to the
+        // negative ones for a real wavelet
+        let weight = if self.wavelet.is_analytic() { 4.0 } else { 2.0 };
+        let norm = 1.0 / F::N_FFT as f32;
+
+        for (i, spectrum) in input.iter().enumerate() {
+            self.out_signal.fill(Complex32::ZERO);
+            for (scale, out) in self.out_signal.iter_mut().take(self.scales).enumerate() {
+                let center_hz = cwt_frequency_hz(scale, self.scales, self.min_hz, self.max_hz);
+                let mut energy = 0.0;
+                for (bin, value) in spectrum.iter().enumerate() {
+                    let response = self.wavelet.response(bin as f32 * bin_hz / center_hz);
+                    let power = value.norm_sqr() * response * response;
+                    energy += if bin == 0 || bin == F::N_FFT / 2 {
+                        power
+                    } else {
+                        weight * power
+                    };
+                }
+                *out = Complex32::new((energy * norm).sqrt(), 0.0);
+            }
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decomposes time-domain frames with a discrete wavelet transform into octave bands, for
+/// transient-focused analysis (see [`wavelet`](crate::wavelet)).
+///
+/// The input is a frame of audio, such as the windowed frames of an audio input (see
+/// [`FftGraph::audio_input_frames`](crate::graph::FftGraph::audio_input_frames)). Level `j`
+/// (from 1) holds the detail between `sample_rate / 2^(j + 1)` and `sample_rate / 2^j`, and the
+/// approximation after the last level holds the rest. Every frame, bin `j - 1` of the `levels`
+/// output holds the square root of the energy of the coefficients of level `j`, bin `levels`
+/// that of the approximation, and the bins after it are zero. The wavelets are orthogonal, so the
+/// squares of the bins add up to the energy of the frame.
+///
+/// The output is a column of a scale × time matrix, which a [block processor](crate::block) reads
+/// as a [`FrameBlock`](crate::block::FrameBlock) of levels over the last few hops.
+#[derive(Clone)]
+pub struct DiscreteWaveletTransform<F: Fft> {
+    pub wavelet: DiscreteWavelet,
+    levels: usize,
+    approximation: Vec<f32>,
+    next: Vec<f32>,
+    detail: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> DiscreteWaveletTransform<F> {
+    /// Creates a transform of `levels` levels, at most [`max_dwt_levels`] of the FFT length.
+    pub fn new(levels: usize) -> Self {
+        Self {
+            wavelet: DiscreteWavelet::default(),
+            levels: levels.min(max_dwt_levels(F::N_FFT)),
+            approximation: vec![0.0; F::N_FFT],
+            next: vec![0.0; F::N_FFT / 2],
+            detail: vec![0.0; F::N_FFT / 2],
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+}
+
+impl<F: Fft> Default for DiscreteWaveletTransform<F> {
+    fn default() -> Self {
+        Self::new(6)
+    }
+}
+
+/// Returns the square root of the energy of `coefficients`.
+fn coefficient_magnitude(coefficients: &[f32]) -> Complex32 {
+    let energy = coefficients.iter().map(|c| c * c).sum::<f32>();
+    Complex32::new(energy.sqrt(), 0.0)
+}
+
+processor_builder! {
+    DiscreteWaveletTransform => DiscreteWaveletTransformBuilder {
+        wavelet: DiscreteWavelet [Choice, 0.0, (DiscreteWavelet::ALL.len() - 1) as f32],
+    }
+}
+
+impl<F: Fft> FftProcessor for DiscreteWaveletTransform<F> {
+    processor_clone!();
+
+    fft_processor_io! {
+        inputs { input: F::AudioBlock }
+        outputs { levels: F::RealFft }
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        DiscreteWaveletTransformBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        DiscreteWaveletTransformBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::AudioBlock>(0).unwrap();
+
+        for (i, frame) in input.iter().enumerate() {
+            self.approximation.copy_from_slice(frame);
+            self.out_signal.fill(Complex32::ZERO);
+            let mut length = F::N_FFT;
+            for level in 0..self.levels {
+                let half = length / 2;
+                dwt_step(
+                    self.wavelet,
+                    &self.approximation[..length],
+                    &mut self.next[..half],
+                    &mut self.detail[..half],
+                );
+                self.approximation[..half].copy_from_slice(&self.next[..half]);
+                self.out_signal[level] = coefficient_magnitude(&self.detail[..half]);
+                length = half;
+            }
+            self.out_signal[self.levels] = coefficient_magnitude(&self.approximation[..length]);
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,540 @@
+//! Control-rate sources producing one `f32` per frame, for modulating the parameter inputs of
+//! other processors from within the graph.
+
+use std::f32::consts::TAU;
+
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    denormal::flush_denormal,
+    fft_processor_io,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    random::{FrameRng, stream_seed},
+    signal::Fft,
+};
+
+/// The waveform of a [`FrameLfo`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    /// Rises from -1 to 1 over each cycle.
+    Saw,
+    Square,
+}
+
+impl LfoShape {
+    pub const ALL: [LfoShape; 4] = [Self::Sine, Self::Triangle, Self::Saw, Self::Square];
+
+    /// Looks up a shape by its lowercase name (e.g. `"triangle"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sine" => Some(Self::Sine),
+            "triangle" => Some(Self::Triangle),
+            "saw" => Some(Self::Saw),
+            "square" => Some(Self::Square),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the waveform at `phase` (in cycles, `0..1`), between -1 and 1.
+    pub fn value(&self, phase: f32) -> f32 {
+        match self {
+            Self::Sine => (TAU * phase).sin(),
+            Self::Triangle => 4.0 * ((phase - 0.25).rem_euclid(1.0) - 0.5).abs() - 1.0,
+            Self::Saw => 2.0 * phase - 1.0,
+            Self::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+/// A low-frequency oscillator advancing once per hop.
+///
+/// Outputs `offset + depth * shape(phase)`. The `rate`, `depth` and `offset` inputs override the
+/// corresponding fields while connected. Rates above half the frame rate alias.
+///
+/// With `sync_beats` set, the oscillator instead completes one cycle every `sync_beats` beats of
+/// the graph's [`Transport`](crate::processor::Transport), ignoring `rate_hz` and the `rate`
+/// input. While the transport plays, the phase follows its position, so the oscillator stays
+/// aligned with the beat after jumps; otherwise it keeps running at the current tempo.
+#[derive(Clone)]
+pub struct FrameLfo<F: Fft> {
+    pub shape: LfoShape,
+    pub rate_hz: f32,
+    pub sync_beats: Option<f32>,
+    pub depth: f32,
+    pub offset: f32,
+    /// The phase the oscillator starts at when allocated, in cycles.
+    pub start_phase: f32,
+    phase: f32,
+    frame: FrameInfo,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> FrameLfo<F> {
+    pub fn new(shape: LfoShape, rate_hz: f32) -> Self {
+        Self {
+            shape,
+            rate_hz,
+            sync_beats: None,
+            depth: 1.0,
+            offset: 0.0,
+            start_phase: 0.0,
+            phase: 0.0,
+            frame: FrameInfo::default(),
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the current phase in cycles.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+}
+
+impl<F: Fft> Default for FrameLfo<F> {
+    fn default() -> Self {
+        Self::new(LfoShape::Sine, 1.0)
+    }
+}
+
+processor_builder! {
+    FrameLfo => FrameLfoBuilder {
+        shape: LfoShape [Choice, 0.0, (LfoShape::ALL.len() - 1) as f32],
+        rate_hz: f32 [Hertz, 0.0, 20.0],
+        sync_beats: Option<f32> [Beats, 0.0, 64.0, None],
+        depth: f32 [None, -1.0, 1.0],
+        offset: f32 [None, -1.0, 1.0],
+        start_phase: f32 [None, 0.0, 1.0, None],
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameLfo<F> {
+    fft_processor_io! {
+        inputs { rate: f32, depth: f32, offset: f32 }
+        outputs { output: f32 }
+    }
+
+    processor_state!(phase);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        FrameLfoBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        FrameLfoBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.phase = self.start_phase.rem_euclid(1.0);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = |index: usize, default: f32| {
+            inputs
+                .input_as::<f32>(index)
+                .and_then(|values| values.first().copied())
+                .unwrap_or(default)
+        };
+        let depth = input(1, self.depth);
+        let offset = input(2, self.offset);
+
+        let transport = self.frame.transport;
+        let rate_hz = match self.sync_beats {
+            Some(beats) if beats > 0.0 => {
+                if transport.playing {
+                    let cycles = transport.beat / beats as f64 + self.start_phase as f64;
+                    self.phase = cycles.rem_euclid(1.0) as f32;
+                }
+                1.0 / transport.beats_to_seconds(beats)
+            }
+            _ => input(0, self.rate_hz),
+        };
+
+        let value = offset + depth * self.shape.value(self.phase);
+        outputs.set_output_as::<f32>(0, 0, &value)?;
+
+        self.phase = (self.phase + rate_hz * self.frame.hop_seconds()).rem_euclid(1.0);
+
+        Ok(())
+    }
+}
+
+/// Follows the level of a spectrum, outputting its RMS amplitude.
+///
+/// The level rises with a time constant of `attack_seconds` and falls with one of
+/// `release_seconds`. The level is compensated for the graph's window and overlap, so a full-scale
+/// sine reads as about 0.7, the RMS of the sine itself.
+#[derive(Clone)]
+pub struct FrameEnvelope<F: Fft> {
+    pub attack_seconds: f32,
+    pub release_seconds: f32,
+    level: f32,
+    frame: FrameInfo,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> FrameEnvelope<F> {
+    pub fn new(attack_seconds: f32, release_seconds: f32) -> Self {
+        Self {
+            attack_seconds,
+            release_seconds,
+            level: 0.0,
+            frame: FrameInfo::default(),
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the current level.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    fn coeff(&self, seconds: f32) -> f32 {
+        let hop_seconds = self.frame.hop_seconds();
+        if seconds <= 0.0 || hop_seconds <= 0.0 {
+            0.0
+        } else {
+            (-hop_seconds / seconds).exp()
+        }
+    }
+}
+
+impl<F: Fft> Default for FrameEnvelope<F> {
+    fn default() -> Self {
+        Self::new(0.01, 0.2)
+    }
+}
+
+processor_builder! {
+    FrameEnvelope => FrameEnvelopeBuilder {
+        attack_seconds: f32 [Seconds, 0.0, 2.0],
+        release_seconds: f32 [Seconds, 0.0, 10.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameEnvelope<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: f32 }
+    }
+
+    processor_state!(level);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        FrameEnvelopeBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        FrameEnvelopeBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.level = 0.0;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        // the STFT window is normalized so its energy summed over all overlapping frames is one
+        let overlap = self.frame.fft_length as f32 / self.frame.hop_length.max(1) as f32;
+
+        for (i, spectrum) in input.iter().enumerate() {
+            // Parseval's theorem for the one-sided spectrum: every bin but DC and Nyquist stands
+            // for two bins of the full spectrum
+            let last = spectrum.len() - 1;
+            let energy: f32 = spectrum
+                .iter()
+                .enumerate()
+                .map(|(bin, value)| {
+                    let weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+                    weight * value.norm_sqr()
+                })
+                .sum();
+            let rms = (energy * overlap / F::N_FFT as f32).sqrt();
+
+            let coeff = if rms > self.level {
+                self.coeff(self.attack_seconds)
+            } else {
+                self.coeff(self.release_seconds)
+            };
+            self.level = rms + (self.level - rms) * coeff;
+            if self.frame.flush_denormals {
+                self.level = flush_denormal(self.level);
+            }
+
+            outputs.set_output_as::<f32>(0, i, &self.level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Outputs random values between `min` and `max`, drawing a new one `rate_hz` times per second.
+///
+/// With a `glide_seconds` above zero the output moves towards each new value with that time
+/// constant instead of jumping. The values are reproducible: they only depend on `seed`, the
+/// graph's seed and the frames they're drawn at, see [`random`](crate::random). The `rate` input
+/// overrides `rate_hz` while connected.
+///
+/// With `sync_beats` set, a new value is drawn every `sync_beats` beats of the graph's
+/// [`Transport`](crate::processor::Transport) instead, ignoring `rate_hz` and the `rate` input.
+/// While the transport plays, draws land on multiples of `sync_beats`.
+#[derive(Clone)]
+pub struct FrameRandom<F: Fft> {
+    pub rate_hz: f32,
+    pub sync_beats: Option<f32>,
+    pub min: f32,
+    pub max: f32,
+    pub glide_seconds: f32,
+    pub seed: u32,
+    rng: FrameRng,
+    // whether the first value has been drawn since the processor was allocated
+    started: bool,
+    // progress towards the next draw, in draws
+    phase: f32,
+    target: f32,
+    value: f32,
+    frame: FrameInfo,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> FrameRandom<F> {
+    pub fn new(rate_hz: f32, min: f32, max: f32) -> Self {
+        Self {
+            rate_hz,
+            sync_beats: None,
+            min,
+            max,
+            glide_seconds: 0.0,
+            seed: 0x2545_f491,
+            rng: FrameRng::default(),
+            started: false,
+            phase: 0.0,
+            target: 0.0,
+            value: 0.0,
+            frame: FrameInfo::default(),
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    fn draw(&mut self) {
+        self.target = self.rng.next_range(self.min, self.max);
+    }
+}
+
+impl<F: Fft> Default for FrameRandom<F> {
+    fn default() -> Self {
+        Self::new(1.0, 0.0, 1.0)
+    }
+}
+
+processor_builder! {
+    FrameRandom => FrameRandomBuilder {
+        rate_hz: f32 [Hertz, 0.0, 20.0],
+        sync_beats: Option<f32> [Beats, 0.0, 64.0, None],
+        min: f32 [None, -1.0, 1.0],
+        max: f32 [None, -1.0, 1.0],
+        glide_seconds: f32 [Seconds, 0.0, 10.0],
+        seed: u32 [None, 0.0, u32::MAX as f32],
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameRandom<F> {
+    fft_processor_io! {
+        inputs { rate: f32 }
+        outputs { output: f32 }
+    }
+
+    processor_state!(started, phase, target, value);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        FrameRandomBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        FrameRandomBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.started = false;
+        self.phase = 0.0;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+        self.rng = FrameRng::new(stream_seed(info.seed, self.seed as u64));
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let hop_seconds = self.frame.hop_seconds();
+        let transport = self.frame.transport;
+
+        // the first value is drawn at the first frame rather than on allocation, so it depends
+        // on the graph's seed too
+        if !self.started {
+            self.draw();
+            self.value = self.target;
+            self.started = true;
+        }
+
+        match self.sync_beats {
+            Some(beats) if beats > 0.0 && transport.playing => {
+                // draw whenever the position crosses a multiple of `beats`
+                let phase = (transport.beat / beats as f64).rem_euclid(1.0) as f32;
+                if phase < self.phase {
+                    self.draw();
+                }
+                self.phase = phase;
+            }
+            sync_beats => {
+                let rate_hz = match sync_beats {
+                    Some(beats) if beats > 0.0 => 1.0 / transport.beats_to_seconds(beats),
+                    _ => inputs
+                        .input_as::<f32>(0)
+                        .and_then(|values| values.first().copied())
+                        .unwrap_or(self.rate_hz),
+                };
+                self.phase += rate_hz.max(0.0) * hop_seconds;
+                if self.phase >= 1.0 {
+                    self.phase = self.phase.fract();
+                    self.draw();
+                }
+            }
+        }
+
+        if self.glide_seconds > 0.0 && hop_seconds > 0.0 {
+            let coeff = (-hop_seconds / self.glide_seconds).exp();
+            self.value = self.target + (self.value - self.target) * coeff;
+            if self.frame.flush_denormals {
+                self.value = flush_denormal(self.value);
+            }
+        } else {
+            self.value = self.target;
+        }
+
+        outputs.set_output_as::<f32>(0, 0, &self.value)?;
+
+        Ok(())
+    }
+}
+
+/// Follows the graph's [`Transport`](crate::processor::Transport), for driving triggers, counters
+/// and samplers in time with the host.
+///
+/// Outputs the position in beats, the phase within the current division of `division` beats
+/// (`0..1`), a trigger on the first frame of every division while the transport plays, the
+/// playing state as a gate, and the tempo in BPM. The position is output as an `f32`, so it loses
+/// precision after a few hours of beats; use the phase for anything that repeats.
+#[derive(Clone)]
+pub struct BeatClock<F: Fft> {
+    pub division: f32,
+    last_division: Option<i64>,
+    frame: FrameInfo,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> BeatClock<F> {
+    pub fn new(division: f32) -> Self {
+        Self {
+            division,
+            last_division: None,
+            frame: FrameInfo::default(),
+            _f: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<F: Fft> Default for BeatClock<F> {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+processor_builder! {
+    BeatClock => BeatClockBuilder {
+        division: f32 [Beats, 0.0, 64.0, None],
+    }
+}
+
+impl<F: Fft> FftProcessor for BeatClock<F> {
+    fft_processor_io! {
+        inputs {}
+        outputs { beat: f32, phase: f32, tick: bool, playing: bool, bpm: f32 }
+    }
+
+    processor_state!(last_division);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        BeatClockBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        BeatClockBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.last_division = None;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        _inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let transport = self.frame.transport;
+        let divisions = transport.beat / self.division.max(f32::EPSILON) as f64;
+        let division = divisions.floor() as i64;
+
+        let tick = transport.playing && self.last_division != Some(division);
+        self.last_division = transport.playing.then_some(division);
+
+        outputs.set_output_as::<f32>(0, 0, &(transport.beat as f32))?;
+        outputs.set_output_as::<f32>(1, 0, &(divisions.rem_euclid(1.0) as f32))?;
+        outputs.set_output_as::<bool>(2, 0, &tick)?;
+        outputs.set_output_as::<bool>(3, 0, &transport.playing)?;
+        outputs.set_output_as::<f32>(4, 0, &transport.bpm)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+//! An RNNoise-style denoiser: a model predicts one gain per band from band features, and the
+//! gains are interpolated over the bins.
+//!
+//! The band layout is RNNoise's 22 bands (see [`RNNOISE_CENTERS_HZ`]), placed at the graph's FFT
+//! length and sample rate as [`TriangularBands`], so the same model runs at any `N_FFT`. Every
+//! frame, the model gets [`DENOISE_FEATURES`] features:
+//!
+//! - the 22 band cepstral coefficients (the DCT of the log10 band powers), with the first 6
+//!   summed over the last three frames,
+//! - the first and second differences over the last three frames of the first 6 coefficients,
+//! - the spectral variability: the average distance of each of the last 8 cepstra to its nearest
+//!   neighbor among them.
+//!
+//! Band powers are divided by the squared FFT length first, so the features don't depend on it.
+//! RNNoise's pitch features need the time signal and are left out, so its published weights don't
+//! apply as-is; the model must be trained on these features (e.g. exported to ONNX and run with
+//! [`OnnxModel`](super::model::OnnxModel)).
+//!
+//! [`RNNOISE_CENTERS_HZ`]: crate::bands::RNNOISE_CENTERS_HZ
+
+use raug::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    bands::{RNNOISE_CENTERS_HZ, TriangularBands},
+    builtins::model::SpectralModel,
+    fft_processor_io,
+    history::FrameHistory,
+    processor::{FftProcessor, ParamSpec, ParamUnit},
+    processor_state,
+    signal::{Complex32, Fft},
+};
+
+/// The number of bands, and of gains the model predicts.
+pub const DENOISE_BANDS: usize = RNNOISE_CENTERS_HZ.len();
+/// The number of features the model takes.
+pub const DENOISE_FEATURES: usize = DENOISE_BANDS + 2 * DELTA_COEFFS + 1;
+
+/// The number of coefficients the differences are taken of.
+const DELTA_COEFFS: usize = 6;
+/// The number of cepstra the spectral variability is measured over.
+const CEPSTRUM_MEMORY: usize = 8;
+/// Added to band powers before taking their logarithm.
+const POWER_FLOOR: f32 = 1e-10;
+/// How much of the previous frame's gain every band keeps at least, as in RNNoise.
+const GAIN_RELEASE: f32 = 0.6;
+
+#[derive(Debug, Error)]
+pub enum DenoiseError {
+    #[error("model takes {found} features, expected {DENOISE_FEATURES}")]
+    InputLength { found: usize },
+    #[error("model produces {found} values, expected {DENOISE_BANDS} or {}", DENOISE_BANDS + 1)]
+    OutputLength { found: usize },
+}
+
+/// Denoises with a [`SpectralModel`] predicting band gains, see the [module docs](self).
+///
+/// The model produces [`DENOISE_BANDS`] gains between 0 and 1, optionally followed by a voice
+/// activity probability, which is output as `vad` (and is 0 otherwise). A band's gain falls by
+/// at most 40% per frame, which keeps the tails of words from being cut off. The `amount` input
+/// (or field) blends between the input (0) and the fully denoised signal (1) in dB.
+pub struct RnnDenoise<F: Fft, M: SpectralModel> {
+    pub amount: f32,
+    model: M,
+    sample_rate: f32,
+    bands: TriangularBands,
+    dct: Vec<f32>,
+    powers: [f32; DENOISE_BANDS],
+    cepstra: FrameHistory<[f32; DENOISE_BANDS]>,
+    features: [f32; DENOISE_FEATURES],
+    model_output: Vec<f32>,
+    gains: [f32; DENOISE_BANDS],
+    bin_gains: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft, M: SpectralModel> RnnDenoise<F, M> {
+    /// Wraps `model`, checking that its input and output lengths match the module docs.
+    pub fn new(model: M) -> Result<Self, DenoiseError> {
+        if model.input_len() != DENOISE_FEATURES {
+            return Err(DenoiseError::InputLength {
+                found: model.input_len(),
+            });
+        }
+        let output_len = model.output_len();
+        if output_len != DENOISE_BANDS && output_len != DENOISE_BANDS + 1 {
+            return Err(DenoiseError::OutputLength { found: output_len });
+        }
+
+        // the orthonormal DCT-II, one row per coefficient
+        let mut dct = vec![0.0; DENOISE_BANDS * DENOISE_BANDS];
+        for (i, row) in dct.chunks_exact_mut(DENOISE_BANDS).enumerate() {
+            let scale = if i == 0 { 0.5f32.sqrt() } else { 1.0 };
+            for (j, value) in row.iter_mut().enumerate() {
+                let angle =
+                    std::f32::consts::PI * (j as f32 + 0.5) * i as f32 / DENOISE_BANDS as f32;
+                *value = scale * angle.cos() * (2.0 / DENOISE_BANDS as f32).sqrt();
+            }
+        }
+
+        Ok(Self {
+            amount: 1.0,
+            model,
+            sample_rate: 0.0,
+            // replaced once the sample rate is known
+            bands: TriangularBands::rnnoise(48_000.0, F::N_FFT),
+            dct,
+            powers: [0.0; DENOISE_BANDS],
+            cepstra: FrameHistory::new(CEPSTRUM_MEMORY),
+            features: [0.0; DENOISE_FEATURES],
+            model_output: vec![0.0; output_len],
+            gains: [1.0; DENOISE_BANDS],
+            bin_gains: vec![1.0; F::N_REAL_BINS],
+            out_signal: Box::new(F::RealFft::default()),
+        })
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+
+    /// Returns the current gain of every band.
+    pub fn gains(&self) -> &[f32; DENOISE_BANDS] {
+        &self.gains
+    }
+
+    /// Computes the features of `spectrum` into `self.features`.
+    fn compute_features(&mut self, spectrum: &[Complex32]) {
+        self.bands.powers(spectrum, &mut self.powers);
+        let scale = 1.0 / (F::N_FFT * F::N_FFT) as f32;
+        for power in &mut self.powers {
+            *power = (*power * scale + POWER_FLOOR).log10();
+        }
+
+        let mut cepstrum = [0.0; DENOISE_BANDS];
+        for (coeff, row) in cepstrum
+            .iter_mut()
+            .zip(self.dct.chunks_exact(DENOISE_BANDS))
+        {
+            *coeff = row.iter().zip(&self.powers).map(|(a, b)| a * b).sum();
+        }
+        self.cepstra.push(&cepstrum);
+
+        let (current, previous, before) = (
+            self.cepstra.get(0),
+            self.cepstra.get(1),
+            self.cepstra.get(2),
+        );
+        self.features[..DENOISE_BANDS].copy_from_slice(current);
+        for i in 0..DELTA_COEFFS {
+            self.features[i] = current[i] + previous[i] + before[i];
+            self.features[DENOISE_BANDS + i] = current[i] - before[i];
+            self.features[DENOISE_BANDS + DELTA_COEFFS + i] =
+                current[i] - 2.0 * previous[i] + before[i];
+        }
+
+        let mut variability = 0.0;
+        for i in 0..self.cepstra.len() {
+            let mut nearest = f32::INFINITY;
+            for j in (0..self.cepstra.len()).filter(|&j| j != i) {
+                let distance = self
+                    .cepstra
+                    .get(i)
+                    .iter()
+                    .zip(self.cepstra.get(j))
+                    .map(|(a, b)| (a - b) * (a - b))
+                    .sum();
+                nearest = f32::min(nearest, distance);
+            }
+            variability += nearest;
+        }
+        self.features[DENOISE_FEATURES - 1] = variability / self.cepstra.len() as f32;
+    }
+}
+
+impl<F: Fft, M: SpectralModel> FftProcessor for RnnDenoise<F, M> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, amount: f32 }
+        outputs { output: F::RealFft, vad: f32 }
+    }
+
+    processor_state!(cepstra, gains);
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        vec![ParamSpec::new("amount", ParamUnit::None, 0.0, 1.0, 1.0)]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if name != "amount" {
+            return false;
+        }
+        self.amount = value;
+        true
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.bands = TriangularBands::rnnoise(sample_rate, F::N_FFT);
+        self.cepstra.clear();
+        self.gains = [1.0; DENOISE_BANDS];
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.allocate(new);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let amount = inputs.input_as::<f32>(1);
+
+        for (i, input) in input.iter().enumerate() {
+            let amount = amount
+                .and_then(|amount| amount.get(i).copied())
+                .unwrap_or(self.amount)
+                .clamp(0.0, 1.0);
+
+            self.compute_features(input);
+            if let Err(e) = self.model.run(&self.features, &mut self.model_output) {
+                return Err(ProcessorError::ProcessingError(e));
+            }
+
+            for (gain, &predicted) in self.gains.iter_mut().zip(&self.model_output) {
+                *gain = predicted.clamp(0.0, 1.0).max(GAIN_RELEASE * *gain);
+            }
+            self.bands.interpolate(&self.gains, &mut self.bin_gains);
+
+            self.out_signal.copy_from_slice(input);
+            for (out, &gain) in self.out_signal.iter_mut().zip(&self.bin_gains) {
+                *out *= gain.powf(amount);
+            }
+            let vad = self.model_output.get(DENOISE_BANDS).copied().unwrap_or(0.0);
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<f32>(1, i, &vad)?;
+        }
+
+        Ok(())
+    }
+}
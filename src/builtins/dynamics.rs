@@ -0,0 +1,836 @@
+//! Dynamics processors keyed off a second spectrum.
+
+use raug::prelude::*;
+
+use crate::{
+    bands::{BARK_EDGES_HZ, Bands},
+    builder::processor_builder,
+    denormal::{flush_denormal, flush_real_denormals},
+    diagnostics::{self, RuntimeDiagnostic},
+    fft_processor_io,
+    history::Lookahead,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::{Complex32, Fft},
+    smooth::{SmoothedParam, Smoothing},
+    units,
+};
+
+/// Returns the coefficient of a one-pole filter with time constant `seconds`, stepped once per
+/// hop.
+fn one_pole(seconds: f32, hop_seconds: f32) -> f32 {
+    if seconds <= 0.0 || hop_seconds <= 0.0 {
+        0.0
+    } else {
+        (-hop_seconds / seconds).exp()
+    }
+}
+
+/// Frequency-dependent sidechain compression: attenuates each band of the main input by how loud
+/// the same band is in the sidechain, e.g. to carve space for a voiceover.
+///
+/// The bands are split at `band_edges_hz` (the Bark critical bands by default). In every band
+/// where the sidechain level exceeds `threshold_db`, the main input is attenuated by the excess
+/// times `1 - 1 / ratio`, by at most `max_depth_db`. The attenuation follows the sidechain with
+/// `attack_seconds` and `release_seconds`. The `amount` input scales the attenuation in dB between
+/// none (0) and full (1), smoothed over a few hops.
+///
+/// With a look-ahead (see [`set_lookahead_frames`](Self::set_lookahead_frames), or the graph's
+/// [`set_lookahead_frames`](crate::graph::FftGraph::set_lookahead_frames) if that's more), the
+/// main input is delayed by that many hops while the sidechain is not, so the attenuation is
+/// already in place when a sidechain onset reaches the main input. An attack time close to the
+/// look-ahead duration gives the smoothest result. The look-ahead adds to the latency of the
+/// graph.
+///
+/// Levels are measured like [`FrameEnvelope`](crate::builtins::control::FrameEnvelope), so a
+/// full-scale sine in one band reads as -3 dB.
+#[derive(Clone)]
+pub struct SpectralDuck<F: Fft> {
+    pub band_edges_hz: Vec<f32>,
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub max_depth_db: f32,
+    pub attack_seconds: f32,
+    pub release_seconds: f32,
+    pub amount: f32,
+    smoothed_amount: SmoothedParam,
+    frame: FrameInfo,
+    // the edges the bands were computed for
+    edges: Vec<f32>,
+    bands: Bands,
+    powers: Vec<f32>,
+    // current attenuation per band, in dB
+    reduction_db: Vec<f32>,
+    // the look-ahead set on the processor and on the graph, the larger of which is used
+    lookahead_frames: usize,
+    graph_lookahead: usize,
+    lookahead: Lookahead<F::RealFft>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralDuck<F> {
+    pub fn new(threshold_db: f32, ratio: f32) -> Self {
+        // a single band until the sample rate is known
+        let bands = Bands::from_edges_hz(&[], 1.0, F::N_FFT);
+        Self {
+            band_edges_hz: BARK_EDGES_HZ.to_vec(),
+            threshold_db,
+            ratio,
+            max_depth_db: 24.0,
+            attack_seconds: 0.01,
+            release_seconds: 0.25,
+            amount: 1.0,
+            smoothed_amount: SmoothedParam::new(1.0),
+            frame: FrameInfo::default(),
+            edges: Vec::new(),
+            powers: vec![0.0; bands.len()],
+            reduction_db: vec![0.0; bands.len()],
+            bands,
+            lookahead_frames: 0,
+            graph_lookahead: 0,
+            lookahead: Lookahead::new(0),
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothed_amount.set_smoothing(smoothing);
+    }
+
+    /// Returns the number of hops the main input is delayed by.
+    pub fn lookahead_frames(&self) -> usize {
+        self.lookahead.frames()
+    }
+
+    /// Sets the number of hops the main input is delayed by, unless the graph's look-ahead is
+    /// more. This changes the processor's latency, so it should be set before the graph is
+    /// allocated.
+    pub fn set_lookahead_frames(&mut self, frames: usize) {
+        self.lookahead_frames = frames;
+        self.resize_lookahead();
+    }
+
+    fn resize_lookahead(&mut self) {
+        let frames = self.lookahead_frames.max(self.graph_lookahead);
+        if frames != self.lookahead.frames() {
+            self.lookahead = Lookahead::new(frames);
+        }
+    }
+
+    /// Returns the current attenuation of every band in dB, before `amount` is applied.
+    pub fn reduction_db(&self) -> &[f32] {
+        &self.reduction_db
+    }
+
+    pub fn bands(&self) -> &Bands {
+        &self.bands
+    }
+
+    fn update_bands(&mut self) {
+        if self.frame.sample_rate <= 0.0 {
+            return;
+        }
+        self.edges.clone_from(&self.band_edges_hz);
+        self.bands = Bands::from_edges_hz(&self.edges, self.frame.sample_rate, F::N_FFT);
+        self.powers.resize(self.bands.len(), 0.0);
+        self.reduction_db.resize(self.bands.len(), 0.0);
+    }
+}
+
+impl<F: Fft> Default for SpectralDuck<F> {
+    fn default() -> Self {
+        Self::new(-40.0, 4.0)
+    }
+}
+
+processor_builder! {
+    SpectralDuck => SpectralDuckBuilder {
+        threshold_db: f32 [Decibels, -100.0, 0.0],
+        ratio: f32 [None, 1.0, 20.0],
+        max_depth_db: f32 [Decibels, 0.0, 60.0],
+        attack_seconds: f32 [Seconds, 0.0, 1.0],
+        release_seconds: f32 [Seconds, 0.0, 5.0],
+        amount: f32 [None, 0.0, 1.0, Internal],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralDuck<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, sidechain: F::RealFft, amount: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(smoothed_amount, reduction_db, lookahead);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralDuckBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralDuckBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn latency_frames(&self) -> usize {
+        self.lookahead.frames()
+    }
+
+    fn set_graph_lookahead(&mut self, frames: usize) {
+        self.graph_lookahead = frames;
+        self.resize_lookahead();
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.frame.sample_rate = sample_rate;
+        self.update_bands();
+        self.reduction_db.fill(0.0);
+        self.lookahead.clear();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.frame.sample_rate = new;
+        self.update_bands();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+        self.smoothed_amount.set_hop_seconds(info.hop_seconds());
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let sidechain = inputs.input_as::<F::RealFft>(1).unwrap();
+        let amount = inputs.input_as::<f32>(2);
+
+        if self.edges != self.band_edges_hz {
+            self.update_bands();
+        }
+
+        let hop_seconds = self.frame.hop_seconds();
+        let attack = one_pole(self.attack_seconds, hop_seconds);
+        let release = one_pole(self.release_seconds, hop_seconds);
+        // see `FrameEnvelope` for the scaling of band powers to signal levels
+        let overlap = self.frame.fft_length as f32 / self.frame.hop_length.max(1) as f32;
+        let scale = overlap / F::N_FFT as f32;
+        let slope = 1.0 - 1.0 / self.ratio.max(1.0);
+
+        for (i, (input, sidechain)) in input.iter().zip(sidechain.iter()).enumerate() {
+            self.smoothed_amount.set_target(
+                amount
+                    .and_then(|amount| amount.get(i).copied())
+                    .unwrap_or(self.amount),
+            );
+            let amount = self.smoothed_amount.advance().clamp(0.0, 1.0);
+
+            self.bands.powers(sidechain, &mut self.powers);
+            for (reduction, &power) in self.reduction_db.iter_mut().zip(&self.powers) {
+                let level_db = units::power_to_db(power * scale);
+                let target = ((level_db - self.threshold_db).max(0.0) * slope)
+                    .min(self.max_depth_db.max(0.0));
+                let coeff = if target > *reduction { attack } else { release };
+                *reduction = target + (*reduction - target) * coeff;
+            }
+            if self.frame.flush_denormals {
+                flush_real_denormals(&mut self.reduction_db);
+            }
+
+            self.lookahead.push(input);
+            let input = self.lookahead.current();
+            for (band, range) in self.bands.iter().enumerate() {
+                let gain = units::db_to_magnitude(-self.reduction_db[band] * amount);
+                for bin in range {
+                    self.out_signal[bin] = input[bin] * gain;
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How far masking spreads from a masker to higher bands, in dB per band.
+const UPWARD_SPREAD_DB: f32 = 10.0;
+/// How far masking spreads from a masker to lower bands, in dB per band.
+const DOWNWARD_SPREAD_DB: f32 = 25.0;
+
+/// Unmasks one signal from another in a mix, e.g. keeping vocals intelligible over a dense
+/// backing track.
+///
+/// The masking threshold in every band is estimated from the `masker` input with a simple
+/// psychoacoustic model: the masker's band levels are spread to neighboring bands, falling by
+/// 10 dB per band towards higher and 25 dB per band towards lower frequencies, and lowered by
+/// `masking_offset_db`. Wherever the `input` falls below that threshold while above `floor_db`,
+/// the deficit (at most `max_correction_db`) is corrected: by boosting the input, by cutting the
+/// masker, or split between both according to `balance` (0 boosts only, 1 cuts only). The
+/// corrections follow with `attack_seconds` and `release_seconds`, and the `amount` input scales
+/// them between none (0) and full (1), smoothed over a few hops.
+///
+/// The spreading assumes bands about one critical band wide, as the default Bark bands are. Levels
+/// are measured like in [`SpectralDuck`].
+#[derive(Clone)]
+pub struct Unmask<F: Fft> {
+    pub band_edges_hz: Vec<f32>,
+    pub masking_offset_db: f32,
+    pub max_correction_db: f32,
+    pub floor_db: f32,
+    pub balance: f32,
+    pub attack_seconds: f32,
+    pub release_seconds: f32,
+    pub amount: f32,
+    smoothed_amount: SmoothedParam,
+    frame: FrameInfo,
+    // the edges the bands were computed for
+    edges: Vec<f32>,
+    bands: Bands,
+    // linear power weights from every masker band (rows) to every band (columns)
+    spread: Vec<f32>,
+    input_powers: Vec<f32>,
+    masker_powers: Vec<f32>,
+    // current correction per band, in dB
+    correction_db: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+    out_masker: Box<F::RealFft>,
+}
+
+impl<F: Fft> Unmask<F> {
+    pub fn new(max_correction_db: f32, balance: f32) -> Self {
+        // a single band until the sample rate is known
+        let bands = Bands::from_edges_hz(&[], 1.0, F::N_FFT);
+        Self {
+            band_edges_hz: BARK_EDGES_HZ.to_vec(),
+            masking_offset_db: 10.0,
+            max_correction_db,
+            floor_db: -70.0,
+            balance,
+            attack_seconds: 0.02,
+            release_seconds: 0.3,
+            amount: 1.0,
+            smoothed_amount: SmoothedParam::new(1.0),
+            frame: FrameInfo::default(),
+            edges: Vec::new(),
+            spread: vec![1.0; bands.len() * bands.len()],
+            input_powers: vec![0.0; bands.len()],
+            masker_powers: vec![0.0; bands.len()],
+            correction_db: vec![0.0; bands.len()],
+            bands,
+            out_signal: Box::new(F::RealFft::default()),
+            out_masker: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothed_amount.set_smoothing(smoothing);
+    }
+
+    /// Returns the current correction of every band in dB, before `amount` and `balance` are
+    /// applied.
+    pub fn correction_db(&self) -> &[f32] {
+        &self.correction_db
+    }
+
+    pub fn bands(&self) -> &Bands {
+        &self.bands
+    }
+
+    fn update_bands(&mut self) {
+        if self.frame.sample_rate <= 0.0 {
+            return;
+        }
+        self.edges.clone_from(&self.band_edges_hz);
+        self.bands = Bands::from_edges_hz(&self.edges, self.frame.sample_rate, F::N_FFT);
+
+        let num_bands = self.bands.len();
+        self.spread.resize(num_bands * num_bands, 0.0);
+        for masker in 0..num_bands {
+            for band in 0..num_bands {
+                let attenuation_db = if band >= masker {
+                    UPWARD_SPREAD_DB * (band - masker) as f32
+                } else {
+                    DOWNWARD_SPREAD_DB * (masker - band) as f32
+                };
+                self.spread[masker * num_bands + band] = 10.0f32.powf(-attenuation_db / 10.0);
+            }
+        }
+
+        self.input_powers.resize(num_bands, 0.0);
+        self.masker_powers.resize(num_bands, 0.0);
+        self.correction_db.resize(num_bands, 0.0);
+    }
+}
+
+impl<F: Fft> Default for Unmask<F> {
+    fn default() -> Self {
+        Self::new(6.0, 0.5)
+    }
+}
+
+processor_builder! {
+    Unmask => UnmaskBuilder {
+        masking_offset_db: f32 [Decibels, 0.0, 30.0],
+        max_correction_db: f32 [Decibels, 0.0, 30.0],
+        floor_db: f32 [Decibels, -120.0, 0.0],
+        balance: f32 [None, 0.0, 1.0],
+        attack_seconds: f32 [Seconds, 0.0, 1.0],
+        release_seconds: f32 [Seconds, 0.0, 5.0],
+        amount: f32 [None, 0.0, 1.0, Internal],
+    }
+}
+
+impl<F: Fft> FftProcessor for Unmask<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, masker: F::RealFft, amount: f32 }
+        outputs { output: F::RealFft, masker: F::RealFft }
+    }
+
+    processor_state!(smoothed_amount, correction_db);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        UnmaskBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        UnmaskBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.frame.sample_rate = sample_rate;
+        self.update_bands();
+        self.correction_db.fill(0.0);
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.frame.sample_rate = new;
+        self.update_bands();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+        self.smoothed_amount.set_hop_seconds(info.hop_seconds());
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let masker = inputs.input_as::<F::RealFft>(1).unwrap();
+        let amount = inputs.input_as::<f32>(2);
+
+        if self.edges != self.band_edges_hz {
+            self.update_bands();
+        }
+
+        let hop_seconds = self.frame.hop_seconds();
+        let attack = one_pole(self.attack_seconds, hop_seconds);
+        let release = one_pole(self.release_seconds, hop_seconds);
+        let overlap = self.frame.fft_length as f32 / self.frame.hop_length.max(1) as f32;
+        let scale = overlap / F::N_FFT as f32;
+        let balance = self.balance.clamp(0.0, 1.0);
+        let num_bands = self.bands.len();
+
+        for (i, (input, masker)) in input.iter().zip(masker.iter()).enumerate() {
+            self.smoothed_amount.set_target(
+                amount
+                    .and_then(|amount| amount.get(i).copied())
+                    .unwrap_or(self.amount),
+            );
+            let amount = self.smoothed_amount.advance().clamp(0.0, 1.0);
+
+            self.bands.powers(input, &mut self.input_powers);
+            self.bands.powers(masker, &mut self.masker_powers);
+
+            for band in 0..num_bands {
+                let spread_power: f32 = (0..num_bands)
+                    .map(|source| {
+                        self.masker_powers[source] * self.spread[source * num_bands + band]
+                    })
+                    .sum();
+                let threshold_db =
+                    units::power_to_db(spread_power * scale) - self.masking_offset_db;
+                let level_db = units::power_to_db(self.input_powers[band] * scale);
+
+                let target = if level_db > self.floor_db {
+                    (threshold_db - level_db).clamp(0.0, self.max_correction_db.max(0.0))
+                } else {
+                    0.0
+                };
+                let correction = &mut self.correction_db[band];
+                let coeff = if target > *correction {
+                    attack
+                } else {
+                    release
+                };
+                *correction = target + (*correction - target) * coeff;
+            }
+            if self.frame.flush_denormals {
+                flush_real_denormals(&mut self.correction_db);
+            }
+
+            for (band, range) in self.bands.iter().enumerate() {
+                let correction_db = self.correction_db[band] * amount;
+                let boost = units::db_to_magnitude(correction_db * (1.0 - balance));
+                let cut = units::db_to_magnitude(-correction_db * balance);
+                for bin in range {
+                    self.out_signal[bin] = input[bin] * boost;
+                    self.out_masker[bin] = masker[bin] * cut;
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<F::RealFft>(1, i, &*self.out_masker)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps the loudness of a processing chain's output at that of its input, so aggressive
+/// spectral processing (gating, filtering) doesn't change how loud the result is perceived.
+///
+/// Connect the chain's output to `input` and its input to `reference`. The power of both is
+/// averaged over `time_seconds`, and `input` is scaled by the gain bringing its average to that
+/// of `reference`, offset by `target_db` and limited to `max_gain_db` either way. The averages
+/// only follow while `reference` is above `gate_db`, so pauses don't pull the gain up. The gain is
+/// also output in dB.
+///
+/// Levels are measured over the whole spectrum like
+/// [`FrameEnvelope`](crate::builtins::control::FrameEnvelope).
+#[derive(Clone)]
+pub struct AutoGain<F: Fft> {
+    pub target_db: f32,
+    pub time_seconds: f32,
+    pub max_gain_db: f32,
+    pub gate_db: f32,
+    frame: FrameInfo,
+    input_power: f32,
+    reference_power: f32,
+    gain_db: f32,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> AutoGain<F> {
+    pub fn new(time_seconds: f32) -> Self {
+        Self {
+            target_db: 0.0,
+            time_seconds,
+            max_gain_db: 24.0,
+            gate_db: -70.0,
+            frame: FrameInfo::default(),
+            input_power: 0.0,
+            reference_power: 0.0,
+            gain_db: 0.0,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the gain currently applied, in dB.
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+}
+
+impl<F: Fft> Default for AutoGain<F> {
+    fn default() -> Self {
+        Self::new(3.0)
+    }
+}
+
+processor_builder! {
+    AutoGain => AutoGainBuilder {
+        target_db: f32 [Decibels, -24.0, 24.0],
+        time_seconds: f32 [Seconds, 0.0, 30.0],
+        max_gain_db: f32 [Decibels, 0.0, 48.0],
+        gate_db: f32 [Decibels, -120.0, 0.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for AutoGain<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, reference: F::RealFft }
+        outputs { output: F::RealFft, gain_db: f32 }
+    }
+
+    processor_state!(input_power, reference_power, gain_db);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        AutoGainBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        AutoGainBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.input_power = 0.0;
+        self.reference_power = 0.0;
+        self.gain_db = 0.0;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let reference = inputs.input_as::<F::RealFft>(1).unwrap();
+
+        let coeff = one_pole(self.time_seconds, self.frame.hop_seconds());
+        let overlap = self.frame.fft_length as f32 / self.frame.hop_length.max(1) as f32;
+        let scale = overlap / F::N_FFT as f32;
+        let max_gain_db = self.max_gain_db.max(0.0);
+
+        for (i, (input, reference)) in input.iter().zip(reference.iter()).enumerate() {
+            let reference_power = frame_power(reference) * scale;
+            if units::power_to_db(reference_power) > self.gate_db {
+                let input_power = frame_power(input) * scale;
+                self.input_power = input_power + (self.input_power - input_power) * coeff;
+                self.reference_power =
+                    reference_power + (self.reference_power - reference_power) * coeff;
+                if self.frame.flush_denormals {
+                    self.input_power = flush_denormal(self.input_power);
+                    self.reference_power = flush_denormal(self.reference_power);
+                }
+            }
+
+            if self.input_power > 0.0 && self.reference_power > 0.0 {
+                self.gain_db = (units::power_to_db(self.reference_power)
+                    - units::power_to_db(self.input_power)
+                    + self.target_db)
+                    .clamp(-max_gain_db, max_gain_db);
+            }
+
+            let gain = units::db_to_magnitude(self.gain_db);
+            for (out, bin) in self.out_signal.iter_mut().zip(input.iter()) {
+                *out = bin * gain;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<f32>(1, i, &self.gain_db)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Caps the magnitudes of a spectrum, so a feedback patch running away can't make the
+/// overlap-add output explode.
+///
+/// Every bin is limited to a level of `bin_ceiling_db`, keeping its phase, and then the whole
+/// frame is scaled down if its level exceeds `ceiling_db`. The frame's gain reduction takes effect
+/// at once and recovers with `release_seconds`. Levels are measured like
+/// [`FrameEnvelope`](crate::builtins::control::FrameEnvelope), so a full-scale sine reads as
+/// -3 dB; a bin's level is that of a sine concentrated in it. The reduction in dB is also output,
+/// and the start of every stretch of limiting is reported as a
+/// [`RuntimeDiagnostic::Clipping`](crate::diagnostics::RuntimeDiagnostic::Clipping).
+///
+/// Graphs can limit every audio output this way before resynthesis, see
+/// [`FftGraph::set_output_limiter`](crate::graph::FftGraph::set_output_limiter).
+#[derive(Clone)]
+pub struct SpectralLimiter<F: Fft> {
+    pub ceiling_db: f32,
+    pub bin_ceiling_db: f32,
+    pub release_seconds: f32,
+    frame: FrameInfo,
+    reduction_db: f32,
+    limiting: bool,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralLimiter<F> {
+    pub fn new(ceiling_db: f32) -> Self {
+        Self {
+            ceiling_db,
+            bin_ceiling_db: ceiling_db,
+            release_seconds: 0.1,
+            frame: FrameInfo::default(),
+            reduction_db: 0.0,
+            limiting: false,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the frame's current gain reduction in dB.
+    pub fn reduction_db(&self) -> f32 {
+        self.reduction_db
+    }
+
+    /// Limits one spectrum in place.
+    pub fn limit(&mut self, spectrum: &mut [Complex32]) {
+        let ceilings = self.ceilings();
+        limit_spectrum(
+            spectrum,
+            ceilings,
+            &mut self.reduction_db,
+            &mut self.limiting,
+        );
+    }
+
+    fn ceilings(&self) -> LimiterCeilings {
+        let overlap = self.frame.fft_length as f32 / self.frame.hop_length.max(1) as f32;
+        LimiterCeilings {
+            scale: overlap / F::N_FFT as f32,
+            ceiling_db: self.ceiling_db,
+            bin_ceiling_db: self.bin_ceiling_db,
+            release: one_pole(self.release_seconds, self.frame.hop_seconds()),
+            flush_denormals: self.frame.flush_denormals,
+        }
+    }
+}
+
+/// The settings of a [`SpectralLimiter`] for one hop.
+#[derive(Clone, Copy)]
+struct LimiterCeilings {
+    // scales a spectrum's energy to the mean square of its signal
+    scale: f32,
+    ceiling_db: f32,
+    bin_ceiling_db: f32,
+    release: f32,
+    flush_denormals: bool,
+}
+
+/// Limits `spectrum` in place, updating the gain reduction and whether it's limiting.
+fn limit_spectrum(
+    spectrum: &mut [Complex32],
+    ceilings: LimiterCeilings,
+    reduction_db: &mut f32,
+    limiting: &mut bool,
+) {
+    if ceilings.scale <= 0.0 {
+        return;
+    }
+
+    // the magnitude of a bin holding the whole of a sine at the bin ceiling
+    let max_magnitude =
+        (units::db_to_power(ceilings.bin_ceiling_db) / (2.0 * ceilings.scale)).sqrt();
+    let mut peak = 0.0f32;
+    for bin in spectrum.iter_mut() {
+        let magnitude = bin.norm();
+        if !magnitude.is_finite() {
+            *bin = Complex32::ZERO;
+        } else if magnitude > max_magnitude {
+            *bin *= max_magnitude / magnitude;
+        }
+        peak = peak.max(magnitude);
+    }
+
+    let level_db = units::power_to_db(frame_power(spectrum) * ceilings.scale);
+    let target = (level_db - ceilings.ceiling_db).max(0.0);
+    *reduction_db = if target >= *reduction_db {
+        target
+    } else {
+        target + (*reduction_db - target) * ceilings.release
+    };
+    if ceilings.flush_denormals {
+        *reduction_db = flush_denormal(*reduction_db);
+    }
+    let gain = units::db_to_magnitude(-*reduction_db);
+    for bin in spectrum.iter_mut() {
+        *bin *= gain;
+    }
+
+    let was_limiting = *limiting;
+    *limiting = target > 0.0 || peak > max_magnitude;
+    if *limiting && !was_limiting {
+        diagnostics::report(RuntimeDiagnostic::Clipping {
+            source: "SpectralLimiter",
+            peak: units::db_to_magnitude(level_db),
+        });
+    }
+}
+
+impl<F: Fft> Default for SpectralLimiter<F> {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+processor_builder! {
+    SpectralLimiter => SpectralLimiterBuilder {
+        ceiling_db: f32 [Decibels, -60.0, 24.0],
+        bin_ceiling_db: f32 [Decibels, -60.0, 24.0],
+        release_seconds: f32 [Seconds, 0.0, 5.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralLimiter<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft, reduction_db: f32 }
+    }
+
+    processor_state!(reduction_db, limiting);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralLimiterBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralLimiterBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.reduction_db = 0.0;
+        self.limiting = false;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        let ceilings = self.ceilings();
+
+        for (i, input) in input.iter().enumerate() {
+            self.out_signal.copy_from_slice(input);
+            limit_spectrum(
+                &mut self.out_signal,
+                ceilings,
+                &mut self.reduction_db,
+                &mut self.limiting,
+            );
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<f32>(1, i, &self.reduction_db)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the energy of a spectrum, counting the bins between DC and Nyquist twice for their
+/// negative-frequency mirror images.
+fn frame_power(spectrum: &[Complex32]) -> f32 {
+    let last = spectrum.len().saturating_sub(1);
+    spectrum
+        .iter()
+        .enumerate()
+        .map(|(bin, value)| {
+            let weight = if bin == 0 || bin == last { 1.0 } else { 2.0 };
+            weight * value.norm_sqr()
+        })
+        .sum()
+}
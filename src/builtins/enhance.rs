@@ -0,0 +1,139 @@
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    fft_processor_io,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::{Complex32, Fft},
+    smooth::{SmoothedParam, Smoothing},
+};
+
+/// Synthesizes upper harmonics from band-limited content by spectral translation.
+///
+/// Every bin below `cutoff_hz` is copied to its integer multiples (2x, 3x, ... up to
+/// `harmonics`) with its phase multiplied accordingly, which keeps the generated partials coherent
+/// from frame to frame. Only partials landing above the cutoff are added, each one attenuated by
+/// `falloff` relative to the previous harmonic. The `amount` input scales the added content and
+/// is smoothed over a few hops.
+#[derive(Clone)]
+pub struct Exciter<F: Fft> {
+    pub cutoff_hz: f32,
+    pub harmonics: usize,
+    pub falloff: f32,
+    pub amount: f32,
+    smoothed_amount: SmoothedParam,
+    sample_rate: f32,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> Exciter<F> {
+    pub fn new(cutoff_hz: f32, harmonics: usize) -> Self {
+        Self {
+            cutoff_hz,
+            harmonics,
+            falloff: 0.5,
+            amount: 0.25,
+            smoothed_amount: SmoothedParam::new(0.25),
+            sample_rate: 0.0,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothed_amount.set_smoothing(smoothing);
+    }
+}
+
+impl<F: Fft> Default for Exciter<F> {
+    fn default() -> Self {
+        Self::new(3000.0, 3)
+    }
+}
+
+processor_builder! {
+    Exciter => ExciterBuilder {
+        cutoff_hz: f32 [Hertz, 20.0, 20000.0],
+        harmonics: usize [None, 2.0, 16.0],
+        falloff: f32 [None, 0.0, 1.0],
+        amount: f32 [None, 0.0, 1.0, Internal],
+    }
+}
+
+impl<F: Fft> FftProcessor for Exciter<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, amount: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(smoothed_amount);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        ExciterBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        ExciterBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.smoothed_amount.set_hop_seconds(info.hop_seconds());
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let amount = inputs.input_as::<f32>(1);
+
+        let cutoff_bin = if self.sample_rate > 0.0 {
+            (F::hz_to_bin(self.cutoff_hz, self.sample_rate) as usize).min(F::N_REAL_BINS)
+        } else {
+            F::N_REAL_BINS
+        };
+
+        for (i, input) in input.iter().enumerate() {
+            self.smoothed_amount.set_target(
+                amount
+                    .and_then(|amount| amount.get(i).copied())
+                    .unwrap_or(self.amount),
+            );
+            let amount = self.smoothed_amount.advance();
+
+            self.out_signal.copy_from_slice(input);
+
+            let mut gain = amount;
+            for harmonic in 2..=self.harmonics {
+                gain *= self.falloff;
+                for (bin, source) in input.iter().enumerate().take(cutoff_bin).skip(1) {
+                    let target = bin * harmonic;
+                    if target >= F::N_REAL_BINS {
+                        break;
+                    }
+                    if target < cutoff_bin {
+                        continue;
+                    }
+                    let (magnitude, phase) = source.to_polar();
+                    self.out_signal[target] +=
+                        Complex32::from_polar(magnitude * gain, phase * harmonic as f32);
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
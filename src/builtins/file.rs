@@ -0,0 +1,327 @@
+//! Processors writing frames to and reading them from [frame files](crate::frame_file).
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use raug::prelude::*;
+
+use crate::{
+    core::WindowFunction,
+    fft_processor_io,
+    frame_file::{FrameFileError, FrameFileHeader, FrameFileReader, FrameFileWriter},
+    processor::{FftProcessor, FrameInfo, ParamSpec, ParamUnit},
+    processor_state,
+    signal::Fft,
+};
+
+/// Writes every input frame to a frame file and passes it through unchanged.
+///
+/// The header is written along with the first frame, taking the sample rate and hop length from
+/// the graph and the window function from `window_fn`, which should match the graph's. Writes
+/// are buffered but still happen on the processing thread, so this is meant for offline
+/// rendering; use a [`FrameBuffer`](crate::frame_buffer::FrameBuffer) to capture frames in real
+/// time. Write errors fail the frame, and no more frames are written after one.
+pub struct FrameWriter<F: Fft> {
+    pub window_fn: WindowFunction,
+    sink: Option<Box<dyn Write + Send>>,
+    writer: Option<FrameFileWriter<Box<dyn Write + Send>>>,
+    frame: FrameInfo,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> FrameWriter<F> {
+    /// Creates a writer writing to `writer`, which should be buffered.
+    pub fn new(writer: impl Write + Send + 'static, window_fn: WindowFunction) -> Self {
+        Self {
+            window_fn,
+            sink: Some(Box::new(writer)),
+            writer: None,
+            frame: FrameInfo::default(),
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates (or truncates) the file at `path` to write to.
+    pub fn create(path: impl AsRef<Path>, window_fn: WindowFunction) -> std::io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), window_fn))
+    }
+
+    /// Flushes the frames written so far.
+    pub fn flush(&mut self) -> Result<(), FrameFileError> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn write(&mut self, frame: &F::RealFft) -> Result<(), FrameFileError> {
+        if self.writer.is_none() {
+            let Some(sink) = self.sink.take() else {
+                // writing the header failed
+                return Ok(());
+            };
+            let header = FrameFileHeader {
+                sample_rate: self.frame.sample_rate,
+                fft_length: F::N_FFT,
+                hop_length: self.frame.hop_length,
+                window_fn: self.window_fn,
+            };
+            self.writer = Some(FrameFileWriter::new(sink, header)?);
+        }
+        let writer = self.writer.as_mut().unwrap();
+        let result = writer.write_frame(frame);
+        if result.is_err() {
+            self.writer = None;
+        }
+        result
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameWriter<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft }
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            if let Err(e) = self.write(input) {
+                return Err(ProcessorError::ProcessingError(Box::new(e)));
+            }
+            outputs.set_output_as::<F::RealFft>(0, i, input)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Plays back the frames of a frame file, one per hop.
+///
+/// All frames are read into memory up front, so playback is real-time safe. Frames are played in
+/// order regardless of the graph's sample rate and hop length; compare them with
+/// [`header`](Self::header) to check that the file matches. At the end, playback starts over if
+/// `looping` is set and outputs silence otherwise, until the `restart` trigger. Besides the
+/// spectrum, the processor outputs the playhead (`0..1`) and a trigger on the last frame.
+pub struct FrameReader<F: Fft> {
+    pub looping: bool,
+    header: FrameFileHeader,
+    frames: Vec<F::RealFft>,
+    position: usize,
+}
+
+impl<F: Fft> FrameReader<F> {
+    /// Creates a reader playing `frames`, described by `header`.
+    pub fn new(header: FrameFileHeader, frames: Vec<F::RealFft>) -> Self {
+        Self {
+            looping: false,
+            header,
+            frames,
+            position: 0,
+        }
+    }
+
+    /// Reads all frames from the file at `path`, which must have been analyzed with an FFT
+    /// length of `F::N_FFT`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FrameFileError> {
+        Self::read(FrameFileReader::open(path)?)
+    }
+
+    /// Reads all remaining frames from `reader`, which must have been analyzed with an FFT
+    /// length of `F::N_FFT`.
+    pub fn read(mut reader: FrameFileReader<impl Read>) -> Result<Self, FrameFileError> {
+        let header = *reader.header();
+        if header.num_bins() != F::N_REAL_BINS {
+            return Err(FrameFileError::FrameLength {
+                expected: F::N_REAL_BINS,
+                found: header.num_bins(),
+            });
+        }
+        let mut frames = Vec::new();
+        let mut frame = F::RealFft::default();
+        while reader.read_frame(&mut frame)? {
+            frames.push(frame.clone());
+        }
+        Ok(Self::new(header, frames))
+    }
+
+    pub fn header(&self) -> &FrameFileHeader {
+        &self.header
+    }
+
+    pub fn frames(&self) -> &[F::RealFft] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl<F: Fft> Default for FrameReader<F> {
+    fn default() -> Self {
+        Self::new(
+            FrameFileHeader {
+                sample_rate: 48_000.0,
+                fft_length: F::N_FFT,
+                hop_length: F::N_FFT / 4,
+                window_fn: WindowFunction::Hann,
+            },
+            Vec::new(),
+        )
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameReader<F> {
+    fft_processor_io! {
+        inputs { restart: bool }
+        outputs { output: F::RealFft, position: f32, end: bool }
+    }
+
+    processor_state!(position);
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        vec![ParamSpec::new("looping", ParamUnit::Toggle, 0.0, 1.0, 0.0)]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if name != "looping" {
+            return false;
+        }
+        self.looping = value != 0.0;
+        true
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.position = 0;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let restart = inputs
+            .input_as::<bool>(0)
+            .and_then(|values| values.first().copied())
+            .unwrap_or(false);
+        if restart {
+            self.position = 0;
+        }
+        if self.looping && self.position >= self.frames.len() {
+            self.position = 0;
+        }
+
+        let Some(frame) = self.frames.get(self.position) else {
+            outputs.set_output_as::<F::RealFft>(0, 0, &F::RealFft::default())?;
+            outputs.set_output_as::<f32>(1, 0, &1.0)?;
+            return Ok(());
+        };
+        outputs.set_output_as::<F::RealFft>(0, 0, frame)?;
+        outputs.set_output_as::<f32>(1, 0, &(self.position as f32 / self.frames.len() as f32))?;
+        self.position += 1;
+        outputs.set_output_as::<bool>(2, 0, &(self.position == self.frames.len()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        graph::{DynFftGraph, FftGraph},
+        signal::{Fft256, Fft512, RealFft256},
+    };
+
+    /// Keeps a copy of every frame passing through.
+    struct Record(Arc<Mutex<Vec<RealFft256>>>);
+
+    impl FftProcessor for Record {
+        fft_processor_io! {
+            inputs { input: RealFft256 }
+            outputs { output: RealFft256 }
+        }
+
+        fn process(
+            &mut self,
+            inputs: ProcessorInputs,
+            mut outputs: ProcessorOutputs,
+        ) -> ProcResult<()> {
+            for (i, input) in inputs.input_as::<RealFft256>(0).unwrap().iter().enumerate() {
+                self.0.lock().unwrap().push(*input);
+                outputs.set_output_as::<RealFft256>(0, i, input)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reads_back_the_frames_written() {
+        let path =
+            std::env::temp_dir().join(format!("raug-fft-frames-{}.rfft", std::process::id()));
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut graph = FftGraph::<Fft256>::new(64, WindowFunction::Hamming);
+            let input = graph.add_audio_input();
+            let record = graph.add_processor(Record(recorded.clone()));
+            let writer = graph.add_processor(
+                FrameWriter::<Fft256>::create(&path, WindowFunction::Hamming).unwrap(),
+            );
+            let output = graph.add_audio_output();
+            DynFftGraph::connect(&mut graph, input, 0, record, 0).unwrap();
+            DynFftGraph::connect(&mut graph, record, 0, writer, 0).unwrap();
+            DynFftGraph::connect(&mut graph, writer, 0, output, 0).unwrap();
+            let signal: Vec<f32> = (0..2048).map(|i| (i as f32 * 0.1).sin()).collect();
+            graph.render(&[&signal], 44_100.0, 64).unwrap();
+            // dropping the graph flushes the file
+        }
+
+        let reader = FrameReader::<Fft256>::open(&path);
+        let other_length = FrameReader::<Fft512>::open(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let reader = reader.unwrap();
+        let header = FrameFileHeader {
+            sample_rate: 44_100.0,
+            fft_length: 256,
+            hop_length: 64,
+            window_fn: WindowFunction::Hamming,
+        };
+        assert_eq!(*reader.header(), header);
+        let recorded = recorded.lock().unwrap();
+        assert!(!recorded.is_empty());
+        assert_eq!(reader.frames().len(), recorded.len());
+        for (read, recorded) in reader.frames().iter().zip(recorded.iter()) {
+            assert!(read.iter().zip(recorded.iter()).all(|(a, b)| {
+                a.re.to_bits() == b.re.to_bits() && a.im.to_bits() == b.im.to_bits()
+            }));
+        }
+
+        assert!(matches!(
+            other_length,
+            Err(FrameFileError::FrameLength {
+                expected: 257,
+                found: 129
+            })
+        ));
+    }
+}
@@ -0,0 +1,800 @@
+use raug::prelude::*;
+
+use crate::{
+    bands::TriangularBands,
+    builder::processor_builder,
+    fft_processor_io,
+    fir::FirDesigner,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::Fft,
+    smooth::{SmoothedParam, Smoothing},
+    units,
+};
+
+/// A magnitude response in Hz, parameterized by a processor's parameter inputs.
+pub type ParamResponse = dyn Fn(f32, &[f32]) -> f32 + Send;
+
+/// Applies a zero-phase filter whose magnitude response is specified in Hz.
+///
+/// The response function receives a frequency in Hz and the current values of the processor's
+/// parameter inputs, and returns a linear gain. The per-bin curve is re-evaluated whenever a
+/// parameter or the sample rate changes, so the response can vary over time. Parameter changes
+/// are smoothed over a few hops.
+pub struct DesignedFilter<F: Fft> {
+    designer: FirDesigner,
+    response: Box<ParamResponse>,
+    smoothed: Vec<SmoothedParam>,
+    params: Vec<f32>,
+    defaults: Vec<f32>,
+    curve: Vec<f32>,
+    dirty: bool,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> DesignedFilter<F> {
+    /// Creates a new filter with one parameter input per entry of `defaults`.
+    pub fn new(
+        defaults: impl Into<Vec<f32>>,
+        response: impl Fn(f32, &[f32]) -> f32 + Send + 'static,
+    ) -> Self {
+        let defaults = defaults.into();
+        Self {
+            designer: FirDesigner::new(0.0),
+            response: Box::new(response),
+            smoothed: defaults.iter().map(|&x| SmoothedParam::new(x)).collect(),
+            params: defaults.clone(),
+            defaults,
+            curve: vec![1.0; F::N_REAL_BINS],
+            dirty: true,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the current per-bin gain curve.
+    pub fn curve(&self) -> &[f32] {
+        &self.curve
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for param in self.smoothed.iter_mut() {
+            param.set_smoothing(smoothing);
+        }
+    }
+
+    fn update_curve(&mut self) {
+        if self.designer.sample_rate <= 0.0 {
+            return;
+        }
+        let response = &self.response;
+        let params = &self.params;
+        self.designer
+            .gain_curve_into(|hz| response(hz, params), &mut self.curve);
+        self.dirty = false;
+    }
+}
+
+impl<F: Fft> FftProcessor for DesignedFilter<F> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let mut specs = vec![SignalSpec::new("input", F::RealFft::signal_type())];
+        for i in 0..self.defaults.len() {
+            specs.push(SignalSpec::new(format!("param{i}"), f32::signal_type()));
+        }
+        specs
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        vec![SignalSpec::new("output", F::RealFft::signal_type())]
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        vec![AnyBuffer::zeros::<F::RealFft>(size)]
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.designer.sample_rate = sample_rate;
+        self.update_curve();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.designer.sample_rate = new;
+        self.update_curve();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for param in self.smoothed.iter_mut() {
+            param.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    processor_state!(smoothed);
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            for (index, (param, smoothed)) in
+                self.params.iter_mut().zip(&mut self.smoothed).enumerate()
+            {
+                smoothed.set_target(
+                    inputs
+                        .input_as::<f32>(index + 1)
+                        .and_then(|values| values.get(i).copied())
+                        .unwrap_or(self.defaults[index]),
+                );
+                let value = smoothed.advance();
+                if *param != value {
+                    *param = value;
+                    self.dirty = true;
+                }
+            }
+
+            if self.dirty {
+                self.update_curve();
+            }
+
+            self.out_signal.copy_from_slice(input);
+            for (bin, &gain) in self.out_signal.iter_mut().zip(&self.curve) {
+                *bin *= gain;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EqBandKind {
+    #[default]
+    Peak,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+}
+
+/// A single band of a [`LinearPhaseEq`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    pub kind: EqBandKind,
+    pub freq: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+impl EqBand {
+    pub fn new(kind: EqBandKind, freq: f32, gain_db: f32, q: f32) -> Self {
+        Self {
+            kind,
+            freq,
+            gain_db,
+            q,
+        }
+    }
+
+    /// Evaluates the magnitude response of the band's analog prototype at `hz`.
+    pub fn magnitude(&self, hz: f32) -> f32 {
+        if self.freq <= 0.0 || self.q <= 0.0 {
+            return 1.0;
+        }
+        let w = hz / self.freq;
+        let w2 = w * w;
+        let a = 10.0f32.powf(self.gain_db / 40.0);
+        let q = self.q;
+
+        let power = match self.kind {
+            EqBandKind::Peak => {
+                let num = (1.0 - w2).powi(2) + (w * a / q).powi(2);
+                let den = (1.0 - w2).powi(2) + (w / (a * q)).powi(2);
+                num / den
+            }
+            EqBandKind::LowShelf => {
+                let cross = (a.sqrt() * w / q).powi(2);
+                let num = (a - w2).powi(2) + cross;
+                let den = (1.0 - a * w2).powi(2) + cross;
+                a * a * num / den
+            }
+            EqBandKind::HighShelf => {
+                let cross = (a.sqrt() * w / q).powi(2);
+                let num = (1.0 - a * w2).powi(2) + cross;
+                let den = (a - w2).powi(2) + cross;
+                a * a * num / den
+            }
+            EqBandKind::LowPass => 1.0 / ((1.0 - w2).powi(2) + (w / q).powi(2)),
+            EqBandKind::HighPass => w2 * w2 / ((1.0 - w2).powi(2) + (w / q).powi(2)),
+        };
+
+        power.sqrt()
+    }
+}
+
+/// A parametric equalizer applied as a zero-phase per-bin gain curve.
+///
+/// Each band exposes `freq`, `gain` (dB) and `q` inputs, which override the band's configured
+/// values while connected. Parameter changes are smoothed over a few hops, and the composite
+/// response of all bands is only re-evaluated while a parameter is changing. Since the gains are
+/// purely real, the equalizer adds no phase distortion and no latency beyond that of the FFT graph
+/// itself.
+#[derive(Clone)]
+pub struct LinearPhaseEq<F: Fft> {
+    bands: Vec<EqBand>,
+    // freq, gain and q of each band
+    smoothed: Vec<[SmoothedParam; 3]>,
+    current: Vec<EqBand>,
+    sample_rate: f32,
+    curve: Vec<f32>,
+    dirty: bool,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> LinearPhaseEq<F> {
+    pub fn new(bands: impl Into<Vec<EqBand>>) -> Self {
+        let bands = bands.into();
+        Self {
+            smoothed: bands
+                .iter()
+                .map(|band| [band.freq, band.gain_db, band.q].map(SmoothedParam::new))
+                .collect(),
+            current: bands.clone(),
+            bands,
+            sample_rate: 0.0,
+            curve: vec![1.0; F::N_REAL_BINS],
+            dirty: true,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn bands(&self) -> &[EqBand] {
+        &self.bands
+    }
+
+    /// Returns the current per-bin gain curve.
+    pub fn curve(&self) -> &[f32] {
+        &self.curve
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for param in self.smoothed.iter_mut().flatten() {
+            param.set_smoothing(smoothing);
+        }
+    }
+
+    fn update_curve(&mut self) {
+        if self.sample_rate <= 0.0 {
+            return;
+        }
+        for ((_, hz), gain) in F::bin_frequencies(self.sample_rate).zip(&mut self.curve) {
+            *gain = self.current.iter().map(|band| band.magnitude(hz)).product();
+        }
+        self.dirty = false;
+    }
+}
+
+impl<F: Fft> Default for LinearPhaseEq<F> {
+    fn default() -> Self {
+        Self::new([
+            EqBand::new(EqBandKind::LowShelf, 100.0, 0.0, 0.707),
+            EqBand::new(EqBandKind::Peak, 1000.0, 0.0, 1.0),
+            EqBand::new(EqBandKind::HighShelf, 8000.0, 0.0, 0.707),
+        ])
+    }
+}
+
+impl<F: Fft> FftProcessor for LinearPhaseEq<F> {
+    processor_clone!();
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let mut specs = vec![SignalSpec::new("input", F::RealFft::signal_type())];
+        for i in 0..self.bands.len() {
+            specs.push(SignalSpec::new(format!("band{i}_freq"), f32::signal_type()));
+            specs.push(SignalSpec::new(format!("band{i}_gain"), f32::signal_type()));
+            specs.push(SignalSpec::new(format!("band{i}_q"), f32::signal_type()));
+        }
+        specs
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        vec![SignalSpec::new("output", F::RealFft::signal_type())]
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        vec![AnyBuffer::zeros::<F::RealFft>(size)]
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_curve();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+        self.update_curve();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for param in self.smoothed.iter_mut().flatten() {
+            param.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    processor_state!(smoothed);
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            let param = |index: usize, default: f32| {
+                inputs
+                    .input_as::<f32>(index)
+                    .and_then(|values| values.get(i).copied())
+                    .unwrap_or(default)
+            };
+
+            for (band_index, ((band, current), smoothed)) in self
+                .bands
+                .iter()
+                .zip(self.current.iter_mut())
+                .zip(self.smoothed.iter_mut())
+                .enumerate()
+            {
+                let base = 1 + band_index * 3;
+                let [freq, gain_db, q] = smoothed;
+                freq.set_target(param(base, band.freq));
+                gain_db.set_target(param(base + 1, band.gain_db));
+                q.set_target(param(base + 2, band.q));
+                let updated = EqBand {
+                    freq: freq.advance(),
+                    gain_db: gain_db.advance(),
+                    q: q.advance(),
+                    ..*band
+                };
+                if *current != updated {
+                    *current = updated;
+                    self.dirty = true;
+                }
+            }
+
+            if self.dirty {
+                self.update_curve();
+            }
+
+            self.out_signal.copy_from_slice(input);
+            for (bin, &gain) in self.out_signal.iter_mut().zip(&self.curve) {
+                *bin *= gain;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Tilts the spectrum by a constant slope in dB per octave around a pivot frequency.
+///
+/// Bins above the pivot are boosted and bins below it are cut for positive slopes (and vice
+/// versa). The `tilt` and `pivot` inputs override the configured values while connected, and are
+/// smoothed over a few hops.
+#[derive(Clone)]
+pub struct SpectralTilt<F: Fft> {
+    pub tilt_db_per_octave: f32,
+    pub pivot_hz: f32,
+    tilt: SmoothedParam,
+    pivot: SmoothedParam,
+    current: (f32, f32),
+    sample_rate: f32,
+    curve: Vec<f32>,
+    dirty: bool,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralTilt<F> {
+    pub fn new(tilt_db_per_octave: f32, pivot_hz: f32) -> Self {
+        Self {
+            tilt_db_per_octave,
+            pivot_hz,
+            tilt: SmoothedParam::new(tilt_db_per_octave),
+            pivot: SmoothedParam::new(pivot_hz),
+            current: (tilt_db_per_octave, pivot_hz),
+            sample_rate: 0.0,
+            curve: vec![1.0; F::N_REAL_BINS],
+            dirty: true,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.tilt.set_smoothing(smoothing);
+        self.pivot.set_smoothing(smoothing);
+    }
+
+    fn update_curve(&mut self) {
+        if self.sample_rate <= 0.0 {
+            return;
+        }
+        let (tilt, pivot) = self.current;
+        for (bin, gain) in self.curve.iter_mut().enumerate() {
+            // DC has no octave position, so give it the same gain as the first bin
+            let hz = F::bin_to_hz(bin.max(1), self.sample_rate);
+            let octaves = (hz / pivot.max(f32::EPSILON)).log2();
+            *gain = units::db_to_magnitude(tilt * octaves);
+        }
+        self.dirty = false;
+    }
+}
+
+impl<F: Fft> Default for SpectralTilt<F> {
+    fn default() -> Self {
+        Self::new(0.0, 1000.0)
+    }
+}
+
+processor_builder! {
+    SpectralTilt => SpectralTiltBuilder {
+        tilt_db_per_octave: f32 [DecibelsPerOctave, -12.0, 12.0, Internal],
+        pivot_hz: f32 [Hertz, 20.0, 20000.0, Internal],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralTilt<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, tilt: f32, pivot: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(tilt, pivot);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralTiltBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralTiltBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.tilt.reset(self.tilt_db_per_octave);
+        self.pivot.reset(self.pivot_hz);
+        self.current = (self.tilt_db_per_octave, self.pivot_hz);
+        self.update_curve();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+        self.update_curve();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.tilt.set_hop_seconds(info.hop_seconds());
+        self.pivot.set_hop_seconds(info.hop_seconds());
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let tilt = inputs.input_as::<f32>(1);
+        let pivot = inputs.input_as::<f32>(2);
+
+        for (i, input) in input.iter().enumerate() {
+            self.tilt.set_target(
+                tilt.and_then(|tilt| tilt.get(i).copied())
+                    .unwrap_or(self.tilt_db_per_octave),
+            );
+            self.pivot.set_target(
+                pivot
+                    .and_then(|pivot| pivot.get(i).copied())
+                    .unwrap_or(self.pivot_hz),
+            );
+            let current = (self.tilt.advance(), self.pivot.advance());
+            if self.current != current {
+                self.current = current;
+                self.dirty = true;
+            }
+
+            if self.dirty {
+                self.update_curve();
+            }
+
+            self.out_signal.copy_from_slice(input);
+            for (bin, &gain) in self.out_signal.iter_mut().zip(&self.curve) {
+                *bin *= gain;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How an [`AdaptiveBand`] bends its normalized feature (`0..=1`) before mapping it to a gain.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureCurve {
+    #[default]
+    Linear,
+    /// Changes slowly at low feature values and quickly at high ones.
+    Squared,
+    /// Changes quickly at low feature values and slowly at high ones.
+    SquareRoot,
+    /// Changes slowly at both ends of the range and quickly in the middle.
+    SmoothStep,
+    /// The start of the range below the middle, the end of it from the middle on.
+    Step,
+}
+
+impl FeatureCurve {
+    pub const ALL: [FeatureCurve; 5] = [
+        Self::Linear,
+        Self::Squared,
+        Self::SquareRoot,
+        Self::SmoothStep,
+        Self::Step,
+    ];
+
+    /// Looks up a curve by its snake-case name (e.g. `"smooth_step"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Self::Linear),
+            "squared" => Some(Self::Squared),
+            "square_root" => Some(Self::SquareRoot),
+            "smooth_step" => Some(Self::SmoothStep),
+            "step" => Some(Self::Step),
+            _ => None,
+        }
+    }
+
+    /// Maps `value`, clamped to `0..=1`, onto `0..=1`.
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => value,
+            Self::Squared => value * value,
+            Self::SquareRoot => value.sqrt(),
+            Self::SmoothStep => value * value * (3.0 - 2.0 * value),
+            Self::Step => {
+                if value < 0.5 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// A single band of an [`AdaptiveGain`], whose gain moves from `low_db` to `high_db` as the
+/// feature rises through its range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveBand {
+    pub center_hz: f32,
+    pub low_db: f32,
+    pub high_db: f32,
+    pub curve: FeatureCurve,
+}
+
+impl AdaptiveBand {
+    pub fn new(center_hz: f32, low_db: f32, high_db: f32) -> Self {
+        Self {
+            center_hz,
+            low_db,
+            high_db,
+            curve: FeatureCurve::Linear,
+        }
+    }
+
+    pub fn with_curve(mut self, curve: FeatureCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Returns the band's gain in dB for the normalized feature `value`.
+    pub fn gain_db(&self, value: f32) -> f32 {
+        self.low_db + (self.high_db - self.low_db) * self.curve.apply(value)
+    }
+}
+
+/// Applies per-band gains driven by a control feature, routing an analysis (such as the
+/// `brightness` output of [`SpectralCentroid`](crate::builtins::analysis::SpectralCentroid)) into
+/// an effect.
+///
+/// The `feature` input (or field, while unconnected) is normalized from
+/// `feature_min..feature_max` to `0..1`, smoothed over a few hops, and mapped to a gain for every
+/// band by [`AdaptiveBand::gain_db`]. The gains are interpolated in dB between the band centers,
+/// and held beyond the first and last center. The gain curve is only re-evaluated while the
+/// feature is changing.
+///
+/// [`brightness`](Self::brightness) sets up an adaptive brightness control: the highs are boosted
+/// for dull input and cut for bright input, keeping the tonal balance steady.
+#[derive(Clone)]
+pub struct AdaptiveGain<F: Fft> {
+    pub feature: f32,
+    pub feature_min: f32,
+    pub feature_max: f32,
+    bands: Vec<AdaptiveBand>,
+    smoothed: SmoothedParam,
+    current: f32,
+    sample_rate: f32,
+    layout: TriangularBands,
+    band_gains: Vec<f32>,
+    curve: Vec<f32>,
+    dirty: bool,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> AdaptiveGain<F> {
+    /// Creates the processor with the given bands, which are sorted by their centers.
+    pub fn new(bands: impl Into<Vec<AdaptiveBand>>) -> Self {
+        let mut bands = bands.into();
+        bands.sort_by(|a, b| a.center_hz.total_cmp(&b.center_hz));
+        Self {
+            feature: 0.0,
+            feature_min: 0.0,
+            feature_max: 1.0,
+            band_gains: vec![0.0; bands.len()],
+            bands,
+            smoothed: SmoothedParam::new(0.0),
+            current: 0.0,
+            sample_rate: 0.0,
+            // replaced once the sample rate is known
+            layout: TriangularBands::from_centers_hz(&[], 48_000.0, F::N_FFT),
+            curve: vec![1.0; F::N_REAL_BINS],
+            dirty: true,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Creates an adaptive brightness control for a feature between 0 (dull) and 1 (bright):
+    /// bins from `high_hz` up are boosted by `range_db` at 0 and cut by `range_db` at 1, fading
+    /// to no change at two octaves below `high_hz`.
+    pub fn brightness(high_hz: f32, range_db: f32) -> Self {
+        Self::new([
+            AdaptiveBand::new(high_hz / 4.0, 0.0, 0.0),
+            AdaptiveBand::new(high_hz, range_db, -range_db),
+        ])
+    }
+
+    pub fn bands(&self) -> &[AdaptiveBand] {
+        &self.bands
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothed.set_smoothing(smoothing);
+    }
+
+    /// Returns the current gain of every band in dB.
+    pub fn band_gains_db(&self) -> &[f32] {
+        &self.band_gains
+    }
+
+    fn update_layout(&mut self) {
+        let centers_hz = self
+            .bands
+            .iter()
+            .map(|band| band.center_hz)
+            .collect::<Vec<_>>();
+        self.layout = TriangularBands::from_centers_hz(&centers_hz, self.sample_rate, F::N_FFT);
+        self.dirty = true;
+    }
+
+    fn update_curve(&mut self) {
+        if self.sample_rate <= 0.0 {
+            return;
+        }
+        let range = self.feature_max - self.feature_min;
+        let value = if range.abs() <= f32::EPSILON {
+            0.0
+        } else {
+            (self.current - self.feature_min) / range
+        };
+        for (gain, band) in self.band_gains.iter_mut().zip(&self.bands) {
+            *gain = band.gain_db(value);
+        }
+
+        self.curve.fill(0.0);
+        self.layout.interpolate(&self.band_gains, &mut self.curve);
+        for gain in &mut self.curve {
+            *gain = units::db_to_magnitude(*gain);
+        }
+        self.dirty = false;
+    }
+}
+
+impl<F: Fft> Default for AdaptiveGain<F> {
+    fn default() -> Self {
+        Self::brightness(4000.0, 6.0)
+    }
+}
+
+processor_builder! {
+    AdaptiveGain => AdaptiveGainBuilder {
+        feature: f32 [None, -1000.0, 20000.0, Internal],
+        feature_min: f32 [None, -1000.0, 20000.0],
+        feature_max: f32 [None, -1000.0, 20000.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for AdaptiveGain<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, feature: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(smoothed);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        AdaptiveGainBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        AdaptiveGainBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_layout();
+        self.update_curve();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+        self.update_layout();
+        self.update_curve();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.smoothed.set_hop_seconds(info.hop_seconds());
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let feature = inputs.input_as::<f32>(1);
+
+        for (i, input) in input.iter().enumerate() {
+            self.smoothed.set_target(
+                feature
+                    .and_then(|feature| feature.get(i).copied())
+                    .unwrap_or(self.feature),
+            );
+            let current = self.smoothed.advance();
+            if self.current != current {
+                self.current = current;
+                self.dirty = true;
+            }
+
+            if self.dirty {
+                self.update_curve();
+            }
+
+            self.out_signal.copy_from_slice(input);
+            for (bin, &gain) in self.out_signal.iter_mut().zip(&self.curve) {
+                *bin *= gain;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,2 +1,21 @@
+pub mod analysis;
+pub mod control;
+#[cfg(feature = "ml")]
+pub mod denoise;
+pub mod dynamics;
+pub mod enhance;
+pub mod file;
+pub mod filter;
+pub mod model;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod restoration;
+pub mod routing;
+pub mod sampler;
+pub mod shift;
+pub mod spatial;
+pub mod stereo;
+pub mod synthesis;
 pub mod transforms;
+pub mod trigger;
 pub mod util;
@@ -0,0 +1,349 @@
+//! Running learned models (e.g. neural denoisers or source separators) on spectral frames.
+//!
+//! A [`SpectralModel`] maps a vector of features to a vector of outputs once per frame. A
+//! [`ModelProcessor`] extracts the features from its input spectrum, runs the model, and applies
+//! its outputs back to the spectrum as gains or magnitudes. With the `ml` feature, [`OnnxModel`]
+//! runs ONNX models with tract.
+
+use std::error::Error;
+
+use raug::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    bands::MelFilterbank,
+    fft_processor_io,
+    history::set_magnitude,
+    processor::{FftProcessor, ParamSpec, ParamUnit},
+    signal::Fft,
+    units,
+};
+
+/// Added to magnitudes before taking their logarithm.
+const LOG_FLOOR: f32 = 1e-6;
+
+/// A model run once per frame.
+pub trait SpectralModel
+where
+    Self: Send + 'static,
+{
+    /// Returns the number of features the model takes.
+    fn input_len(&self) -> usize;
+
+    /// Returns the number of values the model produces.
+    fn output_len(&self) -> usize;
+
+    /// Runs the model on `input`, writing its result to `output`.
+    fn run(
+        &mut self,
+        input: &[f32],
+        output: &mut [f32],
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// The features a [`ModelProcessor`] feeds to its model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelFeatures {
+    /// The magnitude of every bin.
+    Magnitudes,
+    /// The natural logarithm of the magnitude of every bin.
+    LogMagnitudes,
+    /// The magnitudes summed by a [`MelFilterbank`] with `bands` filters between `min_hz` and
+    /// `max_hz`.
+    Mel {
+        bands: usize,
+        min_hz: f32,
+        max_hz: f32,
+    },
+    /// The natural logarithm of the mel magnitudes.
+    LogMel {
+        bands: usize,
+        min_hz: f32,
+        max_hz: f32,
+    },
+}
+
+impl ModelFeatures {
+    /// Returns the number of features for spectra of `num_bins` bins.
+    pub fn len(&self, num_bins: usize) -> usize {
+        match self {
+            Self::Magnitudes | Self::LogMagnitudes => num_bins,
+            Self::Mel { bands, .. } | Self::LogMel { bands, .. } => *bands,
+        }
+    }
+
+    pub fn is_log(&self) -> bool {
+        matches!(self, Self::LogMagnitudes | Self::LogMel { .. })
+    }
+
+    fn filterbank(&self, sample_rate: f32, fft_length: usize) -> Option<MelFilterbank> {
+        match *self {
+            Self::Mel {
+                bands,
+                min_hz,
+                max_hz,
+            }
+            | Self::LogMel {
+                bands,
+                min_hz,
+                max_hz,
+            } => Some(MelFilterbank::new(
+                bands,
+                min_hz,
+                max_hz,
+                sample_rate,
+                fft_length,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// How a [`ModelProcessor`] applies its model's outputs, which hold one value per bin, or per mel
+/// band with mel features.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ModelOutput {
+    /// Gains multiplying the bins, e.g. a denoising mask.
+    #[default]
+    Gains,
+    /// New magnitudes, keeping the phases of the input.
+    Magnitudes,
+    /// The natural logarithms of new magnitudes, keeping the phases of the input.
+    LogMagnitudes,
+}
+
+#[derive(Debug, Error)]
+pub enum ModelError {
+    #[error("model takes {found} features, expected {expected}")]
+    InputLength { expected: usize, found: usize },
+    #[error("model produces {found} values, expected {expected}")]
+    OutputLength { expected: usize, found: usize },
+}
+
+/// Runs a [`SpectralModel`] on every input frame, see the [module docs](self).
+///
+/// With mel features, per-band outputs are spread back over the bins with
+/// [`MelFilterbank::expand`]. The `amount` input (or field) blends between the input (0) and the
+/// model's result (1); gains are blended in dB. Model errors fail the frame.
+///
+/// Models usually allocate while running, so the processor is only as real-time safe as the
+/// model it runs.
+pub struct ModelProcessor<F: Fft, M: SpectralModel> {
+    pub amount: f32,
+    model: M,
+    features: ModelFeatures,
+    output: ModelOutput,
+    sample_rate: f32,
+    filterbank: Option<MelFilterbank>,
+    magnitudes: Vec<f32>,
+    model_input: Vec<f32>,
+    model_output: Vec<f32>,
+    bin_values: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft, M: SpectralModel> ModelProcessor<F, M> {
+    /// Wraps `model`, checking that its input and output lengths match `features`.
+    pub fn new(model: M, features: ModelFeatures, output: ModelOutput) -> Result<Self, ModelError> {
+        let expected = features.len(F::N_REAL_BINS);
+        if model.input_len() != expected {
+            return Err(ModelError::InputLength {
+                expected,
+                found: model.input_len(),
+            });
+        }
+        if model.output_len() != expected {
+            return Err(ModelError::OutputLength {
+                expected,
+                found: model.output_len(),
+            });
+        }
+
+        Ok(Self {
+            amount: 1.0,
+            model,
+            features,
+            output,
+            sample_rate: 0.0,
+            // replaced once the sample rate is known
+            filterbank: features.filterbank(48_000.0, F::N_FFT),
+            magnitudes: vec![0.0; F::N_REAL_BINS],
+            model_input: vec![0.0; expected],
+            model_output: vec![0.0; expected],
+            bin_values: vec![0.0; F::N_REAL_BINS],
+            out_signal: Box::new(F::RealFft::default()),
+        })
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+
+    pub fn features(&self) -> ModelFeatures {
+        self.features
+    }
+
+    pub fn output(&self) -> ModelOutput {
+        self.output
+    }
+}
+
+impl<F: Fft, M: SpectralModel> FftProcessor for ModelProcessor<F, M> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, amount: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        vec![ParamSpec::new("amount", ParamUnit::None, 0.0, 1.0, 1.0)]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if name != "amount" {
+            return false;
+        }
+        self.amount = value;
+        true
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.filterbank = self.features.filterbank(sample_rate, F::N_FFT);
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.allocate(new);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let amount = inputs.input_as::<f32>(1);
+
+        for (i, input) in input.iter().enumerate() {
+            let amount = amount
+                .and_then(|amount| amount.get(i).copied())
+                .unwrap_or(self.amount)
+                .clamp(0.0, 1.0);
+
+            units::magnitudes(input, &mut self.magnitudes);
+            match &self.filterbank {
+                Some(filterbank) => filterbank.apply(&self.magnitudes, &mut self.model_input),
+                None => self.model_input.copy_from_slice(&self.magnitudes),
+            }
+            if self.features.is_log() {
+                for feature in &mut self.model_input {
+                    *feature = (*feature + LOG_FLOOR).ln();
+                }
+            }
+
+            if let Err(e) = self.model.run(&self.model_input, &mut self.model_output) {
+                return Err(ProcessorError::ProcessingError(e));
+            }
+
+            match &self.filterbank {
+                Some(filterbank) => filterbank.expand(&self.model_output, &mut self.bin_values),
+                None => self.bin_values.copy_from_slice(&self.model_output),
+            }
+
+            self.out_signal.copy_from_slice(input);
+            for ((out, &value), &magnitude) in self
+                .out_signal
+                .iter_mut()
+                .zip(&self.bin_values)
+                .zip(&self.magnitudes)
+            {
+                match self.output {
+                    ModelOutput::Gains => *out *= value.max(0.0).powf(amount),
+                    ModelOutput::Magnitudes | ModelOutput::LogMagnitudes => {
+                        let target = if self.output == ModelOutput::LogMagnitudes {
+                            value.exp()
+                        } else {
+                            value.max(0.0)
+                        };
+                        set_magnitude(out, magnitude + (target - magnitude) * amount);
+                    }
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`ModelProcessor`] running an ONNX model.
+#[cfg(feature = "ml")]
+pub type OnnxSpectralProcessor<F> = ModelProcessor<F, OnnxModel>;
+
+/// An ONNX model run with tract.
+///
+/// The model must take a single `f32` tensor of shape `[1, input_len]` and produce an `f32`
+/// tensor with `output_len` elements as its first output.
+#[cfg(feature = "ml")]
+pub struct OnnxModel {
+    plan: tract_onnx::prelude::TypedRunnableModel<tract_onnx::prelude::TypedModel>,
+    input_len: usize,
+    output_len: usize,
+}
+
+#[cfg(feature = "ml")]
+impl OnnxModel {
+    /// Loads and optimizes the model at `path`, fixing its input to `[1, input_len]`.
+    pub fn load(
+        path: impl AsRef<std::path::Path>,
+        input_len: usize,
+    ) -> tract_onnx::prelude::TractResult<Self> {
+        use tract_onnx::prelude::*;
+
+        let model = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, input_len]).into())?
+            .into_optimized()?;
+        let output_len = model
+            .output_fact(0)?
+            .shape
+            .as_concrete()
+            .map(|shape| shape.iter().product())
+            .ok_or_else(|| TractError::msg("model output has no fixed shape"))?;
+
+        Ok(Self {
+            plan: model.into_runnable()?,
+            input_len,
+            output_len,
+        })
+    }
+}
+
+#[cfg(feature = "ml")]
+impl SpectralModel for OnnxModel {
+    fn input_len(&self) -> usize {
+        self.input_len
+    }
+
+    fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    fn run(
+        &mut self,
+        input: &[f32],
+        output: &mut [f32],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        use tract_onnx::prelude::*;
+
+        let input = Tensor::from_shape(&[1, self.input_len], input)?;
+        let result = self.plan.run(tvec!(input.into()))?;
+        let values = result[0].as_slice::<f32>()?;
+        output.copy_from_slice(&values[..output.len()]);
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Processors streaming frames to and from other processes or machines over UDP.
+//!
+//! Every frame is split into chunks of [`MAX_BINS_PER_PACKET`] bins (the last one holding the
+//! rest), each sent as one datagram. A datagram is a 20-byte header followed by the bins as pairs
+//! of real and imaginary `f32`s:
+//!
+//! - the 4 bytes `RFFN`
+//! - the frame's sequence number (`u32`), counting up from 0 and wrapping around
+//! - the FFT length (`u32`)
+//! - the first bin in the datagram (`u32`)
+//! - the number of bins in the datagram (`u32`)
+//!
+//! All numbers are little-endian. UDP doesn't guarantee delivery or order, so a receiver only
+//! outputs frames whose datagrams all arrived, counting each chunk once however often it arrives,
+//! and skips frames older than the latest one.
+
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use raug::prelude::*;
+
+use crate::{
+    fft_processor_io,
+    processor::FftProcessor,
+    signal::{Complex32, Fft},
+};
+
+/// The bytes every datagram starts with.
+pub const NET_MAGIC: [u8; 4] = *b"RFFN";
+/// The most bins sent in one datagram, keeping datagrams around 8 KB.
+pub const MAX_BINS_PER_PACKET: usize = 1024;
+
+const HEADER_LENGTH: usize = 20;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_bits(read_u32(bytes, offset))
+}
+
+/// Sends every input frame to `destination` and passes it through unchanged.
+///
+/// The socket is non-blocking, so frames that can't be sent immediately are dropped rather than
+/// stalling the audio thread; see [`dropped_frames`](Self::dropped_frames).
+pub struct FrameNetSend<F: Fft> {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    sequence: u32,
+    dropped_frames: u64,
+    packet: Vec<u8>,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> FrameNetSend<F> {
+    /// Creates a sender sending from `socket`, which is made non-blocking.
+    pub fn new(socket: UdpSocket, destination: SocketAddr) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            destination,
+            sequence: 0,
+            dropped_frames: 0,
+            packet: Vec::with_capacity(HEADER_LENGTH + MAX_BINS_PER_PACKET * 8),
+            _f: std::marker::PhantomData,
+        })
+    }
+
+    /// Creates a sender sending to `destination` from an ephemeral port.
+    pub fn connect(destination: impl ToSocketAddrs) -> io::Result<Self> {
+        let destination = destination
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address given"))?;
+        let local: SocketAddr = if destination.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        Self::new(UdpSocket::bind(local)?, destination)
+    }
+
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// Returns the number of frames that couldn't be sent completely.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    fn send(&mut self, frame: &[Complex32]) -> bool {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        for (chunk, bins) in frame.chunks(MAX_BINS_PER_PACKET).enumerate() {
+            self.packet.clear();
+            self.packet.extend_from_slice(&NET_MAGIC);
+            self.packet.extend_from_slice(&sequence.to_le_bytes());
+            self.packet
+                .extend_from_slice(&(F::N_FFT as u32).to_le_bytes());
+            self.packet
+                .extend_from_slice(&((chunk * MAX_BINS_PER_PACKET) as u32).to_le_bytes());
+            self.packet
+                .extend_from_slice(&(bins.len() as u32).to_le_bytes());
+            for bin in bins {
+                self.packet.extend_from_slice(&bin.re.to_le_bytes());
+                self.packet.extend_from_slice(&bin.im.to_le_bytes());
+            }
+            if self.socket.send_to(&self.packet, self.destination).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameNetSend<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft }
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            if !self.send(input) {
+                self.dropped_frames += 1;
+            }
+            outputs.set_output_as::<F::RealFft>(0, i, input)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Receives frames sent by a [`FrameNetSend`] with the same FFT length.
+///
+/// Every hop, all datagrams that arrived since are read without blocking, and the latest frame
+/// that arrived completely is output, along with a trigger. If no new frame is complete, the
+/// previous frame is repeated if `hold` is set, and silence is output otherwise. Datagrams for a
+/// different FFT length are ignored.
+pub struct FrameNetReceive<F: Fft> {
+    pub hold: bool,
+    socket: UdpSocket,
+    // the frame being assembled, its sequence number and a bit per chunk received so far
+    pending: Box<F::RealFft>,
+    pending_sequence: Option<u32>,
+    pending_chunks: u64,
+    last_sequence: Option<u32>,
+    packet: Vec<u8>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> FrameNetReceive<F> {
+    /// Creates a receiver reading from `socket`, which is made non-blocking.
+    pub fn new(socket: UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            hold: true,
+            socket,
+            pending: Box::new(F::RealFft::default()),
+            pending_sequence: None,
+            pending_chunks: 0,
+            last_sequence: None,
+            packet: vec![0; HEADER_LENGTH + MAX_BINS_PER_PACKET * 8],
+            out_signal: Box::new(F::RealFft::default()),
+        })
+    }
+
+    /// Creates a receiver listening on `address`.
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::new(UdpSocket::bind(address)?)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Returns the mask of received chunks once every chunk of a frame arrived.
+    fn all_chunks() -> u64 {
+        let chunks = F::N_REAL_BINS.div_ceil(MAX_BINS_PER_PACKET);
+        debug_assert!(chunks <= 64, "too many chunks per frame");
+        u64::MAX >> (64 - chunks)
+    }
+
+    /// Returns whether `sequence` comes after `other`, allowing for wrap-around.
+    fn is_newer(sequence: u32, other: Option<u32>) -> bool {
+        other.is_none_or(|other| (sequence.wrapping_sub(other) as i32) > 0)
+    }
+
+    /// Reads one datagram into the pending frame, returning whether it completed the frame.
+    fn receive(&mut self, length: usize) -> bool {
+        let packet = &self.packet[..length];
+        if length < HEADER_LENGTH
+            || packet[..4] != NET_MAGIC
+            || read_u32(packet, 8) as usize != F::N_FFT
+        {
+            return false;
+        }
+        let sequence = read_u32(packet, 4);
+        let start = read_u32(packet, 12) as usize;
+        let count = read_u32(packet, 16) as usize;
+        // only whole chunks are accepted, so each can be told apart by its first bin
+        if !start.is_multiple_of(MAX_BINS_PER_PACKET)
+            || start >= F::N_REAL_BINS
+            || count != MAX_BINS_PER_PACKET.min(F::N_REAL_BINS - start)
+            || length != HEADER_LENGTH + count * 8
+        {
+            return false;
+        }
+
+        if self.pending_sequence != Some(sequence) {
+            // drop frames older than the one being assembled or already output
+            if !Self::is_newer(sequence, self.pending_sequence)
+                || !Self::is_newer(sequence, self.last_sequence)
+            {
+                return false;
+            }
+            self.pending_sequence = Some(sequence);
+            self.pending_chunks = 0;
+        }
+
+        let bins = packet[HEADER_LENGTH..].chunks_exact(8);
+        for (bin, bytes) in self.pending[start..start + count].iter_mut().zip(bins) {
+            *bin = Complex32::new(read_f32(bytes, 0), read_f32(bytes, 4));
+        }
+        self.pending_chunks |= 1 << (start / MAX_BINS_PER_PACKET);
+        self.pending_chunks == Self::all_chunks()
+    }
+}
+
+impl<F: Fft> FftProcessor for FrameNetReceive<F> {
+    fft_processor_io! {
+        inputs {}
+        outputs { output: F::RealFft, received: bool }
+    }
+
+    fn process(
+        &mut self,
+        _inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let mut received = false;
+        while let Ok(length) = self.socket.recv(&mut self.packet) {
+            if self.receive(length) {
+                std::mem::swap(&mut self.out_signal, &mut self.pending);
+                self.last_sequence = self.pending_sequence.take();
+                received = true;
+            }
+        }
+
+        if received || self.hold {
+            outputs.set_output_as::<F::RealFft>(0, 0, &*self.out_signal)?;
+        } else {
+            outputs.set_output_as::<F::RealFft>(0, 0, &F::RealFft::default())?;
+        }
+        outputs.set_output_as::<bool>(1, 0, &received)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Fft4096;
+
+    /// Writes a datagram of `count` bins from `start` of frame `sequence` into `receiver` and
+    /// reads it, returning whether it completed the frame.
+    fn receive_chunk(
+        receiver: &mut FrameNetReceive<Fft4096>,
+        sequence: u32,
+        start: usize,
+        count: usize,
+    ) -> bool {
+        let mut packet = NET_MAGIC.to_vec();
+        for value in [sequence, 4096, start as u32, count as u32] {
+            packet.extend_from_slice(&value.to_le_bytes());
+        }
+        for bin in start..start + count {
+            packet.extend_from_slice(&(bin as f32).to_le_bytes());
+            packet.extend_from_slice(&0.0f32.to_le_bytes());
+        }
+        receiver.packet[..packet.len()].copy_from_slice(&packet);
+        receiver.receive(packet.len())
+    }
+
+    #[test]
+    fn completes_frames_once_every_chunk_arrived() {
+        let mut receiver = FrameNetReceive::<Fft4096>::bind("127.0.0.1:0").unwrap();
+
+        // 2049 bins in chunks of 1024, 1024 and 1, the first arriving three times
+        assert!(!receive_chunk(&mut receiver, 0, 0, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 0, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 0, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 2048, 1));
+        assert!(receive_chunk(&mut receiver, 0, 1024, 1024));
+        assert_eq!(receiver.pending[2048], Complex32::new(2048.0, 0.0));
+    }
+
+    #[test]
+    fn ignores_chunks_that_are_misaligned_or_cut_short() {
+        let mut receiver = FrameNetReceive::<Fft4096>::bind("127.0.0.1:0").unwrap();
+
+        assert!(!receive_chunk(&mut receiver, 0, 0, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 1024, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 1025, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 2048, 0));
+        assert!(receive_chunk(&mut receiver, 0, 2048, 1));
+    }
+
+    #[test]
+    fn restarts_assembly_for_newer_frames() {
+        let mut receiver = FrameNetReceive::<Fft4096>::bind("127.0.0.1:0").unwrap();
+
+        assert!(!receive_chunk(&mut receiver, 0, 0, 1024));
+        assert!(!receive_chunk(&mut receiver, 0, 1024, 1024));
+        // the chunks of frame 0 don't count towards frame 1
+        assert!(!receive_chunk(&mut receiver, 1, 2048, 1));
+        assert!(!receive_chunk(&mut receiver, 0, 2048, 1));
+        assert!(!receive_chunk(&mut receiver, 1, 0, 1024));
+        assert!(receive_chunk(&mut receiver, 1, 1024, 1024));
+    }
+}
@@ -0,0 +1,438 @@
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    denormal::flush_denormals,
+    fft_processor_io,
+    history::{FrameHistory, lerp_magnitudes, set_magnitude},
+    peaks,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::Fft,
+    units,
+};
+
+/// Reconstructs clipped frames by interpolating bin magnitudes from the nearest unclipped
+/// neighbors.
+///
+/// Frames are flagged as clipped either through the `clip` input (any non-zero value) or, if
+/// `detect` is enabled, when their energy above `cutoff_hz` rises more than `threshold_db` over
+/// the average of the surrounding frames. Only bins deviating from the interpolated estimate by
+/// more than `threshold_db` are replaced, and their phase is preserved.
+///
+/// The `radius` frames before a frame are available for interpolation, and the output is delayed
+/// so that as many frames after it are too, or as many as the graph's look-ahead if that's more
+/// (see [`FftGraph::set_lookahead_frames`](crate::graph::FftGraph::set_lookahead_frames)).
+#[derive(Clone)]
+pub struct Declip<F: Fft> {
+    pub detect: bool,
+    pub threshold_db: f32,
+    pub cutoff_hz: f32,
+    radius: usize,
+    lookahead: usize,
+    sample_rate: f32,
+    flush_denormals: bool,
+    // (spectrum, externally flagged, high-frequency energy)
+    history: FrameHistory<(F::RealFft, bool, f32)>,
+    estimate: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> Declip<F> {
+    pub fn new(radius: usize) -> Self {
+        Self {
+            detect: true,
+            threshold_db: 6.0,
+            cutoff_hz: 8000.0,
+            radius,
+            lookahead: radius,
+            sample_rate: 0.0,
+            flush_denormals: false,
+            history: FrameHistory::new(2 * radius + 1),
+            estimate: vec![0.0; F::N_REAL_BINS],
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the number of frames before a frame that are used for interpolating it.
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Returns the number of frames after a frame that are used for interpolating it, which the
+    /// output is delayed by.
+    pub fn lookahead_frames(&self) -> usize {
+        self.lookahead
+    }
+
+    fn high_frequency_energy(&self, frame: &F::RealFft) -> f32 {
+        let cutoff_bin = if self.sample_rate > 0.0 {
+            F::hz_to_bin(self.cutoff_hz, self.sample_rate) as usize
+        } else {
+            F::N_REAL_BINS / 2
+        };
+        frame
+            .iter()
+            .skip(cutoff_bin.min(F::N_REAL_BINS))
+            .map(|bin| bin.norm_sqr())
+            .sum()
+    }
+
+    fn is_clipped(&self, age: usize, total_energy: f32) -> bool {
+        let (_, flagged, energy) = self.history.get(age);
+        if *flagged {
+            return true;
+        }
+        if !self.detect || self.history.len() < 2 {
+            return false;
+        }
+        let neighbor_mean = (total_energy - energy) / (self.history.len() - 1) as f32;
+        *energy > neighbor_mean * 10.0f32.powf(self.threshold_db / 10.0)
+    }
+}
+
+impl<F: Fft> Default for Declip<F> {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+processor_builder! {
+    Declip => DeclipBuilder {
+        detect: bool [Toggle, 0.0, 1.0],
+        threshold_db: f32 [Decibels, 0.0, 60.0],
+        cutoff_hz: f32 [Hertz, 20.0, 20000.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for Declip<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, clip: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(history);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        DeclipBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        DeclipBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn latency_frames(&self) -> usize {
+        self.lookahead
+    }
+
+    fn set_graph_lookahead(&mut self, frames: usize) {
+        let lookahead = self.radius.max(frames);
+        if lookahead != self.lookahead {
+            self.lookahead = lookahead;
+            self.history = FrameHistory::new(self.radius + lookahead + 1);
+        }
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.history.clear();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.flush_denormals = info.flush_denormals;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let clip = inputs.input_as::<f32>(1);
+
+        for (i, input) in input.iter().enumerate() {
+            let flagged = clip
+                .and_then(|clip| clip.get(i))
+                .is_some_and(|&flag| flag != 0.0);
+            let energy = self.high_frequency_energy(input);
+            self.history.push(&(input.clone(), flagged, energy));
+            if self.flush_denormals {
+                // the held frames are summed and interpolated on every hop
+                flush_denormals(&mut self.history.get_mut(0).0);
+            }
+
+            let center = self.lookahead;
+            self.out_signal.copy_from_slice(&self.history.get(center).0);
+
+            let total_energy: f32 = self.history.iter().map(|(_, _, energy)| energy).sum();
+
+            if self.is_clipped(center, total_energy) {
+                let newer = (0..center)
+                    .rev()
+                    .find(|&age| !self.is_clipped(age, total_energy));
+                let older = (center + 1..self.history.len())
+                    .find(|&age| !self.is_clipped(age, total_energy));
+
+                let (a, b, t) = match (older, newer) {
+                    (Some(older), Some(newer)) => {
+                        let t = (older - center) as f32 / (older - newer) as f32;
+                        (older, newer, t)
+                    }
+                    (Some(age), None) | (None, Some(age)) => (age, age, 0.0),
+                    // nothing to interpolate from, pass the frame through untouched
+                    (None, None) => {
+                        outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+                        continue;
+                    }
+                };
+
+                lerp_magnitudes(
+                    &self.history.get(a).0,
+                    &self.history.get(b).0,
+                    t,
+                    &mut self.estimate,
+                );
+
+                let tolerance = units::db_to_magnitude(self.threshold_db);
+                for (bin, &estimate) in self.out_signal.iter_mut().zip(&self.estimate) {
+                    let magnitude = bin.norm();
+                    if magnitude > estimate * tolerance || magnitude * tolerance < estimate {
+                        set_magnitude(bin, estimate);
+                    }
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes mains hum by tracking its fundamental and notching it along with its harmonics.
+///
+/// The fundamental is searched for within `max_drift_hz` of `mains_hz` (usually 50 or 60 Hz) and
+/// refined with parabolic interpolation, so the notches follow the hum as it drifts. Each notch is
+/// evaluated from the exact (fractional) harmonic frequency rather than snapped to a bin, and its
+/// depth adapts to how far the harmonic stands out from the surrounding noise floor, up to
+/// `max_depth_db`.
+#[derive(Clone)]
+pub struct HumRemove<F: Fft> {
+    pub mains_hz: f32,
+    pub max_drift_hz: f32,
+    pub max_depth_db: f32,
+    /// Notch half-width in bins. The harmonics aren't notched if it isn't positive.
+    pub width: f32,
+    /// Smoothing coefficient for the frequency tracker and notch depths, in the range (0, 1].
+    pub tracking: f32,
+    sample_rate: f32,
+    tracked_hz: f32,
+    depths: Vec<f32>,
+    magnitudes: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> HumRemove<F> {
+    pub fn new(mains_hz: f32, harmonics: usize) -> Self {
+        Self {
+            mains_hz,
+            max_drift_hz: 1.0,
+            max_depth_db: 40.0,
+            width: 2.0,
+            tracking: 0.1,
+            sample_rate: 0.0,
+            tracked_hz: mains_hz,
+            depths: vec![0.0; harmonics],
+            magnitudes: vec![0.0; F::N_REAL_BINS],
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the number of harmonics (including the fundamental) that are notched.
+    pub fn harmonics(&self) -> usize {
+        self.depths.len()
+    }
+
+    /// Returns the currently tracked hum fundamental in Hz.
+    pub fn tracked_hz(&self) -> f32 {
+        self.tracked_hz
+    }
+
+    /// Finds the interpolated peak frequency (in fractional bins) and magnitude near `center`.
+    fn find_peak(frame: &[f32], center: f32, radius: f32) -> Option<(f32, f32)> {
+        let lo = (center - radius).floor().max(1.0) as usize;
+        let hi = (center + radius).ceil() as usize;
+        let peak = peaks::max_bin_in(frame, lo..hi + 1)?;
+        Some(peaks::interpolate_peak(frame, peak))
+    }
+
+    fn noise_floor(frame: &[f32], center: f32, width: f32) -> f32 {
+        let lo = (center - 3.0 * width).max(0.0) as usize;
+        let hi = ((center + 3.0 * width) as usize).min(frame.len() - 1);
+        let mut sum = 0.0;
+        let mut count = 0;
+        for (bin, &magnitude) in frame.iter().enumerate().take(hi + 1).skip(lo) {
+            if (bin as f32 - center).abs() > width {
+                sum += magnitude;
+                count += 1;
+            }
+        }
+        if count > 0 { sum / count as f32 } else { 0.0 }
+    }
+}
+
+impl<F: Fft> Default for HumRemove<F> {
+    fn default() -> Self {
+        Self::new(60.0, 8)
+    }
+}
+
+processor_builder! {
+    HumRemove => HumRemoveBuilder {
+        mains_hz: f32 [Hertz, 40.0, 70.0, None],
+        max_drift_hz: f32 [Hertz, 0.0, 5.0],
+        max_depth_db: f32 [Decibels, 0.0, 80.0],
+        width: f32 [Bins, 0.5, 8.0],
+        tracking: f32 [None, 0.0, 1.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for HumRemove<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft, frequency: f32 }
+    }
+
+    processor_state!(tracked_hz, depths);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        HumRemoveBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        HumRemoveBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.tracked_hz = self.mains_hz;
+        self.depths.fill(0.0);
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            self.out_signal.copy_from_slice(input);
+
+            if self.sample_rate > 0.0 && self.width > 0.0 && !self.depths.is_empty() {
+                units::magnitudes(input, &mut self.magnitudes);
+
+                let bins_per_hz = F::hz_to_bin(1.0, self.sample_rate);
+
+                // estimate the fundamental from the magnitude-weighted harmonic peaks
+                let mut weighted_hz = 0.0;
+                let mut total_weight = 0.0;
+                for harmonic in 1..=self.depths.len() {
+                    let k = harmonic as f32;
+                    let center = self.tracked_hz * k * bins_per_hz;
+                    let radius = (self.max_drift_hz * k * bins_per_hz).max(1.0);
+                    if let Some((peak, magnitude)) =
+                        Self::find_peak(&self.magnitudes, center, radius)
+                    {
+                        weighted_hz += peak / bins_per_hz / k * magnitude;
+                        total_weight += magnitude;
+                    }
+                }
+                if total_weight > f32::EPSILON {
+                    let estimate = (weighted_hz / total_weight).clamp(
+                        self.mains_hz - self.max_drift_hz,
+                        self.mains_hz + self.max_drift_hz,
+                    );
+                    self.tracked_hz += (estimate - self.tracked_hz) * self.tracking;
+                }
+
+                for (harmonic, depth) in self.depths.iter_mut().enumerate() {
+                    let center = self.tracked_hz * (harmonic + 1) as f32 * bins_per_hz;
+                    if center + self.width >= F::N_REAL_BINS as f32 {
+                        *depth = 0.0;
+                        continue;
+                    }
+
+                    // adapt the depth to how far the harmonic sticks out of the noise floor
+                    let peak = Self::find_peak(&self.magnitudes, center, 1.0)
+                        .map_or(0.0, |(_, magnitude)| magnitude);
+                    let floor = Self::noise_floor(&self.magnitudes, center, self.width);
+                    let target = if peak > f32::EPSILON && floor > f32::EPSILON {
+                        units::magnitude_to_db(peak / floor).clamp(0.0, self.max_depth_db)
+                    } else {
+                        0.0
+                    };
+                    *depth += (target - *depth) * self.tracking;
+
+                    // raised-cosine notch evaluated at each bin's exact distance from the harmonic
+                    let min_gain = units::db_to_magnitude(-*depth);
+                    let lo = (center - self.width).ceil().max(0.0) as usize;
+                    let hi = (center + self.width).floor() as usize;
+                    for bin in lo..=hi {
+                        let distance = (bin as f32 - center) / self.width;
+                        let shape = 0.5 * (1.0 + (std::f32::consts::PI * distance).cos());
+                        self.out_signal[bin] *= 1.0 - (1.0 - min_gain) * shape;
+                    }
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<f32>(1, i, &self.tracked_hz)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        WindowFunction,
+        graph::{DynFftGraph, FftGraph},
+        signal::Fft1024,
+    };
+
+    #[test]
+    fn hum_remove_without_width_leaves_the_spectrum_finite() {
+        // the harmonics of 64 Hz fall exactly on bins, where a zero-width notch would divide 0 by 0
+        let sample_rate = 64.0 * 1024.0;
+        let mut graph = FftGraph::<Fft1024>::new(256, WindowFunction::Hann);
+        let input = graph.add_audio_input();
+        let mut hum_remove = HumRemove::<Fft1024>::new(64.0, 4);
+        hum_remove.width = 0.0;
+        hum_remove.tracking = 0.0;
+        let hum_remove = graph.add_processor(hum_remove);
+        let output = graph.add_audio_output();
+        DynFftGraph::connect(&mut graph, input, 0, hum_remove, 0).unwrap();
+        DynFftGraph::connect(&mut graph, hum_remove, 0, output, 0).unwrap();
+
+        let signal: Vec<f32> = (0..1024 * 8)
+            .map(|i| (core::f32::consts::TAU * 64.0 * i as f32 / sample_rate).sin())
+            .collect();
+        let output = graph.render(&[&signal], sample_rate, 256).unwrap();
+        assert!(output[0].iter().all(|sample| sample.is_finite()));
+    }
+}
@@ -0,0 +1,372 @@
+//! Processors that split spectra into parallel chains and recombine them.
+
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    processor::{FftProcessor, FrameInfo, ParamSmoothing, ParamSpec, ParamUnit},
+    processor_clone, processor_state,
+    signal::{Complex32, Fft},
+    smooth::{SmoothedParam, Smoothing},
+};
+
+/// Sums any number of spectra into one, each scaled by its own linear gain.
+///
+/// The spectral inputs come first, followed by one gain input per spectrum which overrides the
+/// corresponding entry of `gains` while connected. Gain changes are smoothed over a few hops, and
+/// unconnected spectral inputs are skipped. The number of inputs is fixed on construction; missing
+/// entries of `gains` count as unity gain.
+#[derive(Clone)]
+pub struct SpectralMix<F: Fft> {
+    pub gains: Vec<f32>,
+    smoothed: Vec<SmoothedParam>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralMix<F> {
+    /// Creates a mix of `num_inputs` spectra at unity gain.
+    pub fn new(num_inputs: usize) -> Self {
+        Self::with_gains(vec![1.0; num_inputs])
+    }
+
+    /// Creates a mix with one input per gain.
+    pub fn with_gains(gains: Vec<f32>) -> Self {
+        Self {
+            smoothed: gains.iter().copied().map(SmoothedParam::new).collect(),
+            gains,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the number of spectral inputs.
+    pub fn num_inputs(&self) -> usize {
+        self.smoothed.len()
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for gain in self.smoothed.iter_mut() {
+            gain.set_smoothing(smoothing);
+        }
+    }
+}
+
+impl<F: Fft> Default for SpectralMix<F> {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralMix<F> {
+    processor_clone!();
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let spectra = (0..self.num_inputs())
+            .map(|i| SignalSpec::new(format!("input{i}"), F::RealFft::signal_type()));
+        let gains =
+            (0..self.num_inputs()).map(|i| SignalSpec::new(format!("gain{i}"), f32::signal_type()));
+        spectra.chain(gains).collect()
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        vec![SignalSpec::new("output", F::RealFft::signal_type())]
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        vec![AnyBuffer::zeros::<F::RealFft>(size)]
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        (0..self.smoothed.len())
+            .map(|i| {
+                ParamSpec::new(format!("gain{i}"), ParamUnit::None, 0.0, 4.0, 1.0)
+                    .smoothing(ParamSmoothing::Internal)
+            })
+            .collect()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let gain = name
+            .strip_prefix("gain")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| self.gains.get_mut(index));
+        match gain {
+            Some(gain) => {
+                *gain = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for gain in self.smoothed.iter_mut() {
+            gain.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    processor_state!(smoothed);
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let num_inputs = self.num_inputs();
+        let num_frames = (0..num_inputs)
+            .filter_map(|input| inputs.input_as::<F::RealFft>(input))
+            .map(|spectra| spectra.len())
+            .max()
+            .unwrap_or(0);
+
+        for i in 0..num_frames {
+            self.out_signal.fill(Complex32::ZERO);
+
+            for input in 0..num_inputs {
+                let target = inputs
+                    .input_as::<f32>(num_inputs + input)
+                    .and_then(|gain| gain.get(i).copied())
+                    .unwrap_or_else(|| self.gains.get(input).copied().unwrap_or(1.0));
+                let smoothed = &mut self.smoothed[input];
+                smoothed.set_target(target);
+                let gain = smoothed.advance();
+
+                let Some(spectrum) = inputs
+                    .input_as::<F::RealFft>(input)
+                    .and_then(|spectra| spectra.get(i))
+                else {
+                    continue;
+                };
+                if gain == 0.0 {
+                    continue;
+                }
+                for (out, bin) in self.out_signal.iter_mut().zip(spectrum.iter()) {
+                    *out += bin * gain;
+                }
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits one spectrum into frequency bands, e.g. for multiband processing chains that are
+/// recombined with a [`SpectralMix`].
+///
+/// The bands are separated at `crossovers_hz`, which must be ascending, and output in order from
+/// lowest to highest. With an `overlap_octaves` of 0 every bin goes to exactly one band; otherwise
+/// neighboring bands are crossfaded over that many octaves around each crossover. Either way the
+/// band gains sum to one, so mixing all bands at unity gain reconstructs the input. One input per
+/// crossover overrides the configured frequency while connected, smoothed over a few hops. The
+/// number of crossovers is fixed on construction.
+#[derive(Clone)]
+pub struct SpectralCrossover<F: Fft> {
+    pub crossovers_hz: Vec<f32>,
+    pub overlap_octaves: f32,
+    smoothed: Vec<SmoothedParam>,
+    // the crossovers and overlap the gains were computed for
+    params: Vec<f32>,
+    overlap: f32,
+    sample_rate: f32,
+    // one curve of `N_REAL_BINS` gains per band
+    gains: Vec<f32>,
+    dirty: bool,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralCrossover<F> {
+    pub fn new(crossovers_hz: Vec<f32>, overlap_octaves: f32) -> Self {
+        Self {
+            smoothed: crossovers_hz
+                .iter()
+                .copied()
+                .map(SmoothedParam::new)
+                .collect(),
+            params: crossovers_hz.clone(),
+            gains: vec![0.0; (crossovers_hz.len() + 1) * F::N_REAL_BINS],
+            crossovers_hz,
+            overlap_octaves,
+            overlap: overlap_octaves,
+            sample_rate: 0.0,
+            dirty: true,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Returns the number of output bands.
+    pub fn num_bands(&self) -> usize {
+        self.smoothed.len() + 1
+    }
+
+    /// Returns the gain curve of the band at `index`.
+    pub fn band_gains(&self, index: usize) -> &[f32] {
+        self.gains
+            .get(index * F::N_REAL_BINS..(index + 1) * F::N_REAL_BINS)
+            .unwrap_or(&[])
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for crossover in self.smoothed.iter_mut() {
+            crossover.set_smoothing(smoothing);
+        }
+    }
+
+    /// Returns the fraction of `hz` that lies above the crossover at `crossover_hz`.
+    fn above(&self, hz: f32, crossover_hz: f32) -> f32 {
+        if self.overlap <= 0.0 {
+            return if hz >= crossover_hz { 1.0 } else { 0.0 };
+        }
+        let octaves = (hz.max(f32::EPSILON) / crossover_hz.max(f32::EPSILON)).log2();
+        (octaves / self.overlap + 0.5).clamp(0.0, 1.0)
+    }
+
+    fn update_gains(&mut self) {
+        if self.sample_rate <= 0.0 {
+            return;
+        }
+        for (bin, hz) in F::bin_frequencies(self.sample_rate) {
+            // each band gets what lies above the previous crossover but not above its own
+            let mut above_lower = 1.0;
+            for band in 0..self.num_bands() {
+                let above_upper = match self.params.get(band) {
+                    Some(&crossover_hz) => self.above(hz, crossover_hz).min(above_lower),
+                    None => 0.0,
+                };
+                self.gains[band * F::N_REAL_BINS + bin] = above_lower - above_upper;
+                above_lower = above_upper;
+            }
+        }
+        self.dirty = false;
+    }
+}
+
+impl<F: Fft> Default for SpectralCrossover<F> {
+    fn default() -> Self {
+        Self::new(vec![200.0, 2000.0], 0.0)
+    }
+}
+
+processor_builder! {
+    SpectralCrossover => SpectralCrossoverBuilder {
+        overlap_octaves: f32 [Octaves, 0.0, 4.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralCrossover<F> {
+    processor_clone!();
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let mut specs = vec![SignalSpec::new("input", F::RealFft::signal_type())];
+        for i in 0..self.smoothed.len() {
+            specs.push(SignalSpec::new(format!("crossover{i}"), f32::signal_type()));
+        }
+        specs
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        (0..self.num_bands())
+            .map(|i| SignalSpec::new(format!("band{i}"), F::RealFft::signal_type()))
+            .collect()
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        (0..self.num_bands())
+            .map(|_| AnyBuffer::zeros::<F::RealFft>(size))
+            .collect()
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        let mut specs = SpectralCrossoverBuilder::<F>::param_spec();
+        for (i, &crossover_hz) in self.crossovers_hz.iter().enumerate() {
+            specs.push(ParamSpec::new(
+                format!("crossover{i}"),
+                ParamUnit::Hertz,
+                20.0,
+                20000.0,
+                crossover_hz,
+            ));
+        }
+        specs
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if SpectralCrossoverBuilder::<F>::set_param(self, name, value) {
+            return true;
+        }
+        let crossover = name
+            .strip_prefix("crossover")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| self.crossovers_hz.get_mut(index));
+        match crossover {
+            Some(crossover_hz) => {
+                *crossover_hz = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_gains();
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+        self.update_gains();
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for crossover in self.smoothed.iter_mut() {
+            crossover.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    processor_state!(smoothed);
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            for (index, (param, smoothed)) in
+                self.params.iter_mut().zip(&mut self.smoothed).enumerate()
+            {
+                smoothed.set_target(
+                    inputs
+                        .input_as::<f32>(index + 1)
+                        .and_then(|values| values.get(i).copied())
+                        .or_else(|| self.crossovers_hz.get(index).copied())
+                        .unwrap_or(*param),
+                );
+                let value = smoothed.advance();
+                if *param != value {
+                    *param = value;
+                    self.dirty = true;
+                }
+            }
+            if self.overlap != self.overlap_octaves {
+                self.overlap = self.overlap_octaves;
+                self.dirty = true;
+            }
+
+            if self.dirty {
+                self.update_gains();
+            }
+
+            for band in 0..self.num_bands() {
+                let gains = &self.gains[band * F::N_REAL_BINS..(band + 1) * F::N_REAL_BINS];
+                for ((out, bin), gain) in self.out_signal.iter_mut().zip(input.iter()).zip(gains) {
+                    *out = bin * gain;
+                }
+                outputs.set_output_as::<F::RealFft>(band, i, &*self.out_signal)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,560 @@
+//! Processors recording spectral frames into a [`FrameBuffer`] and playing them back.
+
+use std::f32::consts::TAU;
+
+use raug::prelude::*;
+
+use crate::{
+    FftError,
+    builder::processor_builder,
+    fft_processor_io,
+    frame_buffer::FrameBuffer,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    random::{FrameRng, stream_seed},
+    signal::{Complex32, Fft},
+    state::{StateReader, StateValue, StateWriter},
+};
+
+/// What a [`SpectralSampler`] is doing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerState {
+    #[default]
+    Idle,
+    Recording,
+    Playing,
+}
+
+/// Saved as its index.
+impl StateValue for SamplerState {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&(*self as u8));
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        *self = match state.read_new::<u8>()? {
+            0 => Self::Idle,
+            1 => Self::Recording,
+            2 => Self::Playing,
+            other => {
+                return Err(FftError::InvalidState(format!(
+                    "invalid sampler state {other}"
+                )));
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Records frames into a [`FrameBuffer`] and plays them back at any speed.
+///
+/// While the `record` gate is open, incoming frames are recorded from the start of the buffer,
+/// overwriting the previous recording, and passed through. Recording stops when the gate closes
+/// or the buffer is full. Otherwise, the recording plays while the `play` gate is open, or while
+/// `playing` is set if it's unconnected.
+///
+/// The playhead advances by `speed` frames per hop (negative speeds play backwards), and wraps
+/// around at the ends if `looping` is set or stops at them otherwise. Playback works like a phase
+/// vocoder: magnitudes are interpolated between neighboring frames, and phases advance by the
+/// phase difference between them, so playing at speeds other than 1 stretches time without
+/// smearing partials. The `speed` and `loop` inputs override the fields while connected, and the
+/// `position` input (`0..1` over the recording) moves the playhead, e.g. for scrubbing.
+///
+/// Besides the spectrum, the processor outputs the playhead (`0..1`) and a trigger whenever
+/// playback wraps around or reaches an end. If the buffer is locked by another thread, the frame
+/// is output silent and not recorded.
+#[derive(Clone)]
+pub struct SpectralSampler<F: Fft> {
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    buffer: FrameBuffer<F>,
+    state: SamplerState,
+    recording: bool,
+    position: f32,
+    // the phase of each bin of the playback output
+    phases: Vec<f32>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralSampler<F> {
+    /// Creates a sampler recording into and playing from `buffer`, which may be shared with
+    /// other processors.
+    pub fn new(buffer: FrameBuffer<F>) -> Self {
+        Self {
+            speed: 1.0,
+            looping: true,
+            playing: true,
+            buffer,
+            state: SamplerState::Idle,
+            recording: false,
+            position: 0.0,
+            phases: vec![0.0; F::N_REAL_BINS],
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Creates a sampler with its own buffer of `capacity` frames.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::new(FrameBuffer::new(capacity))
+    }
+
+    pub fn buffer(&self) -> &FrameBuffer<F> {
+        &self.buffer
+    }
+
+    pub fn state(&self) -> SamplerState {
+        self.state
+    }
+
+    /// Returns the playhead in frames.
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Starts playback at the frame at `index`, taking its phases as they are.
+    fn start_at(&mut self, frames: &[F::RealFft], index: usize) {
+        for (phase, bin) in self.phases.iter_mut().zip(frames[index].iter()) {
+            *phase = bin.arg();
+        }
+    }
+
+    /// Writes the frame at the playhead and advances it, returning whether it wrapped around or
+    /// reached an end.
+    fn play(&mut self, frames: &[F::RealFft], speed: f32, looping: bool) -> bool {
+        let len = frames.len();
+        let index = (self.position.floor() as usize).min(len - 1);
+        let next = if index + 1 < len {
+            index + 1
+        } else if looping {
+            0
+        } else {
+            index
+        };
+        let t = self.position - index as f32;
+
+        for (((out, phase), a), b) in self
+            .out_signal
+            .iter_mut()
+            .zip(self.phases.iter_mut())
+            .zip(frames[index].iter())
+            .zip(frames[next].iter())
+        {
+            let magnitude = a.norm() + (b.norm() - a.norm()) * t;
+            *out = Complex32::from_polar(magnitude, *phase);
+            // advance by the phase difference between the frames, in the direction of playback
+            let delta = (b * a.conj()).arg();
+            *phase = (*phase + delta * speed.signum()).rem_euclid(TAU);
+        }
+
+        let length = len as f32;
+        let position = self.position + speed;
+        if looping {
+            self.position = position.rem_euclid(length);
+            !(0.0..length).contains(&position)
+        } else {
+            self.position = position.clamp(0.0, length - 1.0);
+            self.position != position
+        }
+    }
+}
+
+impl<F: Fft> Default for SpectralSampler<F> {
+    fn default() -> Self {
+        Self::with_capacity(256)
+    }
+}
+
+processor_builder! {
+    SpectralSampler => SpectralSamplerBuilder {
+        speed: f32 [None, -4.0, 4.0],
+        looping: bool [Toggle, 0.0, 1.0],
+        playing: bool [Toggle, 0.0, 1.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralSampler<F> {
+    fft_processor_io! {
+        inputs {
+            input: F::RealFft,
+            record: bool,
+            play: bool,
+            position: f32,
+            speed: f32,
+            "loop": bool,
+        }
+        outputs { output: F::RealFft, position: f32, end: bool }
+    }
+
+    processor_state!(buffer, state, recording, position, phases);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralSamplerBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralSamplerBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.state = SamplerState::Idle;
+        self.recording = false;
+        self.position = 0.0;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let bool_input = |index: usize, i: usize| {
+            inputs
+                .input_as::<bool>(index)
+                .and_then(|values| values.get(i).copied())
+        };
+        let f32_input = |index: usize, i: usize| {
+            inputs
+                .input_as::<f32>(index)
+                .and_then(|values| values.get(i).copied())
+        };
+
+        let buffer = self.buffer.clone();
+        for (i, input) in input.iter().enumerate() {
+            let record = bool_input(1, i).unwrap_or(false);
+            let play = bool_input(2, i).unwrap_or(self.playing);
+            let speed = f32_input(4, i).unwrap_or(self.speed);
+            let looping = bool_input(5, i).unwrap_or(self.looping);
+
+            let Some(mut store) = buffer.try_lock() else {
+                outputs.set_output_as::<F::RealFft>(0, i, &F::RealFft::default())?;
+                continue;
+            };
+
+            // a rising record gate starts a new recording
+            if record && !self.recording {
+                store.clear();
+                self.state = SamplerState::Recording;
+            }
+            self.recording = record;
+            if self.state == SamplerState::Recording && !(record && store.push(input)) {
+                self.state = SamplerState::Idle;
+            }
+
+            if self.state == SamplerState::Recording {
+                outputs.set_output_as::<F::RealFft>(0, i, input)?;
+                outputs.set_output_as::<f32>(1, i, &0.0)?;
+                continue;
+            }
+
+            let frames = store.frames();
+            if !play || frames.is_empty() {
+                self.state = SamplerState::Idle;
+                outputs.set_output_as::<F::RealFft>(0, i, &F::RealFft::default())?;
+                outputs.set_output_as::<f32>(1, i, &0.0)?;
+                continue;
+            }
+
+            let length = frames.len() as f32;
+            let jump = f32_input(3, i).map(|position| position.clamp(0.0, 1.0) * length);
+            if let Some(position) = jump {
+                self.position = position.min(length - 1.0);
+            }
+            self.position = self.position.clamp(0.0, length - 1.0);
+            if self.state != SamplerState::Playing || jump.is_some() {
+                self.start_at(frames, self.position as usize);
+                self.state = SamplerState::Playing;
+            }
+
+            outputs.set_output_as::<f32>(1, i, &(self.position / length))?;
+            let end = self.play(frames, speed, looping);
+            drop(store);
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+            outputs.set_output_as::<bool>(2, i, &end)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default, Clone)]
+struct Grain {
+    // the read position in frames
+    position: f32,
+    // the read speed in frames per hop
+    speed: f32,
+    // the pitch as a frequency ratio
+    ratio: f32,
+    age: usize,
+    length: usize,
+    // the phase of each output bin
+    phases: Vec<f32>,
+}
+
+impl StateValue for Grain {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.position);
+        state.write(&self.speed);
+        state.write(&self.ratio);
+        state.write(&self.age);
+        state.write(&self.length);
+        state.write(&self.phases);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.position)?;
+        state.read(&mut self.speed)?;
+        state.read(&mut self.ratio)?;
+        state.read(&mut self.age)?;
+        state.read(&mut self.length)?;
+        state.read(&mut self.phases)
+    }
+}
+
+impl Grain {
+    fn is_active(&self) -> bool {
+        self.age < self.length
+    }
+
+    /// Returns the Hann envelope of the grain for its current hop.
+    fn envelope(&self) -> f32 {
+        let t = (self.age as f32 + 0.5) / self.length as f32;
+        0.5 - 0.5 * (TAU * t).cos()
+    }
+}
+
+/// Granular synthesis from the frames in a [`FrameBuffer`].
+///
+/// Grains are spawned `density` times per second, plus once on every `trigger`, each reading the
+/// buffer from around `position` (`0..1` over the recording) jittered by up to
+/// `position_jitter`, at `speed` frames per hop. Every grain lasts `grain_seconds`, is shaped by
+/// a Hann envelope and is pitched by `pitch_semitones`, jittered by up to `pitch_jitter`
+/// semitones, by scaling bin frequencies, with its phases advanced like a phase vocoder's. At
+/// most `max_grains` play at once; grains spawned beyond that are dropped.
+///
+/// Overlapping grains are summed and scaled by the inverse square root of the expected number of
+/// overlapping grains, so the level stays roughly the same as density and grain length change.
+/// The `position`, `density` and `pitch` inputs override the fields while connected. Besides the
+/// spectrum, the processor outputs the number of active grains. Jitter is reproducible: it only
+/// depends on `seed`, the graph's seed and the frames grains spawn at, see
+/// [`random`](crate::random).
+///
+/// If the buffer is empty or locked by another thread, no grains are spawned or advanced and the
+/// frame is output silent.
+#[derive(Clone)]
+pub struct SpectralGranular<F: Fft> {
+    pub density: f32,
+    pub grain_seconds: f32,
+    pub position: f32,
+    pub position_jitter: f32,
+    pub speed: f32,
+    pub pitch_semitones: f32,
+    pub pitch_jitter: f32,
+    pub gain: f32,
+    pub seed: u32,
+    buffer: FrameBuffer<F>,
+    grains: Vec<Grain>,
+    rng: FrameRng,
+    // progress towards the next grain, in grains
+    spawn_phase: f32,
+    frame: FrameInfo,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> SpectralGranular<F> {
+    /// Creates a granulator reading from `buffer` and playing at most `max_grains` grains at
+    /// once.
+    pub fn new(buffer: FrameBuffer<F>, max_grains: usize) -> Self {
+        let grains = (0..max_grains)
+            .map(|_| Grain {
+                position: 0.0,
+                speed: 1.0,
+                ratio: 1.0,
+                age: 0,
+                length: 0,
+                phases: vec![0.0; F::N_REAL_BINS],
+            })
+            .collect();
+        Self {
+            density: 10.0,
+            grain_seconds: 0.2,
+            position: 0.0,
+            position_jitter: 0.05,
+            speed: 1.0,
+            pitch_semitones: 0.0,
+            pitch_jitter: 0.0,
+            gain: 1.0,
+            seed: 0x2545_f491,
+            buffer,
+            grains,
+            rng: FrameRng::default(),
+            spawn_phase: 0.0,
+            frame: FrameInfo::default(),
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn buffer(&self) -> &FrameBuffer<F> {
+        &self.buffer
+    }
+
+    pub fn max_grains(&self) -> usize {
+        self.grains.len()
+    }
+
+    /// Returns the number of grains currently playing.
+    pub fn active_grains(&self) -> usize {
+        self.grains.iter().filter(|grain| grain.is_active()).count()
+    }
+
+    /// Starts a grain at `position` (`0..1`) in the recording, if one is free.
+    fn spawn(&mut self, frames: &[F::RealFft], position: f32, pitch_semitones: f32) {
+        let length = (self.grain_seconds * self.frame.frame_rate())
+            .round()
+            .max(1.0) as usize;
+        let position = position + self.position_jitter * self.rng.next_bipolar();
+        let position = position.clamp(0.0, 1.0) * (frames.len() - 1) as f32;
+        let pitch_semitones = pitch_semitones + self.pitch_jitter * self.rng.next_bipolar();
+        let speed = self.speed;
+
+        let Some(grain) = self.grains.iter_mut().find(|grain| !grain.is_active()) else {
+            return;
+        };
+        grain.position = position;
+        grain.speed = speed;
+        grain.ratio = 2f32.powf(pitch_semitones / 12.0);
+        grain.age = 0;
+        grain.length = length;
+        // start with the phases of the source bins each output bin reads from
+        let frame = &frames[position as usize];
+        for (bin, phase) in grain.phases.iter_mut().enumerate() {
+            let source = (bin as f32 / grain.ratio).round() as usize;
+            *phase = frame.get(source).map_or(0.0, |value| value.arg());
+        }
+    }
+}
+
+impl<F: Fft> Default for SpectralGranular<F> {
+    fn default() -> Self {
+        Self::new(FrameBuffer::new(256), 32)
+    }
+}
+
+processor_builder! {
+    SpectralGranular => SpectralGranularBuilder {
+        density: f32 [Hertz, 0.0, 200.0],
+        grain_seconds: f32 [Seconds, 0.01, 2.0],
+        position: f32 [None, 0.0, 1.0],
+        position_jitter: f32 [None, 0.0, 1.0],
+        speed: f32 [None, -4.0, 4.0],
+        pitch_semitones: f32 [Semitones, -24.0, 24.0],
+        pitch_jitter: f32 [Semitones, 0.0, 24.0],
+        gain: f32 [None, 0.0, 4.0],
+        seed: u32 [None, 0.0, u32::MAX as f32],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralGranular<F> {
+    fft_processor_io! {
+        inputs { position: f32, density: f32, pitch: f32, trigger: bool }
+        outputs { output: F::RealFft, grains: f32 }
+    }
+
+    processor_state!(grains, spawn_phase);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralGranularBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralGranularBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.spawn_phase = 0.0;
+        for grain in &mut self.grains {
+            grain.length = 0;
+        }
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+        self.rng = FrameRng::new(stream_seed(info.seed, self.seed as u64));
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let f32_input = |index: usize, default: f32| {
+            inputs
+                .input_as::<f32>(index)
+                .and_then(|values| values.first().copied())
+                .unwrap_or(default)
+        };
+        let position = f32_input(0, self.position);
+        let density = f32_input(1, self.density);
+        let pitch_semitones = f32_input(2, self.pitch_semitones);
+        let trigger = inputs
+            .input_as::<bool>(3)
+            .and_then(|values| values.first().copied())
+            .unwrap_or(false);
+
+        self.out_signal.fill(Complex32::ZERO);
+
+        let buffer = self.buffer.clone();
+        if let Some(store) = buffer.try_lock().filter(|store| !store.is_empty()) {
+            let frames = store.frames();
+
+            self.spawn_phase += density.max(0.0) * self.frame.hop_seconds();
+            while self.spawn_phase >= 1.0 {
+                self.spawn_phase -= 1.0;
+                self.spawn(frames, position, pitch_semitones);
+            }
+            if trigger {
+                self.spawn(frames, position, pitch_semitones);
+            }
+
+            let overlap = density * self.grain_seconds;
+            let scale = self.gain / overlap.max(1.0).sqrt();
+            let last = frames.len() - 1;
+            for grain in self.grains.iter_mut().filter(|grain| grain.is_active()) {
+                let index = (grain.position as usize).min(last);
+                let next = (index + 1).min(last);
+                let t = grain.position - index as f32;
+                let envelope = grain.envelope() * scale;
+                let (a, b) = (&frames[index], &frames[next]);
+
+                for (bin, (out, phase)) in self
+                    .out_signal
+                    .iter_mut()
+                    .zip(grain.phases.iter_mut())
+                    .enumerate()
+                {
+                    let source = (bin as f32 / grain.ratio).round() as usize;
+                    let (Some(a), Some(b)) = (a.get(source), b.get(source)) else {
+                        continue;
+                    };
+                    let magnitude = a.norm() + (b.norm() - a.norm()) * t;
+                    *out += Complex32::from_polar(magnitude * envelope, *phase);
+                    let delta = (b * a.conj()).arg();
+                    *phase = (*phase + delta * grain.ratio * grain.speed.signum()).rem_euclid(TAU);
+                }
+
+                grain.position = (grain.position + grain.speed).clamp(0.0, last as f32);
+                grain.age += 1;
+            }
+        }
+
+        outputs.set_output_as::<F::RealFft>(0, 0, &*self.out_signal)?;
+        outputs.set_output_as::<f32>(1, 0, &(self.active_grains() as f32))?;
+
+        Ok(())
+    }
+}
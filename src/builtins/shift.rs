@@ -0,0 +1,141 @@
+use std::f32::consts::{PI, TAU};
+
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    fft_processor_io,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::{Complex32, Fft},
+    smooth::{SmoothedParam, Smoothing},
+};
+
+/// Shifts every frequency by a constant offset in Hz (single-sideband frequency shifting).
+///
+/// Unlike pitch shifting, harmonic relationships are not preserved. Bins are moved by the nearest
+/// whole number of bins and every frame is rotated by a phase that advances with the exact shift,
+/// so the resynthesized partials land on the requested frequency. Content shifted past DC or
+/// Nyquist is discarded rather than folded back, and bins within `edge_bins` of either edge are
+/// faded out to avoid hard cutoffs. Changes of the `shift` input are smoothed over a few hops.
+#[derive(Clone)]
+pub struct FreqShift<F: Fft> {
+    pub shift_hz: f32,
+    pub edge_bins: usize,
+    shift: SmoothedParam,
+    frame: FrameInfo,
+    phase: f32,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> FreqShift<F> {
+    pub fn new(shift_hz: f32) -> Self {
+        Self {
+            shift_hz,
+            edge_bins: 4,
+            shift: SmoothedParam::new(shift_hz),
+            frame: FrameInfo::default(),
+            phase: 0.0,
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.shift.set_smoothing(smoothing);
+    }
+
+    fn edge_gain(&self, bin: usize) -> f32 {
+        let distance = bin.min(F::N_REAL_BINS - 1 - bin);
+        if distance >= self.edge_bins {
+            1.0
+        } else {
+            0.5 - 0.5 * (PI * distance as f32 / self.edge_bins as f32).cos()
+        }
+    }
+}
+
+impl<F: Fft> Default for FreqShift<F> {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+processor_builder! {
+    FreqShift => FreqShiftBuilder {
+        shift_hz: f32 [Hertz, -5000.0, 5000.0, Internal],
+        edge_bins: usize [Bins, 0.0, 64.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for FreqShift<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, shift: f32 }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(shift, phase);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        FreqShiftBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        FreqShiftBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.phase = 0.0;
+        self.shift.reset(self.shift_hz);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+        self.shift.set_hop_seconds(info.hop_seconds());
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let shift = inputs.input_as::<f32>(1);
+
+        for (i, input) in input.iter().enumerate() {
+            self.shift.set_target(
+                shift
+                    .and_then(|shift| shift.get(i).copied())
+                    .unwrap_or(self.shift_hz),
+            );
+            let shift_hz = self.shift.advance();
+
+            if self.frame.sample_rate <= 0.0 {
+                outputs.set_output_as::<F::RealFft>(0, i, input)?;
+                continue;
+            }
+
+            let shift_bins = F::hz_to_bin(shift_hz, self.frame.sample_rate);
+            let offset = shift_bins.round() as isize;
+
+            self.phase += TAU * shift_bins * self.frame.hop_length as f32 / F::N_FFT as f32;
+            self.phase = self.phase.rem_euclid(TAU);
+            let rotation = Complex32::from_polar(1.0, self.phase);
+
+            self.out_signal.fill(Complex32::ZERO);
+            for (bin, value) in input.iter().enumerate() {
+                let target = bin as isize + offset;
+                if target < 0 || target >= F::N_REAL_BINS as isize {
+                    continue;
+                }
+                let target = target as usize;
+                self.out_signal[target] = value * rotation * self.edge_gain(target);
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
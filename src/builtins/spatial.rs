@@ -0,0 +1,459 @@
+use std::sync::Arc;
+
+use raug::prelude::*;
+
+use crate::{
+    FftError,
+    builder::processor_builder,
+    fft_processor_io,
+    fir::FirDesigner,
+    processor::{FftProcessor, FrameInfo, ParamSpec, ParamUnit},
+    processor_clone, processor_state,
+    signal::{Complex32, Fft},
+    smooth::{SmoothedParam, Smoothing},
+};
+
+/// A measured pair of head-related impulse responses for a single direction.
+#[derive(Debug, Clone)]
+pub struct HrirMeasurement {
+    /// Azimuth in degrees, counter-clockwise from the front.
+    pub azimuth: f32,
+    /// Elevation in degrees, upwards from the horizontal plane.
+    pub elevation: f32,
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+}
+
+/// A set of head-related transfer functions, transformed to a fixed FFT length.
+///
+/// HRIRs longer than the FFT length are truncated. Since the transfer functions are applied by
+/// per-bin multiplication, HRIRs should be much shorter than the FFT length to keep time-domain
+/// aliasing inaudible.
+#[derive(Debug, Clone)]
+pub struct HrtfSet {
+    n_fft: usize,
+    directions: Vec<[f32; 3]>,
+    left: Vec<Vec<Complex32>>,
+    right: Vec<Vec<Complex32>>,
+}
+
+impl HrtfSet {
+    pub fn new(n_fft: usize, measurements: &[HrirMeasurement]) -> Result<Self, FftError> {
+        let mut directions = Vec::with_capacity(measurements.len());
+        let mut left = Vec::with_capacity(measurements.len());
+        let mut right = Vec::with_capacity(measurements.len());
+        for measurement in measurements {
+            directions.push(direction_vector(measurement.azimuth, measurement.elevation));
+            let l = &measurement.left[..measurement.left.len().min(n_fft)];
+            let r = &measurement.right[..measurement.right.len().min(n_fft)];
+            left.push(FirDesigner::spectrum(l, n_fft)?);
+            right.push(FirDesigner::spectrum(r, n_fft)?);
+        }
+        Ok(Self {
+            n_fft,
+            directions,
+            left,
+            right,
+        })
+    }
+
+    /// Returns the number of bins of the transfer functions, `n_fft / 2 + 1`.
+    pub fn num_bins(&self) -> usize {
+        self.n_fft / 2 + 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.directions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.directions.is_empty()
+    }
+
+    /// Interpolates the transfer functions for a direction from the (up to) three nearest
+    /// measurements.
+    ///
+    /// Magnitudes are blended with inverse-distance weights and the phase of the nearest
+    /// measurement is kept, which avoids the comb filtering of blending complex responses with
+    /// different delays.
+    pub fn interpolate(
+        &self,
+        azimuth: f32,
+        elevation: f32,
+        left: &mut [Complex32],
+        right: &mut [Complex32],
+    ) {
+        let target = direction_vector(azimuth, elevation);
+
+        let mut nearest = [(usize::MAX, f32::INFINITY); 3];
+        for (index, direction) in self.directions.iter().enumerate() {
+            let dot =
+                direction[0] * target[0] + direction[1] * target[1] + direction[2] * target[2];
+            let distance = dot.clamp(-1.0, 1.0).acos();
+            if let Some(slot) = nearest.iter().position(|&(_, d)| distance < d) {
+                nearest[slot..].rotate_right(1);
+                nearest[slot] = (index, distance);
+            }
+        }
+
+        let (closest, closest_distance) = nearest[0];
+        if closest == usize::MAX {
+            left.fill(Complex32::ZERO);
+            right.fill(Complex32::ZERO);
+            return;
+        }
+        if closest_distance < 1e-4 {
+            left.copy_from_slice(&self.left[closest]);
+            right.copy_from_slice(&self.right[closest]);
+            return;
+        }
+
+        let mut weights = [0.0; 3];
+        for (weight, &(index, distance)) in weights.iter_mut().zip(&nearest) {
+            if index != usize::MAX {
+                *weight = 1.0 / distance;
+            }
+        }
+        let total: f32 = weights.iter().sum();
+
+        for (channel, out) in [(&self.left, left), (&self.right, right)] {
+            for (bin, out) in out.iter_mut().enumerate() {
+                let mut magnitude = 0.0;
+                for (&weight, &(index, _)) in weights.iter().zip(&nearest) {
+                    if index != usize::MAX {
+                        magnitude += channel[index][bin].norm() * weight / total;
+                    }
+                }
+                *out = Complex32::from_polar(magnitude, channel[closest][bin].arg());
+            }
+        }
+    }
+}
+
+fn direction_vector(azimuth: f32, elevation: f32) -> [f32; 3] {
+    let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+    [
+        elevation.cos() * azimuth.cos(),
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+    ]
+}
+
+/// Binaural panner that filters a mono spectrum with the HRTFs of a direction.
+///
+/// The `azimuth` and `elevation` inputs (in degrees) override the configured direction while
+/// connected. The transfer functions are only re-interpolated when the direction changes.
+#[derive(Clone)]
+pub struct Hrtf<F: Fft> {
+    pub azimuth: f32,
+    pub elevation: f32,
+    set: Arc<HrtfSet>,
+    current: Option<(f32, f32)>,
+    left_hrtf: Vec<Complex32>,
+    right_hrtf: Vec<Complex32>,
+    left: Box<F::RealFft>,
+    right: Box<F::RealFft>,
+}
+
+impl<F: Fft> Hrtf<F> {
+    /// Creates a new binaural panner from an HRTF set transformed to `F::N_FFT`, failing if the
+    /// set was transformed to another FFT length.
+    pub fn new(set: Arc<HrtfSet>) -> Result<Self, FftError> {
+        if set.num_bins() != F::N_REAL_BINS {
+            return Err(FftError::Transform(
+                "the HRTF set was transformed to a different FFT length",
+            ));
+        }
+        Ok(Self {
+            azimuth: 0.0,
+            elevation: 0.0,
+            set,
+            current: None,
+            left_hrtf: vec![Complex32::ZERO; F::N_REAL_BINS],
+            right_hrtf: vec![Complex32::ZERO; F::N_REAL_BINS],
+            left: Box::new(F::RealFft::default()),
+            right: Box::new(F::RealFft::default()),
+        })
+    }
+
+    /// Creates a new binaural panner, transforming the given measurements to `F::N_FFT`.
+    pub fn from_measurements(measurements: &[HrirMeasurement]) -> Result<Self, FftError> {
+        Self::new(Arc::new(HrtfSet::new(F::N_FFT, measurements)?))
+    }
+}
+
+impl<F: Fft> FftProcessor for Hrtf<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, azimuth: f32, elevation: f32 }
+        outputs { left: F::RealFft, right: F::RealFft }
+    }
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("azimuth", ParamUnit::Degrees, -180.0, 180.0, 0.0),
+            ParamSpec::new("elevation", ParamUnit::Degrees, -90.0, 90.0, 0.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "azimuth" => self.azimuth = value,
+            "elevation" => self.elevation = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        let azimuth = inputs.input_as::<f32>(1);
+        let elevation = inputs.input_as::<f32>(2);
+
+        for (i, input) in input.iter().enumerate() {
+            let direction = (
+                azimuth
+                    .and_then(|azimuth| azimuth.get(i).copied())
+                    .unwrap_or(self.azimuth),
+                elevation
+                    .and_then(|elevation| elevation.get(i).copied())
+                    .unwrap_or(self.elevation),
+            );
+            if self.current != Some(direction) {
+                self.set.interpolate(
+                    direction.0,
+                    direction.1,
+                    &mut self.left_hrtf,
+                    &mut self.right_hrtf,
+                );
+                self.current = Some(direction);
+            }
+
+            for (bin, value) in input.iter().enumerate() {
+                self.left[bin] = value * self.left_hrtf[bin];
+                self.right[bin] = value * self.right_hrtf[bin];
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.left)?;
+            outputs.set_output_as::<F::RealFft>(1, i, &*self.right)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of channels of a first-order ambisonic (B-format) signal.
+pub const B_FORMAT_CHANNELS: usize = 4;
+
+/// Rotates a first-order ambisonic soundfield, optionally by a different amount per frequency.
+///
+/// Inputs and outputs are the four B-format spectra in ACN order (W, Y, Z, X), followed by the
+/// `yaw`, `pitch` and `roll` inputs (in degrees), which override the configured angles while
+/// connected and are smoothed over a few hops. The yaw additionally changes by `spread` degrees
+/// per octave relative to `pivot_hz`, smearing sources across the horizontal plane by frequency.
+/// Groups of ambisonic channels can be added to a graph with
+/// [`FftGraph::add_audio_inputs`](crate::graph::FftGraph::add_audio_inputs).
+#[derive(Clone)]
+pub struct SpectralRotate<F: Fft> {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub spread: f32,
+    pub pivot_hz: f32,
+    angles: [SmoothedParam; 3],
+    sample_rate: f32,
+    out_signals: [Box<F::RealFft>; B_FORMAT_CHANNELS],
+}
+
+impl<F: Fft> SpectralRotate<F> {
+    pub fn new(yaw: f32, pitch: f32, roll: f32) -> Self {
+        Self {
+            yaw,
+            pitch,
+            roll,
+            spread: 0.0,
+            pivot_hz: 1000.0,
+            angles: [yaw, pitch, roll].map(SmoothedParam::new),
+            sample_rate: 0.0,
+            out_signals: std::array::from_fn(|_| Box::new(F::RealFft::default())),
+        }
+    }
+
+    pub fn with_spread(mut self, spread: f32, pivot_hz: f32) -> Self {
+        self.spread = spread;
+        self.pivot_hz = pivot_hz;
+        self
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for angle in self.angles.iter_mut() {
+            angle.set_smoothing(smoothing);
+        }
+    }
+}
+
+impl<F: Fft> Default for SpectralRotate<F> {
+    fn default() -> Self {
+        Self::new(0.0, 0.0, 0.0)
+    }
+}
+
+processor_builder! {
+    SpectralRotate => SpectralRotateBuilder {
+        yaw: f32 [Degrees, -180.0, 180.0, Internal],
+        pitch: f32 [Degrees, -90.0, 90.0, Internal],
+        roll: f32 [Degrees, -180.0, 180.0, Internal],
+        spread: f32 [None, -180.0, 180.0],
+        pivot_hz: f32 [Hertz, 20.0, 20000.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralRotate<F> {
+    fft_processor_io! {
+        inputs {
+            w: F::RealFft,
+            y: F::RealFft,
+            z: F::RealFft,
+            x: F::RealFft,
+            yaw: f32,
+            pitch: f32,
+            roll: f32,
+        }
+        outputs { w: F::RealFft, y: F::RealFft, z: F::RealFft, x: F::RealFft }
+    }
+
+    processor_state!(angles);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        SpectralRotateBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        SpectralRotateBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        for (angle, value) in self
+            .angles
+            .iter_mut()
+            .zip([self.yaw, self.pitch, self.roll])
+        {
+            angle.reset(value);
+        }
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for angle in self.angles.iter_mut() {
+            angle.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let w = inputs.input_as::<F::RealFft>(0).unwrap();
+        let y = inputs.input_as::<F::RealFft>(1).unwrap();
+        let z = inputs.input_as::<F::RealFft>(2).unwrap();
+        let x = inputs.input_as::<F::RealFft>(3).unwrap();
+        let angle = |index: usize, i: usize, default: f32| {
+            inputs
+                .input_as::<f32>(index)
+                .and_then(|angle| angle.get(i).copied())
+                .unwrap_or(default)
+        };
+
+        for i in 0..w.len() {
+            let defaults = [self.yaw, self.pitch, self.roll];
+            for (index, (smoothed, default)) in self.angles.iter_mut().zip(defaults).enumerate() {
+                smoothed.set_target(angle(4 + index, i, default));
+            }
+            let [yaw, pitch, roll] = self
+                .angles
+                .each_mut()
+                .map(|angle| angle.advance().to_radians());
+
+            // pitch and roll are the same for every bin: R_pr = Ry(pitch) * Rx(roll)
+            let (sp, cp) = pitch.sin_cos();
+            let (sr, cr) = roll.sin_cos();
+            let pitch_roll = [
+                [cp, sp * sr, sp * cr],
+                [0.0, cr, -sr],
+                [-sp, cp * sr, cp * cr],
+            ];
+
+            let spread = self.spread.to_radians();
+
+            self.out_signals[0].copy_from_slice(&w[i]);
+
+            for bin in 0..F::N_REAL_BINS {
+                let bin_yaw = if spread != 0.0 && self.sample_rate > 0.0 {
+                    let hz = F::bin_to_hz(bin.max(1), self.sample_rate);
+                    yaw + spread * (hz / self.pivot_hz).log2()
+                } else {
+                    yaw
+                };
+                let (sy, cy) = bin_yaw.sin_cos();
+                let yaw_rotation = [[cy, -sy, 0.0], [sy, cy, 0.0], [0.0, 0.0, 1.0]];
+
+                let mut rotation = [[0.0f32; 3]; 3];
+                for (row, rotation) in rotation.iter_mut().enumerate() {
+                    for (col, rotation) in rotation.iter_mut().enumerate() {
+                        *rotation = (0..3)
+                            .map(|k| yaw_rotation[row][k] * pitch_roll[k][col])
+                            .sum();
+                    }
+                }
+
+                let v = [x[i][bin], y[i][bin], z[i][bin]];
+                let rotated = rotation.map(|row| v[0] * row[0] + v[1] * row[1] + v[2] * row[2]);
+
+                self.out_signals[3][bin] = rotated[0];
+                self.out_signals[1][bin] = rotated[1];
+                self.out_signals[2][bin] = rotated[2];
+            }
+
+            for (channel, out_signal) in self.out_signals.iter().enumerate() {
+                outputs.set_output_as::<F::RealFft>(channel, i, &**out_signal)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{Fft256, Fft512};
+
+    fn measurements() -> Vec<HrirMeasurement> {
+        vec![HrirMeasurement {
+            azimuth: 0.0,
+            elevation: 0.0,
+            left: vec![1.0, 0.5],
+            right: vec![0.5, 1.0],
+        }]
+    }
+
+    #[test]
+    fn rejects_sets_of_another_fft_length() {
+        let set = Arc::new(HrtfSet::new(512, &measurements()).unwrap());
+        assert!(Hrtf::<Fft512>::new(set.clone()).is_ok());
+        assert!(Hrtf::<Fft256>::new(set).is_err());
+        assert!(Hrtf::<Fft256>::from_measurements(&measurements()).is_ok());
+    }
+}
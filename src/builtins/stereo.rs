@@ -0,0 +1,261 @@
+//! Stereo processors.
+//!
+//! Stereo spectra are routed as channel pairs: a processor's first input is the left channel and
+//! its second input is the right channel (or mid and side, respectively), and stereo outputs
+//! follow the same order. [`FftGraph::add_stereo_input`](crate::graph::FftGraph::add_stereo_input)
+//! and [`FftGraph::add_stereo_output`](crate::graph::FftGraph::add_stereo_output) create channel
+//! pairs that follow this convention.
+
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    fft_processor_io,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::Fft,
+    smooth::{SmoothedParam, Smoothing},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MidSideMode {
+    /// Converts left/right spectra to mid/side.
+    #[default]
+    Encode,
+    /// Converts mid/side spectra back to left/right.
+    Decode,
+}
+
+/// Converts between left/right and mid/side spectra.
+#[derive(Clone)]
+pub struct MidSide<F: Fft> {
+    pub mode: MidSideMode,
+    first: Box<F::RealFft>,
+    second: Box<F::RealFft>,
+}
+
+impl<F: Fft> MidSide<F> {
+    pub fn new(mode: MidSideMode) -> Self {
+        Self {
+            mode,
+            first: Box::new(F::RealFft::default()),
+            second: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn encode() -> Self {
+        Self::new(MidSideMode::Encode)
+    }
+
+    pub fn decode() -> Self {
+        Self::new(MidSideMode::Decode)
+    }
+}
+
+impl<F: Fft> Default for MidSide<F> {
+    fn default() -> Self {
+        Self::encode()
+    }
+}
+
+impl<F: Fft> FftProcessor for MidSide<F> {
+    processor_clone!();
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let (first, second) = match self.mode {
+            MidSideMode::Encode => ("left", "right"),
+            MidSideMode::Decode => ("mid", "side"),
+        };
+        vec![
+            SignalSpec::new(first, F::RealFft::signal_type()),
+            SignalSpec::new(second, F::RealFft::signal_type()),
+        ]
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        let (first, second) = match self.mode {
+            MidSideMode::Encode => ("mid", "side"),
+            MidSideMode::Decode => ("left", "right"),
+        };
+        vec![
+            SignalSpec::new(first, F::RealFft::signal_type()),
+            SignalSpec::new(second, F::RealFft::signal_type()),
+        ]
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        vec![
+            AnyBuffer::zeros::<F::RealFft>(size),
+            AnyBuffer::zeros::<F::RealFft>(size),
+        ]
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let a = inputs.input_as::<F::RealFft>(0).unwrap();
+        let b = inputs.input_as::<F::RealFft>(1).unwrap();
+
+        // encoding halves the sum and difference so that decoding is a plain sum and difference
+        let scale = match self.mode {
+            MidSideMode::Encode => 0.5,
+            MidSideMode::Decode => 1.0,
+        };
+
+        for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+            for (((first, second), a), b) in self
+                .first
+                .iter_mut()
+                .zip(self.second.iter_mut())
+                .zip(a.iter())
+                .zip(b.iter())
+            {
+                *first = (a + b) * scale;
+                *second = (a - b) * scale;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.first)?;
+            outputs.set_output_as::<F::RealFft>(1, i, &*self.second)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adjusts the stereo width of a pair of left/right spectra, separately below and above a
+/// crossover frequency.
+///
+/// A width of 0 collapses the image to mono, 1 leaves it unchanged, and values above 1 widen it
+/// by boosting the side signal. The two band widths are blended over one octave centered on
+/// `crossover_hz`. The `low_width` and `high_width` inputs override the configured values while
+/// connected, and are smoothed over a few hops.
+#[derive(Clone)]
+pub struct StereoWidth<F: Fft> {
+    pub low_width: f32,
+    pub high_width: f32,
+    pub crossover_hz: f32,
+    widths: [SmoothedParam; 2],
+    sample_rate: f32,
+    left: Box<F::RealFft>,
+    right: Box<F::RealFft>,
+}
+
+impl<F: Fft> StereoWidth<F> {
+    pub fn new(low_width: f32, high_width: f32, crossover_hz: f32) -> Self {
+        Self {
+            low_width,
+            high_width,
+            crossover_hz,
+            widths: [low_width, high_width].map(SmoothedParam::new),
+            sample_rate: 0.0,
+            left: Box::new(F::RealFft::default()),
+            right: Box::new(F::RealFft::default()),
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for width in self.widths.iter_mut() {
+            width.set_smoothing(smoothing);
+        }
+    }
+
+    fn high_band_weight(&self, bin: usize) -> f32 {
+        if self.sample_rate <= 0.0 || self.crossover_hz <= 0.0 {
+            return 1.0;
+        }
+        let hz = F::bin_to_hz(bin, self.sample_rate);
+        let octaves = (hz.max(f32::EPSILON) / self.crossover_hz).log2();
+        (octaves + 0.5).clamp(0.0, 1.0)
+    }
+}
+
+impl<F: Fft> Default for StereoWidth<F> {
+    fn default() -> Self {
+        Self::new(1.0, 1.0, 200.0)
+    }
+}
+
+processor_builder! {
+    StereoWidth => StereoWidthBuilder {
+        low_width: f32 [None, 0.0, 4.0, Internal],
+        high_width: f32 [None, 0.0, 4.0, Internal],
+        crossover_hz: f32 [Hertz, 20.0, 20000.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for StereoWidth<F> {
+    fft_processor_io! {
+        inputs { left: F::RealFft, right: F::RealFft, low_width: f32, high_width: f32 }
+        outputs { left: F::RealFft, right: F::RealFft }
+    }
+
+    processor_state!(widths);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        StereoWidthBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        StereoWidthBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.widths[0].reset(self.low_width);
+        self.widths[1].reset(self.high_width);
+    }
+
+    fn sample_rate_changed(&mut self, _old: f32, new: f32) {
+        self.sample_rate = new;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for width in self.widths.iter_mut() {
+            width.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let left = inputs.input_as::<F::RealFft>(0).unwrap();
+        let right = inputs.input_as::<F::RealFft>(1).unwrap();
+        let low_width = inputs.input_as::<f32>(2);
+        let high_width = inputs.input_as::<f32>(3);
+
+        for (i, (left, right)) in left.iter().zip(right.iter()).enumerate() {
+            self.widths[0].set_target(
+                low_width
+                    .and_then(|width| width.get(i).copied())
+                    .unwrap_or(self.low_width),
+            );
+            self.widths[1].set_target(
+                high_width
+                    .and_then(|width| width.get(i).copied())
+                    .unwrap_or(self.high_width),
+            );
+            let [low_width, high_width] = self.widths.each_mut().map(|width| width.advance());
+
+            for bin in 0..F::N_REAL_BINS {
+                let weight = self.high_band_weight(bin);
+                let width = low_width + (high_width - low_width) * weight;
+
+                let mid = (left[bin] + right[bin]) * 0.5;
+                let side = (left[bin] - right[bin]) * 0.5 * width;
+                self.left[bin] = mid + side;
+                self.right[bin] = mid - side;
+            }
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.left)?;
+            outputs.set_output_as::<F::RealFft>(1, i, &*self.right)?;
+        }
+
+        Ok(())
+    }
+}
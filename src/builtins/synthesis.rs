@@ -0,0 +1,460 @@
+//! Processors that synthesize spectra rather than transform existing ones.
+
+use std::{
+    f32::consts::{PI, TAU},
+    sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+};
+
+use raug::prelude::*;
+
+use crate::{
+    FftError,
+    builder::processor_builder,
+    fft_processor_io, peaks,
+    processor::{FftProcessor, FrameInfo, ParamSpec, ParamUnit},
+    processor_clone, processor_state,
+    signal::{Complex32, Fft},
+    state::{StateReader, StateWriter},
+    units,
+};
+
+/// Reconstructs plausible phases for magnitude-only frames, e.g. from a model or a drawn
+/// spectrogram, so they can be resynthesized.
+///
+/// Only the magnitude of each input bin is used. Peaks more than `threshold_db` below the loudest
+/// bin are ignored; every other local maximum has its frequency refined by quadratic
+/// interpolation, and its phase is advanced by that frequency from the previous frame. The bins
+/// around each peak, up to the lowest bin between it and the next peak, are locked to the peak's
+/// phase (alternating by half a cycle per bin, matching the main lobe of a zero-phase window), so
+/// partials stay coherent across frames. Without any peaks, every bin advances at its center
+/// frequency.
+#[derive(Clone)]
+pub struct PhaseReconstruct<F: Fft> {
+    pub threshold_db: f32,
+    frame: FrameInfo,
+    magnitudes: Vec<f32>,
+    phases: Vec<f32>,
+    peaks: Vec<usize>,
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> PhaseReconstruct<F> {
+    pub fn new() -> Self {
+        Self {
+            threshold_db: 60.0,
+            frame: FrameInfo::default(),
+            magnitudes: vec![0.0; F::N_REAL_BINS],
+            phases: vec![0.0; F::N_REAL_BINS],
+            peaks: Vec::with_capacity(F::N_REAL_BINS),
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+
+    /// Reconstructs phases for the next frame's `magnitudes` and returns the resulting spectrum.
+    ///
+    /// This is what [`process`](FftProcessor::process) does for every input frame, for use by other
+    /// processors. Missing magnitudes count as zero.
+    pub fn reconstruct(&mut self, magnitudes: &[f32]) -> &F::RealFft {
+        self.magnitudes.fill(0.0);
+        for (magnitude, &value) in self.magnitudes.iter_mut().zip(magnitudes) {
+            *magnitude = value.abs();
+        }
+        self.update_phases();
+        self.write_output();
+        &self.out_signal
+    }
+
+    fn write_output(&mut self) {
+        for ((out, &magnitude), &phase) in self
+            .out_signal
+            .iter_mut()
+            .zip(&self.magnitudes)
+            .zip(&self.phases)
+        {
+            *out = Complex32::from_polar(magnitude, phase);
+        }
+    }
+
+    /// Returns the phase advance over one hop of a partial at `bin` (fractional).
+    fn advance(&self, bin: f32) -> f32 {
+        TAU * bin * self.frame.hop_length as f32 / F::N_FFT as f32
+    }
+
+    fn update_phases(&mut self) {
+        let num_bins = self.magnitudes.len();
+        let threshold = peaks::threshold_below_max(&self.magnitudes, self.threshold_db);
+
+        self.peaks.clear();
+        peaks::find_peaks(
+            &self.magnitudes,
+            threshold.max(f32::EPSILON),
+            &mut self.peaks,
+        );
+
+        if self.peaks.is_empty() {
+            for bin in 0..num_bins {
+                self.phases[bin] = (self.phases[bin] + self.advance(bin as f32)).rem_euclid(TAU);
+            }
+            return;
+        }
+
+        let mut start = 0;
+        for index in 0..self.peaks.len() {
+            let peak = self.peaks[index];
+            let end = match self.peaks.get(index + 1) {
+                // the region ends at the lowest bin between this peak and the next
+                Some(&next) => (peak + 1..next)
+                    .min_by(|&a, &b| self.magnitudes[a].total_cmp(&self.magnitudes[b]))
+                    .unwrap_or(next),
+                None => num_bins,
+            };
+
+            let (frequency, _) = peaks::interpolate_peak_log(&self.magnitudes, peak);
+            let peak_phase = (self.phases[peak] + self.advance(frequency)).rem_euclid(TAU);
+            for bin in start..end {
+                let offset = if (bin as isize - peak as isize) % 2 == 0 {
+                    0.0
+                } else {
+                    PI
+                };
+                self.phases[bin] = (peak_phase + offset).rem_euclid(TAU);
+            }
+            start = end;
+        }
+    }
+}
+
+impl<F: Fft> Default for PhaseReconstruct<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+processor_builder! {
+    PhaseReconstruct => PhaseReconstructBuilder {
+        threshold_db: f32 [Decibels, 0.0, 120.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for PhaseReconstruct<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft }
+    }
+
+    processor_state!(phases);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        PhaseReconstructBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        PhaseReconstructBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.phases.fill(0.0);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            units::magnitudes(input, &mut self.magnitudes);
+            self.update_phases();
+            self.write_output();
+
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of magnitudes carried by one update of a [`SpectralTable`].
+const UPDATE_CHUNK: usize = 32;
+
+enum TableUpdate {
+    Values {
+        column: usize,
+        start: usize,
+        len: usize,
+        values: [f32; UPDATE_CHUNK],
+    },
+    Clear,
+}
+
+/// Updates the magnitudes of a [`SpectralTable`] from another thread, e.g. a UI painting a
+/// spectrogram.
+///
+/// Updates are queued without locking and applied by the table before its next frame. The queue
+/// has a fixed capacity, so the methods return `false` if it's full (or the table was dropped);
+/// the updates that did fit are still applied.
+#[derive(Clone)]
+pub struct SpectralTableWriter {
+    sender: SyncSender<TableUpdate>,
+    num_columns: usize,
+    num_bins: usize,
+}
+
+impl SpectralTableWriter {
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    pub fn num_bins(&self) -> usize {
+        self.num_bins
+    }
+
+    /// Sets the magnitude of one bin of one column.
+    pub fn set(&self, column: usize, bin: usize, magnitude: f32) -> bool {
+        self.set_range(column, bin, &[magnitude])
+    }
+
+    /// Sets the magnitudes of a column, starting at its lowest bin.
+    pub fn set_column(&self, column: usize, magnitudes: &[f32]) -> bool {
+        self.set_range(column, 0, magnitudes)
+    }
+
+    /// Sets consecutive magnitudes of a column, starting at bin `start`. Magnitudes beyond the
+    /// table are ignored.
+    pub fn set_range(&self, column: usize, start: usize, magnitudes: &[f32]) -> bool {
+        if column >= self.num_columns {
+            return true;
+        }
+        let magnitudes = &magnitudes[..magnitudes.len().min(self.num_bins.saturating_sub(start))];
+        for (index, chunk) in magnitudes.chunks(UPDATE_CHUNK).enumerate() {
+            let mut values = [0.0; UPDATE_CHUNK];
+            values[..chunk.len()].copy_from_slice(chunk);
+            let update = TableUpdate::Values {
+                column,
+                start: start + index * UPDATE_CHUNK,
+                len: chunk.len(),
+                values,
+            };
+            if !self.send(update) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Sets every magnitude of the table to zero.
+    pub fn clear(&self) -> bool {
+        self.send(TableUpdate::Clear)
+    }
+
+    fn send(&self, update: TableUpdate) -> bool {
+        match self.sender.try_send(update) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+/// Plays back a table of magnitude frames ("columns"), e.g. a spectrogram drawn in a UI, and
+/// resynthesizes it with a [`PhaseReconstruct`].
+///
+/// The playhead advances by `rate` columns per frame, interpolating linearly between neighboring
+/// columns, and wraps around at the end if `looping` is set or stops on the last column
+/// otherwise. The `rate` input overrides `rate` while connected, and the `position` input
+/// (`0..1` over the whole table) overrides the playhead, e.g. for scrubbing. Magnitudes are scaled
+/// by `gain`, and are in the units of the graph's spectra.
+///
+/// The table is updated through the [`SpectralTableWriter`] returned on construction. Besides the
+/// spectrum, the processor outputs the playhead in columns and a trigger on every wrap.
+pub struct SpectralTable<F: Fft> {
+    pub rate: f32,
+    pub looping: bool,
+    pub gain: f32,
+    columns: Vec<f32>,
+    num_columns: usize,
+    position: f32,
+    updates: Receiver<TableUpdate>,
+    magnitudes: Vec<f32>,
+    reconstruct: PhaseReconstruct<F>,
+}
+
+impl<F: Fft> SpectralTable<F> {
+    /// Creates a silent table of `num_columns` columns, along with a writer for filling it in
+    /// that can queue up to `capacity` updates of up to 32 bins each.
+    pub fn new(num_columns: usize, capacity: usize) -> (Self, SpectralTableWriter) {
+        let num_columns = num_columns.max(1);
+        let (sender, updates) = sync_channel(capacity.max(1));
+        let table = Self {
+            rate: 1.0,
+            looping: true,
+            gain: 1.0,
+            columns: vec![0.0; num_columns * F::N_REAL_BINS],
+            num_columns,
+            position: 0.0,
+            updates,
+            magnitudes: vec![0.0; F::N_REAL_BINS],
+            reconstruct: PhaseReconstruct::new(),
+        };
+        let writer = SpectralTableWriter {
+            sender,
+            num_columns,
+            num_bins: F::N_REAL_BINS,
+        };
+        (table, writer)
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    /// Returns the magnitudes of one column.
+    pub fn column(&self, column: usize) -> &[f32] {
+        &self.columns[column * F::N_REAL_BINS..(column + 1) * F::N_REAL_BINS]
+    }
+
+    /// Returns the playhead in columns.
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    pub fn phase_reconstruct_mut(&mut self) -> &mut PhaseReconstruct<F> {
+        &mut self.reconstruct
+    }
+
+    fn apply_updates(&mut self) {
+        while let Ok(update) = self.updates.try_recv() {
+            match update {
+                TableUpdate::Values {
+                    column,
+                    start,
+                    len,
+                    values,
+                } => {
+                    let offset = column * F::N_REAL_BINS + start;
+                    self.columns[offset..offset + len].copy_from_slice(&values[..len]);
+                }
+                TableUpdate::Clear => self.columns.fill(0.0),
+            }
+        }
+    }
+
+    /// Interpolates the magnitudes at the playhead.
+    fn read(&mut self) {
+        let index = self.position.floor() as usize % self.num_columns;
+        let next = if index + 1 < self.num_columns {
+            index + 1
+        } else if self.looping {
+            0
+        } else {
+            index
+        };
+        let t = self.position.fract();
+        let (a, b) = (index * F::N_REAL_BINS, next * F::N_REAL_BINS);
+        for (bin, magnitude) in self.magnitudes.iter_mut().enumerate() {
+            let (a, b) = (self.columns[a + bin], self.columns[b + bin]);
+            *magnitude = (a + (b - a) * t) * self.gain;
+        }
+    }
+
+    /// Advances the playhead by `rate` columns, returning whether it wrapped around.
+    fn advance(&mut self, rate: f32) -> bool {
+        let length = self.num_columns as f32;
+        let position = self.position + rate;
+        if self.looping {
+            self.position = position.rem_euclid(length);
+            !(0.0..length).contains(&position)
+        } else {
+            self.position = position.clamp(0.0, length - 1.0);
+            false
+        }
+    }
+}
+
+impl<F: Fft> FftProcessor for SpectralTable<F> {
+    fft_processor_io! {
+        inputs { rate: f32, position: f32 }
+        outputs { output: F::RealFft, position: f32, wrap: bool }
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.columns);
+        state.write(&self.num_columns);
+        state.write(&self.position);
+        self.reconstruct.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.columns)?;
+        state.read(&mut self.num_columns)?;
+        state.read(&mut self.position)?;
+        self.reconstruct.load_state(state)
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("rate", ParamUnit::None, -4.0, 4.0, 1.0),
+            ParamSpec::new("looping", ParamUnit::Toggle, 0.0, 1.0, 1.0),
+            ParamSpec::new("gain", ParamUnit::None, 0.0, 4.0, 1.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate" => self.rate = value,
+            "looping" => self.looping = value != 0.0,
+            "gain" => self.gain = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.position = 0.0;
+        self.reconstruct.allocate(sample_rate);
+    }
+
+    fn sample_rate_changed(&mut self, old: f32, new: f32) {
+        self.reconstruct.sample_rate_changed(old, new);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.reconstruct.set_frame_info(info);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        self.apply_updates();
+
+        let rate = inputs
+            .input_as::<f32>(0)
+            .and_then(|rate| rate.first().copied())
+            .unwrap_or(self.rate);
+        if let Some(&position) = inputs
+            .input_as::<f32>(1)
+            .and_then(|position| position.first())
+        {
+            let last = (self.num_columns - 1) as f32;
+            self.position = (position.clamp(0.0, 1.0) * self.num_columns as f32).min(last);
+        }
+
+        self.read();
+        let spectrum = self.reconstruct.reconstruct(&self.magnitudes);
+        outputs.set_output_as::<F::RealFft>(0, 0, spectrum)?;
+        outputs.set_output_as::<f32>(1, 0, &self.position)?;
+
+        let wrapped = self.advance(rate);
+        outputs.set_output_as::<bool>(2, 0, &wrapped)?;
+
+        Ok(())
+    }
+}
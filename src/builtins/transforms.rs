@@ -1,12 +1,46 @@
-use std::sync::Arc;
+use std::{marker::PhantomData, sync::Arc};
 
 use raug::prelude::*;
 
 use crate::{
-    processor::FftProcessor,
+    FftError, analytic,
+    builtins::{dynamics::SpectralLimiter, synthesis::PhaseReconstruct},
+    core::FftPlanner,
+    fft_processor_io,
+    processor::{FftProcessor, FrameInfo, ParamSpec, ParamUnit},
+    processor_clone,
+    resolution::resample_spectrum,
     signal::{Complex32, Fft},
+    state::{StateReader, StateWriter},
+    units,
 };
 
+/// Forward and inverse real FFT plans of one length, shared between transform processors.
+#[derive(Clone)]
+pub struct FftPlans {
+    pub forward: Arc<dyn realfft::RealToComplex<f32>>,
+    pub inverse: Arc<dyn realfft::ComplexToReal<f32>>,
+}
+
+impl FftPlans {
+    /// Returns the plans for `fft_length` from the global [`FftPlanner`].
+    pub fn new(fft_length: usize) -> Self {
+        Self::with_planner(FftPlanner::global(), fft_length)
+    }
+
+    pub fn with_planner(planner: &FftPlanner, fft_length: usize) -> Self {
+        Self {
+            forward: planner.plan_forward(fft_length),
+            inverse: planner.plan_inverse(fft_length),
+        }
+    }
+
+    pub fn fft_length(&self) -> usize {
+        self.forward.len()
+    }
+}
+
+#[derive(Clone)]
 pub struct RealFft<F: Fft> {
     plan: Arc<dyn realfft::RealToComplex<f32>>,
     scratch: Vec<Complex32>,
@@ -17,8 +51,12 @@ pub struct RealFft<F: Fft> {
 
 impl<F: Fft> RealFft<F> {
     pub fn new() -> Self {
-        let mut planner = realfft::RealFftPlanner::new();
-        let plan = planner.plan_fft_forward(F::N_FFT);
+        Self::with_plan(FftPlanner::global().plan_forward(F::N_FFT))
+    }
+
+    /// Creates a transform that uses an existing plan, whose length must be `F::N_FFT`.
+    pub fn with_plan(plan: Arc<dyn realfft::RealToComplex<f32>>) -> Self {
+        assert_eq!(plan.len(), F::N_FFT, "FFT plan length mismatch");
         let scratch = plan.make_scratch_vec();
         let rfft_input = plan.make_input_vec();
         let rfft_output = plan.make_output_vec();
@@ -39,17 +77,12 @@ impl<F: Fft> Default for RealFft<F> {
 }
 
 impl<F: Fft> FftProcessor for RealFft<F> {
-    fn input_spec(&self) -> Vec<SignalSpec> {
-        vec![SignalSpec::new("input", F::AudioBlock::signal_type())]
+    fft_processor_io! {
+        inputs { input: F::AudioBlock }
+        outputs { output: F::RealFft }
     }
 
-    fn output_spec(&self) -> Vec<SignalSpec> {
-        vec![SignalSpec::new("output", F::RealFft::signal_type())]
-    }
-
-    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
-        vec![AnyBuffer::zeros::<F::RealFft>(size)]
-    }
+    processor_clone!();
 
     fn process(
         &mut self,
@@ -82,7 +115,14 @@ impl<F: Fft> FftProcessor for RealFft<F> {
     }
 }
 
+/// Resynthesizes a frame of audio from a spectrum.
+///
+/// With `limit` set, the spectrum first passes through a [`SpectralLimiter`], see
+/// [`FftGraph::set_output_limiter`](crate::graph::FftGraph::set_output_limiter).
+#[derive(Clone)]
 pub struct InverseRealFft<F: Fft> {
+    pub limit: bool,
+    limiter: SpectralLimiter<F>,
     plan: Arc<dyn realfft::ComplexToReal<f32>>,
     scratch: Vec<Complex32>,
     irfft_input: Vec<Complex32>,
@@ -92,12 +132,18 @@ pub struct InverseRealFft<F: Fft> {
 
 impl<F: Fft> InverseRealFft<F> {
     pub fn new() -> Self {
-        let mut planner = realfft::RealFftPlanner::new();
-        let plan = planner.plan_fft_inverse(F::N_FFT);
+        Self::with_plan(FftPlanner::global().plan_inverse(F::N_FFT))
+    }
+
+    /// Creates a transform that uses an existing plan, whose length must be `F::N_FFT`.
+    pub fn with_plan(plan: Arc<dyn realfft::ComplexToReal<f32>>) -> Self {
+        assert_eq!(plan.len(), F::N_FFT, "FFT plan length mismatch");
         let scratch = plan.make_scratch_vec();
         let irfft_input = plan.make_input_vec();
         let irfft_output = plan.make_output_vec();
         Self {
+            limit: false,
+            limiter: SpectralLimiter::default(),
             plan,
             scratch,
             irfft_input,
@@ -105,6 +151,14 @@ impl<F: Fft> InverseRealFft<F> {
             out_signal: Box::new(F::AudioBlock::default()),
         }
     }
+
+    pub fn limiter(&self) -> &SpectralLimiter<F> {
+        &self.limiter
+    }
+
+    pub fn limiter_mut(&mut self) -> &mut SpectralLimiter<F> {
+        &mut self.limiter
+    }
 }
 
 impl<F: Fft> Default for InverseRealFft<F> {
@@ -114,16 +168,33 @@ impl<F: Fft> Default for InverseRealFft<F> {
 }
 
 impl<F: Fft> FftProcessor for InverseRealFft<F> {
-    fn input_spec(&self) -> Vec<SignalSpec> {
-        vec![SignalSpec::new("input", F::RealFft::signal_type())]
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::AudioBlock }
+    }
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        let mut params = vec![ParamSpec::new("limit", ParamUnit::Toggle, 0.0, 1.0, 0.0)];
+        params.extend(self.limiter.param_spec());
+        params
     }
 
-    fn output_spec(&self) -> Vec<SignalSpec> {
-        vec![SignalSpec::new("output", F::AudioBlock::signal_type())]
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "limit" => self.limit = value != 0.0,
+            _ => return self.limiter.set_param(name, value),
+        }
+        true
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.limiter.allocate(sample_rate);
     }
 
-    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
-        vec![AnyBuffer::zeros::<F::AudioBlock>(size)]
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.limiter.set_frame_info(info);
     }
 
     fn process(
@@ -138,6 +209,9 @@ impl<F: Fft> FftProcessor for InverseRealFft<F> {
 
             self.irfft_input[0].im = 0.0;
             self.irfft_input[F::N_REAL_BINS - 1].im = 0.0;
+            if self.limit {
+                self.limiter.limit(&mut self.irfft_input);
+            }
 
             let res = self.plan.process_with_scratch(
                 &mut self.irfft_input,
@@ -159,3 +233,340 @@ impl<F: Fft> FftProcessor for InverseRealFft<F> {
         Ok(())
     }
 }
+
+/// How a [`FrameResample`] sets the phases of the spectra it outputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResamplePhase {
+    /// Every bin takes the phase of the input bin its magnitude came from, see
+    /// [`resample_spectrum`].
+    #[default]
+    Nearest,
+    /// Every bin has zero phase, e.g. for models only looking at magnitudes.
+    Zero,
+    /// Phases are reconstructed from the magnitudes frame by frame with a [`PhaseReconstruct`],
+    /// so the output can be resynthesized.
+    Reconstruct,
+}
+
+/// Converts spectra from the FFT length of `In` to that of `Out`, e.g. to feed a model trained
+/// on 256-point frames from a graph analyzing 2048-point frames, or to hand a spectrum to a
+/// graph of another resolution.
+///
+/// Magnitudes are interpolated (or, going to fewer bins, take the loudest bin they cover) and
+/// scaled so a sinusoid keeps its amplitude, see [`resample_spectrum`]. Phases are set according
+/// to `phase`.
+#[derive(Clone)]
+pub struct FrameResample<In: Fft, Out: Fft> {
+    pub phase: ResamplePhase,
+    reconstruct: PhaseReconstruct<Out>,
+    magnitudes: Vec<f32>,
+    out_signal: Box<Out::RealFft>,
+    _in: PhantomData<In>,
+}
+
+impl<In: Fft, Out: Fft> FrameResample<In, Out> {
+    pub fn new(phase: ResamplePhase) -> Self {
+        Self {
+            phase,
+            reconstruct: PhaseReconstruct::new(),
+            magnitudes: vec![0.0; Out::N_REAL_BINS],
+            out_signal: Box::new(Out::RealFft::default()),
+            _in: PhantomData,
+        }
+    }
+
+    /// Converts one spectrum, returning the result.
+    ///
+    /// This is what [`process`](FftProcessor::process) does for every input frame, for use by other
+    /// processors.
+    pub fn resample(&mut self, input: &[Complex32]) -> &Out::RealFft {
+        resample_spectrum(input, &mut self.out_signal[..]);
+        match self.phase {
+            ResamplePhase::Nearest => {}
+            ResamplePhase::Zero => {
+                for value in self.out_signal.iter_mut() {
+                    *value = Complex32::new(value.norm(), 0.0);
+                }
+            }
+            ResamplePhase::Reconstruct => {
+                units::magnitudes(&self.out_signal[..], &mut self.magnitudes);
+                self.out_signal
+                    .copy_from_slice(self.reconstruct.reconstruct(&self.magnitudes));
+            }
+        }
+        &self.out_signal
+    }
+}
+
+impl<In: Fft, Out: Fft> Default for FrameResample<In, Out> {
+    fn default() -> Self {
+        Self::new(ResamplePhase::default())
+    }
+}
+
+impl<In: Fft, Out: Fft> FftProcessor for FrameResample<In, Out> {
+    fft_processor_io! {
+        inputs { input: In::RealFft }
+        outputs { output: Out::RealFft }
+    }
+
+    processor_clone!();
+
+    fn allocate(&mut self, sample_rate: f32) {
+        self.reconstruct.allocate(sample_rate);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.reconstruct.set_frame_info(info);
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        self.reconstruct.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        self.reconstruct.load_state(state)
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<In::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            self.resample(input);
+            outputs.set_output_as::<Out::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A complex (two-sided) FFT of `F::N_FFT` points, from a complex signal to its spectrum, see
+/// [`analytic`](crate::analytic) for the order of the bins.
+///
+/// Like [`RealFft`], the transform isn't normalized.
+#[derive(Clone)]
+pub struct ComplexFftForward<F: Fft> {
+    plan: Arc<dyn rustfft::Fft<f32>>,
+    scratch: Vec<Complex32>,
+    out_signal: Box<F::ComplexFft>,
+}
+
+impl<F: Fft> ComplexFftForward<F> {
+    pub fn new() -> Self {
+        Self::with_plan(FftPlanner::global().plan_complex_forward(F::N_FFT))
+    }
+
+    /// Creates a transform that uses an existing plan, whose length must be `F::N_FFT`.
+    pub fn with_plan(plan: Arc<dyn rustfft::Fft<f32>>) -> Self {
+        assert_eq!(plan.len(), F::N_FFT, "FFT plan length mismatch");
+        assert_eq!(
+            plan.fft_direction(),
+            rustfft::FftDirection::Forward,
+            "FFT plan direction mismatch"
+        );
+        Self {
+            scratch: vec![Complex32::ZERO; plan.get_inplace_scratch_len()],
+            plan,
+            out_signal: Box::new(F::ComplexFft::default()),
+        }
+    }
+}
+
+impl<F: Fft> Default for ComplexFftForward<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Fft> FftProcessor for ComplexFftForward<F> {
+    fft_processor_io! {
+        inputs { input: F::ComplexFft }
+        outputs { output: F::ComplexFft }
+    }
+
+    processor_clone!();
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::ComplexFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            self.out_signal.copy_from_slice(input);
+            self.plan
+                .process_with_scratch(&mut self.out_signal, &mut self.scratch);
+            outputs.set_output_as::<F::ComplexFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The inverse of [`ComplexFftForward`], from a two-sided spectrum back to a complex signal.
+///
+/// Like [`InverseRealFft`], the transform isn't normalized, which the graph's synthesis window
+/// accounts for.
+#[derive(Clone)]
+pub struct ComplexFftInverse<F: Fft> {
+    plan: Arc<dyn rustfft::Fft<f32>>,
+    scratch: Vec<Complex32>,
+    out_signal: Box<F::ComplexFft>,
+}
+
+impl<F: Fft> ComplexFftInverse<F> {
+    pub fn new() -> Self {
+        Self::with_plan(FftPlanner::global().plan_complex_inverse(F::N_FFT))
+    }
+
+    /// Creates a transform that uses an existing plan, whose length must be `F::N_FFT`.
+    pub fn with_plan(plan: Arc<dyn rustfft::Fft<f32>>) -> Self {
+        assert_eq!(plan.len(), F::N_FFT, "FFT plan length mismatch");
+        assert_eq!(
+            plan.fft_direction(),
+            rustfft::FftDirection::Inverse,
+            "FFT plan direction mismatch"
+        );
+        Self {
+            scratch: vec![Complex32::ZERO; plan.get_inplace_scratch_len()],
+            plan,
+            out_signal: Box::new(F::ComplexFft::default()),
+        }
+    }
+}
+
+impl<F: Fft> Default for ComplexFftInverse<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Fft> FftProcessor for ComplexFftInverse<F> {
+    fft_processor_io! {
+        inputs { input: F::ComplexFft }
+        outputs { output: F::ComplexFft }
+    }
+
+    processor_clone!();
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::ComplexFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            self.out_signal.copy_from_slice(input);
+            self.plan
+                .process_with_scratch(&mut self.out_signal, &mut self.scratch);
+            outputs.set_output_as::<F::ComplexFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Extends the real spectra of the graph to two-sided spectra, either of the same real signal or,
+/// with `analytic`, of its analytic signal, whose inverse [`ComplexFftInverse`] is the signal plus
+/// `i` times its Hilbert transform. See [`analytic`](crate::analytic).
+#[derive(Clone)]
+pub struct ComplexSpectrum<F: Fft> {
+    pub analytic: bool,
+    out_signal: Box<F::ComplexFft>,
+}
+
+impl<F: Fft> ComplexSpectrum<F> {
+    pub fn new(analytic: bool) -> Self {
+        Self {
+            analytic,
+            out_signal: Box::new(F::ComplexFft::default()),
+        }
+    }
+}
+
+/// Extends to the two-sided spectrum of the same real signal.
+impl<F: Fft> Default for ComplexSpectrum<F> {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl<F: Fft> FftProcessor for ComplexSpectrum<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::ComplexFft }
+    }
+
+    processor_clone!();
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            if self.analytic {
+                analytic::analytic_spectrum(input, &mut self.out_signal);
+            } else {
+                analytic::two_sided_spectrum(input, &mut self.out_signal);
+            }
+            outputs.set_output_as::<F::ComplexFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns two-sided spectra back into the real spectra of the graph, keeping the real part of the
+/// signal they describe, e.g. to resynthesize the output of a single-sideband modulator. See
+/// [`analytic::one_sided_spectrum`].
+#[derive(Clone)]
+pub struct RealSpectrum<F: Fft> {
+    out_signal: Box<F::RealFft>,
+}
+
+impl<F: Fft> RealSpectrum<F> {
+    pub fn new() -> Self {
+        Self {
+            out_signal: Box::new(F::RealFft::default()),
+        }
+    }
+}
+
+impl<F: Fft> Default for RealSpectrum<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Fft> FftProcessor for RealSpectrum<F> {
+    fft_processor_io! {
+        inputs { input: F::ComplexFft }
+        outputs { output: F::RealFft }
+    }
+
+    processor_clone!();
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::ComplexFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            analytic::one_sided_spectrum(input, &mut self.out_signal);
+            outputs.set_output_as::<F::RealFft>(0, i, &*self.out_signal)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+//! Processors for events within the graph, carried by `bool` signals.
+//!
+//! A gate is `true` for as long as something is on, e.g. while a level is above a threshold. A
+//! trigger is `true` for a single frame when something happens, e.g. at an onset. Unconnected
+//! gate and trigger inputs read as `false`.
+
+use raug::prelude::*;
+
+use crate::{
+    builder::processor_builder,
+    denormal::flush_denormals,
+    fft_processor_io,
+    processor::{FftProcessor, FrameInfo, ParamSpec},
+    processor_clone, processor_state,
+    signal::Fft,
+};
+
+/// Returns the value of a `bool` input for frame `i`, or `false` if it isn't connected.
+fn event(inputs: &ProcessorInputs, index: usize, i: usize) -> bool {
+    inputs
+        .input_as::<bool>(index)
+        .and_then(|values| values.get(i).copied())
+        .unwrap_or(false)
+}
+
+/// Turns a control value into a gate and triggers on its edges.
+///
+/// The gate opens once the input rises above `threshold` and closes once it falls below
+/// `threshold - hysteresis`, so noisy values near the threshold don't retrigger. The `rising` and
+/// `falling` outputs fire on the frames where the gate opens and closes.
+#[derive(Clone)]
+pub struct EdgeDetector<F: Fft> {
+    pub threshold: f32,
+    pub hysteresis: f32,
+    open: bool,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> EdgeDetector<F> {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            hysteresis: 0.0,
+            open: false,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns whether the gate is currently open.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+}
+
+impl<F: Fft> Default for EdgeDetector<F> {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+processor_builder! {
+    EdgeDetector => EdgeDetectorBuilder {
+        threshold: f32 [None, -1.0, 1.0],
+        hysteresis: f32 [None, 0.0, 1.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for EdgeDetector<F> {
+    fft_processor_io! {
+        inputs { input: f32 }
+        outputs { gate: bool, rising: bool, falling: bool }
+    }
+
+    processor_state!(open);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        EdgeDetectorBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        EdgeDetectorBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.open = false;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let Some(input) = inputs.input_as::<f32>(0) else {
+            return Ok(());
+        };
+
+        for (i, &value) in input.iter().enumerate() {
+            let was_open = self.open;
+            if self.open {
+                self.open = value >= self.threshold - self.hysteresis;
+            } else {
+                self.open = value > self.threshold;
+            }
+
+            outputs.set_output_as::<bool>(0, i, &self.open)?;
+            outputs.set_output_as::<bool>(1, i, &(self.open && !was_open))?;
+            outputs.set_output_as::<bool>(2, i, &(!self.open && was_open))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Holds a spectrum, e.g. to freeze the sound at an onset.
+///
+/// A `trigger` captures the current input and holds it until `release` fires, after which the
+/// input passes through again. Triggering while holding captures a new spectrum. If both fire in
+/// the same frame, the trigger wins.
+#[derive(Clone)]
+pub struct Latch<F: Fft> {
+    held: Box<F::RealFft>,
+    holding: bool,
+    flush_denormals: bool,
+}
+
+impl<F: Fft> Latch<F> {
+    pub fn new() -> Self {
+        Self {
+            held: Box::new(F::RealFft::default()),
+            holding: false,
+            flush_denormals: false,
+        }
+    }
+
+    /// Returns whether a spectrum is currently held.
+    pub fn is_holding(&self) -> bool {
+        self.holding
+    }
+}
+
+impl<F: Fft> Default for Latch<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Fft> FftProcessor for Latch<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft, trigger: bool, release: bool }
+        outputs { output: F::RealFft, holding: bool }
+    }
+
+    processor_state!(held, holding);
+
+    processor_clone!();
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.holding = false;
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.flush_denormals = info.flush_denormals;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+
+        for (i, input) in input.iter().enumerate() {
+            if event(&inputs, 1, i) {
+                self.held.copy_from_slice(input);
+                if self.flush_denormals {
+                    flush_denormals(&mut self.held);
+                }
+                self.holding = true;
+            } else if event(&inputs, 2, i) {
+                self.holding = false;
+            }
+
+            let output = if self.holding { &*self.held } else { input };
+            outputs.set_output_as::<F::RealFft>(0, i, output)?;
+            outputs.set_output_as::<bool>(1, i, &self.holding)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts triggers, wrapping around after `length` of them.
+///
+/// With the `trigger` input unconnected, every frame counts, which makes the `wrap` output fire
+/// every `length` frames. `reset` sets the count back to zero. The count is output as an `f32`
+/// so it can drive control inputs directly.
+#[derive(Clone)]
+pub struct Counter<F: Fft> {
+    pub length: usize,
+    count: usize,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> Counter<F> {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length,
+            count: 0,
+            _f: std::marker::PhantomData,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<F: Fft> Default for Counter<F> {
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+processor_builder! {
+    Counter => CounterBuilder {
+        length: usize [None, 1.0, 64.0],
+    }
+}
+
+impl<F: Fft> FftProcessor for Counter<F> {
+    fft_processor_io! {
+        inputs { trigger: bool, reset: bool }
+        outputs { count: f32, wrap: bool }
+    }
+
+    processor_state!(count);
+
+    processor_clone!();
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        CounterBuilder::<F>::param_spec()
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        CounterBuilder::<F>::set_param(self, name, value)
+    }
+
+    fn allocate(&mut self, _sample_rate: f32) {
+        self.count = 0;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let num_frames = inputs.input_as::<bool>(0).map_or(1, |values| values.len());
+
+        for i in 0..num_frames {
+            let counts = inputs
+                .input_as::<bool>(0)
+                .is_none_or(|values| values.get(i).copied().unwrap_or(false));
+
+            let mut wrapped = false;
+            if event(&inputs, 1, i) {
+                self.count = 0;
+            } else if counts {
+                self.count += 1;
+                if self.count >= self.length.max(1) {
+                    self.count = 0;
+                    wrapped = true;
+                }
+            }
+
+            outputs.set_output_as::<f32>(0, i, &(self.count as f32))?;
+            outputs.set_output_as::<bool>(1, i, &wrapped)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,20 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+    mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+};
+
 use raug::prelude::*;
 
-use crate::{prelude::FftProcessor, signal::Fft};
+use crate::{
+    fft_processor_io,
+    prelude::FftProcessor,
+    processor::{FrameInfo, ParamSpec, ParamUnit},
+    processor_clone,
+    signal::Fft,
+};
 
+#[derive(Clone)]
 pub struct Null<F: Fft> {
     _phantom: std::marker::PhantomData<F>,
 }
@@ -21,23 +34,159 @@ impl<F: Fft> Default for Null<F> {
 }
 
 impl<F: Fft> FftProcessor for Null<F> {
+    processor_clone!();
+
     fn name(&self) -> &str {
         "Null"
     }
 
-    fn input_spec(&self) -> Vec<SignalSpec> {
-        vec![]
+    fft_processor_io! {
+        inputs {}
+        outputs { output: F::AudioBlock }
     }
 
-    fn output_spec(&self) -> Vec<SignalSpec> {
-        vec![SignalSpec::new("output", F::AudioBlock::signal_type())]
+    fn process(&mut self, _inputs: ProcessorInputs, _outputs: ProcessorOutputs) -> ProcResult<()> {
+        Ok(())
     }
+}
 
-    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
-        vec![AnyBuffer::zeros::<F::AudioBlock>(size)]
+/// A frame captured by a [`Probe`], tagged with the hop it passed through in.
+#[derive(Clone)]
+pub struct ProbeFrame<F: Fft> {
+    /// The index of the hop since the graph was allocated, see [`FrameInfo::frame_index`].
+    pub hop: u64,
+    pub spectrum: F::RealFft,
+}
+
+/// Reads the frames captured by a [`Probe`] from another thread, e.g. a UI drawing the spectrum
+/// at some point of a running patch.
+pub struct ProbeReader<F: Fft> {
+    frames: Receiver<ProbeFrame<F>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<F: Fft> ProbeReader<F> {
+    /// Returns the oldest captured frame that hasn't been read yet.
+    pub fn try_next(&self) -> Option<ProbeFrame<F>> {
+        self.frames.try_recv().ok()
     }
 
-    fn process(&mut self, _inputs: ProcessorInputs, _outputs: ProcessorOutputs) -> ProcResult<()> {
+    /// Calls `f` with every captured frame that hasn't been read yet, oldest first, returning how
+    /// many there were.
+    pub fn drain(&self, mut f: impl FnMut(ProbeFrame<F>)) -> usize {
+        let mut count = 0;
+        while let Ok(frame) = self.frames.try_recv() {
+            f(frame);
+            count += 1;
+        }
+        count
+    }
+
+    /// Skips to the most recently captured frame, if any were captured since the last read.
+    pub fn latest(&self) -> Option<ProbeFrame<F>> {
+        let mut latest = None;
+        self.drain(|frame| latest = Some(frame));
+        latest
+    }
+
+    /// Returns the number of frames the probe dropped because the queue was full since the last
+    /// call, and resets it.
+    pub fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Passes spectra through unchanged while capturing every `decimation`th of them for a
+/// [`ProbeReader`], like an oscilloscope tap for inspecting intermediate spectra of a running
+/// patch.
+///
+/// Insert it on any spectral connection. Captured frames are queued without locking or
+/// allocating; while the queue is full, frames are dropped and counted instead. Capturing can be
+/// switched off with `enabled`, which leaves the spectra passing through.
+pub struct Probe<F: Fft> {
+    pub decimation: usize,
+    pub enabled: bool,
+    sender: SyncSender<ProbeFrame<F>>,
+    dropped: Arc<AtomicU64>,
+    frame: FrameInfo,
+}
+
+impl<F: Fft> Probe<F> {
+    /// Creates a probe capturing every frame, along with a reader that can hold up to `capacity`
+    /// unread frames.
+    pub fn new(capacity: usize) -> (Self, ProbeReader<F>) {
+        let (sender, frames) = sync_channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let probe = Self {
+            decimation: 1,
+            enabled: true,
+            sender,
+            dropped: dropped.clone(),
+            frame: FrameInfo::default(),
+        };
+        (probe, ProbeReader { frames, dropped })
+    }
+
+    /// Captures only every `decimation`th frame.
+    pub fn with_decimation(mut self, decimation: usize) -> Self {
+        self.decimation = decimation.max(1);
+        self
+    }
+
+    fn capture(&self, hop: u64, spectrum: &F::RealFft) {
+        if !self.enabled || !hop.is_multiple_of(self.decimation.max(1) as u64) {
+            return;
+        }
+        let frame = ProbeFrame {
+            hop,
+            spectrum: spectrum.clone(),
+        };
+        if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) =
+            self.sender.try_send(frame)
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<F: Fft> FftProcessor for Probe<F> {
+    fft_processor_io! {
+        inputs { input: F::RealFft }
+        outputs { output: F::RealFft }
+    }
+
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::new("decimation", ParamUnit::Frames, 1.0, 256.0, 1.0),
+            ParamSpec::new("enabled", ParamUnit::Toggle, 0.0, 1.0, 1.0),
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "decimation" => self.decimation = (value.round() as usize).max(1),
+            "enabled" => self.enabled = value != 0.0,
+            _ => return false,
+        }
+        true
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let Some(input) = inputs.input_as::<F::RealFft>(0) else {
+            return Ok(());
+        };
+        for (i, spectrum) in input.iter().enumerate() {
+            self.capture(self.frame.frame_index + i as u64, spectrum);
+            outputs.set_output_as::<F::RealFft>(0, i, spectrum)?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,400 @@
+//! C bindings for embedding an FFT graph in non-Rust hosts.
+//!
+//! Enable the `capi` feature and build the crate as a `cdylib` or `staticlib`; the declarations
+//! are in `include/raug_fft.h`. A graph is created with [`raug_fft_graph_new`], built up with
+//! builtin processors by name, allocated, and then driven with interleaved buffers holding one
+//! channel per audio input or output. Graphs are not thread-safe; a host must not call into the
+//! same graph from two threads at once.
+
+use std::{
+    ffi::{CStr, c_char},
+    mem, ptr, slice,
+};
+
+use raug_graph::graph::NodeIndex;
+
+use crate::{
+    WindowFunction,
+    graph::{DynFftGraph, dyn_graph},
+    processor::Transport,
+    registry::ProcessorParams,
+};
+
+/// The result of a fallible C API call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaugFftStatus {
+    Ok = 0,
+    NullPointer = -1,
+    InvalidArgument = -2,
+    UnknownProcessor = -3,
+    ConnectionFailed = -4,
+    BlockTooLarge = -5,
+    ProcessFailed = -6,
+}
+
+/// An opaque FFT graph handle, along with the scratch buffers used to deinterleave audio.
+pub struct RaugFftGraph {
+    graph: Box<dyn DynFftGraph>,
+    block_size: usize,
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    // the slices of the channels passed to the graph, kept empty between blocks so processing
+    // reuses their allocations, see `recycle`
+    input_slices: Vec<&'static [f32]>,
+    output_slices: Vec<&'static mut [f32]>,
+}
+
+/// Creates an empty graph, returning null if the FFT length or window name is unsupported or the
+/// hop length is zero or longer than the FFT.
+///
+/// `window` names a window function (e.g. `"hann"`) and may be null to use the default.
+///
+/// # Safety
+///
+/// `window` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_new(
+    fft_length: usize,
+    hop_length: usize,
+    window: *const c_char,
+) -> *mut RaugFftGraph {
+    let window_fn = if window.is_null() {
+        WindowFunction::default()
+    } else {
+        // SAFETY: the caller guarantees `window` is a valid C string
+        let name = unsafe { CStr::from_ptr(window) };
+        match name.to_str().ok().and_then(WindowFunction::from_name) {
+            Some(window_fn) => window_fn,
+            None => return ptr::null_mut(),
+        }
+    };
+
+    match dyn_graph(fft_length, hop_length, window_fn) {
+        Ok(graph) => Box::into_raw(Box::new(RaugFftGraph {
+            graph,
+            block_size: 0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            input_slices: Vec::new(),
+            output_slices: Vec::new(),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Destroys a graph created with [`raug_fft_graph_new`]. Passing null does nothing.
+///
+/// # Safety
+///
+/// `graph` must be null or a pointer returned by [`raug_fft_graph_new`] that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_free(graph: *mut RaugFftGraph) {
+    if !graph.is_null() {
+        // SAFETY: the caller guarantees `graph` came from `Box::into_raw` and is still live
+        drop(unsafe { Box::from_raw(graph) });
+    }
+}
+
+/// Adds an audio input, writing its node id to `out_node`.
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle and `out_node` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_add_audio_input(
+    graph: *mut RaugFftGraph,
+    out_node: *mut usize,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    let node = graph.graph.add_audio_input();
+    // SAFETY: forwarded from the caller
+    unsafe { write_node(out_node, node) }
+}
+
+/// Adds an audio output, writing its node id to `out_node`.
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle and `out_node` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_add_audio_output(
+    graph: *mut RaugFftGraph,
+    out_node: *mut usize,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    let node = graph.graph.add_audio_output();
+    // SAFETY: forwarded from the caller
+    unsafe { write_node(out_node, node) }
+}
+
+/// Adds a builtin processor by name, writing its node id to `out_node`.
+///
+/// `param_names` and `param_values` hold `num_params` parameter overrides; both may be null when
+/// `num_params` is zero.
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle, `name` and each of the `num_params` entries of
+/// `param_names` must be NUL-terminated strings, `param_values` must point to `num_params`
+/// floats, and `out_node` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_add_processor(
+    graph: *mut RaugFftGraph,
+    name: *const c_char,
+    param_names: *const *const c_char,
+    param_values: *const f32,
+    num_params: usize,
+    out_node: *mut usize,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    if name.is_null() || (num_params > 0 && (param_names.is_null() || param_values.is_null())) {
+        return RaugFftStatus::NullPointer;
+    }
+
+    // SAFETY: the caller guarantees `name` is a valid C string
+    let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+        return RaugFftStatus::InvalidArgument;
+    };
+
+    let mut params = ProcessorParams::new();
+    if num_params > 0 {
+        // SAFETY: the caller guarantees both arrays hold `num_params` elements
+        let (names, values) = unsafe {
+            (
+                slice::from_raw_parts(param_names, num_params),
+                slice::from_raw_parts(param_values, num_params),
+            )
+        };
+        for (&param_name, &value) in names.iter().zip(values) {
+            if param_name.is_null() {
+                return RaugFftStatus::NullPointer;
+            }
+            // SAFETY: the caller guarantees each name is a valid C string
+            let Ok(param_name) = unsafe { CStr::from_ptr(param_name) }.to_str() else {
+                return RaugFftStatus::InvalidArgument;
+            };
+            params.set(param_name, value);
+        }
+    }
+
+    match graph.graph.add_builtin(name, &params) {
+        // SAFETY: forwarded from the caller
+        Some(node) => unsafe { write_node(out_node, node) },
+        None => RaugFftStatus::UnknownProcessor,
+    }
+}
+
+/// Connects output `source_output` of node `source` to input `target_input` of node `target`.
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_connect(
+    graph: *mut RaugFftGraph,
+    source: usize,
+    source_output: u32,
+    target: usize,
+    target_input: u32,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    match graph.graph.connect(
+        NodeIndex::new(source),
+        source_output,
+        NodeIndex::new(target),
+        target_input,
+    ) {
+        Ok(()) => RaugFftStatus::Ok,
+        Err(e) => {
+            log::debug!("raug_fft_graph_connect failed: {e}");
+            RaugFftStatus::ConnectionFailed
+        }
+    }
+}
+
+/// Allocates the graph for processing blocks of at most `block_size` frames.
+///
+/// This must be called after the graph's audio inputs and outputs have been added.
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_allocate(
+    graph: *mut RaugFftGraph,
+    sample_rate: f32,
+    block_size: usize,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    graph.graph.allocate(sample_rate, block_size);
+    graph.block_size = block_size;
+    graph.inputs = vec![vec![0.0; block_size]; graph.graph.num_audio_inputs()];
+    graph.outputs = vec![vec![0.0; block_size]; graph.graph.num_audio_outputs()];
+    graph.input_slices = Vec::with_capacity(graph.inputs.len());
+    graph.output_slices = Vec::with_capacity(graph.outputs.len());
+    RaugFftStatus::Ok
+}
+
+/// Sets the host's tempo and position in beats, usually before processing each block.
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_set_transport(
+    graph: *mut RaugFftGraph,
+    bpm: f32,
+    beat: f64,
+    playing: bool,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    graph.graph.set_transport(Transport { bpm, beat, playing });
+    RaugFftStatus::Ok
+}
+
+/// Sets the seed of the graph's random numbers, see
+/// [`FftGraph::set_seed`](crate::graph::FftGraph::set_seed).
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_set_seed(
+    graph: *mut RaugFftGraph,
+    seed: u64,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    graph.graph.set_seed(seed);
+    RaugFftStatus::Ok
+}
+
+/// Returns the delay in samples between the audio inputs and outputs, or 0 for a null graph.
+///
+/// # Safety
+///
+/// `graph` must be null or a live graph handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_latency_samples(graph: *const RaugFftGraph) -> usize {
+    // SAFETY: forwarded from the caller
+    unsafe { graph.as_ref() }.map_or(0, |graph| graph.graph.latency_samples())
+}
+
+/// Processes `frames` frames of interleaved audio.
+///
+/// `input` holds one channel per audio input and `output` one channel per audio output, in the
+/// order they were added. `frames` must not exceed the block size passed to
+/// [`raug_fft_graph_allocate`].
+///
+/// # Safety
+///
+/// `graph` must be a live graph handle, `input` must point to `frames` times the number of audio
+/// inputs floats, and `output` must be valid for writes of `frames` times the number of audio
+/// outputs floats.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn raug_fft_graph_process_interleaved(
+    graph: *mut RaugFftGraph,
+    input: *const f32,
+    output: *mut f32,
+    frames: usize,
+) -> RaugFftStatus {
+    // SAFETY: forwarded from the caller
+    let Some(graph) = (unsafe { graph.as_mut() }) else {
+        return RaugFftStatus::NullPointer;
+    };
+    if frames > graph.block_size {
+        return RaugFftStatus::BlockTooLarge;
+    }
+    let num_inputs = graph.inputs.len();
+    let num_outputs = graph.outputs.len();
+    if (num_inputs > 0 && input.is_null()) || (num_outputs > 0 && output.is_null()) {
+        return RaugFftStatus::NullPointer;
+    }
+
+    if num_inputs > 0 {
+        // SAFETY: the caller guarantees `input` holds `frames * num_inputs` floats
+        let input = unsafe { slice::from_raw_parts(input, frames * num_inputs) };
+        for (channel_index, channel) in graph.inputs.iter_mut().enumerate() {
+            for (frame, sample) in channel[..frames].iter_mut().enumerate() {
+                *sample = input[frame * num_inputs + channel_index];
+            }
+        }
+    }
+
+    let mut inputs: Vec<&[f32]> = mem::take(&mut graph.input_slices);
+    inputs.extend(graph.inputs.iter().map(|channel| &channel[..frames]));
+    let mut outputs: Vec<&mut [f32]> = mem::take(&mut graph.output_slices);
+    outputs.extend(
+        graph
+            .outputs
+            .iter_mut()
+            .map(|channel| &mut channel[..frames]),
+    );
+    let result = graph.graph.process_block(&inputs, &mut outputs);
+    graph.input_slices = recycle(inputs);
+    graph.output_slices = recycle(outputs);
+    if let Err(e) = result {
+        log::debug!("raug_fft_graph_process_interleaved failed: {e}");
+        return RaugFftStatus::ProcessFailed;
+    }
+
+    if num_outputs > 0 {
+        // SAFETY: the caller guarantees `output` holds `frames * num_outputs` floats
+        let output = unsafe { slice::from_raw_parts_mut(output, frames * num_outputs) };
+        for (channel_index, channel) in graph.outputs.iter().enumerate() {
+            for (frame, sample) in channel[..frames].iter().enumerate() {
+                output[frame * num_outputs + channel_index] = *sample;
+            }
+        }
+    }
+
+    RaugFftStatus::Ok
+}
+
+/// Empties `slices` and returns its allocation as a vector of another element type of the same
+/// layout, e.g. slices of a longer lifetime, so it can be kept between calls.
+fn recycle<T, U>(mut slices: Vec<T>) -> Vec<U> {
+    const {
+        assert!(mem::size_of::<T>() == mem::size_of::<U>());
+        assert!(mem::align_of::<T>() == mem::align_of::<U>());
+    }
+    slices.clear();
+    let mut slices = mem::ManuallyDrop::new(slices);
+    // SAFETY: the vector is empty, so no element is reinterpreted, and `T` and `U` share the
+    // layout the allocation was made with
+    unsafe { Vec::from_raw_parts(slices.as_mut_ptr().cast::<U>(), 0, slices.capacity()) }
+}
+
+/// Writes `node`'s id through `out_node` if it isn't null.
+///
+/// # Safety
+///
+/// `out_node` must be null or valid for writes.
+unsafe fn write_node(out_node: *mut usize, node: NodeIndex) -> RaugFftStatus {
+    if !out_node.is_null() {
+        // SAFETY: the caller guarantees `out_node` is valid for writes
+        unsafe { out_node.write(node.index()) };
+    }
+    RaugFftStatus::Ok
+}
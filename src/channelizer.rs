@@ -0,0 +1,322 @@
+//! Critically sampled subband processing with a pseudo-QMF polyphase filterbank.
+//!
+//! A [`PolyphaseAnalysis`] splits a stream into `bands` real subband signals of equal width,
+//! each running at `1 / bands` of the sample rate, and a [`PolyphaseSynthesis`] merges them back.
+//! Together they cost a few multiplications per sample and band, and the subbands hold exactly
+//! as many samples as the input, so per-band time-domain processing (saturation, compression,
+//! ...) runs at the cost of processing the full-rate signal once. A [`Channelizer`] pairs them
+//! for streaming:
+//!
+//! ```ignore
+//! let mut channelizer = Channelizer::new(32, CHANNELIZER_TAPS);
+//! channelizer.process(&input, &mut output, |subbands| {
+//!     for sample in subbands.iter_mut() {
+//!         *sample = sample.tanh();
+//!     }
+//! });
+//! ```
+//!
+//! The bands are cosine modulations of one lowpass prototype, as in MPEG audio coding. The
+//! aliasing between neighbouring bands cancels out on resynthesis, so an unprocessed stream
+//! comes back to within about -50 dB with the default taps (near-perfect reconstruction), but
+//! processing a band on its own brings some of it back. Odd bands come out spectrally inverted.
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::core::{WindowFunction, math};
+
+/// The default length of a prototype, in multiples of twice the number of bands.
+pub const CHANNELIZER_TAPS: usize = 8;
+
+/// Returns the response of `filter` at `frequency` radians per sample.
+fn magnitude(filter: &[f64], frequency: f64) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for (index, &x) in filter.iter().enumerate() {
+        re += x * math::cos(frequency * index as f64);
+        im += x * math::sin(frequency * index as f64);
+    }
+    math::sqrtf((re * re + im * im) as f32) as f64
+}
+
+/// Returns a Blackman-windowed sinc lowpass of `2 * bands * taps` samples with a cutoff of
+/// `cutoff` radians per sample.
+fn windowed_sinc(bands: usize, taps: usize, cutoff: f64) -> Vec<f64> {
+    let length = 2 * bands * taps;
+    let window = WindowFunction::Blackman.generate(length);
+    let center = (length - 1) as f64 / 2.0;
+    window
+        .iter()
+        .enumerate()
+        .map(|(index, &w)| {
+            let t = index as f64 - center;
+            let sinc = if t == 0.0 {
+                cutoff / core::f64::consts::PI
+            } else {
+                math::sin(cutoff * t) / (core::f64::consts::PI * t)
+            };
+            sinc * w as f64
+        })
+        .collect()
+}
+
+/// Designs the lowpass prototype of a pseudo-QMF bank of `bands` bands, `2 * bands * taps`
+/// samples long (`taps` is at least 1).
+///
+/// The cutoff is tuned so the prototype passes half the power halfway to the edge of its band,
+/// which makes the responses of neighbouring bands sum to nearly flat. The prototype has unit
+/// gain at DC.
+pub fn pqmf_prototype(bands: usize, taps: usize) -> Vec<f32> {
+    let bands = bands.max(1);
+    let taps = taps.max(1);
+    let crossover = core::f64::consts::PI / (2 * bands) as f64;
+    let (mut low, mut high) = (crossover * 0.5, crossover * 2.0);
+    for _ in 0..48 {
+        let cutoff = (low + high) / 2.0;
+        let filter = windowed_sinc(bands, taps, cutoff);
+        if magnitude(&filter, crossover)
+            < magnitude(&filter, 0.0) * core::f64::consts::FRAC_1_SQRT_2
+        {
+            low = cutoff;
+        } else {
+            high = cutoff;
+        }
+    }
+    let filter = windowed_sinc(bands, taps, (low + high) / 2.0);
+    let gain = magnitude(&filter, 0.0);
+    filter.iter().map(|&x| (x / gain) as f32).collect()
+}
+
+/// Returns the `bands × 2 * bands` cosines modulating the prototype, for the analysis (`sign`
+/// of 1) or synthesis (`sign` of -1) bank.
+fn modulation(bands: usize, length: usize, sign: f64) -> Vec<f32> {
+    let center = (length - 1) as f64 / 2.0;
+    let mut cosines = Vec::with_capacity(bands * 2 * bands);
+    for band in 0..bands {
+        let frequency = (2 * band + 1) as f64 * core::f64::consts::PI / (2 * bands) as f64;
+        let phase = if band % 2 == 0 {
+            core::f64::consts::FRAC_PI_4
+        } else {
+            -core::f64::consts::FRAC_PI_4
+        };
+        for index in 0..2 * bands {
+            let x = frequency * (index as f64 - center) + sign * phase;
+            cosines.push((2.0 * math::cos(x)) as f32);
+        }
+    }
+    cosines
+}
+
+/// Splits a stream into critically sampled subbands, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct PolyphaseAnalysis {
+    bands: usize,
+    prototype: Vec<f32>,
+    cosines: Vec<f32>,
+    // the last `prototype.len()` input samples, newest first
+    history: Vec<f32>,
+    folded: Vec<f32>,
+}
+
+impl PolyphaseAnalysis {
+    /// Creates an analysis bank of `bands` bands with a prototype of `taps` times twice as many
+    /// samples, see [`pqmf_prototype`].
+    pub fn new(bands: usize, taps: usize) -> Self {
+        Self::with_prototype(bands, pqmf_prototype(bands, taps))
+    }
+
+    /// Creates an analysis bank with a custom prototype, whose length is rounded up to a multiple
+    /// of twice the number of bands with zeros.
+    pub fn with_prototype(bands: usize, mut prototype: Vec<f32>) -> Self {
+        let bands = bands.max(1);
+        prototype.resize(prototype.len().div_ceil(2 * bands).max(1) * 2 * bands, 0.0);
+        Self {
+            bands,
+            cosines: modulation(bands, prototype.len(), 1.0),
+            history: vec![0.0; prototype.len()],
+            folded: vec![0.0; 2 * bands],
+            prototype,
+        }
+    }
+
+    pub fn bands(&self) -> usize {
+        self.bands
+    }
+
+    pub fn prototype(&self) -> &[f32] {
+        &self.prototype
+    }
+
+    /// Returns the center frequency of `band` in Hz.
+    pub fn band_frequency(&self, band: usize, sample_rate: f32) -> f32 {
+        (2 * band + 1) as f32 * sample_rate / (4 * self.bands) as f32
+    }
+
+    /// Takes the next [`bands`](Self::bands) input samples and writes the next sample of every
+    /// subband to `subbands`, in order of frequency.
+    pub fn analyze(&mut self, input: &[f32], subbands: &mut [f32]) {
+        let bands = self.bands;
+        let length = self.history.len();
+        self.history.copy_within(..length - bands, bands);
+        for (slot, &sample) in self.history[..bands].iter_mut().zip(input.iter().rev()) {
+            *slot = sample;
+        }
+
+        // the modulation flips sign every `2 * bands` samples, so fold the windowed history
+        self.folded.fill(0.0);
+        for (index, (&sample, &p)) in self.history.iter().zip(&self.prototype).enumerate() {
+            let period = index / (2 * bands);
+            let sign = if period.is_multiple_of(2) { 1.0 } else { -1.0 };
+            self.folded[index % (2 * bands)] += sign * sample * p;
+        }
+
+        for (subband, cosines) in subbands.iter_mut().zip(self.cosines.chunks(2 * bands)) {
+            *subband = cosines.iter().zip(&self.folded).map(|(c, x)| c * x).sum();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+    }
+}
+
+/// Merges critically sampled subbands back into a stream, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct PolyphaseSynthesis {
+    bands: usize,
+    prototype: Vec<f32>,
+    cosines: Vec<f32>,
+    unfolded: Vec<f32>,
+    overlap: Vec<f32>,
+}
+
+impl PolyphaseSynthesis {
+    /// Creates a synthesis bank matching [`PolyphaseAnalysis::new`].
+    pub fn new(bands: usize, taps: usize) -> Self {
+        Self::with_prototype(bands, pqmf_prototype(bands, taps))
+    }
+
+    /// Creates a synthesis bank matching [`PolyphaseAnalysis::with_prototype`].
+    pub fn with_prototype(bands: usize, mut prototype: Vec<f32>) -> Self {
+        let bands = bands.max(1);
+        prototype.resize(prototype.len().div_ceil(2 * bands).max(1) * 2 * bands, 0.0);
+        Self {
+            bands,
+            cosines: modulation(bands, prototype.len(), -1.0),
+            unfolded: vec![0.0; 2 * bands],
+            overlap: vec![0.0; prototype.len()],
+            prototype,
+        }
+    }
+
+    pub fn bands(&self) -> usize {
+        self.bands
+    }
+
+    /// Takes the next sample of every subband and writes the next [`bands`](Self::bands) output
+    /// samples.
+    pub fn synthesize(&mut self, subbands: &[f32], output: &mut [f32]) {
+        let bands = self.bands;
+        self.unfolded.fill(0.0);
+        for (&subband, cosines) in subbands.iter().zip(self.cosines.chunks(2 * bands)) {
+            for (x, c) in self.unfolded.iter_mut().zip(cosines) {
+                *x += subband * c;
+            }
+        }
+
+        // every band adds its share of the output, which the analysis scaled down
+        let gain = bands as f32;
+        for (index, (slot, &p)) in self.overlap.iter_mut().zip(&self.prototype).enumerate() {
+            let period = index / (2 * bands);
+            let sign = if period.is_multiple_of(2) {
+                gain
+            } else {
+                -gain
+            };
+            *slot += sign * self.unfolded[index % (2 * bands)] * p;
+        }
+
+        for (out, &sample) in output.iter_mut().zip(&self.overlap[..bands]) {
+            *out = sample;
+        }
+        let length = self.overlap.len();
+        self.overlap.copy_within(bands.., 0);
+        self.overlap[length - bands..].fill(0.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.overlap.fill(0.0);
+    }
+}
+
+/// Streams audio through a [`PolyphaseAnalysis`] and [`PolyphaseSynthesis`], processing the
+/// subbands in between, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Channelizer {
+    analysis: PolyphaseAnalysis,
+    synthesis: PolyphaseSynthesis,
+    input: Vec<f32>,
+    output: VecDeque<f32>,
+    subbands: Vec<f32>,
+    block: Vec<f32>,
+}
+
+impl Channelizer {
+    /// Creates a channelizer of `bands` bands, see [`PolyphaseAnalysis::new`].
+    pub fn new(bands: usize, taps: usize) -> Self {
+        let prototype = pqmf_prototype(bands, taps);
+        let bands = bands.max(1);
+        let mut channelizer = Self {
+            analysis: PolyphaseAnalysis::with_prototype(bands, prototype.clone()),
+            synthesis: PolyphaseSynthesis::with_prototype(bands, prototype),
+            input: Vec::with_capacity(bands),
+            output: VecDeque::with_capacity(2 * bands),
+            subbands: vec![0.0; bands],
+            block: vec![0.0; bands],
+        };
+        channelizer.reset();
+        channelizer
+    }
+
+    pub fn bands(&self) -> usize {
+        self.analysis.bands()
+    }
+
+    pub fn analysis(&self) -> &PolyphaseAnalysis {
+        &self.analysis
+    }
+
+    /// Returns the delay in samples between the input and the output: one less than the length
+    /// of the prototype.
+    pub fn latency_samples(&self) -> usize {
+        self.analysis.prototype().len() - 1
+    }
+
+    /// Clears all buffered input and output.
+    pub fn reset(&mut self) {
+        self.analysis.reset();
+        self.synthesis.reset();
+        self.input.clear();
+        self.output.clear();
+        // the first subband samples are only complete once a whole block has arrived
+        self.output
+            .extend(core::iter::repeat_n(0.0, self.bands() - 1));
+    }
+
+    /// Processes a block of samples of any length, calling `f` with the next sample of every
+    /// subband each time [`bands`](Self::bands) more input samples have arrived, and writes as
+    /// many output samples.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32], mut f: impl FnMut(&mut [f32])) {
+        let bands = self.bands();
+        for (&sample, out) in input.iter().zip(output.iter_mut()) {
+            self.input.push(sample);
+            if self.input.len() == bands {
+                self.analysis.analyze(&self.input, &mut self.subbands);
+                self.input.clear();
+                f(&mut self.subbands);
+                self.synthesis.synthesize(&self.subbands, &mut self.block);
+                self.output.extend(&self.block);
+            }
+            *out = self.output.pop_front().unwrap_or(0.0);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Folding spectra onto the 12 pitch classes, and matching the result against key and chord
+//! templates.
+//!
+//! A [`Chromagram`] sums the power of every bin into the pitch class nearest to its frequency,
+//! giving a chroma vector with C at index 0. [`match_template`] then finds the root whose rotation
+//! of a major and a minor template correlates best with a chroma vector, e.g. with the
+//! Krumhansl-Kessler [key profiles](MAJOR_KEY_PROFILE) or [triads](MAJOR_TRIAD).
+
+use alloc::vec::Vec;
+
+use crate::core::{Complex32, math};
+
+/// The number of pitch classes.
+pub const PITCH_CLASSES: usize = 12;
+
+/// The names of the pitch classes, starting at C.
+pub const PITCH_CLASS_NAMES: [&str; PITCH_CLASSES] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// The Krumhansl-Kessler probe-tone ratings of the pitch classes in a major key, from the tonic.
+pub const MAJOR_KEY_PROFILE: [f32; PITCH_CLASSES] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+
+/// The Krumhansl-Kessler probe-tone ratings of the pitch classes in a minor key, from the tonic.
+pub const MINOR_KEY_PROFILE: [f32; PITCH_CLASSES] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// The pitch classes of a major triad, from the root.
+pub const MAJOR_TRIAD: [f32; PITCH_CLASSES] = [1., 0., 0., 0., 1., 0., 0., 1., 0., 0., 0., 0.];
+
+/// The pitch classes of a minor triad, from the root.
+pub const MINOR_TRIAD: [f32; PITCH_CLASSES] = [1., 0., 0., 1., 0., 0., 0., 1., 0., 0., 0., 0.];
+
+/// Converts a frequency in Hz to a (fractional) MIDI note number, with A4 at `tuning_hz`.
+pub fn hz_to_midi(hz: f32, tuning_hz: f32) -> f32 {
+    69.0 + 12.0 * math::log2f(hz / tuning_hz)
+}
+
+/// Converts a (fractional) MIDI note number to a frequency in Hz, with A4 at `tuning_hz`.
+pub fn midi_to_hz(note: f32, tuning_hz: f32) -> f32 {
+    tuning_hz * math::powf(2.0, (note - 69.0) / 12.0)
+}
+
+/// Maps the bins of a real spectrum to pitch classes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chromagram {
+    // the first bin mapped, and the pitch class of every bin from it on
+    start: usize,
+    classes: Vec<u8>,
+}
+
+impl Chromagram {
+    /// Maps the bins between `min_hz` and `max_hz` to the nearest pitch class, with A4 at
+    /// `tuning_hz`. Bins below `min_hz` are too coarse to tell neighboring pitch classes apart,
+    /// so it should be high enough for the FFT length, e.g. a few bins.
+    pub fn new(
+        min_hz: f32,
+        max_hz: f32,
+        tuning_hz: f32,
+        sample_rate: f32,
+        fft_length: usize,
+    ) -> Self {
+        let num_bins = fft_length / 2 + 1;
+        let bin_hz = sample_rate / fft_length as f32;
+        let start = ((min_hz / bin_hz) as usize).clamp(1, num_bins);
+        let end = ((max_hz / bin_hz) as usize + 1).clamp(start, num_bins);
+        let classes = (start..end)
+            .map(|bin| {
+                // above 8 Hz the note number is positive, so truncating rounds it
+                let note = hz_to_midi(bin as f32 * bin_hz, tuning_hz).max(0.0) + 0.5;
+                (note as usize % PITCH_CLASSES) as u8
+            })
+            .collect();
+        Self { start, classes }
+    }
+
+    /// Writes the power of every pitch class in `spectrum` to `out`.
+    pub fn apply(&self, spectrum: &[Complex32], out: &mut [f32; PITCH_CLASSES]) {
+        out.fill(0.0);
+        for (value, &class) in spectrum[self.start..].iter().zip(&self.classes) {
+            out[class as usize] += value.norm_sqr();
+        }
+    }
+
+    /// Returns the pitch class of `bin`, or `None` if it lies outside the mapped range.
+    pub fn class_of(&self, bin: usize) -> Option<usize> {
+        let index = bin.checked_sub(self.start)?;
+        self.classes.get(index).map(|&class| class as usize)
+    }
+}
+
+/// Returns the Pearson correlation of `chroma` with `template` rotated to start at `root`, between
+/// -1 and 1 (0 if either is constant).
+pub fn correlate(
+    chroma: &[f32; PITCH_CLASSES],
+    template: &[f32; PITCH_CLASSES],
+    root: usize,
+) -> f32 {
+    let mean = |values: &[f32; PITCH_CLASSES]| values.iter().sum::<f32>() / PITCH_CLASSES as f32;
+    let (chroma_mean, template_mean) = (mean(chroma), mean(template));
+
+    let (mut covariance, mut chroma_variance, mut template_variance) = (0.0, 0.0, 0.0);
+    for (class, &value) in chroma.iter().enumerate() {
+        let a = value - chroma_mean;
+        let b = template[(class + PITCH_CLASSES - root % PITCH_CLASSES) % PITCH_CLASSES]
+            - template_mean;
+        covariance += a * b;
+        chroma_variance += a * a;
+        template_variance += b * b;
+    }
+    let denominator = math::sqrtf(chroma_variance * template_variance);
+    if denominator > 0.0 {
+        covariance / denominator
+    } else {
+        0.0
+    }
+}
+
+/// Finds the best match for `chroma` among all 24 rotations of a major and a minor template.
+///
+/// Returns the index of the match, the root for major matches (0 to 11) and 12 plus the root for
+/// minor matches, and its correlation. See [`template_name`] for naming the index.
+pub fn match_template(
+    chroma: &[f32; PITCH_CLASSES],
+    major: &[f32; PITCH_CLASSES],
+    minor: &[f32; PITCH_CLASSES],
+) -> (usize, f32) {
+    let mut best = (0, f32::NEG_INFINITY);
+    for (offset, template) in [(0, major), (PITCH_CLASSES, minor)] {
+        for root in 0..PITCH_CLASSES {
+            let correlation = correlate(chroma, template, root);
+            if correlation > best.1 {
+                best = (offset + root, correlation);
+            }
+        }
+    }
+    best
+}
+
+/// Returns the name of a [`match_template`] index, e.g. `"C"` or `"F#m"`.
+pub fn template_name(index: usize) -> Option<&'static str> {
+    const NAMES: [&str; 2 * PITCH_CLASSES] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B", "Cm", "C#m", "Dm", "D#m",
+        "Em", "Fm", "F#m", "Gm", "G#m", "Am", "A#m", "Bm",
+    ];
+    NAMES.get(index).copied()
+}
+
+/// Returns a chroma vector with every pitch class of `classes` set to 1, e.g. for building
+/// templates of other chords.
+pub fn chroma_of(classes: &[usize]) -> [f32; PITCH_CLASSES] {
+    let mut chroma = [0.0; PITCH_CLASSES];
+    for &class in classes {
+        chroma[class % PITCH_CLASSES] = 1.0;
+    }
+    chroma
+}
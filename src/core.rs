@@ -0,0 +1,1046 @@
+//! The STFT engine underlying [`FftGraph`](crate::graph::FftGraph), usable without raug.
+//!
+//! This module provides window generation, the input ring buffer that slices a stream into
+//! windowed frames, and the overlap-add stage that turns processed frames back into a stream.
+//! [`StftPipeline`] ties them together with a forward and inverse real FFT for simple
+//! single-channel spectral processing.
+//!
+//! Everything in this module only requires `alloc`. Without the `std` feature, FFTs are supplied
+//! through the [`FrameTransform`] trait; with it, [`RealFftTransform`] plans them with realfft.
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::{
+    FftError,
+    state::{StateReader, StateValue, StateWriter},
+};
+
+pub use num_complex::Complex32;
+
+pub(crate) mod math {
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn cos(x: f64) -> f64 {
+        x.cos()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn cos(x: f64) -> f64 {
+        libm::cos(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn sin(x: f64) -> f64 {
+        x.sin()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn sin(x: f64) -> f64 {
+        libm::sin(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn sqrtf(x: f32) -> f32 {
+        x.sqrt()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn sqrtf(x: f32) -> f32 {
+        libm::sqrtf(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn expf(x: f32) -> f32 {
+        x.exp()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn expf(x: f32) -> f32 {
+        libm::expf(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn ceilf(x: f32) -> f32 {
+        x.ceil()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn ceilf(x: f32) -> f32 {
+        libm::ceilf(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn log10f(x: f32) -> f32 {
+        x.log10()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn log10f(x: f32) -> f32 {
+        libm::log10f(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn lnf(x: f32) -> f32 {
+        x.ln()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn lnf(x: f32) -> f32 {
+        libm::logf(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn roundf(x: f32) -> f32 {
+        x.round()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn roundf(x: f32) -> f32 {
+        libm::roundf(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn log2f(x: f32) -> f32 {
+        x.log2()
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn log2f(x: f32) -> f32 {
+        libm::log2f(x)
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn powf(x: f32, y: f32) -> f32 {
+        x.powf(y)
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    pub fn powf(x: f32, y: f32) -> f32 {
+        libm::powf(x, y)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowFunction {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+    Nuttall,
+    Triangular,
+}
+
+impl WindowFunction {
+    /// Looks up a window function by its lowercase name (e.g. `"hann"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rectangular" => Some(Self::Rectangular),
+            "hann" => Some(Self::Hann),
+            "hamming" => Some(Self::Hamming),
+            "blackman" => Some(Self::Blackman),
+            "nuttall" => Some(Self::Nuttall),
+            "triangular" => Some(Self::Triangular),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rectangular => "rectangular",
+            Self::Hann => "hann",
+            Self::Hamming => "hamming",
+            Self::Blackman => "blackman",
+            Self::Nuttall => "nuttall",
+            Self::Triangular => "triangular",
+        }
+    }
+
+    /// Returns the smallest overlap factor (`fft_length / hop_length`) at which analysis and
+    /// synthesis with this window reconstruct the input, or `None` if they never do closely.
+    pub fn min_overlap(&self) -> Option<usize> {
+        match self {
+            Self::Rectangular => Some(1),
+            Self::Hann | Self::Hamming => Some(4),
+            Self::Blackman | Self::Nuttall => Some(8),
+            Self::Triangular => None,
+        }
+    }
+
+    /// Returns the window of `length` samples, generating it only the first time it's requested
+    /// in this process. Later calls share the same allocation.
+    #[cfg(feature = "std")]
+    pub fn cached(&self, length: usize) -> std::sync::Arc<[f32]> {
+        static CACHE: WindowCache<(WindowFunction, usize)> = WindowCache::new();
+        CACHE.get_or_insert((*self, length), || self.generate(length))
+    }
+
+    pub fn generate(&self, length: usize) -> Vec<f32> {
+        let mut buf = vec![1.0; length];
+        self.apply(&mut buf);
+        buf
+    }
+
+    pub fn apply(&self, buf: &mut [f32]) {
+        let size = buf.len();
+        match self {
+            Self::Rectangular => {}
+            Self::Hann => cosine_window(buf, [0.5, 0.5, 0.0, 0.0]),
+            Self::Hamming => cosine_window(buf, [0.54, 0.46, 0.0, 0.0]),
+            Self::Blackman => cosine_window(buf, [0.35875, 0.48829, 0.14128, 0.01168]),
+            Self::Nuttall => cosine_window(buf, [0.355_768, 0.487_396, 0.144_232, 0.012_604]),
+            Self::Triangular => {
+                for (index, x) in buf.iter_mut().enumerate() {
+                    let y = 1.0
+                        - ((index as f64 - (size - 1) as f64 / 2.0) / (size as f64 / 2.0)).abs();
+                    *x *= y as f32;
+                }
+            }
+        }
+    }
+}
+
+/// Multiplies `buf` by a symmetric generalized cosine window with the given coefficients.
+fn cosine_window(buf: &mut [f32], [a, b, c, d]: [f64; 4]) {
+    let size = buf.len();
+    assert!(1 < size);
+    for (index, x) in buf.iter_mut().enumerate() {
+        let t = (core::f64::consts::PI * index as f64) / (size - 1) as f64;
+        let y = (a - b * math::cos(2.0 * t)) + (c * math::cos(4.0 * t) - d * math::cos(6.0 * t));
+        *x *= y as f32;
+    }
+}
+
+/// Checks that frames of `fft_length` samples can be taken every `hop_length` samples.
+pub fn validate_stft(fft_length: usize, hop_length: usize) -> Result<(), FftError> {
+    let reason = if fft_length == 0 {
+        "FFT length must be non-zero"
+    } else if hop_length == 0 {
+        "hop length must be non-zero"
+    } else if hop_length > fft_length {
+        "hop length must not exceed the FFT length"
+    } else {
+        return Ok(());
+    };
+    Err(FftError::InvalidStft {
+        fft_length,
+        hop_length,
+        reason,
+    })
+}
+
+/// Generates the window used for both analysis and synthesis by the STFT engine.
+///
+/// The window is centered around 0 and normalized so that windowing twice and overlap-adding
+/// frames every `hop_length` samples reconstructs the input.
+pub fn stft_window(window_fn: WindowFunction, fft_length: usize, hop_length: usize) -> Vec<f32> {
+    let mut window = window_fn.generate(fft_length);
+
+    // center the window around 0
+    window.rotate_right(fft_length / 2);
+
+    let overlapping_frames = fft_length / hop_length;
+    let mut window_sum: f32 = window.iter().map(|x| x * x).sum();
+    window_sum *= overlapping_frames as f32;
+    assert_ne!(window_sum, 0.0);
+
+    for x in window.iter_mut() {
+        *x /= math::sqrtf(window_sum);
+    }
+
+    window
+}
+
+/// Returns the largest value the squared `window` reaches when overlap-added every `hop_length`
+/// samples, times the FFT length, i.e. the peak gain of analysis and synthesis with it through
+/// the unnormalized inverse FFT.
+///
+/// This is 1 for windows meeting the constant overlap-add condition at this hop, as normalized by
+/// [`stft_window`], and larger for those that ripple.
+pub fn window_sum_peak(window: &[f32], hop_length: usize) -> f32 {
+    let hop_length = hop_length.max(1);
+    let peak = (0..hop_length.min(window.len()))
+        .map(|offset| {
+            window[offset..]
+                .iter()
+                .step_by(hop_length)
+                .map(|x| x * x)
+                .sum::<f32>()
+        })
+        .fold(0.0, f32::max);
+    peak * window.len() as f32
+}
+
+/// How spectra are scaled relative to the unnormalized FFT of a frame windowed with the
+/// [`stft_window`].
+///
+/// Different consumers expect different conventions: effects don't care, measurements want a
+/// sinusoid to read its amplitude, and models are trained on a particular normalization. The
+/// inverse transform undoes the same scale, so the choice doesn't change the resynthesized audio.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpectralScaling {
+    /// The unnormalized FFT (the default). The builtins' level measurements assume this.
+    #[default]
+    None,
+    /// Divided by the FFT length, like a normalized forward transform.
+    InverseN,
+    /// Divided by the square root of the FFT length, like an orthonormal ("ortho") transform.
+    InverseSqrtN,
+    /// Divided by half the sum of the window, so a sinusoid centered on a bin reads its peak
+    /// amplitude there.
+    WindowCompensated,
+}
+
+impl SpectralScaling {
+    pub const ALL: [Self; 4] = [
+        Self::None,
+        Self::InverseN,
+        Self::InverseSqrtN,
+        Self::WindowCompensated,
+    ];
+
+    /// Looks up a scaling by its name (e.g. `"inverse_sqrt_n"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scaling| scaling.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::InverseN => "inverse_n",
+            Self::InverseSqrtN => "inverse_sqrt_n",
+            Self::WindowCompensated => "window_compensated",
+        }
+    }
+
+    /// Returns the factor applied to the spectra of frames windowed with `window`, whose length
+    /// is the FFT length.
+    pub fn factor(&self, window: &[f32]) -> f32 {
+        match self {
+            Self::None => 1.0,
+            Self::InverseN => 1.0 / window.len().max(1) as f32,
+            Self::InverseSqrtN => 1.0 / math::sqrtf(window.len().max(1) as f32),
+            Self::WindowCompensated => {
+                let sum: f32 = window.iter().sum();
+                if sum.abs() > 0.0 { 2.0 / sum } else { 1.0 }
+            }
+        }
+    }
+}
+
+/// Like [`stft_window`], but generates each combination of window function, FFT length and hop
+/// length only once per process.
+#[cfg(feature = "std")]
+pub fn cached_stft_window(
+    window_fn: WindowFunction,
+    fft_length: usize,
+    hop_length: usize,
+) -> std::sync::Arc<[f32]> {
+    static CACHE: WindowCache<(WindowFunction, usize, usize)> = WindowCache::new();
+    CACHE.get_or_insert((window_fn, fft_length, hop_length), || {
+        stft_window(window_fn, fft_length, hop_length)
+    })
+}
+
+/// A process-wide map of generated windows.
+#[cfg(feature = "std")]
+struct WindowCache<K>(
+    std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<K, std::sync::Arc<[f32]>>>>,
+);
+
+#[cfg(feature = "std")]
+impl<K: Eq + core::hash::Hash> WindowCache<K> {
+    const fn new() -> Self {
+        Self(std::sync::OnceLock::new())
+    }
+
+    fn get_or_insert(&self, key: K, generate: impl FnOnce() -> Vec<f32>) -> std::sync::Arc<[f32]> {
+        let cache = self.0.get_or_init(Default::default);
+        // a generated window is inserted whole, so a poisoned lock is still usable
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .entry(key)
+            .or_insert_with(|| generate().into())
+            .clone()
+    }
+}
+
+/// How the first frames of a stream are formed, before a whole frame of input has arrived.
+///
+/// By default, the first frame starts at the first sample. The other modes center every frame on
+/// a multiple of the hop length like librosa's `center=True`, the first one on the first sample,
+/// by padding the start of the stream with half a frame. They differ in what they pad with, which
+/// changes what the first hops analyze.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaddingMode {
+    /// No padding; the first frame starts at the first sample.
+    #[default]
+    None,
+    /// Pads with zeros (librosa's `pad_mode="constant"`).
+    Zero,
+    /// Pads with the stream mirrored around its first sample, without repeating it (librosa's
+    /// `pad_mode="reflect"`).
+    Reflect,
+    /// Pads with copies of the first sample (librosa's `pad_mode="edge"`).
+    Replicate,
+}
+
+impl PaddingMode {
+    pub const ALL: [Self; 4] = [Self::None, Self::Zero, Self::Reflect, Self::Replicate];
+
+    /// Looks up a padding mode by its lowercase name (e.g. `"reflect"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Zero => "zero",
+            Self::Reflect => "reflect",
+            Self::Replicate => "replicate",
+        }
+    }
+
+    /// Returns the number of samples padded ahead of a stream analyzed in frames of
+    /// `fft_length` samples.
+    pub fn padding(&self, fft_length: usize) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Zero | Self::Reflect | Self::Replicate => fft_length / 2,
+        }
+    }
+}
+
+/// Buffers an incoming stream and slices it into overlapping windowed frames.
+///
+/// The start of the stream is padded according to the [`PaddingMode`], see
+/// [`set_padding`](Self::set_padding).
+#[derive(Debug, Clone, Default)]
+pub struct FrameInput {
+    ring_buffer: VecDeque<f32>,
+    padding: PaddingMode,
+    padding_len: usize,
+    // whether the start of the stream still has to be padded
+    pending_padding: bool,
+    // zeros ahead of the padding, see `set_lead`
+    lead: usize,
+}
+
+impl FrameInput {
+    pub fn new(fft_length: usize) -> Self {
+        Self {
+            ring_buffer: VecDeque::with_capacity(fft_length * 2),
+            padding: PaddingMode::None,
+            padding_len: 0,
+            pending_padding: false,
+            lead: 0,
+        }
+    }
+
+    pub fn padding(&self) -> PaddingMode {
+        self.padding
+    }
+
+    /// Sets how the start of the stream is padded for frames of `fft_length` samples. Takes
+    /// effect when the buffer is next [cleared](Self::clear), which starts a new stream.
+    pub fn set_padding(&mut self, padding: PaddingMode, fft_length: usize) {
+        self.padding = padding;
+        self.padding_len = padding.padding(fft_length);
+    }
+
+    /// Sets the number of zeros every new stream starts with, ahead of its padding, e.g. so an
+    /// overlap-save engine finishes the first samples of the stream with its first frame. Takes
+    /// effect when the buffer is next [cleared](Self::clear).
+    pub fn set_lead(&mut self, len: usize) {
+        self.lead = len;
+    }
+
+    /// Returns the number of buffered samples.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ring_buffer.len()
+    }
+
+    /// Makes room for `additional` more samples plus the padding and lead of a new stream, so
+    /// pushing them doesn't allocate.
+    pub fn reserve(&mut self, additional: usize) {
+        self.ring_buffer
+            .reserve(additional + self.padding_len + self.lead);
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ring_buffer.is_empty()
+    }
+
+    /// Appends samples to the end of the buffer.
+    #[inline]
+    pub fn push(&mut self, samples: &[f32]) {
+        self.ring_buffer.extend(samples);
+        if self.pending_padding {
+            self.pad_start();
+        }
+    }
+
+    /// Writes the oldest `frame.len()` buffered samples, multiplied by `window`, to `frame`.
+    #[inline]
+    pub fn read_windowed(&self, window: &[f32], frame: &mut [f32]) {
+        for (i, (out, w)) in frame.iter_mut().zip(window).enumerate() {
+            *out = self.ring_buffer[i] * w;
+        }
+    }
+
+    /// Writes the oldest `frame.len()` buffered samples to `frame`, unwindowed.
+    #[inline]
+    pub fn read(&self, frame: &mut [f32]) {
+        for (out, sample) in frame.iter_mut().zip(&self.ring_buffer) {
+            *out = *sample;
+        }
+    }
+
+    /// Weights the oldest `prototype.len()` buffered samples by `prototype` and folds them into
+    /// `frame`, for a WOLA filterbank. The center of the prototype folds onto the start of the
+    /// frame.
+    #[inline]
+    pub fn read_folded(&self, prototype: &[f32], frame: &mut [f32]) {
+        frame.fill(0.0);
+        let offset = fold_offset(frame.len(), prototype.len());
+        for (i, (sample, p)) in self.ring_buffer.iter().zip(prototype).enumerate() {
+            frame[(i + offset) % frame.len()] += sample * p;
+        }
+    }
+
+    /// Discards the oldest `hop_length` samples.
+    #[inline]
+    pub fn advance(&mut self, hop_length: usize) {
+        self.ring_buffer.drain(..hop_length);
+    }
+
+    /// Discards the buffered samples, starting a new stream.
+    pub fn clear(&mut self) {
+        self.ring_buffer.clear();
+        self.pending_padding = self.padding_len > 0;
+        self.pad_start();
+    }
+
+    /// Pads the start of the stream once enough of it has arrived. Reflecting needs the first
+    /// `padding_len + 1` samples, which always arrive before the first frame is complete.
+    fn pad_start(&mut self) {
+        let len = self.padding_len;
+        match self.padding {
+            PaddingMode::None => {}
+            PaddingMode::Zero => {
+                for _ in 0..len {
+                    self.ring_buffer.push_front(0.0);
+                }
+            }
+            PaddingMode::Reflect => {
+                if self.ring_buffer.len() <= len {
+                    return;
+                }
+                // pushing the samples after the first to the front in order mirrors them
+                for i in 1..=len {
+                    let sample = self.ring_buffer[2 * i - 1];
+                    self.ring_buffer.push_front(sample);
+                }
+            }
+            PaddingMode::Replicate => {
+                let Some(&first) = self.ring_buffer.front() else {
+                    return;
+                };
+                for _ in 0..len {
+                    self.ring_buffer.push_front(first);
+                }
+            }
+        }
+        for _ in 0..self.lead {
+            self.ring_buffer.push_front(0.0);
+        }
+        self.pending_padding = false;
+    }
+}
+
+impl StateValue for FrameInput {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.ring_buffer);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        // the stream continues where the state was saved
+        self.pending_padding = false;
+        state.read(&mut self.ring_buffer)
+    }
+}
+
+/// Returns where the first of `span` samples folds onto a frame of `frame_length`, so that the
+/// middle of the span lands on the start of the frame.
+fn fold_offset(frame_length: usize, span: usize) -> usize {
+    (frame_length - span / 2 % frame_length) % frame_length
+}
+
+/// Overlap-adds processed frames back into a stream.
+#[derive(Debug, Clone, Default)]
+pub struct OverlapAdd {
+    ring_buffer: VecDeque<f32>,
+    overlap_buffer: VecDeque<f32>,
+    // finished samples still to be discarded, see `skip`
+    skip: usize,
+}
+
+impl OverlapAdd {
+    pub fn new(fft_length: usize) -> Self {
+        Self {
+            ring_buffer: VecDeque::with_capacity(fft_length),
+            overlap_buffer: vec![0.0; fft_length].into(),
+            skip: 0,
+        }
+    }
+
+    /// Returns the number of finished samples ready to be read.
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.ring_buffer.len()
+    }
+
+    /// Adds a frame, multiplied by `window`, and finishes the next `hop_length` samples.
+    #[inline]
+    pub fn add_frame(&mut self, frame: &[f32], window: &[f32], hop_length: usize) {
+        // overlap-add
+        for (i, (x, w)) in frame.iter().zip(window).enumerate() {
+            self.overlap_buffer[i] += x * w;
+        }
+
+        self.finish(hop_length);
+    }
+
+    /// Moves the next `hop_length` samples of the overlap buffer to the finished ones.
+    #[inline]
+    fn finish(&mut self, hop_length: usize) {
+        // advance time for the output
+        let skipped = self.skip.min(hop_length);
+        self.skip -= skipped;
+        self.ring_buffer
+            .extend(self.overlap_buffer.drain(..hop_length).skip(skipped));
+
+        for _ in 0..hop_length {
+            // zero out the overlap buffer for the next iteration
+            self.overlap_buffer.push_back(0.0);
+        }
+    }
+
+    /// Resizes the overlap buffer for frames of `len` samples, clearing the samples still to be
+    /// finished.
+    pub fn set_frame_length(&mut self, len: usize) {
+        self.overlap_buffer.clear();
+        self.overlap_buffer.resize(len, 0.0);
+    }
+
+    /// Extends a frame periodically to the length of `prototype`, weights it by `prototype` and
+    /// adds it, the inverse of [`FrameInput::read_folded`], then finishes the next `hop_length`
+    /// samples. The buffer must have room for the prototype, see
+    /// [`set_frame_length`](Self::set_frame_length).
+    #[inline]
+    pub fn add_unfolded_frame(&mut self, frame: &[f32], prototype: &[f32], hop_length: usize) {
+        let offset = fold_offset(frame.len(), prototype.len());
+        for (i, p) in prototype.iter().enumerate() {
+            self.overlap_buffer[i] += frame[(i + offset) % frame.len()] * p;
+        }
+        self.finish(hop_length);
+    }
+
+    /// Finishes the next `hop_length` samples from the last `hop_length` samples of a frame,
+    /// multiplied by `scale`, for overlap-save. The rest of the frame is discarded, since it
+    /// wrapped around in the circular convolution.
+    #[inline]
+    pub fn save_frame(&mut self, frame: &[f32], scale: f32, hop_length: usize) {
+        let skipped = self.skip.min(hop_length);
+        self.skip -= skipped;
+        let start = frame.len().saturating_sub(hop_length) + skipped;
+        self.ring_buffer
+            .extend(frame[start..].iter().map(|sample| sample * scale));
+    }
+
+    /// Pops the oldest finished sample.
+    #[inline]
+    pub fn pop(&mut self) -> Option<f32> {
+        self.ring_buffer.pop_front()
+    }
+
+    /// Fills `out` with as many finished samples as are available and zeros after them, returning
+    /// the number of samples read.
+    #[inline]
+    pub fn read_available(&mut self, out: &mut [f32]) -> usize {
+        let len = out.len().min(self.ring_buffer.len());
+        for (out, sample) in out.iter_mut().zip(self.ring_buffer.drain(..len)) {
+            *out = sample;
+        }
+        out[len..].fill(0.0);
+        len
+    }
+
+    /// Fills `out` with finished samples if enough are available, returning whether it did.
+    #[inline]
+    pub fn read(&mut self, out: &mut [f32]) -> bool {
+        let len = out.len();
+        if self.ring_buffer.len() < len {
+            return false;
+        }
+        for (out, sample) in out.iter_mut().zip(self.ring_buffer.drain(..len)) {
+            *out = sample;
+        }
+        true
+    }
+
+    /// Queues `len` zeros ahead of the samples still to be finished, delaying the output by `len`
+    /// samples.
+    pub fn delay(&mut self, len: usize) {
+        self.ring_buffer.extend(core::iter::repeat_n(0.0, len));
+    }
+
+    /// Discards the next `len` samples to be finished, e.g. those resynthesized from the padding
+    /// of a [`FrameInput`].
+    pub fn skip(&mut self, len: usize) {
+        self.skip += len;
+    }
+
+    /// Makes room for `additional` more finished samples, so that reading fewer than that many
+    /// behind the writes never allocates.
+    pub fn reserve(&mut self, additional: usize) {
+        self.ring_buffer.reserve(additional);
+    }
+
+    pub fn clear(&mut self) {
+        self.ring_buffer.clear();
+        self.overlap_buffer.iter_mut().for_each(|x| *x = 0.0);
+        self.skip = 0;
+    }
+}
+
+impl StateValue for OverlapAdd {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.ring_buffer);
+        state.write(&self.overlap_buffer);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.ring_buffer)?;
+        state.read(&mut self.overlap_buffer)
+    }
+}
+
+/// A forward and inverse real FFT of a fixed length, used by [`StftPipeline`].
+///
+/// Implementations must do all of their allocation and planning up front, since the transforms
+/// are called from the processing loop.
+pub trait FrameTransform: Send {
+    /// Returns the length of the transform's time-domain frames.
+    fn fft_length(&self) -> usize;
+
+    /// Transforms `time_domain` (of length `fft_length`) into `spectrum` (of length
+    /// `fft_length / 2 + 1`). The contents of `time_domain` may be overwritten.
+    fn forward(
+        &mut self,
+        time_domain: &mut [f32],
+        spectrum: &mut [Complex32],
+    ) -> Result<(), FftError>;
+
+    /// Transforms `spectrum` back into `time_domain` without normalization. The contents of
+    /// `spectrum` may be overwritten.
+    fn inverse(
+        &mut self,
+        spectrum: &mut [Complex32],
+        time_domain: &mut [f32],
+    ) -> Result<(), FftError>;
+}
+
+/// A shared cache of realfft plans, and of rustfft plans for complex transforms.
+///
+/// Planning an FFT is expensive, and every transform of the same length can use the same plan, so
+/// everything in this crate plans through [`FftPlanner::global`] unless told otherwise. Clones of a
+/// planner share its cache.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct FftPlanner {
+    real: std::sync::Arc<std::sync::Mutex<realfft::RealFftPlanner<f32>>>,
+    complex: std::sync::Arc<std::sync::Mutex<rustfft::FftPlanner<f32>>>,
+}
+
+#[cfg(feature = "std")]
+impl FftPlanner {
+    /// Creates a planner with an empty cache.
+    pub fn new() -> Self {
+        Self {
+            real: std::sync::Arc::new(std::sync::Mutex::new(realfft::RealFftPlanner::new())),
+            complex: std::sync::Arc::new(std::sync::Mutex::new(rustfft::FftPlanner::new())),
+        }
+    }
+
+    /// Returns the process-wide planner.
+    pub fn global() -> &'static FftPlanner {
+        static GLOBAL: std::sync::OnceLock<FftPlanner> = std::sync::OnceLock::new();
+        GLOBAL.get_or_init(FftPlanner::new)
+    }
+
+    /// Returns the forward plan for `fft_length`, planning it if it isn't cached yet.
+    pub fn plan_forward(
+        &self,
+        fft_length: usize,
+    ) -> std::sync::Arc<dyn realfft::RealToComplex<f32>> {
+        self.lock().plan_fft_forward(fft_length)
+    }
+
+    /// Returns the inverse plan for `fft_length`, planning it if it isn't cached yet.
+    pub fn plan_inverse(
+        &self,
+        fft_length: usize,
+    ) -> std::sync::Arc<dyn realfft::ComplexToReal<f32>> {
+        self.lock().plan_fft_inverse(fft_length)
+    }
+
+    /// Returns the forward plan of a complex (two-sided) FFT of `fft_length`, planning it if it
+    /// isn't cached yet.
+    pub fn plan_complex_forward(&self, fft_length: usize) -> std::sync::Arc<dyn rustfft::Fft<f32>> {
+        self.lock_complex().plan_fft_forward(fft_length)
+    }
+
+    /// Returns the inverse plan of a complex (two-sided) FFT of `fft_length`, planning it if it
+    /// isn't cached yet.
+    pub fn plan_complex_inverse(&self, fft_length: usize) -> std::sync::Arc<dyn rustfft::Fft<f32>> {
+        self.lock_complex().plan_fft_inverse(fft_length)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, realfft::RealFftPlanner<f32>> {
+        // planning can't leave the cache inconsistent, so a poisoned lock is still usable
+        self.real.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_complex(&self) -> std::sync::MutexGuard<'_, rustfft::FftPlanner<f32>> {
+        self.complex.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for FftPlanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`FrameTransform`] backed by realfft, planned when it is created.
+#[cfg(feature = "std")]
+pub struct RealFftTransform {
+    forward: std::sync::Arc<dyn realfft::RealToComplex<f32>>,
+    inverse: std::sync::Arc<dyn realfft::ComplexToReal<f32>>,
+    scratch: Vec<Complex32>,
+}
+
+#[cfg(feature = "std")]
+impl RealFftTransform {
+    pub fn new(fft_length: usize) -> Self {
+        Self::with_planner(FftPlanner::global(), fft_length)
+    }
+
+    pub fn with_planner(planner: &FftPlanner, fft_length: usize) -> Self {
+        let forward = planner.plan_forward(fft_length);
+        let inverse = planner.plan_inverse(fft_length);
+        let scratch_length = forward.get_scratch_len().max(inverse.get_scratch_len());
+        Self {
+            forward,
+            inverse,
+            scratch: vec![Complex32::ZERO; scratch_length],
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl FrameTransform for RealFftTransform {
+    fn fft_length(&self) -> usize {
+        self.forward.len()
+    }
+
+    fn forward(
+        &mut self,
+        time_domain: &mut [f32],
+        spectrum: &mut [Complex32],
+    ) -> Result<(), FftError> {
+        self.forward
+            .process_with_scratch(time_domain, spectrum, &mut self.scratch)?;
+        Ok(())
+    }
+
+    fn inverse(
+        &mut self,
+        spectrum: &mut [Complex32],
+        time_domain: &mut [f32],
+    ) -> Result<(), FftError> {
+        self.inverse
+            .process_with_scratch(spectrum, time_domain, &mut self.scratch)?;
+        Ok(())
+    }
+}
+
+/// A single-channel STFT analysis/resynthesis pipeline.
+///
+/// Each call to [`process`](StftPipeline::process) buffers the input, runs the given closure on
+/// the spectrum of every complete frame, and writes the resynthesized output.
+///
+/// All buffers are allocated and the transform is planned on construction, so processing never
+/// allocates once the input buffer has grown to its steady-state size.
+#[cfg(feature = "std")]
+pub struct StftPipeline<T: FrameTransform = RealFftTransform> {
+    fft_length: usize,
+    hop_length: usize,
+    window: Vec<f32>,
+    input: FrameInput,
+    output: OverlapAdd,
+    transform: T,
+    time_domain: Vec<f32>,
+    spectrum: Vec<Complex32>,
+}
+
+/// A single-channel STFT analysis/resynthesis pipeline.
+///
+/// Each call to [`process`](StftPipeline::process) buffers the input, runs the given closure on
+/// the spectrum of every complete frame, and writes the resynthesized output.
+///
+/// All buffers are allocated on construction, so processing never allocates once the input
+/// buffer has grown to its steady-state size.
+#[cfg(not(feature = "std"))]
+pub struct StftPipeline<T: FrameTransform> {
+    fft_length: usize,
+    hop_length: usize,
+    window: Vec<f32>,
+    input: FrameInput,
+    output: OverlapAdd,
+    transform: T,
+    time_domain: Vec<f32>,
+    spectrum: Vec<Complex32>,
+}
+
+#[cfg(feature = "std")]
+impl StftPipeline {
+    /// Creates a pipeline, or returns [`FftError::InvalidStft`] if frames of `fft_length` samples
+    /// can't be taken every `hop_length` samples.
+    pub fn new(
+        fft_length: usize,
+        hop_length: usize,
+        window_fn: WindowFunction,
+    ) -> Result<Self, FftError> {
+        validate_stft(fft_length, hop_length)?;
+        Self::with_transform(RealFftTransform::new(fft_length), hop_length, window_fn)
+    }
+}
+
+impl<T: FrameTransform> StftPipeline<T> {
+    /// Creates a pipeline around an already planned transform, or returns
+    /// [`FftError::InvalidStft`] if frames of its length can't be taken every `hop_length` samples.
+    pub fn with_transform(
+        transform: T,
+        hop_length: usize,
+        window_fn: WindowFunction,
+    ) -> Result<Self, FftError> {
+        let fft_length = transform.fft_length();
+        validate_stft(fft_length, hop_length)?;
+        Ok(Self {
+            fft_length,
+            hop_length,
+            window: stft_window(window_fn, fft_length, hop_length),
+            input: FrameInput::new(fft_length),
+            output: OverlapAdd::new(fft_length),
+            transform,
+            time_domain: vec![0.0; fft_length],
+            spectrum: vec![Complex32::ZERO; fft_length / 2 + 1],
+        })
+    }
+
+    pub fn fft_length(&self) -> usize {
+        self.fft_length
+    }
+
+    pub fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+
+    /// Returns the delay in samples between the input and the output, assuming blocks of
+    /// `hop_length` samples.
+    pub fn latency_samples(&self) -> usize {
+        self.fft_length - self.hop_length
+    }
+
+    pub fn padding_mode(&self) -> PaddingMode {
+        self.input.padding()
+    }
+
+    /// Sets how the start of the stream is padded (none by default), taking effect on the next
+    /// [`reset`](Self::reset). The resynthesized padding is discarded, so the output doesn't
+    /// move.
+    pub fn set_padding_mode(&mut self, padding: PaddingMode) {
+        self.input.set_padding(padding, self.fft_length);
+    }
+
+    /// Clears all buffered input and output.
+    pub fn reset(&mut self) {
+        self.input.clear();
+        self.output.clear();
+        self.output
+            .skip(self.input.padding().padding(self.fft_length));
+    }
+
+    /// Processes a block of samples, calling `f` on the spectrum of every complete frame.
+    ///
+    /// Returns the number of resynthesized samples written to the start of `output`. If fewer are
+    /// available than `output` holds, e.g. before the first frame is complete, the rest of it is
+    /// zero-filled.
+    pub fn process(
+        &mut self,
+        input: &[f32],
+        output: &mut [f32],
+        mut f: impl FnMut(&mut [Complex32]),
+    ) -> Result<usize, FftError> {
+        self.input.push(input);
+
+        while self.input.len() >= self.fft_length {
+            self.input
+                .read_windowed(&self.window, &mut self.time_domain);
+            self.input.advance(self.hop_length);
+
+            self.transform
+                .forward(&mut self.time_domain, &mut self.spectrum)?;
+
+            f(&mut self.spectrum);
+
+            // the imaginary parts of DC and Nyquist must be zero for the inverse transform
+            self.spectrum[0].im = 0.0;
+            let nyquist = self.spectrum.len() - 1;
+            self.spectrum[nyquist].im = 0.0;
+
+            self.transform
+                .inverse(&mut self.spectrum, &mut self.time_domain)?;
+
+            self.output
+                .add_frame(&self.time_domain, &self.window, self.hop_length);
+        }
+
+        Ok(self.output.read_available(output))
+    }
+}
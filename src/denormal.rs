@@ -0,0 +1,154 @@
+//! Protection against denormal (subnormal) floats.
+//!
+//! State that decays towards zero, such as feedback paths and frame histories, eventually reaches
+//! the subnormal range, where arithmetic on x86 is many times slower. An
+//! [`FftGraph`](crate::graph::FftGraph) with denormal protection enabled holds a
+//! [`DenormalGuard`] while processing, which makes the CPU flush subnormals to zero where the
+//! architecture supports it. Processors with feedback should additionally flush their state with
+//! [`flush_denormals`] (or [`flush_real_denormals`] for envelopes and averages) when
+//! [`FrameInfo::flush_denormals`](crate::processor::FrameInfo) is set, which covers the remaining
+//! architectures.
+
+use crate::core::Complex32;
+
+/// Returns zero if `x` is subnormal, and `x` otherwise.
+#[inline]
+pub fn flush_denormal(x: f32) -> f32 {
+    if x.is_subnormal() { 0.0 } else { x }
+}
+
+/// Flushes the subnormal values of `values` to zero.
+#[inline]
+pub fn flush_real_denormals(values: &mut [f32]) {
+    for value in values {
+        *value = flush_denormal(*value);
+    }
+}
+
+/// Flushes the subnormal components of `values` to zero.
+#[inline]
+pub fn flush_denormals(values: &mut [Complex32]) {
+    for value in values {
+        value.re = flush_denormal(value.re);
+        value.im = flush_denormal(value.im);
+    }
+}
+
+/// Enables flush-to-zero (and denormals-are-zero, on x86) for the current thread until dropped,
+/// restoring the previous floating point mode afterwards.
+///
+/// This is a no-op on architectures other than x86 with SSE and AArch64.
+pub struct DenormalGuard {
+    previous: Option<u64>,
+}
+
+impl DenormalGuard {
+    /// Whether the guard has any effect on this architecture.
+    pub const SUPPORTED: bool = cfg!(any(
+        all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "sse"
+        ),
+        target_arch = "aarch64"
+    ));
+
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            previous: arch::enable_flush_to_zero(),
+        }
+    }
+}
+
+impl Drop for DenormalGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous {
+            arch::restore(previous);
+        }
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse"
+))]
+mod arch {
+    use core::arch::asm;
+
+    const FLUSH_TO_ZERO: u32 = 1 << 15;
+    const DENORMALS_ARE_ZERO: u32 = 1 << 6;
+
+    fn read_mxcsr() -> u32 {
+        let mut csr = 0u32;
+        // SAFETY: stmxcsr only stores the SSE control register to the given address
+        unsafe {
+            asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack, preserves_flags));
+        }
+        csr
+    }
+
+    fn write_mxcsr(csr: u32) {
+        // SAFETY: only the flush-to-zero and denormals-are-zero bits are ever changed
+        unsafe {
+            asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, readonly, preserves_flags));
+        }
+    }
+
+    pub(super) fn enable_flush_to_zero() -> Option<u64> {
+        let previous = read_mxcsr();
+        write_mxcsr(previous | FLUSH_TO_ZERO | DENORMALS_ARE_ZERO);
+        Some(previous as u64)
+    }
+
+    pub(super) fn restore(previous: u64) {
+        write_mxcsr(previous as u32);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use core::arch::asm;
+
+    const FLUSH_TO_ZERO: u64 = 1 << 24;
+
+    fn read_fpcr() -> u64 {
+        let fpcr: u64;
+        // SAFETY: reading the floating point control register has no side effects
+        unsafe {
+            asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags));
+        }
+        fpcr
+    }
+
+    fn write_fpcr(fpcr: u64) {
+        // SAFETY: only the flush-to-zero bit is ever changed
+        unsafe {
+            asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack, preserves_flags));
+        }
+    }
+
+    pub(super) fn enable_flush_to_zero() -> Option<u64> {
+        let previous = read_fpcr();
+        write_fpcr(previous | FLUSH_TO_ZERO);
+        Some(previous)
+    }
+
+    pub(super) fn restore(previous: u64) {
+        write_fpcr(previous);
+    }
+}
+
+#[cfg(not(any(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse"
+    ),
+    target_arch = "aarch64"
+)))]
+mod arch {
+    pub(super) fn enable_flush_to_zero() -> Option<u64> {
+        None
+    }
+
+    pub(super) fn restore(_previous: u64) {}
+}
@@ -0,0 +1,180 @@
+//! Reporting problems from the audio thread without allocating or locking.
+//!
+//! Formatting a log message allocates, and a logger may lock or write to a file, neither of which
+//! belongs on the audio thread. Graphs and processors [`report`] a [`RuntimeDiagnostic`] instead,
+//! a small `Copy` value queued in a process-wide ring of [`DIAGNOSTIC_CAPACITY`] entries. A
+//! control thread drains the ring and forwards its entries to the [`log`] crate:
+//!
+//! ```ignore
+//! std::thread::spawn(|| loop {
+//!     raug_fft::diagnostics::forward_to_log();
+//!     std::thread::sleep(std::time::Duration::from_millis(100));
+//! });
+//! ```
+//!
+//! Diagnostics reported while the ring is full are dropped and counted, and the count is logged
+//! with the next drained batch. (Problems with how a graph is put together are found before it
+//! runs instead, see [`FftGraph::diagnostics`](crate::graph::FftGraph::diagnostics).) A builtin
+//! detecting a problem while processing reports it the same way:
+//!
+//! ```ignore
+//! if !value.is_finite() {
+//!     diagnostics::report(RuntimeDiagnostic::InvalidValue { source: "my_processor" });
+//! }
+//! ```
+
+use std::{
+    fmt,
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+    },
+};
+
+use crate::handle::ParamName;
+
+/// The number of diagnostics that can be queued before new ones are dropped.
+pub const DIAGNOSTIC_CAPACITY: usize = 256;
+
+/// A problem reported from the audio thread, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuntimeDiagnostic {
+    /// An audio output of a graph was zero-filled because not enough had been resynthesized yet.
+    Underrun { output: usize, samples: usize },
+    /// A node produced NaN or infinite values on an output, which were replaced by zeros.
+    NonFinite { node: usize, output: usize },
+    /// A change queued through an [`FftGraphHandle`](crate::handle::FftGraphHandle) targeted a
+    /// node that doesn't exist.
+    MissingNode { node: usize },
+    /// A queued parameter change named a parameter the node doesn't have.
+    UnknownParam { node: usize, name: ParamName },
+    /// A processor limited its output, which peaked at `peak` before.
+    Clipping { source: &'static str, peak: f32 },
+    /// A processor met NaN or infinite values in its input or state.
+    InvalidValue { source: &'static str },
+    /// Any other message.
+    Message {
+        level: log::Level,
+        source: &'static str,
+        message: &'static str,
+    },
+}
+
+impl RuntimeDiagnostic {
+    /// Returns the level the diagnostic is logged at.
+    pub fn level(&self) -> log::Level {
+        match self {
+            Self::Underrun { .. } | Self::MissingNode { .. } | Self::UnknownParam { .. } => {
+                log::Level::Debug
+            }
+            Self::NonFinite { .. } | Self::Clipping { .. } | Self::InvalidValue { .. } => {
+                log::Level::Warn
+            }
+            Self::Message { level, .. } => *level,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Underrun { output, samples } => write!(
+                f,
+                "FftGraph underrun at output index {output}, {samples} samples short"
+            ),
+            Self::NonFinite { node, output } => write!(
+                f,
+                "node {node} produced NaN or infinite values on output {output}"
+            ),
+            Self::MissingNode { node } => {
+                write!(f, "ignoring a queued change to missing node {node}")
+            }
+            Self::UnknownParam { node, name } => {
+                write!(f, "node {node} has no parameter {:?}", name.as_str())
+            }
+            Self::Clipping { source, peak } => {
+                write!(f, "{source} clipped a peak of {peak}")
+            }
+            Self::InvalidValue { source } => {
+                write!(f, "{source} met NaN or infinite values")
+            }
+            Self::Message {
+                source, message, ..
+            } => write!(f, "{source}: {message}"),
+        }
+    }
+}
+
+struct Channel {
+    sender: SyncSender<RuntimeDiagnostic>,
+    // only ever locked by the thread draining the ring
+    receiver: Mutex<Receiver<RuntimeDiagnostic>>,
+    dropped: AtomicU64,
+}
+
+static CHANNEL: OnceLock<Channel> = OnceLock::new();
+
+fn channel() -> &'static Channel {
+    CHANNEL.get_or_init(|| {
+        let (sender, receiver) = sync_channel(DIAGNOSTIC_CAPACITY);
+        Channel {
+            sender,
+            receiver: Mutex::new(receiver),
+            dropped: AtomicU64::new(0),
+        }
+    })
+}
+
+/// Allocates the ring if it doesn't exist yet, so the first [`report`] doesn't. Graphs call this
+/// when they're allocated.
+pub fn init() {
+    channel();
+}
+
+/// Queues `diagnostic` without allocating or locking. Returns `false` if the ring is full, in
+/// which case the diagnostic is dropped and counted.
+pub fn report(diagnostic: RuntimeDiagnostic) -> bool {
+    let channel = channel();
+    match channel.sender.try_send(diagnostic) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+            channel.dropped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+/// Calls `f` with every queued diagnostic, oldest first, returning how many there were.
+///
+/// Meant for a control thread; concurrent calls take turns.
+pub fn drain(mut f: impl FnMut(RuntimeDiagnostic)) -> usize {
+    let channel = channel();
+    let receiver = channel
+        .receiver
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut count = 0;
+    while let Ok(diagnostic) = receiver.try_recv() {
+        f(diagnostic);
+        count += 1;
+    }
+    count
+}
+
+/// Returns the number of diagnostics dropped because the ring was full since the last call, and
+/// resets it.
+pub fn take_dropped() -> u64 {
+    channel().dropped.swap(0, Ordering::Relaxed)
+}
+
+/// Logs every queued diagnostic at its [`level`](RuntimeDiagnostic::level), and a warning if any
+/// were dropped. Returns how many were logged.
+pub fn forward_to_log() -> usize {
+    let count = drain(|diagnostic| log::log!(diagnostic.level(), "{diagnostic}"));
+    let dropped = take_dropped();
+    if dropped > 0 {
+        log::warn!("dropped {dropped} diagnostics reported while the queue was full");
+    }
+    count
+}
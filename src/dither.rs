@@ -0,0 +1,64 @@
+//! Dither and quantization of resynthesized audio to a fixed-point bit depth.
+//!
+//! Rounding a signal to the steps of a fixed-point format (e.g. 16-bit for a CD) turns the
+//! rounding error into distortion correlated with the signal. Adding triangular (TPDF) noise of
+//! one step either way before rounding decorrelates it, leaving a constant noise floor instead.
+//! [`Dither`] does both, and graphs can apply it to their audio outputs, see
+//! [`FftGraph::set_output_dither`](crate::graph::FftGraph::set_output_dither).
+
+use crate::{core::math, random::FrameRng};
+
+/// The bit depths a [`Dither`] can quantize to.
+pub const DITHER_BITS: core::ops::RangeInclusive<u32> = 2..=24;
+
+/// Applies TPDF dither and quantizes samples in `-1..1` to a fixed-point bit depth, see the
+/// [module docs](self).
+///
+/// Quantized samples are multiples of [`step`](Self::step) and clamped to the range of the
+/// format, so `sample / step` is the exact fixed-point value to write, e.g. to a 16-bit file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dither {
+    bits: u32,
+    rng: FrameRng,
+}
+
+impl Dither {
+    /// Creates a dither for `bits` bits (clamped to [`DITHER_BITS`]), drawing its noise from
+    /// `seed`.
+    pub fn new(bits: u32, seed: u64) -> Self {
+        Self {
+            bits: bits.clamp(*DITHER_BITS.start(), *DITHER_BITS.end()),
+            rng: FrameRng::new(seed),
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Returns the difference between two neighboring values of the format, `2^(1 - bits)`.
+    pub fn step(&self) -> f32 {
+        1.0 / (1u32 << (self.bits - 1)) as f32
+    }
+
+    /// Dithers and quantizes one sample.
+    #[inline]
+    pub fn process_sample(&mut self, sample: f32) -> f32 {
+        let step = self.step();
+        // the difference of two uniform values has a triangular distribution
+        let noise = (self.rng.next_unit() - self.rng.next_unit()) * step;
+        let quantized = math::roundf((sample + noise) / step) * step;
+        if quantized.is_nan() {
+            0.0
+        } else {
+            quantized.clamp(-1.0, 1.0 - step)
+        }
+    }
+
+    /// Dithers and quantizes `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process_sample(*sample);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+use crate::{
+    FftError, WindowFunction,
+    core::{Complex32, FftPlanner},
+};
+
+/// Designs filters from magnitude responses specified in Hz.
+///
+/// Responses are given as functions mapping a frequency in Hz to a linear gain. They can be
+/// sampled directly onto FFT bins as zero-phase gain curves, or turned into linear-phase FIR
+/// taps with either the window method or the frequency sampling method.
+#[derive(Debug, Clone, Copy)]
+pub struct FirDesigner {
+    pub sample_rate: f32,
+    pub window: WindowFunction,
+}
+
+impl FirDesigner {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            window: WindowFunction::Hamming,
+        }
+    }
+
+    pub fn with_window(mut self, window: WindowFunction) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Designs a linear-phase FIR filter with the window method.
+    ///
+    /// The ideal response is sampled on a dense grid, transformed to an impulse response, truncated
+    /// to `taps` samples around its center and tapered with the designer's window.
+    pub fn window_method(&self, taps: usize, response: impl Fn(f32) -> f32) -> Vec<f32> {
+        let grid = (taps * 8).next_power_of_two().max(1024);
+        let mut h = Self::linear_phase_taps(taps, grid, self.sample_rate, &response);
+        self.window.apply(&mut h);
+        h
+    }
+
+    /// Designs a linear-phase FIR filter with the frequency sampling method.
+    ///
+    /// The response is sampled at exactly `taps` evenly spaced frequencies, which the resulting
+    /// filter passes through exactly.
+    pub fn frequency_sampling(&self, taps: usize, response: impl Fn(f32) -> f32) -> Vec<f32> {
+        Self::linear_phase_taps(taps, taps, self.sample_rate, &response)
+    }
+
+    /// Samples a response onto the bins of an `n_fft`-point real FFT as a zero-phase gain curve.
+    pub fn gain_curve(&self, n_fft: usize, response: impl Fn(f32) -> f32) -> Vec<f32> {
+        let mut curve = vec![0.0; n_fft / 2 + 1];
+        self.gain_curve_into(response, &mut curve);
+        curve
+    }
+
+    /// Samples a response onto the real FFT bins in `curve` without allocating.
+    ///
+    /// The FFT length is inferred from the curve length (`n_fft / 2 + 1` bins).
+    pub fn gain_curve_into(&self, response: impl Fn(f32) -> f32, curve: &mut [f32]) {
+        let n_fft = (curve.len().saturating_sub(1) * 2).max(1);
+        let hz_per_bin = self.sample_rate / n_fft as f32;
+        for (bin, gain) in curve.iter_mut().enumerate() {
+            *gain = response(bin as f32 * hz_per_bin);
+        }
+    }
+
+    /// Computes the complex frequency response of `taps` on the bins of an `n_fft`-point real FFT.
+    pub fn spectrum(taps: &[f32], n_fft: usize) -> Result<Vec<Complex32>, FftError> {
+        assert!(
+            taps.len() <= n_fft,
+            "filter length must not exceed the FFT length"
+        );
+        let plan = FftPlanner::global().plan_forward(n_fft);
+        let mut input = plan.make_input_vec();
+        let mut output = plan.make_output_vec();
+        input[..taps.len()].copy_from_slice(taps);
+        plan.process(&mut input, &mut output)?;
+        Ok(output)
+    }
+
+    fn linear_phase_taps(
+        taps: usize,
+        grid: usize,
+        sample_rate: f32,
+        response: &impl Fn(f32) -> f32,
+    ) -> Vec<f32> {
+        let center = (taps as f32 - 1.0) / 2.0;
+        let hz_per_point = sample_rate / grid as f32;
+        let gains: Vec<f32> = (0..=grid / 2)
+            .map(|k| response(k as f32 * hz_per_point))
+            .collect();
+
+        (0..taps)
+            .map(|n| {
+                let t = n as f32 - center;
+                let mut sum = gains[0];
+                for (k, &gain) in gains.iter().enumerate().skip(1) {
+                    let phase = 2.0 * PI * k as f32 * t / grid as f32;
+                    if 2 * k == grid {
+                        // the Nyquist point only appears once in the spectrum
+                        sum += gain * phase.cos();
+                    } else {
+                        sum += 2.0 * gain * phase.cos();
+                    }
+                }
+                sum / grid as f32
+            })
+            .collect()
+    }
+}
+
+/// Builds a response function that linearly interpolates between `(hz, gain)` breakpoints.
+///
+/// Frequencies below the first or above the last breakpoint hold the nearest breakpoint's gain.
+/// The breakpoints must be sorted by frequency.
+pub fn breakpoints(points: &[(f32, f32)]) -> impl Fn(f32) -> f32 + '_ {
+    move |hz| {
+        let Some(&(first_hz, first_gain)) = points.first() else {
+            return 1.0;
+        };
+        if hz <= first_hz {
+            return first_gain;
+        }
+        for pair in points.windows(2) {
+            let ((hz0, gain0), (hz1, gain1)) = (pair[0], pair[1]);
+            if hz <= hz1 {
+                let t = if hz1 > hz0 {
+                    (hz - hz0) / (hz1 - hz0)
+                } else {
+                    1.0
+                };
+                return gain0 + (gain1 - gain0) * t;
+            }
+        }
+        points.last().map_or(1.0, |&(_, gain)| gain)
+    }
+}
@@ -0,0 +1,155 @@
+//! Storage for recorded spectral frames, shared between the processors that record and play them.
+//!
+//! A [`FrameBuffer`] holds up to a fixed number of frames, all allocated on construction. Clones
+//! share the same frames, so one processor can record into a buffer while others play it back.
+//! Buffers can be registered under a name with [`FftGraph::add_frame_buffer`], so processors
+//! created separately (e.g. from a patch) can find them.
+//!
+//! Processors only ever try to lock a buffer and skip the frame if another thread holds it, so
+//! reading or loading a buffer from elsewhere never blocks the audio thread.
+//!
+//! [`FftGraph::add_frame_buffer`]: crate::graph::FftGraph::add_frame_buffer
+
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+use crate::{
+    FftError,
+    signal::Fft,
+    state::{StateReader, StateValue, StateWriter},
+};
+
+/// The frames held by a [`FrameBuffer`].
+pub struct FrameStore<F: Fft> {
+    frames: Vec<F::RealFft>,
+    len: usize,
+}
+
+impl<F: Fft> FrameStore<F> {
+    /// Returns the number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the maximum number of frames.
+    pub fn capacity(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.frames.len()
+    }
+
+    /// Returns the recorded frames in order.
+    pub fn frames(&self) -> &[F::RealFft] {
+        &self.frames[..self.len]
+    }
+
+    pub fn get(&self, index: usize) -> Option<&F::RealFft> {
+        self.frames().get(index)
+    }
+
+    /// Appends a frame, returning `false` if the buffer is full.
+    pub fn push(&mut self, frame: &F::RealFft) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.frames[self.len].clone_from(frame);
+        self.len += 1;
+        true
+    }
+
+    /// Forgets all recorded frames.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Replaces the recorded frames, keeping as many of `frames` as fit.
+    pub fn set_frames(&mut self, frames: &[F::RealFft]) {
+        self.len = frames.len().min(self.frames.len());
+        self.frames[..self.len].clone_from_slice(&frames[..self.len]);
+    }
+}
+
+/// The recorded frames. Loading fails if they don't fit.
+impl<F: Fft> StateValue for FrameStore<F> {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.frames[..self.len]);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        let len = state.read_len()?;
+        if len > self.frames.len() {
+            return Err(FftError::InvalidState(format!(
+                "{len} frames don't fit in a buffer of {}",
+                self.frames.len()
+            )));
+        }
+        for frame in &mut self.frames[..len] {
+            state.read(frame)?;
+        }
+        self.len = len;
+        Ok(())
+    }
+}
+
+/// A shared, fixed-capacity recording of spectral frames, see the [module docs](self).
+pub struct FrameBuffer<F: Fft> {
+    store: Arc<Mutex<FrameStore<F>>>,
+}
+
+impl<F: Fft> Clone for FrameBuffer<F> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<F: Fft> FrameBuffer<F> {
+    /// Creates an empty buffer with room for `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            store: Arc::new(Mutex::new(FrameStore {
+                frames: vec![F::RealFft::default(); capacity],
+                len: 0,
+            })),
+        }
+    }
+
+    /// Locks the buffer, waiting for the audio thread to finish with it if necessary.
+    ///
+    /// Don't call this from the audio thread, use [`try_lock`](Self::try_lock) instead.
+    pub fn lock(&self) -> MutexGuard<'_, FrameStore<F>> {
+        // the store is consistent after every method, so a poisoned lock is still usable
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the buffer if no other thread holds it.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, FrameStore<F>>> {
+        match self.store.try_lock() {
+            Ok(store) => Some(store),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Returns whether both handles refer to the same frames.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.store, &other.store)
+    }
+}
+
+/// The frames of the shared recording, saved and loaded under the lock.
+impl<F: Fft> StateValue for FrameBuffer<F> {
+    fn save(&self, state: &mut StateWriter) {
+        self.lock().save(state);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        self.lock().load(state)
+    }
+}
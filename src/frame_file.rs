@@ -0,0 +1,417 @@
+//! A simple file format for sequences of spectral frames, for analyzing offline and resynthesizing
+//! later (e.g. in real time).
+//!
+//! A frame file starts with the 8 bytes `RFFTFRMS`, followed by chunks. Every chunk is a 4-byte
+//! ASCII id, the length of its payload in bytes as a little-endian `u32`, and the payload:
+//!
+//! - `HEAD` describes the analysis and comes before any frames: the format version (`u32`), the
+//!   sample rate (`f32`), the FFT and hop lengths (`u32` each), and the name of the window
+//!   function as a length-prefixed (`u8`) string, see [`WindowFunction::name`].
+//! - `FRAM` holds one frame: `fft_length / 2 + 1` bins as pairs of real and imaginary `f32`s.
+//!
+//! All numbers are little-endian. Readers skip chunks they don't know, so later versions can add
+//! chunks without breaking older readers. A file that ends inside a chunk, e.g. because writing it
+//! was interrupted, fails with [`FrameFileError::Truncated`].
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::core::{Complex32, WindowFunction};
+
+/// The bytes every frame file starts with.
+pub const FRAME_FILE_MAGIC: [u8; 8] = *b"RFFTFRMS";
+/// The format version written by [`FrameFileWriter`].
+pub const FRAME_FILE_VERSION: u32 = 1;
+
+const HEADER_CHUNK: [u8; 4] = *b"HEAD";
+const FRAME_CHUNK: [u8; 4] = *b"FRAM";
+
+#[derive(Debug, Error)]
+pub enum FrameFileError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a frame file")]
+    InvalidMagic,
+    #[error("frame file ends inside a chunk")]
+    Truncated,
+    #[error("unsupported frame file version {0}")]
+    UnsupportedVersion(u32),
+    #[error("frame file has no header")]
+    MissingHeader,
+    #[error("invalid {0} chunk")]
+    InvalidChunk(&'static str),
+    #[error("unknown window function {0:?}")]
+    UnknownWindow(String),
+    #[error("frame has {found} bins, expected {expected}")]
+    FrameLength { expected: usize, found: usize },
+}
+
+/// How the frames in a frame file were analyzed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameFileHeader {
+    pub sample_rate: f32,
+    pub fft_length: usize,
+    pub hop_length: usize,
+    pub window_fn: WindowFunction,
+}
+
+impl FrameFileHeader {
+    /// Returns the number of bins in every frame.
+    pub fn num_bins(&self) -> usize {
+        self.fft_length / 2 + 1
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let name = self.window_fn.name().as_bytes();
+        let mut payload = Vec::with_capacity(17 + name.len());
+        payload.extend_from_slice(&FRAME_FILE_VERSION.to_le_bytes());
+        payload.extend_from_slice(&self.sample_rate.to_le_bytes());
+        payload.extend_from_slice(&(self.fft_length as u32).to_le_bytes());
+        payload.extend_from_slice(&(self.hop_length as u32).to_le_bytes());
+        payload.push(name.len() as u8);
+        payload.extend_from_slice(name);
+        payload
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, FrameFileError> {
+        let field = |offset: usize| -> Result<[u8; 4], FrameFileError> {
+            payload
+                .get(offset..offset + 4)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or(FrameFileError::InvalidChunk("HEAD"))
+        };
+
+        let version = u32::from_le_bytes(field(0)?);
+        if version != FRAME_FILE_VERSION {
+            return Err(FrameFileError::UnsupportedVersion(version));
+        }
+        let name_length = *payload
+            .get(16)
+            .ok_or(FrameFileError::InvalidChunk("HEAD"))? as usize;
+        let name = payload
+            .get(17..17 + name_length)
+            .ok_or(FrameFileError::InvalidChunk("HEAD"))?;
+        let name = String::from_utf8_lossy(name);
+        let window_fn = WindowFunction::from_name(&name)
+            .ok_or_else(|| FrameFileError::UnknownWindow(name.into_owned()))?;
+
+        Ok(Self {
+            sample_rate: f32::from_le_bytes(field(4)?),
+            fft_length: u32::from_le_bytes(field(8)?) as usize,
+            hop_length: u32::from_le_bytes(field(12)?) as usize,
+            window_fn,
+        })
+    }
+}
+
+/// Writes frames to a frame file, see the [module docs](self).
+pub struct FrameFileWriter<W: Write> {
+    writer: W,
+    header: FrameFileHeader,
+    buffer: Vec<u8>,
+}
+
+impl FrameFileWriter<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path` and writes the header.
+    pub fn create(path: impl AsRef<Path>, header: FrameFileHeader) -> Result<Self, FrameFileError> {
+        Self::new(BufWriter::new(File::create(path)?), header)
+    }
+}
+
+impl<W: Write> FrameFileWriter<W> {
+    /// Writes the magic bytes and the header to `writer`.
+    pub fn new(mut writer: W, header: FrameFileHeader) -> Result<Self, FrameFileError> {
+        writer.write_all(&FRAME_FILE_MAGIC)?;
+        write_chunk(&mut writer, HEADER_CHUNK, &header.encode())?;
+        Ok(Self {
+            writer,
+            header,
+            buffer: Vec::with_capacity(header.num_bins() * 8),
+        })
+    }
+
+    pub fn header(&self) -> &FrameFileHeader {
+        &self.header
+    }
+
+    /// Appends a frame, which must have as many bins as the header says.
+    pub fn write_frame(&mut self, frame: &[Complex32]) -> Result<(), FrameFileError> {
+        if frame.len() != self.header.num_bins() {
+            return Err(FrameFileError::FrameLength {
+                expected: self.header.num_bins(),
+                found: frame.len(),
+            });
+        }
+        self.buffer.clear();
+        for bin in frame {
+            self.buffer.extend_from_slice(&bin.re.to_le_bytes());
+            self.buffer.extend_from_slice(&bin.im.to_le_bytes());
+        }
+        write_chunk(&mut self.writer, FRAME_CHUNK, &self.buffer)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), FrameFileError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, FrameFileError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads frames from a frame file, see the [module docs](self).
+pub struct FrameFileReader<R: Read> {
+    reader: R,
+    header: FrameFileHeader,
+    buffer: Vec<u8>,
+}
+
+impl FrameFileReader<BufReader<File>> {
+    /// Opens the file at `path` and reads the header.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FrameFileError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> FrameFileReader<R> {
+    /// Checks the magic bytes and reads up to and including the header from `reader`.
+    pub fn new(mut reader: R) -> Result<Self, FrameFileError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(truncated)?;
+        if magic != FRAME_FILE_MAGIC {
+            return Err(FrameFileError::InvalidMagic);
+        }
+
+        let mut buffer = Vec::new();
+        loop {
+            match read_chunk(&mut reader, &mut buffer)? {
+                Some(HEADER_CHUNK) => break,
+                Some(FRAME_CHUNK) | None => return Err(FrameFileError::MissingHeader),
+                Some(_) => {}
+            }
+        }
+        let header = FrameFileHeader::decode(&buffer)?;
+
+        Ok(Self {
+            reader,
+            header,
+            buffer,
+        })
+    }
+
+    pub fn header(&self) -> &FrameFileHeader {
+        &self.header
+    }
+
+    /// Reads the next frame into `frame`, which must have as many bins as the header says.
+    /// Returns `false` at the end of the file.
+    pub fn read_frame(&mut self, frame: &mut [Complex32]) -> Result<bool, FrameFileError> {
+        let num_bins = self.header.num_bins();
+        if frame.len() != num_bins {
+            return Err(FrameFileError::FrameLength {
+                expected: num_bins,
+                found: frame.len(),
+            });
+        }
+
+        loop {
+            match read_chunk(&mut self.reader, &mut self.buffer)? {
+                Some(FRAME_CHUNK) => break,
+                Some(_) => {}
+                None => return Ok(false),
+            }
+        }
+        if self.buffer.len() != num_bins * 8 {
+            return Err(FrameFileError::FrameLength {
+                expected: num_bins,
+                found: self.buffer.len() / 8,
+            });
+        }
+
+        for (bin, bytes) in frame.iter_mut().zip(self.buffer.chunks_exact(8)) {
+            let re = f32::from_le_bytes(bytes[..4].try_into().unwrap());
+            let im = f32::from_le_bytes(bytes[4..].try_into().unwrap());
+            *bin = Complex32::new(re, im);
+        }
+        Ok(true)
+    }
+
+    /// Reads all remaining frames.
+    pub fn read_all(&mut self) -> Result<Vec<Vec<Complex32>>, FrameFileError> {
+        let mut frames = Vec::new();
+        let mut frame = vec![Complex32::ZERO; self.header.num_bins()];
+        while self.read_frame(&mut frame)? {
+            frames.push(frame.clone());
+        }
+        Ok(frames)
+    }
+}
+
+/// Reports the end of the file where more of a chunk was expected as [`FrameFileError::Truncated`].
+fn truncated(error: io::Error) -> FrameFileError {
+    if error.kind() == io::ErrorKind::UnexpectedEof {
+        FrameFileError::Truncated
+    } else {
+        FrameFileError::Io(error)
+    }
+}
+
+fn write_chunk(writer: &mut impl Write, id: [u8; 4], payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&id)?;
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)
+}
+
+/// Reads the next chunk's payload into `payload` and returns its id, or `None` at the end of the
+/// file.
+fn read_chunk(
+    reader: &mut impl Read,
+    payload: &mut Vec<u8>,
+) -> Result<Option<[u8; 4]>, FrameFileError> {
+    let mut id = [0; 4];
+    let mut filled = 0;
+    while filled < id.len() {
+        match reader.read(&mut id[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(FrameFileError::Truncated),
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let mut length = [0; 4];
+    reader.read_exact(&mut length).map_err(truncated)?;
+    let length = u32::from_le_bytes(length) as usize;
+
+    // read what's there rather than trusting the length, which may be corrupt
+    payload.clear();
+    reader.take(length as u64).read_to_end(payload)?;
+    if payload.len() < length {
+        return Err(FrameFileError::Truncated);
+    }
+    Ok(Some(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn header() -> FrameFileHeader {
+        FrameFileHeader {
+            sample_rate: 44_100.0,
+            fft_length: 16,
+            hop_length: 4,
+            window_fn: WindowFunction::Blackman,
+        }
+    }
+
+    fn frames() -> Vec<Vec<Complex32>> {
+        (0..3)
+            .map(|frame| {
+                (0..header().num_bins())
+                    .map(|bin| Complex32::new(frame as f32 / 3.0 + bin as f32, -1e-40 * bin as f32))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write(frames: &[Vec<Complex32>]) -> Vec<u8> {
+        let mut writer = FrameFileWriter::new(Vec::new(), header()).unwrap();
+        for frame in frames {
+            writer.write_frame(frame).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn read(bytes: &[u8]) -> Result<Vec<Vec<Complex32>>, FrameFileError> {
+        FrameFileReader::new(Cursor::new(bytes))?.read_all()
+    }
+
+    #[test]
+    fn round_trips_header_and_frames() {
+        let frames = frames();
+        let bytes = write(&frames);
+
+        let mut reader = FrameFileReader::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(*reader.header(), header());
+        let read = reader.read_all().unwrap();
+        assert_eq!(read.len(), frames.len());
+        for (read, frame) in read.iter().zip(&frames) {
+            for (read, bin) in read.iter().zip(frame) {
+                assert_eq!(read.re.to_bits(), bin.re.to_bits());
+                assert_eq!(read.im.to_bits(), bin.im.to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let mut bytes = write(&frames()[..1]);
+        write_chunk(&mut bytes, *b"NOTE", b"hello").unwrap();
+        bytes.extend_from_slice(&write(&frames()[1..2])[FRAME_FILE_MAGIC.len()..]);
+        // a header chunk among the frames is skipped too
+        assert_eq!(read(&bytes).unwrap(), frames()[..2]);
+    }
+
+    #[test]
+    fn rejects_frames_of_the_wrong_length() {
+        let mut writer = FrameFileWriter::new(Vec::new(), header()).unwrap();
+        assert!(matches!(
+            writer.write_frame(&[Complex32::ZERO; 3]),
+            Err(FrameFileError::FrameLength {
+                expected: 9,
+                found: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = write(&frames());
+        for length in [
+            0,
+            5,
+            FRAME_FILE_MAGIC.len() + 6,
+            bytes.len() - 2,
+            bytes.len() - 75,
+        ] {
+            assert!(
+                matches!(read(&bytes[..length]), Err(FrameFileError::Truncated)),
+                "{length} bytes"
+            );
+        }
+        // a file cut off between chunks has fewer frames
+        assert_eq!(read(&bytes[..bytes.len() - 80]).unwrap(), frames()[..2]);
+    }
+
+    #[test]
+    fn rejects_other_magic_and_versions() {
+        let mut bytes = write(&frames());
+        bytes[0] = b'X';
+        assert!(matches!(read(&bytes), Err(FrameFileError::InvalidMagic)));
+
+        let mut bytes = write(&frames());
+        // the version is the first field of the header chunk
+        let version = FRAME_FILE_MAGIC.len() + 8;
+        bytes[version..version + 4].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            read(&bytes),
+            Err(FrameFileError::UnsupportedVersion(2))
+        ));
+
+        let mut bytes = FRAME_FILE_MAGIC.to_vec();
+        write_chunk(&mut bytes, FRAME_CHUNK, &[0; 72]).unwrap();
+        assert!(matches!(read(&bytes), Err(FrameFileError::MissingHeader)));
+    }
+}
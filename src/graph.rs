@@ -1,29 +1,110 @@
-use std::{collections::BTreeMap, ops::Deref};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, mem,
+    ops::Deref,
+    sync::{
+        Arc,
+        mpsc::{Receiver, SyncSender, sync_channel},
+    },
+    time::Duration,
+};
 
-use raug::{graph::GraphRunResult, prelude::*, processor::io::ProcessMode};
+use raug::{
+    graph::{GraphRunResult, node::ProcessNodeError},
+    prelude::*,
+    processor::io::ProcessMode,
+};
 
 use crate::{
-    WindowFunction,
-    builtins::transforms::{InverseRealFft, RealFft},
+    FftError, WindowFunction,
+    block::{BlockNode, FftBlockProcessor},
+    builtins::transforms::{FftPlans, InverseRealFft, RealFft},
+    core::{
+        Complex32, PaddingMode, SpectralScaling, cached_stft_window, validate_stft, window_sum_peak,
+    },
+    denormal::DenormalGuard,
+    diagnostics::{self, RuntimeDiagnostic},
+    dither::Dither,
+    frame_buffer::FrameBuffer,
+    handle::{COMMAND_CAPACITY, FftGraphHandle, GraphCommand},
+    metrics::{CostEstimate, FftGraphMetrics, ProcessTimer},
     node::{FftInput, FftOutput, FftProcessorNode},
     prelude::util::Null,
-    processor::FftProcessor,
-    signal::Fft,
+    processor::{FftProcessor, FrameInfo, Transport},
+    random::stream_seed,
+    registry::{ProcessorFactory, ProcessorParams, ProcessorRegistry},
+    scratch::ScratchPool,
+    signal::{Fft, Fft64, Fft128, Fft256, Fft512, Fft1024, Fft2048, Fft4096, Fft8192},
+    state::{GraphState, StateReader, StateWriter},
+    subgraph::FramePort,
+    wola::WolaPrototype,
 };
 
+#[cfg(feature = "profiling")]
+use crate::profile::{ProfileEntry, ProfileReport};
+
 use raug_graph::{
     graph::{AbstractGraph, DuplicateConnectionMode, Graph, NodeIndex, VisitResult},
-    petgraph::{Direction, visit::EdgeRef},
+    petgraph::{
+        Direction, algo,
+        visit::{EdgeRef, IntoEdgeReferences},
+    },
     prelude::{GraphBuilder, NodeBuilder},
 };
 
+/// The number of hops [`FftGraph::estimate_cost`] times.
+pub const COST_ESTIMATE_HOPS: usize = 32;
+
 pub struct FftGraph<F: Fft> {
     graph: Graph<Self>,
 
     sample_rate: f32,
     block_size: usize,
     hop_length: usize,
-    window: Vec<f32>,
+    window_fn: WindowFunction,
+    window: Arc<[f32]>,
+    // the peak gain of the window, see `OutputNormalization::WindowSumPeak`
+    window_sum_peak: f32,
+    window_crossfade: Option<WindowCrossfade>,
+    frame_index: u64,
+    // the samples buffered towards the next frame by a graph without audio inputs, which is
+    // clocked by the length of the host's blocks instead, see `advance_clock`
+    idle_samples: usize,
+    transport: Transport,
+    seed: u64,
+    denormal_protection: bool,
+    nan_guard: bool,
+    output_limiter: bool,
+    output_dither: Option<u32>,
+    padding_mode: PaddingMode,
+    spectral_scaling: SpectralScaling,
+    lookahead_frames: usize,
+    // the factor of `spectral_scaling` for the current window
+    spectral_scale: f32,
+    engine: StftEngine,
+    // designed for `StftEngine::Wola`
+    wola: Option<WolaPrototype>,
+    // one per audio output, rebuilt when the graph is allocated
+    dithers: Vec<Dither>,
+    metrics: Arc<FftGraphMetrics>,
+    // changes queued through `FftGraphHandle`s, applied before every hop
+    commands: Receiver<GraphCommand>,
+    command_sender: SyncSender<GraphCommand>,
+    unconnected_inputs: UnconnectedInputs,
+    // fed to unconnected spectrum and audio inputs under `UnconnectedInputs::Zeros`
+    zero_spectrum: AnyBuffer,
+    zero_block: AnyBuffer,
+    feedback: Vec<FeedbackEdge>,
+    // read by unconnected sidechain inputs
+    silence: Vec<f32>,
+    frame_inputs: Vec<NodeIndex>,
+    frame_outputs: Vec<NodeIndex>,
+    frame_buffers: BTreeMap<String, FrameBuffer<F>>,
+    plans: FftPlans,
+    // creates the processors added by name with `add_builtin`
+    registry: ProcessorRegistry<F>,
+    // lent to every processor in turn, sized for the one needing the most
+    scratch: ScratchPool,
 
     inputs: BTreeMap<NodeIndex, FftInput<F>>,
     outputs: BTreeMap<NodeIndex, FftOutput<F>>,
@@ -47,30 +128,62 @@ impl<F: Fft> AbstractGraph for FftGraph<F> {
 }
 
 impl<F: Fft> FftGraph<F> {
+    /// Creates an empty graph.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hop_length` is zero or larger than the FFT length, see
+    /// [`try_new`](Self::try_new).
     pub fn new(hop_length: usize, window_fn: WindowFunction) -> Self {
-        let mut window = window_fn.generate(F::N_FFT);
-
-        // center the window around 0
-        window.rotate_right(F::N_FFT / 2);
-
-        let overlapping_frames = F::N_FFT / hop_length;
-        let mut window_sum: f32 = window.iter().map(|x| x * x).sum();
-        window_sum *= overlapping_frames as f32;
-        assert_ne!(window_sum, 0.0);
+        Self::try_new(hop_length, window_fn).unwrap_or_else(|e| panic!("{e}"))
+    }
 
-        for x in window.iter_mut() {
-            *x /= window_sum.sqrt();
-        }
+    pub fn try_new(hop_length: usize, window_fn: WindowFunction) -> Result<Self, FftError> {
+        validate_stft(F::N_FFT, hop_length)?;
+        let window = cached_stft_window(window_fn, F::N_FFT, hop_length);
+        let (command_sender, commands) = sync_channel(COMMAND_CAPACITY);
 
-        Self {
+        Ok(Self {
             graph: Graph::new(),
             sample_rate: 0.0,
             block_size: 0,
             hop_length,
+            window_fn,
+            window_sum_peak: window_sum_peak(&window, hop_length),
             window,
+            window_crossfade: None,
+            frame_index: 0,
+            idle_samples: 0,
+            transport: Transport::default(),
+            seed: 0,
+            denormal_protection: true,
+            nan_guard: false,
+            output_limiter: false,
+            output_dither: None,
+            padding_mode: PaddingMode::None,
+            spectral_scaling: SpectralScaling::None,
+            lookahead_frames: 0,
+            spectral_scale: 1.0,
+            engine: StftEngine::OverlapAdd,
+            wola: None,
+            dithers: Vec::new(),
+            metrics: Arc::default(),
+            commands,
+            command_sender,
+            unconnected_inputs: UnconnectedInputs::default(),
+            zero_spectrum: AnyBuffer::zeros::<F::RealFft>(1),
+            zero_block: AnyBuffer::zeros::<F::AudioBlock>(1),
+            feedback: Vec::new(),
+            silence: Vec::new(),
+            frame_inputs: Vec::new(),
+            frame_outputs: Vec::new(),
+            frame_buffers: BTreeMap::new(),
+            plans: FftPlans::new(F::N_FFT),
+            registry: ProcessorRegistry::with_builtins(),
+            scratch: ScratchPool::default(),
             inputs: BTreeMap::new(),
             outputs: BTreeMap::new(),
-        }
+        })
     }
 
     pub fn fft_length(&self) -> usize {
@@ -81,275 +194,2868 @@ impl<F: Fft> FftGraph<F> {
         self.hop_length
     }
 
-    pub fn add_audio_input(&mut self) -> NodeIndex {
-        let null = self.add_processor(Null::<F>::new());
-        let fft = self.add_processor(RealFft::<F>::new());
-        self.graph.connect(null, 0, fft, 0).unwrap();
-        self.inputs.insert(null, FftInput::<F>::default());
-        fft
+    pub fn window_fn(&self) -> WindowFunction {
+        self.window_fn
     }
 
-    pub fn add_audio_output(&mut self) -> NodeIndex {
-        let idx = self.add_processor(InverseRealFft::<F>::new());
-        self.outputs.insert(idx, FftOutput::<F>::default());
-        idx
+    /// Switches to another analysis/synthesis window.
+    ///
+    /// While the graph is running, the old window is crossfaded into the new one over one window
+    /// length (`fft_length / hop_length` hops), so the change doesn't click. Changing the window
+    /// again during a crossfade starts a new crossfade from the current mix.
+    pub fn set_window(&mut self, window_fn: WindowFunction) {
+        if window_fn == self.window_fn {
+            return;
+        }
+        self.window_fn = window_fn;
+        let window = cached_stft_window(window_fn, F::N_FFT, self.hop_length);
+        let previous = mem::replace(&mut self.window, window);
+        self.window_sum_peak = window_sum_peak(&self.window, self.hop_length);
+        self.spectral_scale = self.spectral_scaling.factor(&self.window);
+
+        if self.frame_index == 0 {
+            // nothing has been windowed with the previous window yet
+            self.window_crossfade = None;
+            return;
+        }
+
+        let from = match self.window_crossfade.take() {
+            Some(crossfade) => crossfade.blended.into(),
+            None => previous,
+        };
+        self.window_crossfade = Some(WindowCrossfade {
+            blended: from.to_vec(),
+            from,
+            hop: 0,
+            hops: (F::N_FFT / self.hop_length).max(1),
+        });
     }
 
-    pub fn add_processor(&mut self, processor: impl FftProcessor) -> NodeIndex {
-        let mut node = FftProcessorNode::new(processor);
-        node.allocate(self.sample_rate);
-        node.resize_buffers(self.sample_rate);
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
 
-        self.graph.add_node(node)
+    pub fn block_size(&self) -> usize {
+        self.block_size
     }
 
-    pub fn allocate(&mut self, sample_rate: f32, block_size: usize) {
-        self.sample_rate = sample_rate;
-        self.block_size = block_size;
+    pub fn num_audio_inputs(&self) -> usize {
+        self.inputs.len()
+    }
 
-        self.graph.visit_mut(|_i, node| {
-            node.allocate(sample_rate);
-            VisitResult::Continue::<()>
-        });
+    /// Returns the musical time of the next frame.
+    pub fn transport(&self) -> Transport {
+        self.transport
     }
 
-    pub fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
-        self.sample_rate = sample_rate;
-        self.block_size = block_size;
+    /// Sets the musical time of the next frame, usually at the start of every block.
+    ///
+    /// Processors see the transport in their [`FrameInfo`]. While it's playing, the graph moves
+    /// the position forward by one hop after every frame, so hosts only need to set it when the
+    /// tempo or position changes, or to keep it from drifting.
+    pub fn set_transport(&mut self, transport: Transport) {
+        self.transport = transport;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Sets the seed every processor's random numbers are derived from (0 by default), see
+    /// [`random`](crate::random). Rendering with the same seed gives the same output.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn denormal_protection(&self) -> bool {
+        self.denormal_protection
+    }
+
+    /// Sets whether subnormal floats are flushed to zero while processing (enabled by default).
+    ///
+    /// See the [`denormal`](crate::denormal) module for details.
+    pub fn set_denormal_protection(&mut self, enabled: bool) {
+        self.denormal_protection = enabled;
+    }
+
+    pub fn num_audio_outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn nan_guard(&self) -> bool {
+        self.nan_guard
+    }
 
+    /// Sets whether the outputs of every node are checked for NaN and infinite values after it
+    /// processes (disabled by default).
+    ///
+    /// Offending values are replaced with zeros so they don't spread through the rest of the
+    /// graph, the first occurrence per node is reported as a
+    /// [`RuntimeDiagnostic::NonFinite`](crate::diagnostics::RuntimeDiagnostic::NonFinite), and
+    /// [`non_finite_outputs`](Self::non_finite_outputs) reports which nodes produced them.
+    pub fn set_nan_guard(&mut self, enabled: bool) {
+        self.nan_guard = enabled;
+    }
+
+    pub fn output_limiter(&self) -> bool {
+        self.output_limiter
+    }
+
+    /// Sets whether every audio output passes its spectrum through a
+    /// [`SpectralLimiter`](crate::builtins::dynamics::SpectralLimiter) before resynthesis
+    /// (disabled by default), so a runaway feedback patch can't make the output explode. The
+    /// limiters of the outputs' [`InverseRealFft`] nodes are configured through their parameters.
+    pub fn set_output_limiter(&mut self, enabled: bool) {
+        self.output_limiter = enabled;
+        for &node in self.outputs.keys() {
+            self.graph[node]
+                .processor_mut()
+                .set_param("limit", if enabled { 1.0 } else { 0.0 });
+        }
+    }
+
+    pub fn spectral_scaling(&self) -> SpectralScaling {
+        self.spectral_scaling
+    }
+
+    /// Sets how the spectra of the audio inputs are scaled (unnormalized by default), see
+    /// [`SpectralScaling`]. The audio outputs undo the same scale, so the resynthesized audio
+    /// doesn't change, but the builtins measuring levels assume unnormalized spectra.
+    ///
+    /// The scale is applied to the framed inputs and undone on the processed frames, so it holds
+    /// for the whole graph and isn't a parameter of any node.
+    pub fn set_spectral_scaling(&mut self, scaling: SpectralScaling) {
+        self.spectral_scaling = scaling;
+        self.spectral_scale = scaling.factor(&self.window);
+    }
+
+    /// Returns the number of hops processors look ahead at least, see
+    /// [`set_lookahead_frames`](Self::set_lookahead_frames).
+    pub fn lookahead_frames(&self) -> usize {
+        self.lookahead_frames
+    }
+
+    /// Makes the processors that look ahead in time, such as
+    /// [`Declip`](crate::builtins::restoration::Declip) and
+    /// [`SpectralDuck`](crate::builtins::dynamics::SpectralDuck), buffer at least `frames` future
+    /// frames before they output one (none by default, leaving them at their own look-ahead).
+    ///
+    /// The processors delay their output by the frames they look ahead, which
+    /// [`latency_samples`](Self::latency_samples) includes. This changes their buffers, so it
+    /// should be set before the graph is allocated.
+    pub fn set_lookahead_frames(&mut self, frames: usize) {
+        self.lookahead_frames = frames;
         self.graph.visit_mut(|_i, node| {
-            node.resize_buffers(sample_rate);
+            node.processor_mut().set_graph_lookahead(frames);
             VisitResult::Continue::<()>
         });
     }
 
-    #[allow(clippy::needless_range_loop)]
-    fn process_inner(
-        &mut self,
-        inputs: ProcessorInputs,
-        mut outputs: ProcessorOutputs,
-    ) -> ProcResult<()> {
-        self.graph.reset_visitor();
+    pub fn padding_mode(&self) -> PaddingMode {
+        self.padding_mode
+    }
 
-        // if there are no inputs, we can't process anything
-        if self.inputs.is_empty() {
-            return Ok(());
+    /// Sets how the first frames are formed before a whole frame of input has arrived (no
+    /// padding by default), taking effect when the graph is next allocated or renders. See
+    /// [`PaddingMode`].
+    ///
+    /// With padding, frames are centered on multiples of the hop length like librosa's, so the
+    /// first hops analyze the same frames as Python tools do. The resynthesized padding is
+    /// discarded, so the outputs stay aligned with the inputs and the latency doesn't change.
+    pub fn set_padding_mode(&mut self, padding: PaddingMode) {
+        self.padding_mode = padding;
+        for fft_input in self.inputs.values_mut() {
+            fft_input.frames.set_padding(padding, F::N_FFT);
         }
+    }
 
-        let fft_length = self.fft_length();
-        let hop_length = self.hop_length();
+    pub fn engine(&self) -> StftEngine {
+        self.engine
+    }
 
-        let mut input_buffer_length = usize::MAX;
+    /// Sets how audio is framed and resynthesized (overlap-add by default), taking effect when
+    /// the graph is next allocated or renders. See [`StftEngine`].
+    pub fn set_engine(&mut self, engine: StftEngine) {
+        self.engine = engine;
+        self.wola = match engine {
+            StftEngine::Wola { taps } => Some(WolaPrototype::new(F::N_FFT, self.hop_length, taps)),
+            StftEngine::OverlapAdd | StftEngine::OverlapSave => None,
+        };
+        let lead = self.engine_lead();
+        let frame_length = self.frame_length();
+        for fft_input in self.inputs.values_mut() {
+            fft_input.frames.set_lead(lead);
+            // a WOLA frame spans several FFT lengths, so grow the buffer here rather than on the
+            // audio thread
+            fft_input.frames.reserve(frame_length + self.block_size);
+        }
+        for fft_output in self.outputs.values_mut() {
+            fft_output.overlap_add.set_frame_length(frame_length);
+        }
+    }
 
-        // fill our input buffers with the input signals
-        for (input_index, fft_input) in self.inputs.values_mut().enumerate() {
-            let audio_input = inputs.input_as::<f32>(input_index).unwrap();
+    /// Returns the prototype filters of the WOLA filterbank, if the graph uses one, see
+    /// [`StftEngine::Wola`].
+    pub fn wola_prototype(&self) -> Option<&WolaPrototype> {
+        self.wola.as_ref()
+    }
 
-            fft_input
-                .ring_buffer
-                .extend(&audio_input[..self.block_size]);
+    /// Returns the number of samples every frame is analyzed from: the FFT length, or the length
+    /// of the [WOLA prototype](Self::wola_prototype).
+    pub fn frame_length(&self) -> usize {
+        self.wola
+            .as_ref()
+            .map_or(F::N_FFT, WolaPrototype::frame_length)
+    }
 
-            input_buffer_length = input_buffer_length.min(fft_input.ring_buffer.len());
+    /// Returns the number of zeros the audio inputs start with, so an overlap-save engine
+    /// finishes the first samples of the stream with its first frame.
+    fn engine_lead(&self) -> usize {
+        match self.engine {
+            StftEngine::OverlapAdd | StftEngine::Wola { .. } => 0,
+            StftEngine::OverlapSave => F::N_FFT - self.hop_length,
         }
+    }
 
-        // while we still have enough samples to process...
-        while input_buffer_length >= fft_length {
-            for (&node_index, fft_input) in self.inputs.iter_mut() {
-                // window the input
-                for i in 0..fft_length {
-                    fft_input.time_domain[i] = fft_input.ring_buffer[i] * self.window[i];
-                }
+    /// Returns the peak gain of analysis and synthesis normalized away by
+    /// [`OutputNormalization::WindowSumPeak`].
+    fn output_peak(&self) -> f32 {
+        match self.engine {
+            StftEngine::OverlapAdd => self.window_sum_peak,
+            // unwindowed frames don't overlap, and the WOLA prototypes reconstruct exactly
+            StftEngine::OverlapSave | StftEngine::Wola { .. } => 1.0,
+        }
+    }
 
-                // copy the time domain signal to the FFT input
-                self.graph[node_index].outputs[0]
-                    .get_mut_as::<F::AudioBlock>(0)
-                    .unwrap()
-                    .copy_from_slice(&fft_input.time_domain);
+    pub fn output_dither(&self) -> Option<u32> {
+        self.output_dither
+    }
 
-                // advance time for the input
-                fft_input.ring_buffer.drain(..hop_length);
-            }
+    /// Sets the bit depth the audio outputs are dithered and quantized to, see
+    /// [`dither`](crate::dither), or `None` to leave them as they are (the default).
+    ///
+    /// Applies to the outputs of [`process_block`](Self::process_block) and
+    /// [`render`](Self::render), e.g. when rendering to a 16-bit file. The dither noise is derived
+    /// from the graph's [seed](Self::set_seed) and restarts whenever the graph is allocated, so
+    /// renders are reproducible.
+    pub fn set_output_dither(&mut self, bits: Option<u32>) {
+        self.output_dither = bits;
+        self.reset_dithers();
+    }
 
-            // update the input buffer length
-            input_buffer_length -= hop_length;
+    fn reset_dithers(&mut self) {
+        self.dithers.clear();
+        if let Some(bits) = self.output_dither {
+            // a stream no node draws from
+            let seed = stream_seed(self.seed, u64::MAX);
+            self.dithers.extend(
+                (0..self.outputs.len())
+                    .map(|output| Dither::new(bits, stream_seed(seed, output as u64))),
+            );
+        }
+    }
 
-            // traverse the graph and process each node
-            for i in 0..self.graph.visit_path().len() {
-                let node_id = self.graph.visit_path()[i];
-                if let Err(e) = self.process_node(node_id) {
-                    return Err(ProcessorError::SubGraphError(Box::new(e)));
-                }
-            }
+    /// Returns the nodes whose outputs contained NaN or infinite values while the NaN guard was
+    /// enabled.
+    pub fn non_finite_outputs(&self) -> Vec<NonFiniteOutput> {
+        self.graph
+            .digraph()
+            .node_indices()
+            .filter(|&node| self.graph[node].non_finite_hops > 0)
+            .map(|node| NonFiniteOutput {
+                node,
+                name: self.graph[node].name().to_string(),
+                hops: self.graph[node].non_finite_hops,
+            })
+            .collect()
+    }
+
+    /// Resets the counts reported by [`non_finite_outputs`](Self::non_finite_outputs).
+    pub fn clear_non_finite_outputs(&mut self) {
+        self.graph.visit_mut(|_i, node| {
+            node.non_finite_hops = 0;
+            VisitResult::Continue::<()>
+        });
+    }
 
-            // copy the FFT output to the output buffers
-            for (&output_node_idx, fft_output) in self.outputs.iter_mut() {
-                let output_buf = &self.graph[output_node_idx].outputs[0]
-                    .as_slice::<F::AudioBlock>()
-                    .unwrap()[0];
+    /// Returns the number of output samples that were zero-filled because not enough had been
+    /// resynthesized yet, summed over all audio outputs.
+    ///
+    /// The outputs are delayed so that this can't happen while every input is fed as many samples
+    /// as are read from the outputs, see [`latency_samples`](Self::latency_samples); a nonzero
+    /// count means they were fed less.
+    pub fn underruns(&self) -> u64 {
+        self.metrics.underruns()
+    }
 
-                // overlap-add
-                for i in 0..fft_length {
-                    fft_output.overlap_buffer[i] += output_buf[i] * self.window[i];
-                }
+    /// Resets the count reported by [`underruns`](Self::underruns).
+    pub fn clear_underruns(&mut self) {
+        self.metrics.clear_underruns();
+    }
+
+    /// Returns a handle to the graph's [`FftGraphMetrics`], which can be read from any thread
+    /// while the graph is processing.
+    pub fn metrics(&self) -> Arc<FftGraphMetrics> {
+        self.metrics.clone()
+    }
 
-                // advance time for the output
-                fft_output
-                    .ring_buffer
-                    .extend(fft_output.overlap_buffer.drain(..hop_length));
+    /// Returns a handle for changing the graph's processors from other threads while it's
+    /// processing, see [`handle`](crate::handle).
+    pub fn handle(&self) -> FftGraphHandle {
+        FftGraphHandle::new(self.command_sender.clone())
+    }
 
-                for _ in 0..hop_length {
-                    // zero out the overlap buffer for the next iteration
-                    fft_output.overlap_buffer.push_back(0.0);
-                }
-            }
+    /// Sets the parameter `name` of the processor at `node`, see
+    /// [`FftProcessor::set_param`].
+    pub fn set_param(&mut self, node: NodeIndex, name: &str, value: f32) -> Result<(), FftError> {
+        self.node_mut(node)?.set_param(name, value)
+    }
+
+    /// Sets whether the node at `node` is bypassed: instead of processing, it passes each input
+    /// through to the output at the same index if both carry the same type of signal. Its other
+    /// outputs keep their last values. Bypassing a node with latency shifts the signals passing
+    /// through it earlier.
+    pub fn set_bypassed(&mut self, node: NodeIndex, bypassed: bool) -> Result<(), FftError> {
+        self.node_mut(node)?.bypassed = bypassed;
+        Ok(())
+    }
+
+    /// Sets whether the node at `node` is frozen: it stops processing and its outputs keep the
+    /// values of its last hop, e.g. to hold a spectrum.
+    pub fn set_frozen(&mut self, node: NodeIndex, frozen: bool) -> Result<(), FftError> {
+        self.node_mut(node)?.frozen = frozen;
+        Ok(())
+    }
+
+    fn node_mut(&mut self, node: NodeIndex) -> Result<&mut FftProcessorNode, FftError> {
+        if self.graph.digraph().node_weight(node).is_none() {
+            return Err(FftError::Graph(format!(
+                "no node at index {}",
+                node.index()
+            )));
         }
+        Ok(&mut self.graph[node])
+    }
 
-        // for each output, write as many samples as we can to the block's corresponding output
-        for (output_index, fft_output) in self.outputs.values_mut().enumerate() {
-            if fft_output.ring_buffer.len() < inputs.block_size() {
-                log::debug!(
-                    "FftGraph underrun at output index {output_index}, not enough samples in ring buffer"
-                );
+    /// Applies the changes queued through [`FftGraphHandle`]s.
+    fn apply_commands(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            let (GraphCommand::Param { node, .. }
+            | GraphCommand::Bypassed { node, .. }
+            | GraphCommand::Frozen { node, .. }) = command;
+            if self.graph.digraph().node_weight(node).is_none() {
+                diagnostics::report(RuntimeDiagnostic::MissingNode { node: node.index() });
                 continue;
             }
-            for sample_index in 0..inputs.block_size() {
-                let sample = fft_output.ring_buffer.pop_front().unwrap_or(
-                    // shouldn't happen due to the check above, but just in case
-                    0.0,
-                );
-                outputs.set_output_as::<f32>(output_index, sample_index, &sample)?;
+            let node_index = node.index();
+            let node = &mut self.graph[node];
+            match command {
+                GraphCommand::Param { name, value, .. } => {
+                    if !node.processor.set_param(name.as_str(), value) {
+                        diagnostics::report(RuntimeDiagnostic::UnknownParam {
+                            node: node_index,
+                            name,
+                        });
+                    }
+                }
+                GraphCommand::Bypassed { bypassed, .. } => node.bypassed = bypassed,
+                GraphCommand::Frozen { frozen, .. } => node.frozen = frozen,
             }
         }
+    }
+
+    pub fn unconnected_input_policy(&self) -> UnconnectedInputs {
+        self.unconnected_inputs
+    }
+
+    /// Sets how required inputs that aren't connected are handled while processing.
+    pub fn set_unconnected_input_policy(&mut self, policy: UnconnectedInputs) {
+        self.unconnected_inputs = policy;
+    }
+
+    /// Connects `source_output` of `source` to `target_input` of `target` with a delay of one hop,
+    /// so the target receives the output the source produced on the previous hop.
+    ///
+    /// Feedback connections are kept outside the graph's topology, so they may form cycles (e.g.
+    /// a node feeding its own input). They take precedence over a regular connection to the same
+    /// input, and replace an existing feedback connection to it. The delayed signal is silent on
+    /// the first hop after allocation.
+    pub fn connect_feedback(
+        &mut self,
+        source: NodeIndex,
+        source_output: u32,
+        target: NodeIndex,
+        target_input: u32,
+    ) -> Result<(), FftError> {
+        let digraph = self.graph.digraph();
+        let (Some(source_node), Some(target_node)) =
+            (digraph.node_weight(source), digraph.node_weight(target))
+        else {
+            return Err(FftError::Graph(
+                "feedback connection to a missing node".to_string(),
+            ));
+        };
+
+        let Some(output_spec) = source_node.output_spec().get(source_output as usize) else {
+            return Err(FftError::Graph(format!(
+                "{} has no output {source_output}",
+                source_node.name()
+            )));
+        };
+        let Some(input_spec) = target_node.input_spec().get(target_input as usize) else {
+            return Err(FftError::Graph(format!(
+                "{} has no input {target_input}",
+                target_node.name()
+            )));
+        };
+        if output_spec.signal_type != input_spec.signal_type {
+            return Err(FftError::Graph(format!(
+                "cannot feed {} output {source_output} back into {} input {target_input}: \
+                 signal types differ",
+                source_node.name(),
+                target_node.name()
+            )));
+        }
 
+        let buffer = source_node
+            .processor()
+            .create_output_buffers(1)
+            .swap_remove(source_output as usize);
+
+        self.disconnect_feedback(target, target_input);
+        self.feedback.push(FeedbackEdge {
+            source,
+            source_output,
+            target,
+            target_input,
+            buffer,
+        });
         Ok(())
     }
 
-    fn process_node(&mut self, node_id: NodeIndex) -> GraphRunResult<()> {
-        let mut inputs: [Option<*const AnyBuffer>; 32] = [None; 32];
+    /// Connects the output named `source_output` of `source` to the input named `target_input`
+    /// of `target`, with the names from the processors'
+    /// [`output_spec`](FftProcessor::output_spec) and [`input_spec`](FftProcessor::input_spec).
+    ///
+    /// Unknown names fail with [`FftError::UnknownPort`], suggesting the closest name.
+    pub fn connect_named(
+        &mut self,
+        source: NodeIndex,
+        source_output: &str,
+        target: NodeIndex,
+        target_input: &str,
+    ) -> Result<(), FftError> {
+        let (source_output, target_input) =
+            self.port_indices(source, source_output, target, target_input)?;
+        self.graph
+            .connect(source, source_output, target, target_input)
+            .map(|_| ())
+            .map_err(|e| FftError::Graph(format!("{e:?}")))
+    }
 
-        for (source_id, edge) in self
-            .graph
-            .digraph()
-            .edges_directed(node_id, Direction::Incoming)
-            .map(|edge| (edge.source(), edge.weight()))
-        {
-            let source_buffers = &self.graph[source_id].outputs;
-            let buffer = &source_buffers[edge.source_output as usize] as *const AnyBuffer;
+    /// Like [`connect_named`](Self::connect_named), as a feedback connection, see
+    /// [`connect_feedback`](Self::connect_feedback).
+    pub fn connect_feedback_named(
+        &mut self,
+        source: NodeIndex,
+        source_output: &str,
+        target: NodeIndex,
+        target_input: &str,
+    ) -> Result<(), FftError> {
+        let (source_output, target_input) =
+            self.port_indices(source, source_output, target, target_input)?;
+        self.connect_feedback(source, source_output, target, target_input)
+    }
 
-            inputs[edge.target_input as usize] = Some(buffer);
-        }
+    /// Resolves the names of an output of `source` and an input of `target` to indices.
+    fn port_indices(
+        &self,
+        source: NodeIndex,
+        source_output: &str,
+        target: NodeIndex,
+        target_input: &str,
+    ) -> Result<(u32, u32), FftError> {
+        let digraph = self.graph.digraph();
+        let (Some(source_node), Some(target_node)) =
+            (digraph.node_weight(source), digraph.node_weight(target))
+        else {
+            return Err(FftError::Graph("connection to a missing node".to_string()));
+        };
+        Ok((
+            source_node.output_index(source_output)?,
+            target_node.input_index(target_input)?,
+        ))
+    }
 
-        let node = &mut self.graph[node_id];
+    /// Removes the feedback connection to `target_input` of `target`, returning whether there was
+    /// one.
+    pub fn disconnect_feedback(&mut self, target: NodeIndex, target_input: u32) -> bool {
+        let len = self.feedback.len();
+        self.feedback
+            .retain(|edge| edge.target != target || edge.target_input != target_input);
+        self.feedback.len() != len
+    }
 
-        node.process(
-            &inputs[..],
-            ProcEnv {
-                sample_rate: self.sample_rate,
-                block_size: self.block_size,
-                mode: ProcessMode::Block,
-            },
-        )?;
+    /// Returns every required input that isn't connected, as `(node, input index)` pairs.
+    ///
+    /// Spectrum and audio block inputs are required; parameter inputs fall back to the
+    /// processor's own values when unconnected.
+    pub fn unconnected_inputs(&self) -> Vec<(NodeIndex, u32)> {
+        let mut unconnected = Vec::new();
+        for node_id in self.graph.digraph().node_indices() {
+            let node = &self.graph[node_id];
+            for (index, spec) in node.input_spec().iter().enumerate() {
+                let index = index as u32;
+                let connected = self
+                    .graph
+                    .digraph()
+                    .edges_directed(node_id, Direction::Incoming)
+                    .any(|edge| edge.weight().target_input == index)
+                    || self
+                        .feedback
+                        .iter()
+                        .any(|edge| edge.target == node_id && edge.target_input == index);
+                if !connected && Self::is_required(spec) {
+                    unconnected.push((node_id, index));
+                }
+            }
+        }
+        unconnected
+    }
 
-        Ok(())
+    /// Checks the graph for mistakes that would otherwise only show while processing, see
+    /// [`diagnostics`](Self::diagnostics). Fails with [`FftError::InvalidGraph`] listing every
+    /// error found; warnings don't fail.
+    pub fn validate(&self) -> Result<(), FftError> {
+        let errors: Vec<_> = self
+            .diagnostics()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(FftError::InvalidGraph(errors))
+        }
     }
-}
 
-impl<F: Fft> Processor for FftGraph<F> {
-    fn input_spec(&self) -> Vec<SignalSpec> {
-        let mut specs = Vec::with_capacity(self.inputs.len());
-        for i in 0..self.inputs.len() {
-            specs.push(SignalSpec::new(i.to_string(), f32::signal_type()));
+    /// Returns every problem with how the graph is put together:
+    ///
+    /// - connections between ports of different signal types, such as an audio block output
+    ///   feeding a spectrum input,
+    /// - required inputs that aren't connected, see
+    ///   [`unconnected_inputs`](Self::unconnected_inputs),
+    /// - cycles of regular connections, which need a [feedback](Self::connect_feedback) connection
+    ///   to be processed,
+    /// - audio outputs that no audio, sidechain or frame input reaches (a warning, as a graph may
+    ///   generate its output).
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let digraph = self.graph.digraph();
+        let diagnostic = |node: NodeIndex, kind| Diagnostic {
+            node,
+            name: self.graph[node].name().to_string(),
+            kind,
+        };
+        let mut diagnostics = Vec::new();
+
+        let edges = digraph
+            .edge_references()
+            .map(|edge| {
+                let weight = edge.weight();
+                (
+                    edge.source(),
+                    weight.source_output,
+                    edge.target(),
+                    weight.target_input,
+                )
+            })
+            .chain(self.feedback.iter().map(|edge| {
+                (
+                    edge.source,
+                    edge.source_output,
+                    edge.target,
+                    edge.target_input,
+                )
+            }));
+        for (source, source_output, target, target_input) in edges {
+            let output = self.graph[source].output_spec().get(source_output as usize);
+            let input = self.graph[target].input_spec().get(target_input as usize);
+            if let (Some(output), Some(input)) = (output, input)
+                && output.signal_type != input.signal_type
+            {
+                diagnostics.push(diagnostic(
+                    target,
+                    DiagnosticKind::TypeMismatch {
+                        input: target_input,
+                        source: self.graph[source].name().to_string(),
+                        output: source_output,
+                        expected: Self::describe_signal(input),
+                        found: Self::describe_signal(output),
+                    },
+                ));
+            }
         }
-        specs
+
+        for (node, input) in self.unconnected_inputs() {
+            let input_name = self.graph[node].input_spec()[input as usize].name.clone();
+            diagnostics.push(diagnostic(
+                node,
+                DiagnosticKind::MissingInput { input, input_name },
+            ));
+        }
+
+        for component in algo::tarjan_scc(digraph) {
+            let is_cycle = component.len() > 1 || digraph.contains_edge(component[0], component[0]);
+            if is_cycle {
+                diagnostics.extend(
+                    component
+                        .into_iter()
+                        .map(|node| diagnostic(node, DiagnosticKind::Cycle)),
+                );
+            }
+        }
+
+        let reached = self.reachable_from_inputs();
+        for &output in self.outputs.keys() {
+            if !reached.contains(&output) {
+                diagnostics.push(diagnostic(output, DiagnosticKind::UnreachableOutput));
+            }
+        }
+
+        diagnostics
     }
 
-    fn output_spec(&self) -> Vec<SignalSpec> {
-        let mut specs = Vec::with_capacity(self.outputs.len());
-        for i in 0..self.outputs.len() {
-            specs.push(SignalSpec::new(i.to_string(), f32::signal_type()));
+    /// Returns every node downstream of an audio, sidechain or frame input, following regular and
+    /// feedback connections.
+    fn reachable_from_inputs(&self) -> BTreeSet<NodeIndex> {
+        let digraph = self.graph.digraph();
+        let mut reached = BTreeSet::new();
+        let mut pending: Vec<_> = self
+            .inputs
+            .keys()
+            .chain(&self.frame_inputs)
+            .copied()
+            .collect();
+        while let Some(node) = pending.pop() {
+            if !reached.insert(node) {
+                continue;
+            }
+            pending.extend(digraph.neighbors_directed(node, Direction::Outgoing));
+            pending.extend(
+                self.feedback
+                    .iter()
+                    .filter(|edge| edge.source == node)
+                    .map(|edge| edge.target),
+            );
         }
-        specs
+        reached
     }
 
-    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
-        let mut buffers = Vec::with_capacity(self.outputs.len());
-        for _ in 0..self.outputs.len() {
-            buffers.push(AnyBuffer::zeros::<f32>(size));
+    /// Names the kind of signal a port carries, for diagnostics.
+    fn describe_signal(spec: &SignalSpec) -> &'static str {
+        if spec.signal_type == F::RealFft::signal_type() {
+            "spectra"
+        } else if spec.signal_type == F::AudioBlock::signal_type() {
+            "audio blocks"
+        } else if spec.signal_type == f32::signal_type() {
+            "control values"
+        } else if spec.signal_type == bool::signal_type() {
+            "gates"
+        } else {
+            "another signal type"
         }
-        buffers
     }
 
-    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
-        FftGraph::allocate(self, sample_rate, max_block_size);
+    fn is_required(spec: &SignalSpec) -> bool {
+        spec.signal_type == F::RealFft::signal_type()
+            || spec.signal_type == F::AudioBlock::signal_type()
     }
 
-    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
-        FftGraph::resize_buffers(self, sample_rate, block_size);
+    /// Returns the delay in samples between an audio input and the audio outputs.
+    ///
+    /// This is the analysis/resynthesis latency of the STFT (`fft_length - 1`, or one less than
+    /// the [frame length](Self::frame_length) of a WOLA filterbank, the same for every host block
+    /// size) plus the largest latency reported by the processors along any path to an audio
+    /// output.
+    pub fn latency_samples(&self) -> usize {
+        FftOutput::<F>::latency(self.frame_length()) + self.latency_frames() * self.hop_length()
     }
 
-    fn process(
-        &mut self,
-        inputs: ProcessorInputs,
-        outputs: ProcessorOutputs,
-    ) -> Result<(), ProcessorError> {
-        self.process_inner(inputs, outputs)
+    /// Returns the largest number of frames of processor latency along any path to an audio or
+    /// frame output.
+    pub(crate) fn latency_frames(&self) -> usize {
+        let Ok(order) = algo::toposort(self.graph.digraph(), None) else {
+            return 0;
+        };
+
+        let mut path_latency = BTreeMap::new();
+        for node in order {
+            let upstream = self
+                .graph
+                .digraph()
+                .neighbors_directed(node, Direction::Incoming)
+                .filter_map(|source| path_latency.get(&source).copied())
+                .max()
+                .unwrap_or(0);
+            path_latency.insert(node, upstream + self.graph[node].latency_frames());
+        }
+
+        self.outputs
+            .keys()
+            .chain(&self.frame_outputs)
+            .filter_map(|output| path_latency.get(output).copied())
+            .max()
+            .unwrap_or(0)
     }
-}
 
-pub struct FftGraphBuilder<F: Fft>(GraphBuilder<FftGraph<F>>);
+    /// Returns the FFT plans used by the graph's audio inputs and outputs.
+    pub fn plans(&self) -> &FftPlans {
+        &self.plans
+    }
 
-impl<F: Fft> Deref for FftGraphBuilder<F> {
-    type Target = GraphBuilder<FftGraph<F>>;
+    /// Sets the FFT plans used by audio inputs and outputs added from now on, e.g. to share them
+    /// with other graphs. Their length must match the graph's FFT length.
+    pub fn set_plans(&mut self, plans: FftPlans) {
+        assert_eq!(plans.fft_length(), F::N_FFT, "FFT plan length mismatch");
+        self.plans = plans;
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    pub fn add_audio_input(&mut self) -> NodeIndex {
+        self.add_input(FftInput::default())
     }
-}
 
-impl<F: Fft> FftGraphBuilder<F> {
-    pub fn new(hop_length: usize, window_fn: WindowFunction) -> Self {
-        Self(GraphBuilder::from_inner(FftGraph::new(
-            hop_length, window_fn,
-        )))
+    /// Adds an audio input that is only analyzed, returning its FFT node, e.g. to key a ducker or
+    /// a cross-synthesis off another signal than the one being processed.
+    ///
+    /// A sidechain input counts as an audio input, in the order the inputs were added, but reads
+    /// silence instead of failing while unconnected in a raug graph. Its frames are scaled by
+    /// their own gain, see [`set_sidechain_gain`](Self::set_sidechain_gain).
+    pub fn add_sidechain_input(&mut self) -> NodeIndex {
+        self.add_input(FftInput {
+            sidechain: true,
+            ..FftInput::default()
+        })
     }
 
-    pub fn add_audio_input(&self) -> NodeBuilder<FftGraph<F>> {
-        let node_id = self.with_inner(|graph| graph.add_audio_input());
-        NodeBuilder::new(self.0.clone(), node_id)
+    fn add_input(&mut self, mut input: FftInput<F>) -> NodeIndex {
+        input.frames.set_padding(self.padding_mode, F::N_FFT);
+        input.frames.set_lead(self.engine_lead());
+        input.frames.reserve(self.frame_length() + self.block_size);
+        let null = self.add_processor(Null::<F>::new());
+        let fft = self.add_processor(RealFft::<F>::with_plan(self.plans.forward.clone()));
+        self.graph.connect(null, 0, fft, 0).unwrap();
+        self.inputs.insert(null, input);
+        fft
     }
 
-    pub fn add_audio_output(&self) -> NodeBuilder<FftGraph<F>> {
-        let node_id = self.with_inner(|graph| graph.add_audio_output());
-        NodeBuilder::new(self.0.clone(), node_id)
+    /// Returns whether `node` is the FFT node of a sidechain input.
+    pub fn is_sidechain_input(&self, node: NodeIndex) -> bool {
+        self.audio_input_source(node)
+            .and_then(|source| self.inputs.get(&source))
+            .is_some_and(|input| input.sidechain)
     }
-}
 
-impl<F: Fft> Processor for FftGraphBuilder<F> {
-    fn input_spec(&self) -> Vec<SignalSpec> {
-        self.with_inner(|graph| graph.input_spec())
+    /// Returns the linear gain of a sidechain input, or `None` if `sidechain` isn't one.
+    pub fn sidechain_gain(&self, sidechain: NodeIndex) -> Option<f32> {
+        self.audio_input_source(sidechain)
+            .and_then(|source| self.inputs.get(&source))
+            .filter(|input| input.sidechain)
+            .map(|input| input.gain)
     }
 
-    fn output_spec(&self) -> Vec<SignalSpec> {
-        self.with_inner(|graph| graph.output_spec())
+    /// Sets the linear gain applied to a sidechain input before analysis, independently of the
+    /// signals being processed. Returns `false` if `sidechain` isn't the FFT node of a sidechain
+    /// input.
+    pub fn set_sidechain_gain(&mut self, sidechain: NodeIndex, gain: f32) -> bool {
+        let Some(source) = self.audio_input_source(sidechain) else {
+            return false;
+        };
+        match self.inputs.get_mut(&source) {
+            Some(input) if input.sidechain => {
+                input.gain = gain;
+                true
+            }
+            _ => false,
+        }
     }
 
-    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
-        self.with_inner(|graph| graph.create_output_buffers(size))
+    /// Returns the linear gain of an audio output, or `None` if `output` isn't one.
+    pub fn output_gain(&self, output: NodeIndex) -> Option<f32> {
+        self.outputs.get(&output).map(|fft_output| fft_output.gain)
     }
 
-    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
-        self.with_inner(|graph| graph.allocate(sample_rate, max_block_size));
+    /// Sets the linear gain applied to an audio output as it's read, e.g. to trim the
+    /// resynthesized level without multiplying the graph's output outside of it. Returns `false`
+    /// if `output` isn't the inverse FFT node of an audio output.
+    pub fn set_output_gain(&mut self, output: NodeIndex, gain: f32) -> bool {
+        match self.outputs.get_mut(&output) {
+            Some(fft_output) => {
+                fft_output.gain = gain;
+                true
+            }
+            None => false,
+        }
     }
 
-    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
-        self.with_inner(|graph| graph.resize_buffers(sample_rate, block_size));
+    /// Returns the normalization of an audio output, or `None` if `output` isn't one.
+    pub fn output_normalization(&self, output: NodeIndex) -> Option<OutputNormalization> {
+        self.outputs
+            .get(&output)
+            .map(|fft_output| fft_output.normalization)
     }
 
-    fn process(
+    /// Sets how an audio output is normalized as it's read, on top of its
+    /// [gain](Self::set_output_gain). Returns `false` if `output` isn't the inverse FFT node of
+    /// an audio output.
+    pub fn set_output_normalization(
         &mut self,
-        inputs: ProcessorInputs,
-        outputs: ProcessorOutputs,
-    ) -> Result<(), ProcessorError> {
-        self.with_inner(|graph| graph.process(inputs, outputs))
+        output: NodeIndex,
+        normalization: OutputNormalization,
+    ) -> bool {
+        match self.outputs.get_mut(&output) {
+            Some(fft_output) => {
+                fft_output.normalization = normalization;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a spectral input for when the graph is nested in an
+    /// [`FftSubgraph`](crate::subgraph::FftSubgraph), returning the node whose output carries the
+    /// spectrum.
+    pub fn add_frame_input(&mut self) -> NodeIndex {
+        let node = self.add_processor(FramePort::<F>::input());
+        self.frame_inputs.push(node);
+        node
+    }
+
+    /// Adds a spectral output for when the graph is nested in an
+    /// [`FftSubgraph`](crate::subgraph::FftSubgraph), returning the node to connect the spectrum
+    /// to.
+    pub fn add_frame_output(&mut self) -> NodeIndex {
+        let node = self.add_processor(FramePort::<F>::output());
+        self.frame_outputs.push(node);
+        node
+    }
+
+    pub fn frame_inputs(&self) -> &[NodeIndex] {
+        &self.frame_inputs
+    }
+
+    pub fn frame_outputs(&self) -> &[NodeIndex] {
+        &self.frame_outputs
+    }
+
+    /// Creates a [`FrameBuffer`] with room for `capacity` frames and registers it under `name`,
+    /// replacing any buffer previously registered under that name.
+    pub fn add_frame_buffer(&mut self, name: impl Into<String>, capacity: usize) -> FrameBuffer<F> {
+        let buffer = FrameBuffer::new(capacity);
+        self.frame_buffers.insert(name.into(), buffer.clone());
+        buffer
+    }
+
+    /// Returns the frame buffer registered under `name`.
+    pub fn frame_buffer(&self, name: &str) -> Option<FrameBuffer<F>> {
+        self.frame_buffers.get(name).cloned()
+    }
+
+    /// Returns the names of the registered frame buffers in sorted order.
+    pub fn frame_buffer_names(&self) -> impl Iterator<Item = &str> {
+        self.frame_buffers.keys().map(String::as_str)
+    }
+
+    pub fn add_audio_output(&mut self) -> NodeIndex {
+        let mut inverse = InverseRealFft::<F>::with_plan(self.plans.inverse.clone());
+        inverse.limit = self.output_limiter;
+        let idx = self.add_processor(inverse);
+        let mut output = FftOutput::<F>::default();
+        output.overlap_add.set_frame_length(self.frame_length());
+        self.outputs.insert(idx, output);
+        idx
+    }
+
+    /// Adds a group of audio inputs (e.g. the channels of an ambisonic signal), returning their
+    /// FFT nodes in channel order.
+    pub fn add_audio_inputs(&mut self, count: usize) -> Vec<NodeIndex> {
+        (0..count).map(|_| self.add_audio_input()).collect()
+    }
+
+    /// Adds a group of audio outputs, returning their inverse FFT nodes in channel order.
+    pub fn add_audio_outputs(&mut self, count: usize) -> Vec<NodeIndex> {
+        (0..count).map(|_| self.add_audio_output()).collect()
+    }
+
+    /// Adds a left/right pair of audio inputs, returning their FFT nodes in that order.
+    pub fn add_stereo_input(&mut self) -> [NodeIndex; 2] {
+        [self.add_audio_input(), self.add_audio_input()]
+    }
+
+    /// Adds a left/right pair of audio outputs, returning their inverse FFT nodes in that order.
+    pub fn add_stereo_output(&mut self) -> [NodeIndex; 2] {
+        [self.add_audio_output(), self.add_audio_output()]
+    }
+
+    /// Connects output 0 of `source` to input 0 of `target`.
+    fn connect_first(&mut self, source: NodeIndex, target: NodeIndex) -> Result<(), FftError> {
+        self.graph
+            .connect(source, 0, target, 0)
+            .map(|_| ())
+            .map_err(|e| FftError::Graph(format!("{e:?}")))
+    }
+
+    pub fn add_processor(&mut self, processor: impl FftProcessor) -> NodeIndex {
+        self.add_boxed_processor(Box::new(processor))
+    }
+
+    /// Adds a processor working on blocks of consecutive frames, keeping the frames it needs.
+    /// See [`block`](crate::block).
+    pub fn add_block_processor(&mut self, processor: impl FftBlockProcessor<F>) -> NodeIndex {
+        self.add_processor(BlockNode::new(processor))
+    }
+
+    /// Adds a processor by its name in the graph's [registry](Self::registry), or returns `None`
+    /// if there is no processor with that name.
+    pub fn add_builtin(&mut self, name: &str, params: &ProcessorParams) -> Option<NodeIndex> {
+        let processor = self.registry.create(name, params)?;
+        Some(self.add_boxed_processor(processor))
+    }
+
+    /// Returns the registry [`add_builtin`](Self::add_builtin) creates processors with, which
+    /// holds the builtins unless replaced with [`set_registry`](Self::set_registry).
+    pub fn registry(&self) -> &ProcessorRegistry<F> {
+        &self.registry
+    }
+
+    /// Returns the registry of the graph for adding processors of its own, which
+    /// [`add_builtin`](Self::add_builtin) then creates like the builtins.
+    pub fn registry_mut(&mut self) -> &mut ProcessorRegistry<F> {
+        &mut self.registry
+    }
+
+    pub fn set_registry(&mut self, registry: ProcessorRegistry<F>) {
+        self.registry = registry;
+    }
+
+    pub fn add_boxed_processor(&mut self, processor: Box<dyn FftProcessor>) -> NodeIndex {
+        let mut node = FftProcessorNode::new_from_boxed(processor);
+        node.processor_mut()
+            .set_graph_lookahead(self.lookahead_frames);
+        node.allocate(self.sample_rate);
+        node.resize_buffers(self.sample_rate);
+        self.scratch.reserve(node.processor().scratch_size());
+
+        self.graph.add_node(node)
+    }
+
+    pub fn allocate(&mut self, sample_rate: f32, block_size: usize) {
+        diagnostics::init();
+        self.set_sample_rate(sample_rate);
+        self.block_size = block_size;
+        self.frame_index = 0;
+        self.window_crossfade = None;
+        self.silence = vec![0.0; block_size];
+
+        // start over with empty buffers, so the outputs stay exactly the STFT latency behind the
+        // inputs
+        let frame_length = self.frame_length();
+        for fft_input in self.inputs.values_mut() {
+            fft_input.frames.clear();
+            fft_input.frames.reserve(frame_length + block_size);
+        }
+        let padding = self.padding_mode.padding(F::N_FFT);
+        // like the zeros every input starts with
+        self.idle_samples = padding + self.engine_lead();
+        for fft_output in self.outputs.values_mut() {
+            fft_output.reset(block_size, frame_length);
+            fft_output.overlap_add.skip(padding);
+        }
+        self.reset_dithers();
+
+        let scratch = &mut self.scratch;
+        self.graph.visit_mut(|_i, node| {
+            node.allocate(sample_rate);
+            scratch.reserve(node.processor().scratch_size());
+            VisitResult::Continue::<()>
+        });
+
+        for edge in self.feedback.iter_mut() {
+            edge.buffer = self.graph[edge.source]
+                .processor()
+                .create_output_buffers(1)
+                .swap_remove(edge.source_output as usize);
+        }
+    }
+
+    pub fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        self.set_sample_rate(sample_rate);
+        self.block_size = block_size;
+
+        self.graph.visit_mut(|_i, node| {
+            node.resize_buffers(sample_rate);
+            VisitResult::Continue::<()>
+        });
+    }
+
+    /// Updates the sample rate, telling every processor if it changed since the graph was last
+    /// allocated or resized.
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        let old = self.sample_rate;
+        self.sample_rate = sample_rate;
+        if old <= 0.0 || old == sample_rate {
+            return;
+        }
+
+        log::debug!("FftGraph sample rate changed from {old} to {sample_rate}");
+        self.graph.visit_mut(|_i, node| {
+            node.sample_rate_changed(old, sample_rate);
+            VisitResult::Continue::<()>
+        });
+    }
+
+    fn process_inner(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let timer = ProcessTimer::start();
+
+        // in sample mode, the host calls us once per sample of the block, so only that sample is
+        // buffered and only one resynthesized sample is written back
+        let samples = match inputs.mode() {
+            ProcessMode::Block => 0..self.block_size,
+            ProcessMode::Sample(index) => index..index + 1,
+        };
+        self.advance_clock(samples.len());
+
+        // fill our input buffers with the input signals
+        for (input_index, fft_input) in self.inputs.values_mut().enumerate() {
+            let port = input_index as u32;
+            let audio_input = match inputs.input_as::<f32>(input_index) {
+                Some(audio_input) => audio_input,
+                None if fft_input.sidechain => &self.silence[..],
+                None => {
+                    return Err(FftError::MissingInput {
+                        node: "FftGraph".to_string(),
+                        input: port,
+                    }
+                    .into());
+                }
+            };
+            let audio_input =
+                audio_input
+                    .get(samples.clone())
+                    .ok_or_else(|| FftError::BufferSizeMismatch {
+                        node: "FftGraph".to_string(),
+                        port,
+                        expected: samples.end,
+                        found: audio_input.len(),
+                    })?;
+
+            fft_input.frames.push(audio_input);
+        }
+
+        self.process_frames()?;
+
+        // for each output, write as many samples as are available and zero-fill the rest
+        let window_sum_peak = self.output_peak();
+        for (output_index, fft_output) in self.outputs.values_mut().enumerate() {
+            let scale = fft_output.scale(window_sum_peak);
+            let missing = samples
+                .len()
+                .saturating_sub(fft_output.overlap_add.available());
+            if missing > 0 {
+                diagnostics::report(RuntimeDiagnostic::Underrun {
+                    output: output_index,
+                    samples: missing,
+                });
+                self.metrics.add_underruns(missing as u64);
+            }
+            for sample_index in samples.clone() {
+                let sample = fft_output.overlap_add.pop().unwrap_or(0.0) * scale;
+                outputs.set_output_as::<f32>(output_index, sample_index, &sample)?;
+            }
+        }
+
+        timer.finish(&self.metrics);
+        Ok(())
+    }
+
+    /// Processes a block of audio outside of a raug graph.
+    ///
+    /// `inputs` and `outputs` hold one slice per audio input and output, in the order they were
+    /// added. The outputs lag the inputs by [`latency_samples`](Self::latency_samples) whatever the
+    /// length of the blocks, so the output is the same however the input is split up. A graph
+    /// without audio inputs runs a frame for every hop of output instead. The graph must have been
+    /// allocated first.
+    pub fn process_block(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+    ) -> ProcResult<()> {
+        let timer = ProcessTimer::start();
+
+        for (channel, input) in inputs.iter().enumerate() {
+            self.push_input(channel, input);
+        }
+        self.advance_clock(outputs.first().map_or(0, |output| output.len()));
+
+        self.process_frames()?;
+
+        for (channel, output) in outputs.iter_mut().enumerate() {
+            self.read_output(channel, output);
+        }
+
+        timer.finish(&self.metrics);
+        Ok(())
+    }
+
+    /// Buffers samples for the audio input at `channel`.
+    pub(crate) fn push_input(&mut self, channel: usize, samples: &[f32]) {
+        if let Some(fft_input) = self.inputs.values_mut().nth(channel) {
+            fft_input.frames.push(samples);
+        }
+    }
+
+    /// Counts `samples` towards the next frame of a graph without audio inputs, such as one of
+    /// generators, which runs a frame whenever a hop's worth of blocks has passed. Does nothing
+    /// for graphs with audio inputs, which run whenever they have buffered a frame.
+    pub(crate) fn advance_clock(&mut self, samples: usize) {
+        if self.inputs.is_empty() {
+            self.idle_samples += samples;
+        }
+    }
+
+    /// Fills `output` with resynthesized samples of the audio output at `channel`, zero-filling
+    /// whatever isn't available yet.
+    pub(crate) fn read_output(&mut self, channel: usize, output: &mut [f32]) {
+        let window_sum_peak = self.output_peak();
+        let Some(fft_output) = self.outputs.values_mut().nth(channel) else {
+            output.fill(0.0);
+            return;
+        };
+        let read = fft_output.overlap_add.read_available(output);
+        let scale = fft_output.scale(window_sum_peak);
+        if scale != 1.0 {
+            output[..read]
+                .iter_mut()
+                .for_each(|sample| *sample *= scale);
+        }
+        if read < output.len() {
+            diagnostics::report(RuntimeDiagnostic::Underrun {
+                output: channel,
+                samples: output.len() - read,
+            });
+            self.metrics.add_underruns((output.len() - read) as u64);
+        }
+        if let Some(dither) = self.dithers.get_mut(channel) {
+            dither.process(output);
+        }
+    }
+
+    /// Moves the nodes of `old` into this graph, for each `(old, new)` pair of node indices,
+    /// along with the samples buffered by paired audio inputs and outputs.
+    ///
+    /// Both graphs must share their FFT settings, and paired nodes must hold processors of the
+    /// same type. The nodes left behind in `old` are this graph's previous ones.
+    pub fn migrate_nodes(&mut self, old: &mut Self, pairs: &[(NodeIndex, NodeIndex)]) {
+        for &(old_node, new_node) in pairs {
+            mem::swap(&mut old.graph[old_node], &mut self.graph[new_node]);
+            self.scratch
+                .reserve(self.graph[new_node].processor().scratch_size());
+
+            if let (Some(old_output), Some(new_output)) = (
+                old.outputs.get_mut(&old_node),
+                self.outputs.get_mut(&new_node),
+            ) {
+                mem::swap(&mut old_output.overlap_add, &mut new_output.overlap_add);
+            }
+
+            if let (Some(old_input), Some(new_input)) = (
+                old.audio_input_source(old_node),
+                self.audio_input_source(new_node),
+            ) && let (Some(old_input), Some(new_input)) = (
+                old.inputs.get_mut(&old_input),
+                self.inputs.get_mut(&new_input),
+            ) {
+                mem::swap(&mut old_input.frames, &mut new_input.frames);
+            }
+        }
+
+        self.frame_index = old.frame_index;
+        self.idle_samples = old.idle_samples;
+        self.transport = old.transport;
+    }
+
+    /// Creates a graph with the same nodes, connections, processor configurations and settings,
+    /// e.g. to stamp out a configured effect once per track or voice.
+    ///
+    /// Every processor is copied with [`FftProcessor::clone_boxed`], which fails with
+    /// [`FftError::Graph`] for processors that can't be copied (such as ones fed through a
+    /// channel). Nodes keep their indices, so the `NodeIndex`es of this graph address the same
+    /// nodes of the copy. Nothing is carried over from processing: the copy starts out
+    /// unallocated, with empty audio buffers, and its processors are reset when it's allocated.
+    /// Registered [frame buffers](Self::add_frame_buffer) are shared with this graph, like the
+    /// processors' copies of them.
+    pub fn clone_template(&self) -> Result<Self, FftError> {
+        let mut graph = Self::try_new(self.hop_length, self.window_fn)?;
+        graph.seed = self.seed;
+        graph.denormal_protection = self.denormal_protection;
+        graph.nan_guard = self.nan_guard;
+        graph.output_limiter = self.output_limiter;
+        graph.output_dither = self.output_dither;
+        graph.unconnected_inputs = self.unconnected_inputs;
+        graph.plans = self.plans.clone();
+        graph.registry = self.registry.clone();
+        graph.frame_buffers = self.frame_buffers.clone();
+        graph.frame_inputs = self.frame_inputs.clone();
+        graph.frame_outputs = self.frame_outputs.clone();
+
+        // fill the gaps left by removed nodes with placeholders, so every node keeps its index
+        let digraph = self.graph.digraph();
+        let bound = digraph
+            .node_indices()
+            .map(|node| node.index() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut placeholders = Vec::new();
+        for index in 0..bound {
+            let node_id = NodeIndex::new(index);
+            let Some(node) = digraph.node_weight(node_id) else {
+                placeholders.push(graph.add_processor(Null::<F>::new()));
+                continue;
+            };
+            let processor = node.processor().clone_boxed().ok_or_else(|| {
+                FftError::Graph(format!("{} (node {index}) can't be cloned", node.name()))
+            })?;
+            let new_id = graph.add_boxed_processor(processor);
+            debug_assert_eq!(new_id, node_id);
+            graph.graph[new_id].bypassed = node.bypassed;
+            graph.graph[new_id].frozen = node.frozen;
+        }
+
+        for edge in digraph.edge_references() {
+            let weight = edge.weight();
+            graph
+                .graph
+                .connect(
+                    edge.source(),
+                    weight.source_output,
+                    edge.target(),
+                    weight.target_input,
+                )
+                .map_err(|e| FftError::Graph(format!("{e:?}")))?;
+        }
+        for node in placeholders {
+            graph
+                .graph
+                .remove_node(node)
+                .map_err(|e| FftError::Graph(format!("{e:?}")))?;
+        }
+
+        for edge in &self.feedback {
+            graph.connect_feedback(
+                edge.source,
+                edge.source_output,
+                edge.target,
+                edge.target_input,
+            )?;
+        }
+        for (&node, input) in &self.inputs {
+            graph.inputs.insert(
+                node,
+                FftInput {
+                    sidechain: input.sidechain,
+                    gain: input.gain,
+                    ..FftInput::default()
+                },
+            );
+        }
+        for (&node, output) in &self.outputs {
+            let mut copy = FftOutput::default();
+            copy.gain = output.gain;
+            copy.normalization = output.normalization;
+            graph.outputs.insert(node, copy);
+        }
+        graph.set_padding_mode(self.padding_mode);
+        graph.set_engine(self.engine);
+        graph.set_spectral_scaling(self.spectral_scaling);
+        graph.lookahead_frames = self.lookahead_frames;
+
+        Ok(graph)
+    }
+
+    /// Estimates the CPU time and memory it takes to run the graph at `sample_rate` in blocks of
+    /// `block_size` samples, so a host can warn before enabling an expensive patch live.
+    ///
+    /// Silence is processed off-line through a [copy](Self::clone_template) of the graph, leaving
+    /// this one untouched: first enough blocks to cover the graph's latency, then enough to cover
+    /// [`COST_ESTIMATE_HOPS`] hops, which are timed. Processors that do less work on silence
+    /// will look cheaper than they are. A graph without audio inputs doesn't process blocks and
+    /// takes no time.
+    pub fn estimate_cost(
+        &self,
+        sample_rate: f32,
+        block_size: usize,
+    ) -> Result<CostEstimate, FftError> {
+        if block_size == 0 || sample_rate <= 0.0 {
+            return Err(FftError::Graph(format!(
+                "can't estimate the cost of blocks of {block_size} samples at {sample_rate} Hz"
+            )));
+        }
+
+        let mut graph = self.clone_template()?;
+        graph.allocate(sample_rate, block_size);
+
+        let silence = vec![0.0; block_size];
+        let inputs = vec![&silence[..]; graph.num_audio_inputs()];
+        let mut output_buffers = vec![vec![0.0; block_size]; graph.num_audio_outputs()];
+        let mut process = |graph: &mut Self| {
+            let mut outputs: Vec<&mut [f32]> =
+                output_buffers.iter_mut().map(|o| &mut o[..]).collect();
+            graph
+                .process_block(&inputs, &mut outputs)
+                .map_err(|e| FftError::Graph(e.to_string()))
+        };
+
+        let warm_up = graph.latency_samples() + F::N_FFT;
+        for _ in 0..warm_up.div_ceil(block_size) {
+            process(&mut graph)?;
+        }
+
+        let blocks = (COST_ESTIMATE_HOPS * graph.hop_length)
+            .div_ceil(block_size)
+            .max(1);
+        let mut total = Duration::ZERO;
+        let mut max = Duration::ZERO;
+        for _ in 0..blocks {
+            process(&mut graph)?;
+            let duration = graph.metrics.last_process_duration();
+            total += duration;
+            max = max.max(duration);
+        }
+
+        Ok(CostEstimate {
+            sample_rate,
+            block_size,
+            blocks,
+            mean_block_duration: total / blocks as u32,
+            max_block_duration: max,
+            buffer_bytes: graph.buffer_bytes(),
+        })
+    }
+
+    /// Adds up the memory held by the graph's own buffers, see [`CostEstimate::buffer_bytes`].
+    fn buffer_bytes(&self) -> usize {
+        let mut bytes = 0;
+        for node in self.graph.digraph().node_weights() {
+            bytes += node
+                .outputs
+                .iter()
+                .map(any_buffer_bytes::<F>)
+                .sum::<usize>();
+        }
+        bytes += self
+            .feedback
+            .iter()
+            .map(|edge| any_buffer_bytes::<F>(&edge.buffer))
+            .sum::<usize>();
+
+        let scratch = self.scratch.size();
+        bytes += scratch.reals * size_of::<f32>() + scratch.complexes * size_of::<Complex32>();
+
+        // each input keeps up to two frames of samples, each output a block on top of the frames
+        // it's adding up
+        let input_bytes = 2 * F::N_FFT * size_of::<f32>() + size_of::<F::AudioBlock>();
+        let output_bytes = (self.block_size + 2 * F::N_FFT) * size_of::<f32>();
+        bytes += self.inputs.len() * input_bytes + self.outputs.len() * output_bytes;
+        bytes += self.silence.len() * size_of::<f32>();
+        bytes
+    }
+
+    /// Takes a snapshot of everything the graph has accumulated while processing: the samples
+    /// buffered by its audio inputs and outputs, the frame clock and transport, the values held
+    /// by feedback connections and the outputs, bypass and freeze state of every node, along with
+    /// the state of every processor, see [`FftProcessor::save_state`].
+    ///
+    /// Parameters aren't part of the snapshot. Take it from outside the audio thread, e.g. while
+    /// holding the graph's lock, since it allocates.
+    pub fn save_state(&self) -> GraphState {
+        let mut state = StateWriter::new();
+        state.write_bytes(&STATE_MAGIC);
+        state.write(&STATE_VERSION);
+        state.write_len(F::N_FFT);
+        state.write_len(self.hop_length);
+        self.write_state(&mut state);
+        GraphState::from_bytes(state.into_bytes())
+    }
+
+    /// Restores a snapshot taken with [`save_state`](Self::save_state) of this graph or of one
+    /// built the same way, with the same nodes, connections and FFT settings.
+    ///
+    /// Fails without changing the graph if the snapshot doesn't fit it. Any window crossfade in
+    /// progress is finished.
+    pub fn load_state(&mut self, state: &GraphState) -> Result<(), FftError> {
+        let mut reader = StateReader::new(state.as_bytes());
+        if reader.read_bytes(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(FftError::InvalidState("not a graph state".to_string()));
+        }
+        let version = reader.read_new::<u32>()?;
+        if version != STATE_VERSION {
+            return Err(FftError::InvalidState(format!(
+                "unsupported graph state version {version}"
+            )));
+        }
+        let fft_length = reader.read_len()?;
+        let hop_length = reader.read_len()?;
+        if fft_length != F::N_FFT || hop_length != self.hop_length {
+            return Err(FftError::InvalidState(format!(
+                "state of a graph with fft_length {fft_length} and hop_length {hop_length}, \
+                 expected {} and {}",
+                F::N_FFT,
+                self.hop_length
+            )));
+        }
+
+        // processors may have been partially restored before an error, so put everything back
+        let mut backup = StateWriter::new();
+        self.write_state(&mut backup);
+        let backup = backup.into_bytes();
+        let result = self.read_state(&mut reader).and_then(|()| {
+            if reader.is_empty() {
+                Ok(())
+            } else {
+                Err(FftError::InvalidState(format!(
+                    "{} bytes left over",
+                    reader.remaining()
+                )))
+            }
+        });
+        if result.is_err() {
+            self.read_state(&mut StateReader::new(&backup))?;
+        }
+        self.window_crossfade = None;
+        result
+    }
+
+    /// Writes the graph's state without a header, also for graphs nested in an
+    /// [`FftSubgraph`](crate::subgraph::FftSubgraph).
+    pub(crate) fn write_state(&self, state: &mut StateWriter) {
+        state.write(&self.frame_index);
+        state.write(&self.transport);
+
+        state.write_len(self.inputs.len());
+        for input in self.inputs.values() {
+            state.write(&input.frames);
+        }
+        state.write_len(self.outputs.len());
+        for output in self.outputs.values() {
+            state.write(&output.overlap_add);
+        }
+
+        state.write_len(self.feedback.len());
+        for edge in &self.feedback {
+            save_buffer::<F>(&edge.buffer, state);
+        }
+
+        state.write_len(self.graph.node_count());
+        for node_id in self.graph.digraph().node_indices() {
+            let node = &self.graph[node_id];
+            state.write_len(node_id.index());
+            state.write_str(node.name());
+            state.write(&node.bypassed);
+            state.write(&node.frozen);
+            for output in &node.outputs {
+                save_buffer::<F>(output, state);
+            }
+
+            // length-prefixed, so a processor reading the wrong amount is caught
+            let mut processor = StateWriter::new();
+            node.processor.save_state(&mut processor);
+            state.write(&processor.into_bytes());
+        }
+    }
+
+    /// Reads state written by [`write_state`](Self::write_state).
+    pub(crate) fn read_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.frame_index)?;
+        state.read(&mut self.transport)?;
+
+        let num_inputs = state.read_len()?;
+        if num_inputs != self.inputs.len() {
+            return Err(count_mismatch(
+                "audio inputs",
+                self.inputs.len(),
+                num_inputs,
+            ));
+        }
+        for input in self.inputs.values_mut() {
+            state.read(&mut input.frames)?;
+        }
+        let num_outputs = state.read_len()?;
+        if num_outputs != self.outputs.len() {
+            return Err(count_mismatch(
+                "audio outputs",
+                self.outputs.len(),
+                num_outputs,
+            ));
+        }
+        for output in self.outputs.values_mut() {
+            state.read(&mut output.overlap_add)?;
+        }
+
+        let num_feedback = state.read_len()?;
+        if num_feedback != self.feedback.len() {
+            return Err(count_mismatch(
+                "feedback connections",
+                self.feedback.len(),
+                num_feedback,
+            ));
+        }
+        for edge in self.feedback.iter_mut() {
+            load_buffer::<F>(&mut edge.buffer, state)?;
+        }
+
+        let num_nodes = state.read_len()?;
+        if num_nodes != self.graph.node_count() {
+            return Err(count_mismatch("nodes", self.graph.node_count(), num_nodes));
+        }
+        let mut name = String::new();
+        for _ in 0..num_nodes {
+            let node_id = NodeIndex::new(state.read_len()?);
+            state.read(&mut name)?;
+            let node = self.node_mut(node_id)?;
+            if node.name() != name {
+                return Err(FftError::InvalidState(format!(
+                    "state of {name} at node {}, which holds {}",
+                    node_id.index(),
+                    node.name()
+                )));
+            }
+            state.read(&mut node.bypassed)?;
+            state.read(&mut node.frozen)?;
+            for output in node.outputs.iter_mut() {
+                load_buffer::<F>(output, state)?;
+            }
+
+            let len = state.read_len()?;
+            let mut processor = StateReader::new(state.read_bytes(len)?);
+            node.processor.load_state(&mut processor)?;
+            if !processor.is_empty() {
+                return Err(FftError::InvalidState(format!(
+                    "{name} left {} bytes of its state unread",
+                    processor.remaining()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the node whose output carries the windowed time-domain frames of the audio input
+    /// whose FFT node is `node`, e.g. to feed a
+    /// [`GoertzelBank`](crate::builtins::analysis::GoertzelBank).
+    pub fn audio_input_frames(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.audio_input_source(node)
+    }
+
+    /// Returns the node buffering the samples of the audio input whose FFT node is `node`.
+    fn audio_input_source(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.graph
+            .digraph()
+            .neighbors_directed(node, Direction::Incoming)
+            .find(|source| self.inputs.contains_key(source))
+    }
+
+    /// Renders whole signals offline, returning one signal per audio output.
+    ///
+    /// The graph is allocated for `sample_rate` and `block_size`, and its buffers are cleared
+    /// first. The STFT and processor latency is compensated, so each output lines up with the
+    /// inputs and is as long as the longest input.
+    pub fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        sample_rate: f32,
+        block_size: usize,
+    ) -> ProcResult<Vec<Vec<f32>>> {
+        self.allocate(sample_rate, block_size);
+        for fft_input in self.inputs.values_mut() {
+            fft_input.frames.clear();
+        }
+        let padding = self.padding_mode.padding(F::N_FFT);
+        for fft_output in self.outputs.values_mut() {
+            fft_output.overlap_add.clear();
+            fft_output.overlap_add.skip(padding);
+        }
+
+        let length = inputs.iter().map(|input| input.len()).max().unwrap_or(0);
+        // the resynthesized stream starts at the first input sample, delayed only by the
+        // processors, so render one extra frame to flush the tail out of the overlap-add
+        let delay = self.latency_frames() * self.hop_length();
+        let total = length + delay + self.fft_length();
+
+        let mut rendered = vec![Vec::with_capacity(total); self.outputs.len()];
+        if self.inputs.is_empty() {
+            rendered
+                .iter_mut()
+                .for_each(|output| output.resize(length, 0.0));
+            return Ok(rendered);
+        }
+
+        let mut block = vec![0.0; block_size];
+        for start in (0..total).step_by(block_size.max(1)) {
+            for (input_index, fft_input) in self.inputs.values_mut().enumerate() {
+                block.fill(0.0);
+                if let Some(input) = inputs.get(input_index) {
+                    let end = (start + block_size).min(input.len());
+                    if start < end {
+                        block[..end - start].copy_from_slice(&input[start..end]);
+                    }
+                }
+                fft_input.frames.push(&block);
+            }
+
+            self.process_frames()?;
+
+            for (fft_output, output) in self.outputs.values_mut().zip(rendered.iter_mut()) {
+                while let Some(sample) = fft_output.overlap_add.pop() {
+                    output.push(sample);
+                }
+            }
+        }
+
+        let scales: Vec<f32> = self
+            .outputs
+            .values()
+            .map(|fft_output| fft_output.scale(self.output_peak()))
+            .collect();
+        for (channel, output) in rendered.iter_mut().enumerate() {
+            output.drain(..delay.min(output.len()));
+            output.resize(length, 0.0);
+            if scales[channel] != 1.0 {
+                output
+                    .iter_mut()
+                    .for_each(|sample| *sample *= scales[channel]);
+            }
+            if let Some(dither) = self.dithers.get_mut(channel) {
+                dither.process(output);
+            }
+        }
+
+        Ok(rendered)
+    }
+
+    /// Processes every complete frame in the input buffers.
+    pub(crate) fn process_frames(&mut self) -> ProcResult<()> {
+        self.start_frames();
+
+        let _denormal_guard = self.denormal_protection.then(DenormalGuard::new);
+
+        self.metrics.record_ring_occupancy(
+            self.inputs
+                .values()
+                .map(|fft_input| fft_input.frames.len())
+                .max()
+                .unwrap_or(0),
+        );
+
+        // while we still have enough samples to process...
+        while self.frame_ready() {
+            self.process_frame()?;
+        }
+
+        self.metrics.record_ring_occupancy(
+            self.outputs
+                .values()
+                .map(|fft_output| fft_output.overlap_add.available())
+                .max()
+                .unwrap_or(0),
+        );
+
+        Ok(())
+    }
+
+    /// Prepares the graph for processing frames with [`process_frame`](Self::process_frame).
+    pub(crate) fn start_frames(&mut self) {
+        self.graph.reset_visitor();
+    }
+
+    /// Returns whether every audio input has buffered enough samples for the next frame, or
+    /// enough samples have passed for it without audio inputs, see
+    /// [`advance_clock`](Self::advance_clock).
+    pub(crate) fn frame_ready(&self) -> bool {
+        if self.inputs.is_empty() {
+            return self.idle_samples >= self.frame_length();
+        }
+        self.inputs
+            .values()
+            .map(|fft_input| fft_input.frames.len())
+            .min()
+            .is_some_and(|len| len >= self.frame_length())
+    }
+
+    /// Analyzes the next frame of every audio input, processes a hop and overlap-adds the audio
+    /// outputs. The inputs must hold a complete frame, see [`frame_ready`](Self::frame_ready).
+    pub(crate) fn process_frame(&mut self) -> ProcResult<()> {
+        let hop_length = self.hop_length();
+
+        self.advance_window_crossfade();
+
+        for (&node_index, fft_input) in self.inputs.iter_mut() {
+            // window the input
+            match (self.engine, &self.wola) {
+                (StftEngine::OverlapSave, _) => fft_input.frames.read(&mut fft_input.time_domain),
+                (StftEngine::Wola { .. }, Some(wola)) => fft_input
+                    .frames
+                    .read_folded(wola.analysis(), &mut fft_input.time_domain),
+                _ => {
+                    let window = frame_window(&self.window, &self.window_crossfade);
+                    fft_input
+                        .frames
+                        .read_windowed(window, &mut fft_input.time_domain);
+                }
+            }
+            // scaling the frame scales its spectrum the same way
+            let gain = fft_input.gain * self.spectral_scale;
+            if gain != 1.0 {
+                for sample in fft_input.time_domain.iter_mut() {
+                    *sample *= gain;
+                }
+            }
+
+            // copy the time domain signal to the FFT input
+            let node = &mut self.graph[node_index];
+            let Some(time_domain) = node.outputs[0].get_mut_as::<F::AudioBlock>(0) else {
+                return Err(type_mismatch::<F::AudioBlock>(node, 0).into());
+            };
+            time_domain.copy_from_slice(&fft_input.time_domain);
+
+            // advance time for the input
+            fft_input.frames.advance(hop_length);
+        }
+        if self.inputs.is_empty() {
+            self.idle_samples -= hop_length;
+        }
+
+        self.process_hop()?;
+
+        // copy the FFT output to the output buffers
+        let unscale = if self.spectral_scale != 0.0 {
+            1.0 / self.spectral_scale
+        } else {
+            1.0
+        };
+        for (&output_node_idx, fft_output) in self.outputs.iter_mut() {
+            let node = &mut self.graph[output_node_idx];
+            let Some([output_buf, ..]) = node.outputs[0].as_mut_slice::<F::AudioBlock>() else {
+                return Err(type_mismatch::<F::AudioBlock>(node, 0).into());
+            };
+            if unscale != 1.0 {
+                output_buf.iter_mut().for_each(|sample| *sample *= unscale);
+            }
+
+            match (self.engine, &self.wola) {
+                // the inverse FFT isn't normalized
+                (StftEngine::OverlapSave, _) => {
+                    fft_output
+                        .overlap_add
+                        .save_frame(output_buf, 1.0 / F::N_FFT as f32, hop_length)
+                }
+                (StftEngine::Wola { .. }, Some(wola)) => fft_output.overlap_add.add_unfolded_frame(
+                    output_buf,
+                    wola.synthesis(),
+                    hop_length,
+                ),
+                _ => {
+                    let window = frame_window(&self.window, &self.window_crossfade);
+                    fft_output
+                        .overlap_add
+                        .add_frame(output_buf, window, hop_length);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Buffers `len` samples of silence ahead of every audio input, so that its frames start
+    /// `len` samples earlier.
+    pub(crate) fn pad_inputs(&mut self, len: usize) {
+        let silence = vec![0.0; len];
+        for fft_input in self.inputs.values_mut() {
+            fft_input.frames.push(&silence);
+        }
+    }
+
+    /// Delays every audio output by `len` more samples.
+    pub(crate) fn delay_outputs(&mut self, len: usize) {
+        for fft_output in self.outputs.values_mut() {
+            fft_output.overlap_add.delay(len);
+        }
+    }
+
+    /// Steps an ongoing window crossfade to the mix used for the next frame.
+    fn advance_window_crossfade(&mut self) {
+        let Some(crossfade) = &mut self.window_crossfade else {
+            return;
+        };
+        if crossfade.hop >= crossfade.hops {
+            self.window_crossfade = None;
+            return;
+        }
+
+        crossfade.hop += 1;
+        let mix = crossfade.hop as f32 / (crossfade.hops + 1) as f32;
+        for ((blended, from), to) in crossfade
+            .blended
+            .iter_mut()
+            .zip(crossfade.from.iter())
+            .zip(self.window.iter())
+        {
+            *blended = from + (to - from) * mix;
+        }
+    }
+
+    /// Processes every node once and advances to the next hop.
+    fn process_hop(&mut self) -> ProcResult<()> {
+        self.apply_commands();
+
+        // traverse the graph and process each node
+        for i in 0..self.graph.visit_path().len() {
+            let node_id = self.graph.visit_path()[i];
+            if let Err(e) = self.process_node(node_id) {
+                return Err(ProcessorError::SubGraphError(Box::new(e)));
+            }
+        }
+
+        // hold on to the outputs feeding back into the next hop
+        for edge in self.feedback.iter_mut() {
+            let output = &self.graph[edge.source].outputs[edge.source_output as usize];
+            copy_buffer::<F>(output, &mut edge.buffer);
+        }
+
+        self.frame_index += 1;
+        self.metrics.add_hops(1);
+        if self.sample_rate > 0.0 {
+            self.transport
+                .advance(self.hop_length as f32 / self.sample_rate);
+        }
+
+        Ok(())
+    }
+
+    /// Processes one hop of a graph nested in an [`FftSubgraph`](crate::subgraph::FftSubgraph),
+    /// whose frame inputs have been filled by the enclosing graph.
+    pub(crate) fn process_subgraph_hop(&mut self, frame: &FrameInfo) -> ProcResult<()> {
+        // follow the enclosing graph's frame clock
+        self.hop_length = frame.hop_length;
+        self.frame_index = frame.frame_index;
+        self.transport = frame.transport;
+        // the subgraph's node draws its own stream, which its nodes split further
+        self.seed = frame.seed;
+
+        self.graph.reset_visitor();
+        let _denormal_guard = self.denormal_protection.then(DenormalGuard::new);
+        self.process_hop()
+    }
+
+    pub(crate) fn frame_input_mut(&mut self, port: usize) -> Option<&mut F::RealFft> {
+        let node = *self.frame_inputs.get(port)?;
+        self.graph[node].outputs[0].get_mut_as::<F::RealFft>(0)
+    }
+
+    pub(crate) fn frame_output(&self, port: usize) -> Option<&F::RealFft> {
+        let node = *self.frame_outputs.get(port)?;
+        self.graph[node].outputs[0].get_as::<F::RealFft>(0)
+    }
+
+    fn process_node(&mut self, node_id: NodeIndex) -> GraphRunResult<()> {
+        if self.graph[node_id].frozen {
+            return Ok(());
+        }
+
+        // taken out of the node so it can be filled while other nodes are borrowed, and put back
+        // afterwards so it's never reallocated
+        let mut inputs = mem::take(&mut self.graph[node_id].input_buffers);
+        let result = self.process_node_with(node_id, &mut inputs);
+        self.graph[node_id].input_buffers = inputs;
+        result
+    }
+
+    fn process_node_with(
+        &mut self,
+        node_id: NodeIndex,
+        inputs: &mut [Option<*const AnyBuffer>],
+    ) -> GraphRunResult<()> {
+        inputs.fill(None);
+
+        for (source_id, edge) in self
+            .graph
+            .digraph()
+            .edges_directed(node_id, Direction::Incoming)
+            .map(|edge| (edge.source(), edge.weight()))
+        {
+            let source_buffers = &self.graph[source_id].outputs;
+            let buffer = &source_buffers[edge.source_output as usize] as *const AnyBuffer;
+
+            inputs[edge.target_input as usize] = Some(buffer);
+        }
+
+        for edge in self.feedback.iter().filter(|edge| edge.target == node_id) {
+            inputs[edge.target_input as usize] = Some(&edge.buffer as *const AnyBuffer);
+        }
+
+        let node = &self.graph[node_id];
+        for (index, spec) in node.input_spec().iter().enumerate() {
+            if inputs[index].is_some() || !Self::is_required(spec) {
+                continue;
+            }
+            match self.unconnected_inputs {
+                UnconnectedInputs::Zeros if spec.signal_type == F::RealFft::signal_type() => {
+                    inputs[index] = Some(&self.zero_spectrum as *const AnyBuffer);
+                }
+                UnconnectedInputs::Zeros => {
+                    inputs[index] = Some(&self.zero_block as *const AnyBuffer);
+                }
+                UnconnectedInputs::Error => {
+                    let error = FftError::MissingInput {
+                        node: node.name().to_string(),
+                        input: index as u32,
+                    };
+                    return Err(ProcessNodeError {
+                        error: error.into(),
+                        node_name: node.name().to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        let node = &mut self.graph[node_id];
+        if node.bypassed {
+            for (index, input) in inputs.iter().enumerate().take(node.outputs.len()) {
+                if let Some(input) = *input
+                    && node.input_spec[index].signal_type == node.output_spec[index].signal_type
+                {
+                    // SAFETY: the input belongs to another node, a feedback edge or the graph,
+                    // none of which are borrowed while the node is processed
+                    copy_buffer::<F>(unsafe { &*input }, &mut node.outputs[index]);
+                }
+            }
+            return Ok(());
+        }
+
+        let frame = FrameInfo {
+            sample_rate: self.sample_rate,
+            fft_length: F::N_FFT,
+            hop_length: self.hop_length,
+            frame_index: self.frame_index,
+            flush_denormals: self.denormal_protection,
+            transport: self.transport,
+            seed: stream_seed(
+                stream_seed(self.seed, node_id.index() as u64),
+                self.frame_index,
+            ),
+        };
+
+        let node = &mut self.graph[node_id];
+
+        #[cfg(feature = "profiling")]
+        let start = std::time::Instant::now();
+
+        // nodes always process whole frames, whichever mode the host runs the graph in
+        node.process(
+            inputs,
+            ProcEnv {
+                sample_rate: self.sample_rate,
+                block_size: self.block_size,
+                mode: ProcessMode::Block,
+            },
+            &frame,
+            &mut self.scratch.scratch(),
+        )?;
+
+        #[cfg(feature = "profiling")]
+        node.timing.record(start.elapsed());
+
+        if self.nan_guard
+            && let Some(output) = node.sanitize_outputs::<F>()
+        {
+            if node.non_finite_hops == 0 {
+                diagnostics::report(RuntimeDiagnostic::NonFinite {
+                    node: node_id.index(),
+                    output,
+                });
+            }
+            node.non_finite_hops += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a snapshot of the processing time of every node since the last reset.
+    #[cfg(feature = "profiling")]
+    pub fn profile_report(&self) -> ProfileReport {
+        let mut entries = Vec::new();
+        for node in self.graph.digraph().node_indices() {
+            let timing = *self.graph[node].timing();
+            if timing.hops > 0 {
+                entries.push(ProfileEntry {
+                    node,
+                    name: self.graph[node].name().to_string(),
+                    timing,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.timing.mean()));
+        ProfileReport { entries }
+    }
+
+    /// Clears the recorded processing times.
+    #[cfg(feature = "profiling")]
+    pub fn reset_profile(&mut self) {
+        self.graph.visit_mut(|_i, node| {
+            node.timing = Default::default();
+            VisitResult::Continue::<()>
+        });
+    }
+}
+
+impl<F: Fft> Processor for FftGraph<F> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let mut specs = Vec::with_capacity(self.inputs.len());
+        for i in 0..self.inputs.len() {
+            specs.push(SignalSpec::new(i.to_string(), f32::signal_type()));
+        }
+        specs
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        let mut specs = Vec::with_capacity(self.outputs.len());
+        for i in 0..self.outputs.len() {
+            specs.push(SignalSpec::new(i.to_string(), f32::signal_type()));
+        }
+        specs
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        let mut buffers = Vec::with_capacity(self.outputs.len());
+        for _ in 0..self.outputs.len() {
+            buffers.push(AnyBuffer::zeros::<f32>(size));
+        }
+        buffers
+    }
+
+    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
+        FftGraph::allocate(self, sample_rate, max_block_size);
+    }
+
+    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        FftGraph::resize_buffers(self, sample_rate, block_size);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        outputs: ProcessorOutputs,
+    ) -> Result<(), ProcessorError> {
+        self.process_inner(inputs, outputs)
+    }
+}
+
+/// How an [`FftGraph`] handles required processor inputs that aren't connected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnconnectedInputs {
+    /// Feeds the input an empty spectrum or a silent audio block.
+    #[default]
+    Zeros,
+    /// Fails processing with [`FftError::MissingInput`]. Use [`FftGraph::validate`] to catch
+    /// these before processing starts.
+    Error,
+}
+
+/// How an audio output of an [`FftGraph`] is normalized, see
+/// [`FftGraph::set_output_normalization`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputNormalization {
+    /// Leaves the resynthesized level as it is.
+    #[default]
+    None,
+    /// Divides by the peak of the overlap-added squared window, see [`window_sum_peak`], so
+    /// windows that ripple at the graph's hop length never amplify the signal.
+    WindowSumPeak,
+}
+
+/// How an [`FftGraph`] frames its audio inputs and resynthesizes its audio outputs, see
+/// [`FftGraph::set_engine`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StftEngine {
+    /// Frames are windowed, and the processed frames windowed again and overlap-added. Suits
+    /// any processing.
+    #[default]
+    OverlapAdd,
+    /// Fast block convolution: frames aren't windowed, and only the last `hop_length` samples
+    /// of every processed frame are kept, the rest having wrapped around.
+    ///
+    /// Only suits linear filtering, where every spectrum is multiplied by a response whose
+    /// impulse response is at most `fft_length - hop_length + 1` samples long, such as a
+    /// convolution or a fixed EQ. Such a filter is applied exactly, without the ripple and
+    /// smearing of windowing, and with a hop of half the FFT length it takes half the frames per
+    /// sample of overlap-adding at the usual quarter. Nonlinear per-bin edits (gates, pitch
+    /// shifts, ...) wrap around and click at every hop instead. The spectra aren't windowed, so
+    /// the levels builtins measure from them differ too.
+    OverlapSave,
+    /// A weighted overlap-add filterbank, see [`wola`](crate::wola): frames of `taps` FFT
+    /// lengths are weighted by a prototype filter and folded into the FFT, and the processed
+    /// frames unfolded and weighted by its synthesis counterpart.
+    ///
+    /// Every bin gets a much narrower band with steeper skirts than the graph's window gives it,
+    /// for subband dynamics or codec-style processing, at the cost of `taps - 1` more FFT lengths
+    /// of latency. Reconstruction is exact for hops of up to half the FFT length. The graph's
+    /// window isn't used.
+    Wola { taps: usize },
+}
+
+/// A connection delivering its source's output one hop late, see [`FftGraph::connect_feedback`].
+struct FeedbackEdge {
+    source: NodeIndex,
+    source_output: u32,
+    target: NodeIndex,
+    target_input: u32,
+    // the source's output from the previous hop
+    buffer: AnyBuffer,
+}
+
+/// An ongoing transition between two windows, see [`FftGraph::set_window`].
+struct WindowCrossfade {
+    from: Arc<[f32]>,
+    // the mix of `from` and the graph's current window used for this hop's frames
+    blended: Vec<f32>,
+    hop: usize,
+    hops: usize,
+}
+
+/// Returns the window for the current frame, blended if a crossfade is ongoing.
+fn frame_window<'a>(window: &'a [f32], crossfade: &'a Option<WindowCrossfade>) -> &'a [f32] {
+    match crossfade {
+        Some(crossfade) => &crossfade.blended,
+        None => window,
+    }
+}
+
+/// Returns the size in bytes of the values in `buffer` if it holds one of the signal types the
+/// graph passes between nodes, or 0.
+fn any_buffer_bytes<F: Fft>(buffer: &AnyBuffer) -> usize {
+    if let Some(spectra) = buffer.as_slice::<F::RealFft>() {
+        size_of_val(spectra)
+    } else if let Some(blocks) = buffer.as_slice::<F::AudioBlock>() {
+        size_of_val(blocks)
+    } else if let Some(values) = buffer.as_slice::<f32>() {
+        size_of_val(values)
+    } else if let Some(values) = buffer.as_slice::<bool>() {
+        size_of_val(values)
+    } else {
+        0
+    }
+}
+
+/// Copies `source` into `target` if both hold one of the signal types the graph passes between
+/// nodes.
+fn copy_buffer<F: Fft>(source: &AnyBuffer, target: &mut AnyBuffer) {
+    if let (Some(source), Some(target)) = (
+        source.as_slice::<F::RealFft>(),
+        target.as_mut_slice::<F::RealFft>(),
+    ) {
+        target.clone_from_slice(source);
+    } else if let (Some(source), Some(target)) = (
+        source.as_slice::<F::AudioBlock>(),
+        target.as_mut_slice::<F::AudioBlock>(),
+    ) {
+        target.clone_from_slice(source);
+    } else if let (Some(source), Some(target)) =
+        (source.as_slice::<f32>(), target.as_mut_slice::<f32>())
+    {
+        target.copy_from_slice(source);
+    } else if let (Some(source), Some(target)) =
+        (source.as_slice::<bool>(), target.as_mut_slice::<bool>())
+    {
+        target.copy_from_slice(source);
+    }
+}
+
+/// The bytes every [`GraphState`] starts with.
+const STATE_MAGIC: [u8; 8] = *b"RFFTSTAT";
+/// The layout version of [`GraphState`]s.
+const STATE_VERSION: u32 = 1;
+
+fn count_mismatch(what: &str, expected: usize, found: usize) -> FftError {
+    FftError::InvalidState(format!(
+        "state of a graph with {found} {what}, expected {expected}"
+    ))
+}
+
+/// Writes the contents of `buffer` if it holds one of the signal types the graph passes between
+/// nodes, see [`copy_buffer`].
+fn save_buffer<F: Fft>(buffer: &AnyBuffer, state: &mut StateWriter) {
+    if let Some(buffer) = buffer.as_slice::<F::RealFft>() {
+        state.write(buffer);
+    } else if let Some(buffer) = buffer.as_slice::<F::AudioBlock>() {
+        state.write(buffer);
+    } else if let Some(buffer) = buffer.as_slice::<f32>() {
+        state.write(buffer);
+    } else if let Some(buffer) = buffer.as_slice::<bool>() {
+        state.write(buffer);
+    }
+}
+
+/// Reads the contents of `buffer` written by [`save_buffer`].
+fn load_buffer<F: Fft>(
+    buffer: &mut AnyBuffer,
+    state: &mut StateReader<'_>,
+) -> Result<(), FftError> {
+    if let Some(buffer) = buffer.as_mut_slice::<F::RealFft>() {
+        state.read(buffer)
+    } else if let Some(buffer) = buffer.as_mut_slice::<F::AudioBlock>() {
+        state.read(buffer)
+    } else if let Some(buffer) = buffer.as_mut_slice::<f32>() {
+        state.read(buffer)
+    } else if let Some(buffer) = buffer.as_mut_slice::<bool>() {
+        state.read(buffer)
+    } else {
+        Ok(())
+    }
+}
+
+fn type_mismatch<S>(node: &FftProcessorNode, port: u32) -> FftError {
+    FftError::TypeMismatch {
+        node: node.name().to_string(),
+        port,
+        expected: std::any::type_name::<S>(),
+    }
+}
+
+/// A node whose outputs contained NaN or infinite values, see [`FftGraph::set_nan_guard`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonFiniteOutput {
+    pub node: NodeIndex,
+    pub name: String,
+    /// The number of hops in which the node produced non-finite values.
+    pub hops: u64,
+}
+
+/// A problem with how an [`FftGraph`] is put together, see [`FftGraph::diagnostics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub node: NodeIndex,
+    /// The name of the node's processor.
+    pub name: String,
+    pub kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            DiagnosticKind::UnreachableOutput => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (node {}): ", self.name, self.node.index())?;
+        match &self.kind {
+            DiagnosticKind::TypeMismatch {
+                input,
+                source,
+                output,
+                expected,
+                found,
+            } => write!(
+                f,
+                "input {input} takes {expected}, but is connected to output {output} of {source}, \
+                 which produces {found}"
+            ),
+            DiagnosticKind::MissingInput { input, input_name } => {
+                write!(
+                    f,
+                    "required input {input} ({input_name:?}) is not connected"
+                )
+            }
+            DiagnosticKind::Cycle => {
+                f.write_str("is part of a cycle; make one of its connections a feedback connection")
+            }
+            DiagnosticKind::UnreachableOutput => {
+                f.write_str("audio output is not fed by any audio, sidechain or frame input")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// An input is connected to an output of another signal type.
+    TypeMismatch {
+        input: u32,
+        /// The name of the node the input is connected to.
+        source: String,
+        output: u32,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A required input isn't connected.
+    MissingInput { input: u32, input_name: String },
+    /// The node is part of a cycle of regular connections.
+    Cycle,
+    /// No input of the graph reaches the audio output.
+    UnreachableOutput,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+pub struct FftGraphBuilder<F: Fft>(GraphBuilder<FftGraph<F>>);
+
+impl<F: Fft> Deref for FftGraphBuilder<F> {
+    type Target = GraphBuilder<FftGraph<F>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<F: Fft> FftGraphBuilder<F> {
+    pub fn new(hop_length: usize, window_fn: WindowFunction) -> Self {
+        Self(GraphBuilder::from_inner(FftGraph::new(
+            hop_length, window_fn,
+        )))
+    }
+
+    pub fn add_audio_input(&self) -> NodeBuilder<FftGraph<F>> {
+        let node_id = self.with_inner(|graph| graph.add_audio_input());
+        NodeBuilder::new(self.0.clone(), node_id)
+    }
+
+    pub fn add_sidechain_input(&self) -> NodeBuilder<FftGraph<F>> {
+        let node_id = self.with_inner(|graph| graph.add_sidechain_input());
+        NodeBuilder::new(self.0.clone(), node_id)
+    }
+
+    pub fn add_audio_output(&self) -> NodeBuilder<FftGraph<F>> {
+        let node_id = self.with_inner(|graph| graph.add_audio_output());
+        NodeBuilder::new(self.0.clone(), node_id)
+    }
+
+    pub fn add_audio_inputs(&self, count: usize) -> Vec<NodeBuilder<FftGraph<F>>> {
+        let node_ids = self.with_inner(|graph| graph.add_audio_inputs(count));
+        node_ids
+            .into_iter()
+            .map(|node_id| NodeBuilder::new(self.0.clone(), node_id))
+            .collect()
+    }
+
+    pub fn add_audio_outputs(&self, count: usize) -> Vec<NodeBuilder<FftGraph<F>>> {
+        let node_ids = self.with_inner(|graph| graph.add_audio_outputs(count));
+        node_ids
+            .into_iter()
+            .map(|node_id| NodeBuilder::new(self.0.clone(), node_id))
+            .collect()
+    }
+
+    pub fn add_stereo_input(&self) -> [NodeBuilder<FftGraph<F>>; 2] {
+        let node_ids = self.with_inner(|graph| graph.add_stereo_input());
+        node_ids.map(|node_id| NodeBuilder::new(self.0.clone(), node_id))
+    }
+
+    pub fn add_stereo_output(&self) -> [NodeBuilder<FftGraph<F>>; 2] {
+        let node_ids = self.with_inner(|graph| graph.add_stereo_output());
+        node_ids.map(|node_id| NodeBuilder::new(self.0.clone(), node_id))
+    }
+
+    pub fn add_processor(&self, processor: impl FftProcessor) -> NodeBuilder<FftGraph<F>> {
+        let node_id = self.with_inner(|graph| graph.add_processor(processor));
+        NodeBuilder::new(self.0.clone(), node_id)
+    }
+
+    pub fn add_block_processor(
+        &self,
+        processor: impl FftBlockProcessor<F>,
+    ) -> NodeBuilder<FftGraph<F>> {
+        let node_id = self.with_inner(|graph| graph.add_block_processor(processor));
+        NodeBuilder::new(self.0.clone(), node_id)
+    }
+
+    /// Starts a chain of processors at a new audio input, see [`FftChain`].
+    pub fn input(&self) -> FftChain<F> {
+        let input = self.add_audio_input();
+        self.chain(&input)
+    }
+
+    /// Starts a chain of processors fed by output 0 of `node`, see [`FftChain`].
+    pub fn chain(&self, node: &NodeBuilder<FftGraph<F>>) -> FftChain<F> {
+        FftChain {
+            graph: self.0.clone(),
+            last: Ok(node.id()),
+        }
+    }
+
+    pub fn latency_samples(&self) -> usize {
+        self.with_inner(|graph| graph.latency_samples())
+    }
+
+    pub fn set_denormal_protection(&self, enabled: bool) {
+        self.with_inner(|graph| graph.set_denormal_protection(enabled))
+    }
+
+    pub fn set_nan_guard(&self, enabled: bool) {
+        self.with_inner(|graph| graph.set_nan_guard(enabled))
+    }
+
+    pub fn set_output_limiter(&self, enabled: bool) {
+        self.with_inner(|graph| graph.set_output_limiter(enabled))
+    }
+
+    pub fn set_output_gain(&self, output: &NodeBuilder<FftGraph<F>>, gain: f32) -> bool {
+        self.with_inner(|graph| graph.set_output_gain(output.id(), gain))
+    }
+
+    pub fn set_output_normalization(
+        &self,
+        output: &NodeBuilder<FftGraph<F>>,
+        normalization: OutputNormalization,
+    ) -> bool {
+        self.with_inner(|graph| graph.set_output_normalization(output.id(), normalization))
+    }
+
+    pub fn set_output_dither(&self, bits: Option<u32>) {
+        self.with_inner(|graph| graph.set_output_dither(bits))
+    }
+
+    pub fn set_padding_mode(&self, padding: PaddingMode) {
+        self.with_inner(|graph| graph.set_padding_mode(padding))
+    }
+
+    pub fn set_lookahead_frames(&self, frames: usize) {
+        self.with_inner(|graph| graph.set_lookahead_frames(frames))
+    }
+
+    pub fn set_engine(&self, engine: StftEngine) {
+        self.with_inner(|graph| graph.set_engine(engine))
+    }
+
+    pub fn set_spectral_scaling(&self, scaling: SpectralScaling) {
+        self.with_inner(|graph| graph.set_spectral_scaling(scaling))
+    }
+
+    pub fn set_transport(&self, transport: Transport) {
+        self.with_inner(|graph| graph.set_transport(transport))
+    }
+
+    pub fn set_seed(&self, seed: u64) {
+        self.with_inner(|graph| graph.set_seed(seed))
+    }
+
+    pub fn set_window(&self, window_fn: WindowFunction) {
+        self.with_inner(|graph| graph.set_window(window_fn))
+    }
+
+    pub fn connect_feedback(
+        &self,
+        source: &NodeBuilder<FftGraph<F>>,
+        source_output: u32,
+        target: &NodeBuilder<FftGraph<F>>,
+        target_input: u32,
+    ) -> Result<(), FftError> {
+        self.with_inner(|graph| {
+            graph.connect_feedback(source.id(), source_output, target.id(), target_input)
+        })
+    }
+
+    /// See [`FftGraph::connect_named`].
+    pub fn connect_named(
+        &self,
+        source: &NodeBuilder<FftGraph<F>>,
+        source_output: &str,
+        target: &NodeBuilder<FftGraph<F>>,
+        target_input: &str,
+    ) -> Result<(), FftError> {
+        self.with_inner(|graph| {
+            graph.connect_named(source.id(), source_output, target.id(), target_input)
+        })
+    }
+
+    /// See [`FftGraph::connect_feedback_named`].
+    pub fn connect_feedback_named(
+        &self,
+        source: &NodeBuilder<FftGraph<F>>,
+        source_output: &str,
+        target: &NodeBuilder<FftGraph<F>>,
+        target_input: &str,
+    ) -> Result<(), FftError> {
+        self.with_inner(|graph| {
+            graph.connect_feedback_named(source.id(), source_output, target.id(), target_input)
+        })
+    }
+
+    pub fn set_unconnected_input_policy(&self, policy: UnconnectedInputs) {
+        self.with_inner(|graph| graph.set_unconnected_input_policy(policy))
+    }
+
+    pub fn validate(&self) -> Result<(), FftError> {
+        self.with_inner(|graph| graph.validate())
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.with_inner(|graph| graph.diagnostics())
+    }
+
+    pub fn non_finite_outputs(&self) -> Vec<NonFiniteOutput> {
+        self.with_inner(|graph| graph.non_finite_outputs())
+    }
+
+    pub fn underruns(&self) -> u64 {
+        self.with_inner(|graph| graph.underruns())
+    }
+
+    pub fn metrics(&self) -> Arc<FftGraphMetrics> {
+        self.with_inner(|graph| graph.metrics())
+    }
+
+    pub fn handle(&self) -> FftGraphHandle {
+        self.with_inner(|graph| graph.handle())
+    }
+
+    pub fn set_param(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        name: &str,
+        value: f32,
+    ) -> Result<(), FftError> {
+        self.with_inner(|graph| graph.set_param(node.id(), name, value))
+    }
+
+    pub fn set_bypassed(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        bypassed: bool,
+    ) -> Result<(), FftError> {
+        self.with_inner(|graph| graph.set_bypassed(node.id(), bypassed))
+    }
+
+    pub fn set_frozen(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        frozen: bool,
+    ) -> Result<(), FftError> {
+        self.with_inner(|graph| graph.set_frozen(node.id(), frozen))
+    }
+
+    pub fn save_state(&self) -> GraphState {
+        self.with_inner(|graph| graph.save_state())
+    }
+
+    pub fn load_state(&self, state: &GraphState) -> Result<(), FftError> {
+        self.with_inner(|graph| graph.load_state(state))
+    }
+
+    /// See [`FftGraph::clone_template`]. The copy is a separate graph with its own builder.
+    pub fn clone_template(&self) -> Result<Self, FftError> {
+        let graph = self.with_inner(|graph| graph.clone_template())?;
+        Ok(Self(GraphBuilder::from_inner(graph)))
+    }
+
+    pub fn estimate_cost(
+        &self,
+        sample_rate: f32,
+        block_size: usize,
+    ) -> Result<CostEstimate, FftError> {
+        self.with_inner(|graph| graph.estimate_cost(sample_rate, block_size))
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn profile_report(&self) -> ProfileReport {
+        self.with_inner(|graph| graph.profile_report())
+    }
+}
+
+impl<F: Fft> Processor for FftGraphBuilder<F> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        self.with_inner(|graph| graph.input_spec())
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        self.with_inner(|graph| graph.output_spec())
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        self.with_inner(|graph| graph.create_output_buffers(size))
+    }
+
+    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
+        self.with_inner(|graph| graph.allocate(sample_rate, max_block_size));
+    }
+
+    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        self.with_inner(|graph| graph.resize_buffers(sample_rate, block_size));
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        outputs: ProcessorOutputs,
+    ) -> Result<(), ProcessorError> {
+        self.with_inner(|graph| graph.process(inputs, outputs))
+    }
+}
+
+/// A chain of processors being added to an [`FftGraphBuilder`], each one fed by output 0 of the
+/// previous node into its input 0:
+///
+/// ```ignore
+/// let fft_graph = FftGraphBuilder::<Fft1024>::new(256, WindowFunction::Hann);
+/// fft_graph
+///     .input()
+///     .then(SpectralTilt::builder().tilt_db_per_octave(-3.0).build())
+///     .then(StereoWidth::default())
+///     .output()?;
+/// ```
+///
+/// Adding a node never fails, but connecting it can, e.g. if output 0 of the previous node is a
+/// control value and input 0 of the next is a spectrum. The first such error ends the chain; the
+/// remaining processors are dropped and the error is returned by [`output`](Self::output) or
+/// [`end`](Self::end).
+pub struct FftChain<F: Fft> {
+    graph: GraphBuilder<FftGraph<F>>,
+    last: Result<NodeIndex, FftError>,
+}
+
+impl<F: Fft> FftChain<F> {
+    pub fn then(self, processor: impl FftProcessor) -> Self {
+        self.then_boxed(Box::new(processor))
+    }
+
+    pub fn then_block(self, processor: impl FftBlockProcessor<F>) -> Self {
+        self.then_boxed(Box::new(BlockNode::new(processor)))
+    }
+
+    pub fn then_boxed(mut self, processor: Box<dyn FftProcessor>) -> Self {
+        if let Ok(source) = self.last {
+            self.last = self.graph.with_inner(|graph| {
+                let target = graph.add_boxed_processor(processor);
+                graph.connect_first(source, target).map(|()| target)
+            });
+        }
+        self
+    }
+
+    /// Ends the chain at a new audio output, returning the output node.
+    pub fn output(self) -> Result<NodeBuilder<FftGraph<F>>, FftError> {
+        let source = self.last?;
+        let output = self.graph.with_inner(|graph| {
+            let output = graph.add_audio_output();
+            graph.connect_first(source, output).map(|()| output)
+        })?;
+        Ok(NodeBuilder::new(self.graph, output))
+    }
+
+    /// Ends the chain at the last node added, e.g. to connect its other inputs and outputs by
+    /// hand.
+    pub fn end(self) -> Result<NodeBuilder<FftGraph<F>>, FftError> {
+        let last = self.last?;
+        Ok(NodeBuilder::new(self.graph, last))
+    }
+}
+
+/// An [`FftGraph`] with its FFT length erased, for hosts that only know it at runtime (language
+/// bindings, plugin wrappers).
+///
+/// Audio inputs and outputs are passed as one slice per channel, in the order they were added.
+pub trait DynFftGraph: Send {
+    fn fft_length(&self) -> usize;
+
+    fn hop_length(&self) -> usize;
+
+    fn latency_samples(&self) -> usize;
+
+    fn num_audio_inputs(&self) -> usize;
+
+    fn num_audio_outputs(&self) -> usize;
+
+    fn add_audio_input(&mut self) -> NodeIndex;
+
+    fn add_sidechain_input(&mut self) -> NodeIndex;
+
+    fn add_audio_output(&mut self) -> NodeIndex;
+
+    /// Adds a processor by its name in the graph's [`ProcessorRegistry`], which holds the
+    /// builtins and anything added with [`register_processor`](Self::register_processor).
+    ///
+    /// Returns `None` if there is no processor with that name.
+    fn add_builtin(&mut self, name: &str, params: &ProcessorParams) -> Option<NodeIndex>;
+
+    /// Adds an entry to the graph's [`ProcessorRegistry`], replacing any existing entry with the
+    /// same name. The processors it creates must match the graph's FFT length.
+    fn register_processor(&mut self, name: &str, factory: Box<ProcessorFactory>);
+
+    /// Adds a processor built elsewhere. Its signal types must match the graph's FFT length.
+    fn add_boxed_processor(&mut self, processor: Box<dyn FftProcessor>) -> NodeIndex;
+
+    fn connect(
+        &mut self,
+        source: NodeIndex,
+        source_output: u32,
+        target: NodeIndex,
+        target_input: u32,
+    ) -> Result<(), FftError>;
+
+    /// See [`FftGraph::connect_feedback`].
+    fn connect_feedback(
+        &mut self,
+        source: NodeIndex,
+        source_output: u32,
+        target: NodeIndex,
+        target_input: u32,
+    ) -> Result<(), FftError>;
+
+    /// See [`FftGraph::set_transport`].
+    fn set_transport(&mut self, transport: Transport);
+
+    /// See [`FftGraph::set_seed`].
+    fn set_seed(&mut self, seed: u64);
+
+    fn allocate(&mut self, sample_rate: f32, block_size: usize);
+
+    fn process_block(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]]) -> ProcResult<()>;
+
+    fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        sample_rate: f32,
+        block_size: usize,
+    ) -> ProcResult<Vec<Vec<f32>>>;
+}
+
+impl<F: Fft> DynFftGraph for FftGraph<F> {
+    fn fft_length(&self) -> usize {
+        F::N_FFT
+    }
+
+    fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+
+    fn latency_samples(&self) -> usize {
+        FftGraph::latency_samples(self)
+    }
+
+    fn num_audio_inputs(&self) -> usize {
+        FftGraph::num_audio_inputs(self)
+    }
+
+    fn num_audio_outputs(&self) -> usize {
+        FftGraph::num_audio_outputs(self)
+    }
+
+    fn add_audio_input(&mut self) -> NodeIndex {
+        FftGraph::add_audio_input(self)
+    }
+
+    fn add_sidechain_input(&mut self) -> NodeIndex {
+        FftGraph::add_sidechain_input(self)
+    }
+
+    fn add_audio_output(&mut self) -> NodeIndex {
+        FftGraph::add_audio_output(self)
+    }
+
+    fn add_builtin(&mut self, name: &str, params: &ProcessorParams) -> Option<NodeIndex> {
+        FftGraph::add_builtin(self, name, params)
+    }
+
+    fn register_processor(&mut self, name: &str, factory: Box<ProcessorFactory>) {
+        self.registry.register(name, factory);
+    }
+
+    fn add_boxed_processor(&mut self, processor: Box<dyn FftProcessor>) -> NodeIndex {
+        FftGraph::add_boxed_processor(self, processor)
+    }
+
+    fn connect(
+        &mut self,
+        source: NodeIndex,
+        source_output: u32,
+        target: NodeIndex,
+        target_input: u32,
+    ) -> Result<(), FftError> {
+        self.graph
+            .connect(source, source_output, target, target_input)
+            .map(|_| ())
+            .map_err(|e| FftError::Graph(format!("{e:?}")))
+    }
+
+    fn connect_feedback(
+        &mut self,
+        source: NodeIndex,
+        source_output: u32,
+        target: NodeIndex,
+        target_input: u32,
+    ) -> Result<(), FftError> {
+        FftGraph::connect_feedback(self, source, source_output, target, target_input)
+    }
+
+    fn set_transport(&mut self, transport: Transport) {
+        FftGraph::set_transport(self, transport);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        FftGraph::set_seed(self, seed);
+    }
+
+    fn allocate(&mut self, sample_rate: f32, block_size: usize) {
+        FftGraph::allocate(self, sample_rate, block_size);
+    }
+
+    fn process_block(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]]) -> ProcResult<()> {
+        FftGraph::process_block(self, inputs, outputs)
+    }
+
+    fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        sample_rate: f32,
+        block_size: usize,
+    ) -> ProcResult<Vec<Vec<f32>>> {
+        FftGraph::render(self, inputs, sample_rate, block_size)
+    }
+}
+
+/// Creates an empty graph for a runtime FFT length.
+///
+/// Returns [`FftError::UnsupportedFftLength`] if no [`Fft`] size matches `fft_length`, and
+/// [`FftError::InvalidStft`] if `hop_length` is zero or longer than the FFT.
+pub fn dyn_graph(
+    fft_length: usize,
+    hop_length: usize,
+    window_fn: WindowFunction,
+) -> Result<Box<dyn DynFftGraph>, FftError> {
+    fn boxed<F: Fft>(
+        hop_length: usize,
+        window_fn: WindowFunction,
+    ) -> Result<Box<dyn DynFftGraph>, FftError> {
+        Ok(Box::new(FftGraph::<F>::try_new(hop_length, window_fn)?))
+    }
+
+    match fft_length {
+        64 => boxed::<Fft64>(hop_length, window_fn),
+        128 => boxed::<Fft128>(hop_length, window_fn),
+        256 => boxed::<Fft256>(hop_length, window_fn),
+        512 => boxed::<Fft512>(hop_length, window_fn),
+        1024 => boxed::<Fft1024>(hop_length, window_fn),
+        2048 => boxed::<Fft2048>(hop_length, window_fn),
+        4096 => boxed::<Fft4096>(hop_length, window_fn),
+        8192 => boxed::<Fft8192>(hop_length, window_fn),
+        _ => Err(FftError::UnsupportedFftLength(fft_length)),
     }
 }
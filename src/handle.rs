@@ -0,0 +1,115 @@
+//! Controlling an [`FftGraph`](crate::graph::FftGraph) from other threads while it's processing.
+//!
+//! Once a graph is running, changing its processors through the
+//! [`FftGraphBuilder`](crate::graph::FftGraphBuilder) means locking the graph the audio thread is
+//! processing. An [`FftGraphHandle`] queues the changes instead, without locking, and the graph
+//! applies them at the start of its next hop:
+//!
+//! ```ignore
+//! let duck = fft_graph.add_processor(SpectralDuck::default());
+//! let handle = fft_graph.handle();
+//! // on the UI thread
+//! handle.set_param(duck.id(), "threshold_db", -24.0);
+//! handle.set_bypassed(duck.id(), true);
+//! ```
+
+use std::sync::mpsc::{SyncSender, TrySendError};
+
+use raug_graph::graph::NodeIndex;
+
+/// The number of changes that can be queued between two hops.
+pub const COMMAND_CAPACITY: usize = 256;
+
+/// The longest parameter name [`FftGraphHandle::set_param`] can queue, in bytes.
+pub const MAX_PARAM_NAME_LEN: usize = 32;
+
+/// A change queued through an [`FftGraphHandle`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum GraphCommand {
+    Param {
+        node: NodeIndex,
+        name: ParamName,
+        value: f32,
+    },
+    Bypassed {
+        node: NodeIndex,
+        bypassed: bool,
+    },
+    Frozen {
+        node: NodeIndex,
+        frozen: bool,
+    },
+}
+
+/// A parameter name stored inline, so that queueing and applying a change never allocates.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamName {
+    bytes: [u8; MAX_PARAM_NAME_LEN],
+    len: usize,
+}
+
+impl ParamName {
+    fn new(name: &str) -> Option<Self> {
+        let len = name.len();
+        if len > MAX_PARAM_NAME_LEN {
+            return None;
+        }
+        let mut bytes = [0; MAX_PARAM_NAME_LEN];
+        bytes[..len].copy_from_slice(name.as_bytes());
+        Some(Self { bytes, len })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // copied whole from a `str`, so always valid
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
+impl PartialEq for ParamName {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// Queues changes to the processors of an [`FftGraph`](crate::graph::FftGraph) from other
+/// threads, see the [module docs](self).
+///
+/// The queue has a fixed capacity, so the methods return `false` if it's full (or the graph was
+/// dropped). Changes to nodes or parameters that don't exist are ignored when they're applied.
+#[derive(Clone)]
+pub struct FftGraphHandle {
+    sender: SyncSender<GraphCommand>,
+}
+
+impl FftGraphHandle {
+    pub(crate) fn new(sender: SyncSender<GraphCommand>) -> Self {
+        Self { sender }
+    }
+
+    /// Sets the parameter `name` of the processor at `node`, see
+    /// [`FftGraph::set_param`](crate::graph::FftGraph::set_param). Also returns `false` if
+    /// `name` is longer than [`MAX_PARAM_NAME_LEN`].
+    pub fn set_param(&self, node: NodeIndex, name: &str, value: f32) -> bool {
+        let Some(name) = ParamName::new(name) else {
+            return false;
+        };
+        self.send(GraphCommand::Param { node, name, value })
+    }
+
+    /// See [`FftGraph::set_bypassed`](crate::graph::FftGraph::set_bypassed).
+    pub fn set_bypassed(&self, node: NodeIndex, bypassed: bool) -> bool {
+        self.send(GraphCommand::Bypassed { node, bypassed })
+    }
+
+    /// See [`FftGraph::set_frozen`](crate::graph::FftGraph::set_frozen).
+    pub fn set_frozen(&self, node: NodeIndex, frozen: bool) -> bool {
+        self.send(GraphCommand::Frozen { node, frozen })
+    }
+
+    fn send(&self, command: GraphCommand) -> bool {
+        match self.sender.try_send(command) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
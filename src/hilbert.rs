@@ -0,0 +1,257 @@
+//! Instantaneous amplitude and frequency of an audio signal, from its analytic signal.
+//!
+//! A [`HilbertEnvelope`] is used directly as a processor of a raug graph (like an
+//! [`FftGraph`](crate::graph::FftGraph), without any nodes to add), with one audio input and two
+//! outputs:
+//!
+//! - `amplitude`: the magnitude of the analytic signal, a smooth envelope of the input,
+//! - `frequency`: the rate of change of its phase in Hz, e.g. the frequency of a single partial
+//!   or of an FM carrier.
+//!
+//! The analytic signal is computed frame by frame with the complex FFT path (see
+//! [`analytic`](crate::analytic)): every windowed frame of the input is transformed, its negative
+//! frequencies are removed and the positive ones doubled, and the inverse complex transform is
+//! overlap-added back into a complex stream.
+
+use std::{f32::consts::TAU, marker::PhantomData, mem, sync::Arc};
+
+use raug::{prelude::*, processor::io::ProcessMode};
+
+use crate::{
+    FftError, WindowFunction, analytic,
+    core::{Complex32, FftPlanner, FrameInput, OverlapAdd, stft_window, validate_stft},
+    signal::Fft,
+};
+
+/// Outputs the instantaneous amplitude and frequency of its audio input, see the
+/// [module docs](self).
+///
+/// The outputs lag the input by [`latency_samples`](Self::latency_samples) whatever the length of
+/// the blocks, like an [`FftGraph`](crate::graph::FftGraph)'s.
+pub struct HilbertEnvelope<F: Fft> {
+    hop_length: usize,
+    window: Vec<f32>,
+    sample_rate: f32,
+    forward: Arc<dyn realfft::RealToComplex<f32>>,
+    inverse: Arc<dyn rustfft::Fft<f32>>,
+    input: FrameInput,
+    // the real and imaginary parts of the analytic signal
+    real: OverlapAdd,
+    imag: OverlapAdd,
+    previous: Complex32,
+    time_domain: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    analytic: Vec<Complex32>,
+    frame_real: Vec<f32>,
+    frame_imag: Vec<f32>,
+    scratch: Vec<Complex32>,
+    silence: Vec<f32>,
+    _f: PhantomData<F>,
+}
+
+impl<F: Fft> HilbertEnvelope<F> {
+    /// Creates an analyzer taking frames every `hop_length` samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hop_length` is zero or larger than the FFT length, see
+    /// [`try_new`](Self::try_new).
+    pub fn new(hop_length: usize, window_fn: WindowFunction) -> Self {
+        Self::try_new(hop_length, window_fn).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_new(hop_length: usize, window_fn: WindowFunction) -> Result<Self, FftError> {
+        validate_stft(F::N_FFT, hop_length)?;
+        let planner = FftPlanner::global();
+        let forward = planner.plan_forward(F::N_FFT);
+        let inverse = planner.plan_complex_inverse(F::N_FFT);
+        let scratch_len = forward
+            .get_scratch_len()
+            .max(inverse.get_inplace_scratch_len());
+
+        // the STFT window peaks at the ends of the frame, where the circular Hilbert transform
+        // would see the jump between them, so move its peak to the middle
+        let mut window = stft_window(window_fn, F::N_FFT, hop_length);
+        window.rotate_left(F::N_FFT / 2);
+
+        let mut envelope = Self {
+            hop_length,
+            window,
+            sample_rate: 0.0,
+            forward,
+            inverse,
+            input: FrameInput::new(F::N_FFT),
+            real: OverlapAdd::new(F::N_FFT),
+            imag: OverlapAdd::new(F::N_FFT),
+            previous: Complex32::ZERO,
+            time_domain: vec![0.0; F::N_FFT],
+            spectrum: vec![Complex32::ZERO; F::N_REAL_BINS],
+            analytic: vec![Complex32::ZERO; F::N_FFT],
+            frame_real: vec![0.0; F::N_FFT],
+            frame_imag: vec![0.0; F::N_FFT],
+            scratch: vec![Complex32::ZERO; scratch_len],
+            silence: Vec::new(),
+            _f: PhantomData,
+        };
+        envelope.reset(0);
+        Ok(envelope)
+    }
+
+    pub fn fft_length(&self) -> usize {
+        F::N_FFT
+    }
+
+    pub fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+
+    /// Returns the delay in samples between the input and the outputs, `fft_length - 1`.
+    pub fn latency_samples(&self) -> usize {
+        F::N_FFT - 1
+    }
+
+    /// Clears all buffered input and output, with room for blocks of up to `block_size` samples.
+    fn reset(&mut self, block_size: usize) {
+        self.input.clear();
+        self.previous = Complex32::ZERO;
+        let latency = self.latency_samples();
+        for output in [&mut self.real, &mut self.imag] {
+            output.clear();
+            output.reserve(block_size + F::N_FFT);
+            output.delay(latency);
+        }
+    }
+
+    pub fn allocate(&mut self, sample_rate: f32, block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.silence = vec![0.0; block_size];
+        self.reset(block_size);
+    }
+
+    /// Analyzes a block of input, writing as many samples of instantaneous amplitude and frequency
+    /// (in Hz) as there are input samples. Samples that aren't available yet are zero.
+    pub fn process_block(
+        &mut self,
+        input: &[f32],
+        amplitude: &mut [f32],
+        frequency: &mut [f32],
+    ) -> Result<(), FftError> {
+        self.push(input)?;
+        for (amplitude, frequency) in amplitude.iter_mut().zip(frequency.iter_mut()) {
+            (*amplitude, *frequency) = self.next_sample();
+        }
+        Ok(())
+    }
+
+    /// Buffers `input` and analyzes every complete frame.
+    fn push(&mut self, input: &[f32]) -> Result<(), FftError> {
+        self.input.push(input);
+        while self.input.len() >= F::N_FFT {
+            self.process_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the next instantaneous amplitude and frequency, or zeros if none is available.
+    fn next_sample(&mut self) -> (f32, f32) {
+        let (Some(re), Some(im)) = (self.real.pop(), self.imag.pop()) else {
+            return (0.0, 0.0);
+        };
+        let value = Complex32::new(re, im);
+        // the phase advance since the previous sample
+        let frequency = (value * self.previous.conj()).arg() * self.sample_rate / TAU;
+        self.previous = value;
+        (value.norm(), frequency)
+    }
+
+    fn process_frame(&mut self) -> Result<(), FftError> {
+        self.input
+            .read_windowed(&self.window, &mut self.time_domain);
+        self.input.advance(self.hop_length);
+
+        self.forward.process_with_scratch(
+            &mut self.time_domain,
+            &mut self.spectrum,
+            &mut self.scratch,
+        )?;
+        analytic::analytic_spectrum(&self.spectrum, &mut self.analytic);
+        self.inverse
+            .process_with_scratch(&mut self.analytic, &mut self.scratch);
+
+        for ((value, re), im) in self
+            .analytic
+            .iter()
+            .zip(self.frame_real.iter_mut())
+            .zip(self.frame_imag.iter_mut())
+        {
+            *re = value.re;
+            *im = value.im;
+        }
+        self.real
+            .add_frame(&self.frame_real, &self.window, self.hop_length);
+        self.imag
+            .add_frame(&self.frame_imag, &self.window, self.hop_length);
+
+        Ok(())
+    }
+}
+
+impl<F: Fft> Processor for HilbertEnvelope<F> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        vec![SignalSpec::new("input", f32::signal_type())]
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        vec![
+            SignalSpec::new("amplitude", f32::signal_type()),
+            SignalSpec::new("frequency", f32::signal_type()),
+        ]
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        vec![AnyBuffer::zeros::<f32>(size), AnyBuffer::zeros::<f32>(size)]
+    }
+
+    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
+        HilbertEnvelope::allocate(self, sample_rate, max_block_size);
+    }
+
+    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.silence.resize(block_size, 0.0);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> Result<(), ProcessorError> {
+        // in sample mode, the host calls us once per sample of the block
+        let samples = match inputs.mode() {
+            ProcessMode::Block => 0..inputs.block_size(),
+            ProcessMode::Sample(index) => index..index + 1,
+        };
+
+        match inputs
+            .input_as::<f32>(0)
+            .and_then(|input| input.get(samples.clone()))
+        {
+            Some(input) => self.push(input)?,
+            // an unconnected input is silent
+            None => {
+                let silence = mem::take(&mut self.silence);
+                let result = self.push(&silence[..samples.len().min(silence.len())]);
+                self.silence = silence;
+                result?;
+            }
+        }
+
+        for sample_index in samples {
+            let (amplitude, frequency) = self.next_sample();
+            outputs.set_output_as::<f32>(0, sample_index, &amplitude)?;
+            outputs.set_output_as::<f32>(1, sample_index, &frequency)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::{format, vec, vec::Vec};
+
+use crate::{
+    FftError,
+    core::Complex32,
+    state::{StateReader, StateValue, StateWriter},
+};
+
+/// A fixed-length delay line of frames, indexed by age (0 is the most recently pushed frame).
+///
+/// All slots are preallocated on construction, so pushing never allocates.
+#[derive(Clone)]
+pub struct FrameHistory<T> {
+    frames: Vec<T>,
+    head: usize,
+}
+
+impl<T: Clone + Default> FrameHistory<T> {
+    pub fn new(length: usize) -> Self {
+        assert!(length > 0, "FrameHistory length must be non-zero");
+        Self {
+            frames: vec![T::default(); length],
+            head: 0,
+        }
+    }
+
+    /// Returns the number of frames held by the history.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Pushes a new frame, discarding the oldest one.
+    #[inline]
+    pub fn push(&mut self, frame: &T) {
+        self.head = (self.head + 1) % self.frames.len();
+        self.frames[self.head].clone_from(frame);
+    }
+
+    /// Returns the frame pushed `age` pushes ago.
+    #[inline]
+    pub fn get(&self, age: usize) -> &T {
+        &self.frames[self.index_of(age)]
+    }
+
+    /// Returns the frame pushed `age` pushes ago, mutably.
+    #[inline]
+    pub fn get_mut(&mut self, age: usize) -> &mut T {
+        let index = self.index_of(age);
+        &mut self.frames[index]
+    }
+
+    /// Iterates over the frames from newest to oldest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len()).map(|age| self.get(age))
+    }
+
+    /// Resets every frame to its default value.
+    pub fn clear(&mut self) {
+        self.frames.fill(T::default());
+        self.head = 0;
+    }
+
+    #[inline]
+    fn index_of(&self, age: usize) -> usize {
+        assert!(age < self.frames.len(), "frame age out of range");
+        (self.head + self.frames.len() - age) % self.frames.len()
+    }
+}
+
+impl<T: StateValue + Default> StateValue for FrameHistory<T> {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.frames);
+        state.write(&self.head);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.frames)?;
+        state.read(&mut self.head)?;
+        if self.head >= self.frames.len() {
+            return Err(FftError::InvalidState(format!(
+                "history head {} out of range for {} frames",
+                self.head,
+                self.frames.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Delays frames by a fixed number of hops, so that the frames following the delayed one are
+/// already known, for processors that look ahead in time.
+///
+/// A processor pushes every input frame, processes [`current`](Self::current) while looking at
+/// the frames [`ahead`](Self::ahead) of it, and reports [`frames`](Self::frames) as its latency,
+/// which the graph then includes in its overall latency.
+#[derive(Clone)]
+pub struct Lookahead<T> {
+    history: FrameHistory<T>,
+}
+
+impl<T: Clone + Default> Lookahead<T> {
+    /// Creates a look-ahead of `frames` hops. A look-ahead of 0 passes frames through.
+    pub fn new(frames: usize) -> Self {
+        Self {
+            history: FrameHistory::new(frames + 1),
+        }
+    }
+
+    /// Returns the number of hops frames are delayed by.
+    #[inline]
+    pub fn frames(&self) -> usize {
+        self.history.len() - 1
+    }
+
+    #[inline]
+    pub fn push(&mut self, frame: &T) {
+        self.history.push(frame);
+    }
+
+    /// Returns the delayed frame, pushed [`frames`](Self::frames) pushes ago.
+    #[inline]
+    pub fn current(&self) -> &T {
+        self.history.get(self.frames())
+    }
+
+    /// Returns the frame `hops` hops after the current one, up to [`frames`](Self::frames).
+    #[inline]
+    pub fn ahead(&self, hops: usize) -> &T {
+        assert!(hops <= self.frames(), "look-ahead out of range");
+        self.history.get(self.frames() - hops)
+    }
+
+    /// Iterates over the current frame and the frames ahead of it, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..=self.frames()).map(|hops| self.ahead(hops))
+    }
+
+    /// Resets every frame to its default value.
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+}
+
+impl<T: StateValue + Default> StateValue for Lookahead<T> {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.history);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.history)
+    }
+}
+
+/// Linearly interpolates the magnitudes of two spectra, writing the result to `out`.
+pub fn lerp_magnitudes(a: &[Complex32], b: &[Complex32], t: f32, out: &mut [f32]) {
+    for ((out, a), b) in out.iter_mut().zip(a).zip(b) {
+        *out = a.norm() + (b.norm() - a.norm()) * t;
+    }
+}
+
+/// Sets the magnitude of `bin` to `magnitude`, preserving its phase.
+#[inline]
+pub fn set_magnitude(bin: &mut Complex32, magnitude: f32) {
+    let current = bin.norm();
+    if current > f32::EPSILON {
+        *bin *= magnitude / current;
+    } else {
+        *bin = Complex32::new(magnitude, 0.0);
+    }
+}
@@ -0,0 +1,187 @@
+//! The signals processors exchange and the views of their inputs and outputs for one call of
+//! [`FftProcessor::process`](crate::processor::FftProcessor::process).
+//!
+//! With the `raug` feature these are raug's own types, so every processor also runs as a node of
+//! a raug graph. Without it, this module defines stand-ins with the same interface, so processors
+//! can be written and driven by hand without depending on raug:
+//!
+//! ```ignore
+//! let input = AnyBuffer::zeros::<RealFft1024>(1);
+//! let mut outputs = processor.create_output_buffers(1);
+//! processor.process(
+//!     ProcessorInputs::new(&[Some(&input)], 1),
+//!     ProcessorOutputs::new(&mut outputs),
+//! )?;
+//! ```
+
+#[cfg(feature = "raug")]
+pub use raug::prelude::{
+    AnyBuffer, ProcResult, ProcessorError, ProcessorInputs, ProcessorOutputs, Signal, SignalSpec,
+};
+
+#[cfg(not(feature = "raug"))]
+pub use standalone::*;
+
+#[cfg(not(feature = "raug"))]
+mod standalone {
+    use alloc::{boxed::Box, string::String, vec, vec::Vec};
+    use core::any::{Any, TypeId};
+
+    /// A type of value exchanged between processors, one per frame.
+    pub trait Signal: Send + Sync + 'static {
+        fn signal_type() -> SignalType
+        where
+            Self: Sized,
+        {
+            SignalType(TypeId::of::<Self>())
+        }
+    }
+
+    impl Signal for f32 {}
+
+    impl Signal for bool {}
+
+    /// Identifies a [`Signal`] type, so ports of different types can't be connected.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct SignalType(TypeId);
+
+    /// The name and type of a processor's input or output.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SignalSpec {
+        pub name: String,
+        pub signal_type: SignalType,
+    }
+
+    impl SignalSpec {
+        pub fn new(name: impl Into<String>, signal_type: SignalType) -> Self {
+            Self {
+                name: name.into(),
+                signal_type,
+            }
+        }
+    }
+
+    /// A buffer of values of a single [`Signal`] type, chosen when it's created.
+    pub struct AnyBuffer {
+        data: Box<dyn Any + Send>,
+        signal_type: SignalType,
+        len: usize,
+    }
+
+    impl AnyBuffer {
+        /// Creates a buffer of `len` default values of `S`.
+        pub fn zeros<S: Signal + Default + Clone>(len: usize) -> Self {
+            Self {
+                data: Box::new(vec![S::default(); len]),
+                signal_type: S::signal_type(),
+                len,
+            }
+        }
+
+        pub fn signal_type(&self) -> SignalType {
+            self.signal_type
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// Returns the values, or `None` if the buffer holds another type than `S`.
+        pub fn as_slice<S: Signal>(&self) -> Option<&[S]> {
+            self.data.downcast_ref::<Vec<S>>().map(Vec::as_slice)
+        }
+
+        /// Returns the values, or `None` if the buffer holds another type than `S`.
+        pub fn as_mut_slice<S: Signal>(&mut self) -> Option<&mut [S]> {
+            self.data.downcast_mut::<Vec<S>>().map(Vec::as_mut_slice)
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum ProcessorError {
+        #[error("processing error: {0}")]
+        ProcessingError(Box<dyn core::error::Error + Send + Sync>),
+    }
+
+    pub type ProcResult<T> = Result<T, ProcessorError>;
+
+    /// The inputs of a processor for one call, `None` where an input isn't connected.
+    pub struct ProcessorInputs<'a> {
+        inputs: &'a [Option<&'a AnyBuffer>],
+        block_size: usize,
+    }
+
+    impl<'a> ProcessorInputs<'a> {
+        /// Passes `inputs`, each holding `block_size` frames, in the order of the processor's
+        /// [`input_spec`](crate::processor::FftProcessor::input_spec).
+        pub fn new(inputs: &'a [Option<&'a AnyBuffer>], block_size: usize) -> Self {
+            Self { inputs, block_size }
+        }
+
+        /// Returns the frames of input `index`, or `None` if it isn't connected or holds another
+        /// type than `S`.
+        pub fn input_as<S: Signal>(&self, index: usize) -> Option<&'a [S]> {
+            self.inputs.get(index).copied().flatten()?.as_slice::<S>()
+        }
+
+        pub fn block_size(&self) -> usize {
+            self.block_size
+        }
+
+        pub fn len(&self) -> usize {
+            self.inputs.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.inputs.is_empty()
+        }
+    }
+
+    /// The outputs of a processor for one call.
+    pub struct ProcessorOutputs<'a> {
+        outputs: &'a mut [AnyBuffer],
+    }
+
+    impl<'a> ProcessorOutputs<'a> {
+        /// Passes `outputs` in the order of the processor's
+        /// [`output_spec`](crate::processor::FftProcessor::output_spec), usually made with
+        /// [`create_output_buffers`](crate::processor::FftProcessor::create_output_buffers).
+        pub fn new(outputs: &'a mut [AnyBuffer]) -> Self {
+            Self { outputs }
+        }
+
+        /// Writes `value` to frame `sample_index` of output `index`.
+        pub fn set_output_as<S: Signal + Clone>(
+            &mut self,
+            index: usize,
+            sample_index: usize,
+            value: &S,
+        ) -> ProcResult<()> {
+            let slot = self
+                .outputs
+                .get_mut(index)
+                .and_then(AnyBuffer::as_mut_slice::<S>)
+                .and_then(|values| values.get_mut(sample_index))
+                .ok_or_else(|| ProcessorError::ProcessingError("no such output frame".into()))?;
+            slot.clone_from(value);
+            Ok(())
+        }
+
+        /// Returns the frames of output `index`, or `None` if there isn't one holding `S`.
+        pub fn output_as<S: Signal>(&mut self, index: usize) -> Option<&mut [S]> {
+            self.outputs.get_mut(index)?.as_mut_slice::<S>()
+        }
+
+        pub fn len(&self) -> usize {
+            self.outputs.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.outputs.is_empty()
+        }
+    }
+}
@@ -1,72 +1,224 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 use thiserror::Error;
 
+pub mod analytic;
+pub mod bands;
+#[cfg(feature = "raug")]
+pub mod block;
+#[cfg(feature = "raug")]
+pub mod builder;
+#[cfg(feature = "raug")]
 pub mod builtins;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod channelizer;
+pub mod chroma;
+pub mod core;
+pub mod denormal;
+#[cfg(feature = "raug")]
+pub mod diagnostics;
+pub mod dither;
+#[cfg(feature = "std")]
+pub mod fir;
+#[cfg(feature = "raug")]
+pub mod frame_buffer;
+#[cfg(feature = "std")]
+pub mod frame_file;
+#[cfg(feature = "raug")]
 pub mod graph;
+#[cfg(feature = "raug")]
+pub mod handle;
+#[cfg(feature = "raug")]
+pub mod hilbert;
+pub mod history;
+pub mod io;
+#[cfg(feature = "std")]
+pub mod librosa;
+#[cfg(feature = "raug")]
+pub mod metrics;
+#[cfg(feature = "raug")]
+pub mod midi;
+#[cfg(feature = "raug")]
 pub mod node;
+#[cfg(feature = "patch")]
+pub mod patch;
+pub mod peaks;
+#[cfg(feature = "raug")]
+pub mod port;
 pub mod processor;
+#[cfg(feature = "profiling")]
+pub mod profile;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod random;
+#[cfg(feature = "raug")]
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod resample;
+#[cfg(feature = "raug")]
+pub mod resolution;
+pub mod scratch;
 pub mod signal;
+pub mod smooth;
+pub mod state;
+#[cfg(feature = "raug")]
+pub mod subgraph;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod units;
+#[cfg(feature = "raug")]
+pub mod voices;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wavelet;
+pub mod wola;
+
+pub use crate::core::WindowFunction;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::{boxed::Box, vec, vec::Vec};
+
+    pub use crate::io::{AnyBuffer, Signal, SignalSpec};
+}
 
 pub mod prelude {
+    pub use super::analytic::*;
+    pub use super::bands::*;
+    #[cfg(feature = "raug")]
+    pub use super::block::*;
+    #[cfg(feature = "raug")]
+    pub use super::builder::*;
+    #[cfg(feature = "raug")]
     pub use super::builtins::*;
+    pub use super::channelizer::*;
+    pub use super::chroma::*;
+    pub use super::core::*;
+    pub use super::denormal::*;
+    #[cfg(feature = "raug")]
+    pub use super::diagnostics::{DIAGNOSTIC_CAPACITY, RuntimeDiagnostic};
+    pub use super::dither::*;
+    #[cfg(feature = "std")]
+    pub use super::fir::*;
+    #[cfg(feature = "raug")]
+    pub use super::frame_buffer::*;
+    #[cfg(feature = "std")]
+    pub use super::frame_file::*;
+    #[cfg(feature = "raug")]
     pub use super::graph::*;
+    #[cfg(feature = "raug")]
+    pub use super::handle::*;
+    #[cfg(feature = "raug")]
+    pub use super::hilbert::*;
+    pub use super::history::*;
+    pub use super::io::*;
+    #[cfg(feature = "std")]
+    pub use super::librosa::*;
+    #[cfg(feature = "raug")]
+    pub use super::metrics::*;
+    #[cfg(feature = "raug")]
+    pub use super::midi::*;
+    #[cfg(feature = "raug")]
     pub use super::node::*;
+    #[cfg(feature = "patch")]
+    pub use super::patch::*;
+    pub use super::peaks::*;
+    #[cfg(feature = "raug")]
+    pub use super::port::*;
     pub use super::processor::*;
+    #[cfg(feature = "profiling")]
+    pub use super::profile::*;
+    pub use super::random::*;
+    #[cfg(feature = "raug")]
+    pub use super::registry::*;
+    #[cfg(feature = "std")]
+    pub use super::resample::*;
+    #[cfg(feature = "raug")]
+    pub use super::resolution::*;
+    pub use super::scratch::*;
     pub use super::signal::*;
+    pub use super::smooth::*;
+    pub use super::state::*;
+    #[cfg(feature = "raug")]
+    pub use super::subgraph::*;
+    pub use super::units::*;
+    #[cfg(feature = "raug")]
+    pub use super::voices::*;
+    pub use super::wavelet::*;
+    pub use super::wola::*;
+    pub use crate::fft_processor_io;
+    pub use crate::processor_state;
 }
 
 #[derive(Debug, Error)]
-#[error("FFT error: {0}")]
 pub enum FftError {
+    #[cfg(feature = "std")]
+    #[error("FFT error: {0}")]
     RealFft(#[from] realfft::FftError),
+    #[error("FFT error: {0}")]
+    Transform(&'static str),
+    #[error("graph error: {0}")]
+    Graph(alloc::string::String),
+    #[error(
+        "invalid STFT configuration (fft_length {fft_length}, hop_length {hop_length}): {reason}"
+    )]
+    InvalidStft {
+        fft_length: usize,
+        hop_length: usize,
+        reason: &'static str,
+    },
+    #[error("no FFT size matches the FFT length {0}")]
+    UnsupportedFftLength(usize),
+    #[error("{node}: input {input} is not connected")]
+    MissingInput {
+        node: alloc::string::String,
+        input: u32,
+    },
+    #[error("{node}: port {port} holds {found} samples, expected {expected}")]
+    BufferSizeMismatch {
+        node: alloc::string::String,
+        port: u32,
+        expected: usize,
+        found: usize,
+    },
+    #[error("{node} has no {kind} named {name:?}{hint}")]
+    UnknownPort {
+        node: alloc::string::String,
+        /// `"input"`, `"output"` or `"parameter"`.
+        kind: &'static str,
+        name: alloc::string::String,
+        /// The closest name, or the names there are.
+        hint: alloc::string::String,
+    },
+    #[error("{node}: port {port} does not hold signals of type {expected}")]
+    TypeMismatch {
+        node: alloc::string::String,
+        port: u32,
+        expected: &'static str,
+    },
+    #[error("invalid state: {0}")]
+    InvalidState(alloc::string::String),
+    #[cfg(feature = "raug")]
+    #[error("invalid graph: {}", display_list(.0))]
+    InvalidGraph(alloc::vec::Vec<crate::graph::Diagnostic>),
+    #[cfg(feature = "patch")]
+    #[error(transparent)]
+    Patch(#[from] crate::patch::PatchError),
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub enum WindowFunction {
-    Rectangular,
-    #[default]
-    Hann,
-    Hamming,
-    Blackman,
-    Nuttall,
-    Triangular,
-}
+#[cfg(feature = "raug")]
+fn display_list<T: ::core::fmt::Display>(items: &[T]) -> alloc::string::String {
+    use alloc::string::ToString;
 
-impl WindowFunction {
-    pub fn generate(&self, length: usize) -> Vec<f32> {
-        let mut buf = vec![1.0; length];
-        self.apply(&mut buf);
-        buf
-    }
+    let items: alloc::vec::Vec<_> = items.iter().map(ToString::to_string).collect();
+    items.join("; ")
+}
 
-    pub fn apply(&self, buf: &mut [f32]) {
-        let size = buf.len();
-        match self {
-            Self::Rectangular => {}
-            Self::Hann => {
-                for (x, y) in buf.iter_mut().zip(apodize::hanning_iter(size)) {
-                    *x *= y as f32;
-                }
-            }
-            Self::Hamming => {
-                for (x, y) in buf.iter_mut().zip(apodize::hamming_iter(size)) {
-                    *x *= y as f32;
-                }
-            }
-            Self::Blackman => {
-                for (x, y) in buf.iter_mut().zip(apodize::blackman_iter(size)) {
-                    *x *= y as f32;
-                }
-            }
-            Self::Nuttall => {
-                for (x, y) in buf.iter_mut().zip(apodize::nuttall_iter(size)) {
-                    *x *= y as f32;
-                }
-            }
-            Self::Triangular => {
-                for (x, y) in buf.iter_mut().zip(apodize::triangular_iter(size)) {
-                    *x *= y as f32;
-                }
-            }
-        }
+impl From<FftError> for io::ProcessorError {
+    fn from(error: FftError) -> Self {
+        Self::ProcessingError(alloc::boxed::Box::new(error))
     }
 }
@@ -0,0 +1,404 @@
+//! An STFT matching `librosa.stft` and `librosa.istft`, for swapping Python preprocessing for this
+//! crate.
+//!
+//! The graphs' STFT is built for streaming: their windows are zero-phase and normalized for
+//! resynthesis, so their spectra differ from librosa's in phase and scale. [`LibrosaStft`]
+//! instead reproduces librosa's conventions within float tolerance:
+//!
+//! - the window is the periodic window of `win_length` samples (scipy's `fftbins=True`),
+//!   zero-padded on both sides to `n_fft`;
+//! - with a [`PaddingMode`] other than `None` (librosa's `center=True`), the signal is padded by
+//!   `n_fft / 2` samples on both sides, so frame `t` is centered on sample `t * hop_length`;
+//! - spectra are the unnormalized FFTs of the windowed frames;
+//! - the inverse overlap-adds the windowed inverse FFTs and divides by the overlap-added squared
+//!   window wherever it isn't (nearly) zero.
+//!
+//! ```ignore
+//! // librosa.stft(y, n_fft=2048, hop_length=512, win_length=1024, pad_mode="reflect")
+//! let stft = LibrosaStft::new(2048)
+//!     .with_hop_length(512)
+//!     .with_win_length(1024)
+//!     .with_pad_mode(PaddingMode::Reflect);
+//! let frames = stft.stft(&y)?;
+//! let y = stft.istft(&frames, Some(y.len()))?;
+//! ```
+//!
+//! librosa returns `(bins, frames)` arrays, while [`LibrosaStft::stft`] returns one spectrum per
+//! frame, i.e. the transpose.
+//!
+//! The Hann and Hamming windows match scipy's; `WindowFunction::Blackman` has the coefficients
+//! of a Blackman-Harris window, unlike scipy's `"blackman"`. The conventions are tested against
+//! the fixtures in `tests/fixtures/librosa`, written by the `generate.py` script there.
+
+use alloc::vec::Vec;
+
+use crate::{
+    FftError, WindowFunction,
+    core::{Complex32, FftPlanner, PaddingMode, validate_stft},
+};
+
+/// The STFT settings of a `librosa.stft` call, see the [module docs](self).
+///
+/// The defaults match librosa's: a hop of a quarter of `n_fft`, a Hann window as long as the FFT
+/// and centered frames padded with zeros (`pad_mode="constant"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibrosaStft {
+    pub n_fft: usize,
+    pub hop_length: usize,
+    pub win_length: usize,
+    pub window: WindowFunction,
+    /// How the signal is padded on both sides, or `None` for `center=False`.
+    pub pad_mode: PaddingMode,
+}
+
+impl LibrosaStft {
+    pub fn new(n_fft: usize) -> Self {
+        Self {
+            n_fft,
+            hop_length: n_fft / 4,
+            win_length: n_fft,
+            window: WindowFunction::Hann,
+            pad_mode: PaddingMode::Zero,
+        }
+    }
+
+    pub fn with_hop_length(mut self, hop_length: usize) -> Self {
+        self.hop_length = hop_length;
+        self
+    }
+
+    pub fn with_win_length(mut self, win_length: usize) -> Self {
+        self.win_length = win_length;
+        self
+    }
+
+    pub fn with_window(mut self, window: WindowFunction) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_pad_mode(mut self, pad_mode: PaddingMode) -> Self {
+        self.pad_mode = pad_mode;
+        self
+    }
+
+    pub fn center(&self) -> bool {
+        self.pad_mode != PaddingMode::None
+    }
+
+    /// Returns the number of frames of a signal of `len` samples.
+    pub fn num_frames(&self, len: usize) -> usize {
+        let len = len + 2 * self.pad_mode.padding(self.n_fft);
+        if len < self.n_fft || self.hop_length == 0 {
+            0
+        } else {
+            1 + (len - self.n_fft) / self.hop_length
+        }
+    }
+
+    /// Returns the window applied to every frame: the periodic window of `win_length` samples,
+    /// centered in `n_fft` samples.
+    pub fn window(&self) -> Vec<f32> {
+        let mut window = vec![0.0; self.n_fft];
+        // a periodic window is a symmetric one a sample longer, without its last sample
+        let periodic = self.window.generate(self.win_length + 1);
+        let offset = (self.n_fft - self.win_length) / 2;
+        window[offset..offset + self.win_length].copy_from_slice(&periodic[..self.win_length]);
+        window
+    }
+
+    /// Computes the spectra of `signal`, one per frame, like `librosa.stft(signal).T`.
+    pub fn stft(&self, signal: &[f32]) -> Result<Vec<Vec<Complex32>>, FftError> {
+        self.validate()?;
+        let padded = self.pad(signal)?;
+        let window = self.window();
+        let plan = FftPlanner::global().plan_forward(self.n_fft);
+        let mut frame = plan.make_input_vec();
+        let mut scratch = plan.make_scratch_vec();
+
+        let num_frames = self.num_frames(signal.len());
+        let mut spectra = Vec::with_capacity(num_frames);
+        for index in 0..num_frames {
+            let start = index * self.hop_length;
+            let samples = &padded[start..start + self.n_fft];
+            for ((frame, &sample), &window) in frame.iter_mut().zip(samples).zip(&window) {
+                *frame = sample * window;
+            }
+            let mut spectrum = plan.make_output_vec();
+            plan.process_with_scratch(&mut frame, &mut spectrum, &mut scratch)?;
+            spectra.push(spectrum);
+        }
+        Ok(spectra)
+    }
+
+    /// Resynthesizes a signal from one spectrum per frame, like `librosa.istft(spectra.T, length)`.
+    ///
+    /// Without a `length`, the signal is as long as the frames cover, less the padding of
+    /// centered frames.
+    pub fn istft(
+        &self,
+        spectra: &[Vec<Complex32>],
+        length: Option<usize>,
+    ) -> Result<Vec<f32>, FftError> {
+        self.validate()?;
+        let window = self.window();
+        let plan = FftPlanner::global().plan_inverse(self.n_fft);
+        let mut spectrum = plan.make_input_vec();
+        let mut frame = plan.make_output_vec();
+        let mut scratch = plan.make_scratch_vec();
+
+        let expected_len = match spectra.len() {
+            0 => 0,
+            frames => self.n_fft + self.hop_length * (frames - 1),
+        };
+        let mut signal = vec![0.0; expected_len];
+        let mut window_sum = vec![0.0; expected_len];
+        // numpy's inverse FFT is normalized, realfft's isn't
+        let scale = 1.0 / self.n_fft as f32;
+        for (index, frame_spectrum) in spectra.iter().enumerate() {
+            if frame_spectrum.len() != spectrum.len() {
+                return Err(FftError::Transform("spectra must have n_fft / 2 + 1 bins"));
+            }
+            spectrum.copy_from_slice(frame_spectrum);
+            // the inverse transform rejects imaginary parts at DC and Nyquist, which numpy ignores
+            let last = spectrum.len() - 1;
+            spectrum[0].im = 0.0;
+            spectrum[last].im = 0.0;
+            plan.process_with_scratch(&mut spectrum, &mut frame, &mut scratch)?;
+
+            let start = index * self.hop_length;
+            let signal = &mut signal[start..start + self.n_fft];
+            let window_sum = &mut window_sum[start..start + self.n_fft];
+            for (i, (&sample, &w)) in frame.iter().zip(&window).enumerate() {
+                signal[i] += sample * scale * w;
+                window_sum[i] += w * w;
+            }
+        }
+        for (sample, &sum) in signal.iter_mut().zip(&window_sum) {
+            if sum > f32::MIN_POSITIVE {
+                *sample /= sum;
+            }
+        }
+
+        let padding = self.pad_mode.padding(self.n_fft);
+        let mut signal = match length {
+            Some(length) => signal.into_iter().skip(padding).take(length).collect(),
+            None => {
+                let end = signal.len().saturating_sub(padding);
+                signal.get(padding..end).unwrap_or_default().to_vec()
+            }
+        };
+        if let Some(length) = length {
+            signal.resize(length, 0.0);
+        }
+        Ok(signal)
+    }
+
+    fn validate(&self) -> Result<(), FftError> {
+        validate_stft(self.n_fft, self.hop_length)?;
+        if self.win_length == 0 || self.win_length > self.n_fft {
+            return Err(FftError::InvalidStft {
+                fft_length: self.n_fft,
+                hop_length: self.hop_length,
+                reason: "window length must be between 1 and the FFT length",
+            });
+        }
+        Ok(())
+    }
+
+    /// Pads `signal` on both sides like `numpy.pad` with the padding mode.
+    fn pad(&self, signal: &[f32]) -> Result<Vec<f32>, FftError> {
+        let padding = self.pad_mode.padding(self.n_fft);
+        let len = signal.len();
+        let mut padded = Vec::with_capacity(len + 2 * padding);
+        match self.pad_mode {
+            PaddingMode::None => padded.extend_from_slice(signal),
+            PaddingMode::Zero => {
+                padded.resize(padding, 0.0);
+                padded.extend_from_slice(signal);
+                padded.resize(len + 2 * padding, 0.0);
+            }
+            PaddingMode::Reflect => {
+                if len <= padding {
+                    return Err(FftError::Transform(
+                        "reflect padding needs a signal longer than n_fft / 2",
+                    ));
+                }
+                padded.extend(signal[1..=padding].iter().rev());
+                padded.extend_from_slice(signal);
+                padded.extend(signal[len - 1 - padding..len - 1].iter().rev());
+            }
+            PaddingMode::Replicate => {
+                let (Some(&first), Some(&last)) = (signal.first(), signal.last()) else {
+                    return Err(FftError::Transform("edge padding needs a non-empty signal"));
+                };
+                padded.resize(padding, first);
+                padded.extend_from_slice(signal);
+                padded.resize(len + 2 * padding, last);
+            }
+        }
+        Ok(padded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a fixture written by `tests/fixtures/librosa/generate.py`, one row per line.
+    fn fixture(text: &str) -> Vec<Vec<f32>> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                line.split_whitespace()
+                    .map(|value| value.parse().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Parses a fixture of spectra, one frame of interleaved real and imaginary parts per line.
+    fn spectra_fixture(text: &str) -> Vec<Vec<Complex32>> {
+        fixture(text)
+            .iter()
+            .map(|row| {
+                row.chunks(2)
+                    .map(|pair| Complex32::new(pair[0], pair[1]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn signal() -> Vec<f32> {
+        fixture(include_str!("../tests/fixtures/librosa/signal.txt")).remove(0)
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32], tolerance: f32, what: &str) {
+        assert_eq!(actual.len(), expected.len(), "length of {what}");
+        for (index, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (a - e).abs() <= tolerance,
+                "{what} differs at {index}: {a} != {e}"
+            );
+        }
+    }
+
+    /// The settings, stored spectra and stored resynthesis of every STFT fixture.
+    fn stft_cases() -> [(LibrosaStft, &'static str, &'static str); 4] {
+        [
+            (
+                LibrosaStft::new(64),
+                include_str!("../tests/fixtures/librosa/stft_hann_64_16_64_constant.txt"),
+                include_str!("../tests/fixtures/librosa/istft_hann_64_16_64_constant.txt"),
+            ),
+            (
+                LibrosaStft::new(64)
+                    .with_win_length(48)
+                    .with_pad_mode(PaddingMode::Reflect),
+                include_str!("../tests/fixtures/librosa/stft_hann_64_16_48_reflect.txt"),
+                include_str!("../tests/fixtures/librosa/istft_hann_64_16_48_reflect.txt"),
+            ),
+            (
+                LibrosaStft::new(64)
+                    .with_hop_length(20)
+                    .with_window(WindowFunction::Hamming)
+                    .with_pad_mode(PaddingMode::Replicate),
+                include_str!("../tests/fixtures/librosa/stft_hamming_64_20_64_edge.txt"),
+                include_str!("../tests/fixtures/librosa/istft_hamming_64_20_64_edge.txt"),
+            ),
+            (
+                LibrosaStft::new(64).with_pad_mode(PaddingMode::None),
+                include_str!("../tests/fixtures/librosa/stft_hann_64_16_64_nocenter.txt"),
+                include_str!("../tests/fixtures/librosa/istft_hann_64_16_64_nocenter.txt"),
+            ),
+        ]
+    }
+
+    #[test]
+    fn windows_match_librosa() {
+        let hann = LibrosaStft::new(64).with_win_length(48).window();
+        let expected = fixture(include_str!(
+            "../tests/fixtures/librosa/window_hann_64_48.txt"
+        ));
+        assert_close(&hann, &expected[0], 1e-6, "hann window");
+
+        let hamming = LibrosaStft::new(64)
+            .with_window(WindowFunction::Hamming)
+            .window();
+        let expected = fixture(include_str!(
+            "../tests/fixtures/librosa/window_hamming_64_64.txt"
+        ));
+        assert_close(&hamming, &expected[0], 1e-6, "hamming window");
+    }
+
+    #[test]
+    fn padding_matches_numpy() {
+        let signal = signal();
+        let cases = [
+            (
+                PaddingMode::Zero,
+                include_str!("../tests/fixtures/librosa/pad_constant_32.txt"),
+            ),
+            (
+                PaddingMode::Reflect,
+                include_str!("../tests/fixtures/librosa/pad_reflect_32.txt"),
+            ),
+            (
+                PaddingMode::Replicate,
+                include_str!("../tests/fixtures/librosa/pad_edge_32.txt"),
+            ),
+        ];
+        for (pad_mode, expected) in cases {
+            let padded = LibrosaStft::new(64)
+                .with_pad_mode(pad_mode)
+                .pad(&signal)
+                .unwrap();
+            assert_close(&padded, &fixture(expected)[0], 0.0, pad_mode.name());
+        }
+    }
+
+    #[test]
+    fn stft_matches_librosa() {
+        let signal = signal();
+        for (stft, expected, _) in stft_cases() {
+            let expected = spectra_fixture(expected);
+            let spectra = stft.stft(&signal).unwrap();
+            assert_eq!(spectra.len(), stft.num_frames(signal.len()));
+            assert_eq!(spectra.len(), expected.len(), "frames of {stft:?}");
+
+            // the FFTs are single precision, so allow for rounding relative to the loudest bin
+            let loudest = expected
+                .iter()
+                .flatten()
+                .map(|x| x.norm())
+                .fold(0.0, f32::max);
+            for (frame, (spectrum, expected)) in spectra.iter().zip(&expected).enumerate() {
+                for (bin, (x, e)) in spectrum.iter().zip(expected).enumerate() {
+                    assert!(
+                        (x - e).norm() <= 1e-5 * loudest,
+                        "bin {bin} of frame {frame} differs: {x} != {e} ({stft:?})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn istft_matches_librosa() {
+        let signal = signal();
+        for (stft, spectra, expected) in stft_cases() {
+            let spectra = spectra_fixture(spectra);
+            let length = stft.center().then_some(signal.len());
+            let resynthesized = stft.istft(&spectra, length).unwrap();
+            let what = format!("{stft:?}");
+            // the edges of uncentered frames divide by a nearly zero window sum, which magnifies
+            // rounding
+            assert_close(&resynthesized, &fixture(expected)[0], 1e-4, &what);
+
+            // centered frames cover the whole signal, so it comes back
+            if stft.center() {
+                assert_close(&resynthesized, &signal, 1e-5, &what);
+            }
+        }
+    }
+}
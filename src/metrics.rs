@@ -0,0 +1,160 @@
+//! Health counters of an [`FftGraph`](crate::graph::FftGraph), readable from other threads.
+//!
+//! The graph updates its [`FftGraphMetrics`] with relaxed atomic operations while processing, so
+//! reading them never blocks the audio thread. Take the handle with
+//! [`FftGraph::metrics`](crate::graph::FftGraph::metrics) before handing the graph to the host,
+//! and poll it from a UI or control thread:
+//!
+//! ```ignore
+//! let metrics = fft_graph.metrics();
+//! std::thread::spawn(move || loop {
+//!     println!("{} hops, {} underruns", metrics.hops(), metrics.underruns());
+//!     std::thread::sleep(std::time::Duration::from_secs(1));
+//! });
+//! ```
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Counters describing how an [`FftGraph`](crate::graph::FftGraph) has been running, shared with
+/// the threads holding its handle.
+#[derive(Debug, Default)]
+pub struct FftGraphMetrics {
+    hops: AtomicU64,
+    underruns: AtomicU64,
+    max_ring_occupancy: AtomicUsize,
+    last_process_nanos: AtomicU64,
+}
+
+impl FftGraphMetrics {
+    /// Returns the number of hops (frames) processed.
+    pub fn hops(&self) -> u64 {
+        self.hops.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of output samples that were zero-filled because not enough had been
+    /// resynthesized yet, summed over all audio outputs. See
+    /// [`FftGraph::underruns`](crate::graph::FftGraph::underruns).
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Returns the largest number of samples any audio input or output has held at once.
+    pub fn max_ring_occupancy(&self) -> usize {
+        self.max_ring_occupancy.load(Ordering::Relaxed)
+    }
+
+    /// Returns how long the last call to process a block took, including every hop it ran.
+    /// Always zero on `wasm32-unknown-unknown`, which has no clock.
+    pub fn last_process_duration(&self) -> Duration {
+        Duration::from_nanos(self.last_process_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Resets every counter to zero.
+    pub fn reset(&self) {
+        self.hops.store(0, Ordering::Relaxed);
+        self.underruns.store(0, Ordering::Relaxed);
+        self.max_ring_occupancy.store(0, Ordering::Relaxed);
+        self.last_process_nanos.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn clear_underruns(&self) {
+        self.underruns.store(0, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn add_hops(&self, hops: u64) {
+        self.hops.fetch_add(hops, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn add_underruns(&self, samples: u64) {
+        self.underruns.fetch_add(samples, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_ring_occupancy(&self, samples: usize) {
+        self.max_ring_occupancy
+            .fetch_max(samples, Ordering::Relaxed);
+    }
+}
+
+/// Measures one call of a graph's processing for
+/// [`FftGraphMetrics::last_process_duration`].
+///
+/// `Instant` panics on `wasm32-unknown-unknown`, so nothing is measured there.
+pub(crate) struct ProcessTimer {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    start: std::time::Instant,
+}
+
+impl ProcessTimer {
+    #[inline]
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn finish(self, metrics: &FftGraphMetrics) {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        metrics.last_process_nanos.store(
+            self.start.elapsed().as_nanos().min(u64::MAX as u128) as u64,
+            Ordering::Relaxed,
+        );
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        let _ = metrics;
+    }
+}
+
+/// What running an [`FftGraph`](crate::graph::FftGraph) at a given sample rate and block size
+/// costs, measured by [`FftGraph::estimate_cost`](crate::graph::FftGraph::estimate_cost).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostEstimate {
+    pub sample_rate: f32,
+    pub block_size: usize,
+    /// The number of blocks that were timed.
+    pub blocks: usize,
+    /// The mean time it took to process a block.
+    pub mean_block_duration: Duration,
+    /// The longest time it took to process a block, e.g. one in which more hops fell.
+    pub max_block_duration: Duration,
+    /// The memory held by the graph's own buffers in bytes: node outputs, feedback connections,
+    /// scratch space and the sample rings of its audio inputs and outputs. Whatever processors
+    /// allocate internally isn't included.
+    pub buffer_bytes: usize,
+}
+
+impl CostEstimate {
+    /// Returns the duration of the audio in one block.
+    pub fn block_period(&self) -> Duration {
+        if self.sample_rate > 0.0 {
+            Duration::from_secs_f64(self.block_size as f64 / self.sample_rate as f64)
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Returns the share of real time the graph takes on average, e.g. 0.1 if processing a
+    /// block takes a tenth of the block's duration. Above 1, the graph can't keep up.
+    pub fn load(&self) -> f32 {
+        Self::ratio(self.mean_block_duration, self.block_period())
+    }
+
+    /// Returns the share of real time the slowest block took, see [`load`](Self::load).
+    pub fn peak_load(&self) -> f32 {
+        Self::ratio(self.max_block_duration, self.block_period())
+    }
+
+    fn ratio(duration: Duration, period: Duration) -> f32 {
+        if period.is_zero() {
+            0.0
+        } else {
+            duration.as_secs_f32() / period.as_secs_f32()
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! Mapping MIDI messages to processor parameters.
+//!
+//! Builtins take their performable parameters as `f32` inputs, so a [`MidiParams`] node turns
+//! incoming MIDI into one `f32` output per [`MidiMapping`], named after the mapping, to be
+//! connected to those inputs. MIDI arrives as raw bytes through the [`MidiSender`] returned on
+//! construction, so any MIDI source (a hardware port, a host, a sequencer) can drive it from its
+//! own thread:
+//!
+//! ```ignore
+//! let (params, midi) = MidiParams::<Fft1024>::new(
+//!     vec![
+//!         MidiMapping::cc("amount", 1),
+//!         MidiMapping::cc("cutoff_hz", 74)
+//!             .range(100.0, 10_000.0)
+//!             .curve(MidiCurve::Exponential),
+//!     ],
+//!     256,
+//! );
+//! // on the MIDI thread
+//! midi.send(&[0xb0, 74, 64]);
+//! ```
+//!
+//! [`MidiMapping::for_param`] sets a mapping up from a processor's
+//! [`param_spec`](crate::processor::FftProcessor::param_spec), and
+//! [`MidiMapping::is_valid_for`] checks a hand-written one against it.
+
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+
+use raug::prelude::*;
+
+use crate::{
+    processor::{FftProcessor, FrameInfo, ParamSpec, ParamUnit},
+    processor_state,
+    signal::Fft,
+    smooth::{SmoothedParam, Smoothing},
+};
+
+/// A channel voice message, with channels counted from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// A pitch bend between 0 and 16383, centered at 8192.
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+}
+
+impl MidiMessage {
+    /// Parses a message from its raw bytes, returning `None` for messages other than the ones
+    /// above. Note-ons with a velocity of 0 are parsed as note-offs.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let [status, data1, data2, ..] = *bytes else {
+            return None;
+        };
+        let channel = status & 0x0f;
+        let (data1, data2) = (data1 & 0x7f, data2 & 0x7f);
+        match status & 0xf0 {
+            0x80 => Some(Self::NoteOff {
+                channel,
+                note: data1,
+                velocity: data2,
+            }),
+            0x90 if data2 == 0 => Some(Self::NoteOff {
+                channel,
+                note: data1,
+                velocity: 0,
+            }),
+            0x90 => Some(Self::NoteOn {
+                channel,
+                note: data1,
+                velocity: data2,
+            }),
+            0xb0 => Some(Self::ControlChange {
+                channel,
+                controller: data1,
+                value: data2,
+            }),
+            0xe0 => Some(Self::PitchBend {
+                channel,
+                value: data1 as u16 | (data2 as u16) << 7,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn channel(&self) -> u8 {
+        match *self {
+            Self::NoteOff { channel, .. }
+            | Self::NoteOn { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::PitchBend { channel, .. } => channel,
+        }
+    }
+}
+
+/// How a normalized MIDI value (`0..=1`) is mapped onto a parameter's range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MidiCurve {
+    #[default]
+    Linear,
+    /// Equal ratios for equal steps, for frequencies and times. Both ends of the range must be
+    /// above zero.
+    Exponential,
+    /// The square of the value, giving finer control near the start of the range.
+    Squared,
+    /// The start of the range below the middle, the end of it from the middle on.
+    Toggle,
+}
+
+impl MidiCurve {
+    pub fn map(&self, value: f32, min: f32, max: f32) -> f32 {
+        match self {
+            Self::Linear => min + (max - min) * value,
+            Self::Exponential => min * (max / min).powf(value),
+            Self::Squared => min + (max - min) * value * value,
+            Self::Toggle => {
+                if value < 0.5 {
+                    min
+                } else {
+                    max
+                }
+            }
+        }
+    }
+}
+
+/// The part of a MIDI message a [`MidiMapping`] follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiSource {
+    ControlChange(u8),
+    /// The velocity of a note while it's held, and 0 after it's released.
+    NoteVelocity(u8),
+    /// 1 while a note is held, 0 after it's released.
+    NoteGate(u8),
+    /// The number of the most recently played note.
+    LastNote,
+    PitchBend,
+}
+
+/// Maps one MIDI control onto the range of a named parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiMapping {
+    pub name: String,
+    pub source: MidiSource,
+    /// Only messages on this channel are followed, or on any channel if `None`.
+    pub channel: Option<u8>,
+    pub min: f32,
+    pub max: f32,
+    pub curve: MidiCurve,
+    /// The value before the first message, in the parameter's range.
+    pub initial: f32,
+}
+
+impl MidiMapping {
+    /// Maps `source` linearly onto `0..=1` for the parameter `name`, on any channel.
+    pub fn new(name: impl Into<String>, source: MidiSource) -> Self {
+        Self {
+            name: name.into(),
+            source,
+            channel: None,
+            min: 0.0,
+            max: 1.0,
+            curve: MidiCurve::Linear,
+            initial: 0.0,
+        }
+    }
+
+    pub fn cc(name: impl Into<String>, controller: u8) -> Self {
+        Self::new(name, MidiSource::ControlChange(controller))
+    }
+
+    /// Maps `source` onto the range of the parameter `spec` describes, starting at its default.
+    /// Toggles get a [`MidiCurve::Toggle`], and frequencies above 0 Hz a
+    /// [`MidiCurve::Exponential`].
+    pub fn for_param(spec: &ParamSpec, source: MidiSource) -> Self {
+        let curve = match spec.unit {
+            ParamUnit::Toggle => MidiCurve::Toggle,
+            ParamUnit::Hertz if spec.min > 0.0 => MidiCurve::Exponential,
+            _ => MidiCurve::Linear,
+        };
+        Self::new(spec.name.clone(), source)
+            .range(spec.min, spec.max)
+            .curve(curve)
+            .initial(spec.default)
+    }
+
+    /// Returns whether the mapping is named after the parameter `spec` describes and only
+    /// produces values within its range.
+    pub fn is_valid_for(&self, spec: &ParamSpec) -> bool {
+        self.name == spec.name
+            && [self.min, self.max, self.initial]
+                .iter()
+                .all(|&value| (spec.min..=spec.max).contains(&value))
+    }
+
+    /// Sets the range, moving `initial` to `min`.
+    pub fn range(mut self, min: f32, max: f32) -> Self {
+        self.min = min;
+        self.max = max;
+        self.initial = min;
+        self
+    }
+
+    pub fn curve(mut self, curve: MidiCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    pub fn channel(mut self, channel: u8) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    pub fn initial(mut self, initial: f32) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// Returns whether the mapping only ever jumps between `min` and `max`.
+    fn is_stepped(&self) -> bool {
+        self.curve == MidiCurve::Toggle || matches!(self.source, MidiSource::NoteGate(_))
+    }
+
+    /// Returns the parameter value `message` sets, or `None` if the mapping doesn't follow it.
+    pub fn value(&self, message: &MidiMessage) -> Option<f32> {
+        if self
+            .channel
+            .is_some_and(|channel| channel != message.channel())
+        {
+            return None;
+        }
+        let value = match (self.source, *message) {
+            (
+                MidiSource::ControlChange(source),
+                MidiMessage::ControlChange {
+                    controller, value, ..
+                },
+            ) if controller == source => value as f32 / 127.0,
+            (MidiSource::NoteVelocity(source), MidiMessage::NoteOn { note, velocity, .. })
+                if note == source =>
+            {
+                velocity as f32 / 127.0
+            }
+            (MidiSource::NoteGate(source), MidiMessage::NoteOn { note, .. }) if note == source => {
+                1.0
+            }
+            (
+                MidiSource::NoteVelocity(source) | MidiSource::NoteGate(source),
+                MidiMessage::NoteOff { note, .. },
+            ) if note == source => 0.0,
+            (MidiSource::LastNote, MidiMessage::NoteOn { note, .. }) => note as f32 / 127.0,
+            (MidiSource::PitchBend, MidiMessage::PitchBend { value, .. }) => value as f32 / 16383.0,
+            _ => return None,
+        };
+        Some(self.curve.map(value, self.min, self.max))
+    }
+}
+
+/// Sends MIDI to a [`MidiParams`] from another thread.
+///
+/// Messages are queued without locking and applied before the next frame. The queue has a fixed
+/// capacity, so the methods return `false` if it's full (or the node was dropped).
+#[derive(Clone)]
+pub struct MidiSender {
+    sender: SyncSender<MidiMessage>,
+}
+
+impl MidiSender {
+    /// Queues a message from its raw bytes. Messages no mapping could follow are ignored and
+    /// count as sent.
+    pub fn send(&self, bytes: &[u8]) -> bool {
+        match MidiMessage::parse(bytes) {
+            Some(message) => self.send_message(message),
+            None => true,
+        }
+    }
+
+    pub fn send_message(&self, message: MidiMessage) -> bool {
+        match self.sender.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+/// Outputs the values of MIDI-controlled parameters, see the [module docs](self).
+///
+/// There is one `f32` output per mapping, named after it. Jumps in value are smoothed over a few
+/// hops, except for [`MidiCurve::Toggle`] mappings and note gates.
+pub struct MidiParams<F: Fft> {
+    mappings: Vec<MidiMapping>,
+    values: Vec<SmoothedParam>,
+    messages: Receiver<MidiMessage>,
+    _f: std::marker::PhantomData<F>,
+}
+
+impl<F: Fft> MidiParams<F> {
+    /// Creates the node and the sender feeding it, queueing at most `capacity` messages between
+    /// frames.
+    pub fn new(mappings: Vec<MidiMapping>, capacity: usize) -> (Self, MidiSender) {
+        let (sender, messages) = sync_channel(capacity.max(1));
+        let values = mappings
+            .iter()
+            .map(|mapping| {
+                let param = SmoothedParam::new(mapping.initial);
+                if mapping.is_stepped() {
+                    param.with_smoothing(Smoothing::None)
+                } else {
+                    param
+                }
+            })
+            .collect();
+        let params = Self {
+            mappings,
+            values,
+            messages,
+            _f: std::marker::PhantomData,
+        };
+        (params, MidiSender { sender })
+    }
+
+    pub fn mappings(&self) -> &[MidiMapping] {
+        &self.mappings
+    }
+
+    /// Returns the output index of the mapping named `name`.
+    pub fn output_index(&self, name: &str) -> Option<u32> {
+        self.mappings
+            .iter()
+            .position(|mapping| mapping.name == name)
+            .map(|index| index as u32)
+    }
+
+    /// Sets the smoothing of every mapping that is smoothed.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        for (mapping, value) in self.mappings.iter().zip(&mut self.values) {
+            if !mapping.is_stepped() {
+                value.set_smoothing(smoothing);
+            }
+        }
+    }
+
+    /// Applies `message` directly, e.g. before the node is added to a graph.
+    pub fn handle(&mut self, message: &MidiMessage) {
+        for (mapping, value) in self.mappings.iter().zip(&mut self.values) {
+            if let Some(target) = mapping.value(message) {
+                value.set_target(target);
+            }
+        }
+    }
+}
+
+impl<F: Fft> FftProcessor for MidiParams<F> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        vec![]
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        self.mappings
+            .iter()
+            .map(|mapping| SignalSpec::new(mapping.name.as_str(), f32::signal_type()))
+            .collect()
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        self.mappings
+            .iter()
+            .map(|_| AnyBuffer::zeros::<f32>(size))
+            .collect()
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        for value in &mut self.values {
+            value.set_hop_seconds(info.hop_seconds());
+        }
+    }
+
+    processor_state!(values);
+
+    fn process(
+        &mut self,
+        _inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        while let Ok(message) = self.messages.try_recv() {
+            self.handle(&message);
+        }
+
+        for (index, value) in self.values.iter_mut().enumerate() {
+            outputs.set_output_as::<f32>(index, 0, &value.advance())?;
+        }
+
+        Ok(())
+    }
+}
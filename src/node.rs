@@ -1,15 +1,79 @@
-use std::{collections::VecDeque, fmt::Debug, marker::PhantomData};
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
 
 use raug::{graph::node::ProcessNodeError, prelude::*};
 use raug_graph::prelude::*;
 
-use crate::{processor::FftProcessor, signal::Fft};
+#[cfg(feature = "profiling")]
+use crate::profile::NodeTiming;
+use crate::{
+    FftError,
+    core::{FrameInput, OverlapAdd},
+    graph::OutputNormalization,
+    processor::{FftProcessor, FrameInfo},
+    scratch::Scratch,
+    signal::{Complex32, Fft},
+};
+
+/// Returns the number of single-character insertions, deletions and substitutions turning `a`
+/// into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(a != b);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The buffers feeding each input of a node, refilled by the graph before every hop.
+#[derive(Default)]
+pub(crate) struct InputBuffers(Vec<Option<*const AnyBuffer>>);
+
+// SAFETY: the pointers are only dereferenced while the graph owning the pointed-to buffers is
+// processing the node, and are overwritten before every hop.
+unsafe impl Send for InputBuffers {}
+
+impl InputBuffers {
+    fn new(num_inputs: usize) -> Self {
+        Self(vec![None; num_inputs])
+    }
+}
+
+impl Deref for InputBuffers {
+    type Target = [Option<*const AnyBuffer>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for InputBuffers {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
 
 pub struct FftProcessorNode {
     pub(crate) processor: Box<dyn FftProcessor>,
     pub(crate) input_spec: Vec<SignalSpec>,
     pub(crate) output_spec: Vec<SignalSpec>,
     pub(crate) outputs: Vec<AnyBuffer>,
+    pub(crate) input_buffers: InputBuffers,
+    pub(crate) non_finite_hops: u64,
+    pub(crate) bypassed: bool,
+    pub(crate) frozen: bool,
+    #[cfg(feature = "profiling")]
+    pub(crate) timing: NodeTiming,
 }
 
 impl Debug for FftProcessorNode {
@@ -27,11 +91,18 @@ impl FftProcessorNode {
         let input_spec = processor.input_spec();
         let output_spec = processor.output_spec();
         let outputs = processor.create_output_buffers(0);
+        let input_buffers = InputBuffers::new(input_spec.len());
         Self {
             processor,
             input_spec,
             output_spec,
             outputs,
+            input_buffers,
+            non_finite_hops: 0,
+            bypassed: false,
+            frozen: false,
+            #[cfg(feature = "profiling")]
+            timing: NodeTiming::default(),
         }
     }
 
@@ -62,6 +133,75 @@ impl FftProcessorNode {
         self.output_spec.len()
     }
 
+    /// Returns the index of the input named `name`.
+    pub fn input_index(&self, name: &str) -> Result<u32, FftError> {
+        self.port_index(&self.input_spec, "input", name)
+    }
+
+    /// Returns the index of the output named `name`.
+    pub fn output_index(&self, name: &str) -> Result<u32, FftError> {
+        self.port_index(&self.output_spec, "output", name)
+    }
+
+    fn port_index(
+        &self,
+        specs: &[SignalSpec],
+        kind: &'static str,
+        name: &str,
+    ) -> Result<u32, FftError> {
+        match specs.iter().position(|spec| spec.name == name) {
+            Some(index) => Ok(index as u32),
+            None => {
+                let names: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+                Err(self.unknown_name(kind, name, &names))
+            }
+        }
+    }
+
+    /// Returns the error for a missing `kind` named `name`, suggesting the closest of `names`.
+    fn unknown_name(&self, kind: &'static str, name: &str, names: &[&str]) -> FftError {
+        let closest = names
+            .iter()
+            .map(|&candidate| (edit_distance(candidate, name), candidate))
+            .filter(|&(distance, _)| distance <= 2)
+            .min_by_key(|&(distance, _)| distance);
+        let hint = match closest {
+            Some((_, closest)) => format!(", did you mean {closest:?}?"),
+            None if names.is_empty() => format!(" (it has no {kind}s)"),
+            None => format!(" ({kind}s: {})", names.join(", ")),
+        };
+        FftError::UnknownPort {
+            node: self.name().to_string(),
+            kind,
+            name: name.to_string(),
+            hint,
+        }
+    }
+
+    /// Sets the processor's parameter `name`, see [`FftProcessor::set_param`].
+    pub fn set_param(&mut self, name: &str, value: f32) -> Result<(), FftError> {
+        if self.processor.set_param(name, value) {
+            return Ok(());
+        }
+        let specs = self.processor.param_spec();
+        let names: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+        Err(self.unknown_name("parameter", name, &names))
+    }
+
+    /// Returns whether the node passes its inputs through instead of processing them, see
+    /// [`FftGraph::set_bypassed`](crate::graph::FftGraph::set_bypassed).
+    #[inline]
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    /// Returns whether the node holds its outputs instead of processing, see
+    /// [`FftGraph::set_frozen`](crate::graph::FftGraph::set_frozen).
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
     /// Returns a reference to the processor.
     #[inline]
     pub fn processor(&self) -> &dyn FftProcessor {
@@ -74,11 +214,63 @@ impl FftProcessorNode {
         &mut *self.processor
     }
 
+    /// Returns the processing time statistics recorded for this node.
+    #[cfg(feature = "profiling")]
+    #[inline]
+    pub fn timing(&self) -> &NodeTiming {
+        &self.timing
+    }
+
+    /// Returns the number of hops in which the node's outputs contained NaN or infinite values.
+    ///
+    /// Only counted while the graph's NaN guard is enabled, see
+    /// [`FftGraph::set_nan_guard`](crate::graph::FftGraph::set_nan_guard).
+    #[inline]
+    pub fn non_finite_hops(&self) -> u64 {
+        self.non_finite_hops
+    }
+
+    /// Replaces NaN and infinite values in the outputs with zeros, returning the index of the
+    /// first output that contained any.
+    pub(crate) fn sanitize_outputs<F: Fft>(&mut self) -> Option<usize> {
+        let mut first = None;
+        for (index, buffer) in self.outputs.iter_mut().enumerate() {
+            let replaced = if let Some(spectra) = buffer.as_mut_slice::<F::RealFft>() {
+                let mut replaced = false;
+                for bin in spectra.iter_mut().flat_map(|spectrum| spectrum.iter_mut()) {
+                    if !bin.re.is_finite() || !bin.im.is_finite() {
+                        *bin = Complex32::ZERO;
+                        replaced = true;
+                    }
+                }
+                replaced
+            } else if let Some(blocks) = buffer.as_mut_slice::<F::AudioBlock>() {
+                zero_non_finite(blocks.iter_mut().flat_map(|block| block.iter_mut()))
+            } else if let Some(values) = buffer.as_mut_slice::<f32>() {
+                zero_non_finite(values.iter_mut())
+            } else {
+                false
+            };
+
+            if replaced && first.is_none() {
+                first = Some(index);
+            }
+        }
+        first
+    }
+
+    /// Returns the number of frames by which the processor delays its output.
+    #[inline]
+    pub fn latency_frames(&self) -> usize {
+        self.processor.latency_frames()
+    }
+
     /// Allocates memory for the processor.
     #[inline]
     pub fn allocate(&mut self, sample_rate: f32) {
         self.processor.allocate(sample_rate);
         self.outputs = self.processor.create_output_buffers(1);
+        self.input_buffers = InputBuffers::new(self.input_spec.len());
     }
 
     /// Resizes the internal buffers of the processor and updates the sample rate and FFT size.
@@ -89,13 +281,23 @@ impl FftProcessorNode {
         self.processor.resize_buffers(sample_rate);
     }
 
+    /// Tells the processor that the sample rate changed from `old` to `new`.
+    #[inline]
+    pub fn sample_rate_changed(&mut self, old: f32, new: f32) {
+        self.processor.sample_rate_changed(old, new);
+    }
+
     /// Processes the input signals and writes the output signals to the given buffers.
     #[inline]
     pub(crate) fn process(
         &mut self,
         inputs: &[Option<*const AnyBuffer>],
         env: ProcEnv,
+        frame: &FrameInfo,
+        scratch: &mut Scratch,
     ) -> Result<(), ProcessNodeError> {
+        self.processor.set_frame_info(frame);
+
         let inputs = ProcessorInputs {
             input_specs: &self.input_spec,
             inputs,
@@ -106,7 +308,10 @@ impl FftProcessorNode {
             outputs: &mut self.outputs,
             mode: env.mode,
         };
-        if let Err(e) = self.processor.process(inputs, outputs) {
+        if let Err(e) = self
+            .processor
+            .process_with_scratch(inputs, outputs, scratch)
+        {
             return Err(ProcessNodeError {
                 error: e,
                 node_name: self.name().to_string(),
@@ -117,6 +322,17 @@ impl FftProcessorNode {
     }
 }
 
+fn zero_non_finite<'a>(values: impl Iterator<Item = &'a mut f32>) -> bool {
+    let mut replaced = false;
+    for value in values {
+        if !value.is_finite() {
+            *value = 0.0;
+            replaced = true;
+        }
+    }
+    replaced
+}
+
 impl AbstractNode for FftProcessorNode {
     fn name(&self) -> Option<String> {
         Some(self.processor.name().to_string())
@@ -154,31 +370,72 @@ impl AbstractNode for FftProcessorNode {
 }
 
 pub struct FftInput<F: Fft> {
-    pub(crate) ring_buffer: VecDeque<f32>,
+    pub(crate) frames: FrameInput,
     pub(crate) time_domain: F::AudioBlock,
+    /// Whether this is a sidechain input, which reads silence while unconnected.
+    pub(crate) sidechain: bool,
+    /// Applied to every windowed frame before the FFT.
+    pub(crate) gain: f32,
 }
 
 impl<F: Fft> Default for FftInput<F> {
     fn default() -> Self {
         Self {
-            ring_buffer: VecDeque::new(),
+            frames: FrameInput::new(F::N_FFT),
             time_domain: F::AudioBlock::default(),
+            sidechain: false,
+            gain: 1.0,
         }
     }
 }
 
 pub struct FftOutput<F: Fft> {
-    pub(crate) ring_buffer: VecDeque<f32>,
-    pub(crate) overlap_buffer: VecDeque<f32>,
+    pub(crate) overlap_add: OverlapAdd,
+    pub(crate) gain: f32,
+    pub(crate) normalization: OutputNormalization,
     _f: PhantomData<F>,
 }
 
+impl<F: Fft> FftOutput<F> {
+    /// Returns the delay in samples between an audio input and the resynthesized output for
+    /// frames of `frame_length` samples (usually the FFT length), before any processor latency.
+    ///
+    /// A sample is only finished once the last frame overlapping it has been added, which can
+    /// take up to `frame_length - 1` more input samples. Delaying every output by that much means
+    /// the output never runs dry, whatever size the host's blocks are.
+    pub(crate) fn latency(frame_length: usize) -> usize {
+        frame_length - 1
+    }
+
+    /// Clears the output and delays it by its [`latency`](Self::latency), with room for host
+    /// blocks of up to `block_size` samples.
+    pub(crate) fn reset(&mut self, block_size: usize, frame_length: usize) {
+        self.overlap_add.clear();
+        self.overlap_add.reserve(block_size + frame_length);
+        self.overlap_add.delay(Self::latency(frame_length));
+    }
+
+    /// Returns the factor the output is scaled by as it's read, for a window peaking at
+    /// `window_sum_peak`, see [`window_sum_peak`](crate::core::window_sum_peak).
+    pub(crate) fn scale(&self, window_sum_peak: f32) -> f32 {
+        match self.normalization {
+            OutputNormalization::WindowSumPeak if window_sum_peak > 0.0 => {
+                self.gain / window_sum_peak
+            }
+            OutputNormalization::None | OutputNormalization::WindowSumPeak => self.gain,
+        }
+    }
+}
+
 impl<F: Fft> Default for FftOutput<F> {
     fn default() -> Self {
-        Self {
-            ring_buffer: VecDeque::with_capacity(F::N_FFT),
-            overlap_buffer: vec![0.0; F::N_FFT].into(),
+        let mut output = Self {
+            overlap_add: OverlapAdd::new(F::N_FFT),
+            gain: 1.0,
+            normalization: OutputNormalization::None,
             _f: PhantomData,
-        }
+        };
+        output.reset(0, F::N_FFT);
+        output
     }
 }
@@ -0,0 +1,335 @@
+//! Declarative graph descriptions ("patches") that can be loaded from TOML or JSON.
+//!
+//! A patch lists the FFT settings, the nodes with their parameters, and the edges between them,
+//! and optionally the graph's random `seed`.
+//! Nodes are built through a [`ProcessorRegistry`], except for the special node types
+//! `"audio_input"`, `"sidechain_input"` and `"audio_output"`, which become the graph's audio
+//! inputs and outputs in the order they are listed.
+//!
+//! ```toml
+//! fft_length = 1024
+//! hop_length = 256
+//! window = "hann"
+//!
+//! [[nodes]]
+//! id = "in"
+//! type = "audio_input"
+//!
+//! [[nodes]]
+//! id = "tilt"
+//! type = "spectral_tilt"
+//! params = { tilt_db_per_octave = -3.0 }
+//!
+//! [[nodes]]
+//! id = "out"
+//! type = "audio_output"
+//!
+//! [[edges]]
+//! from = "in"
+//! to = "tilt"
+//!
+//! [[edges]]
+//! from = "tilt"
+//! to = "out"
+//! ```
+
+use std::collections::BTreeMap;
+
+use raug_graph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    FftError, WindowFunction,
+    core::validate_stft,
+    graph::{DynFftGraph, FftGraph, dyn_graph},
+    registry::{ProcessorParams, ProcessorRegistry},
+    signal::Fft,
+};
+
+/// The node type that becomes an audio input of the graph.
+pub const AUDIO_INPUT: &str = "audio_input";
+/// The node type that becomes a sidechain input of the graph, see
+/// [`FftGraph::add_sidechain_input`].
+pub const SIDECHAIN_INPUT: &str = "sidechain_input";
+/// The node type that becomes an audio output of the graph.
+pub const AUDIO_OUTPUT: &str = "audio_output";
+
+#[derive(Debug, Error)]
+pub enum PatchError {
+    #[error("invalid TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("TOML serialization failed: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("patch is for an FFT length of {found}, but the graph uses {expected}")]
+    FftLengthMismatch { expected: usize, found: usize },
+    #[error("unsupported FFT length {0}")]
+    UnsupportedFftLength(usize),
+    #[error("unknown window function {0:?}")]
+    UnknownWindow(String),
+    #[error("unknown processor type {0:?}")]
+    UnknownProcessor(String),
+    #[error("duplicate node id {0:?}")]
+    DuplicateNode(String),
+    #[error("edge refers to unknown node {0:?}")]
+    UnknownNode(String),
+    #[error("cannot connect {from:?} to {to:?}: {reason}")]
+    Connection {
+        from: String,
+        to: String,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatchDescription {
+    pub fft_length: usize,
+    pub hop_length: usize,
+    #[serde(default = "default_window")]
+    pub window: String,
+    /// The graph's random seed, see [`FftGraph::set_seed`].
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub seed: u64,
+    #[serde(default)]
+    pub nodes: Vec<NodeDescription>,
+    #[serde(default)]
+    pub edges: Vec<EdgeDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDescription {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeDescription {
+    pub from: String,
+    #[serde(default)]
+    pub output: u32,
+    pub to: String,
+    #[serde(default)]
+    pub input: u32,
+    /// Whether the edge delays its signal by one hop, which allows it to form a cycle. See
+    /// [`FftGraph::connect_feedback`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub feedback: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn default_window() -> String {
+    WindowFunction::default().name().to_string()
+}
+
+/// How the nodes of two patch descriptions relate, matched by node id.
+///
+/// A node is kept if its type and parameters are unchanged and the two patches share their FFT
+/// settings; otherwise a node present in both is changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchDiff {
+    pub kept: Vec<String>,
+    pub changed: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A graph built from a [`PatchDescription`], along with the graph nodes of the patch's node ids.
+pub struct Patch<G> {
+    pub graph: G,
+    pub nodes: BTreeMap<String, NodeIndex>,
+    pub description: PatchDescription,
+}
+
+impl<G> Patch<G> {
+    pub fn node(&self, id: &str) -> Option<NodeIndex> {
+        self.nodes.get(id).copied()
+    }
+}
+
+impl<F: Fft> Patch<FftGraph<F>> {
+    /// Rebuilds the graph from a new description, carrying over the state of kept nodes.
+    ///
+    /// Kept nodes are moved into the new graph as they are, so their internal state (frozen
+    /// spectra, phase accumulators, histories) continues uninterrupted, and kept audio inputs and
+    /// outputs keep their buffered samples. Since the graph only processes whole hops, calling
+    /// this between two blocks swaps the graph at a hop boundary. The new graph is allocated with
+    /// the current sample rate and block size.
+    ///
+    /// On error the current graph is left untouched.
+    pub fn reload(
+        &mut self,
+        description: PatchDescription,
+        registry: &ProcessorRegistry<F>,
+    ) -> Result<PatchDiff, FftError> {
+        let diff = self.description.diff(&description);
+
+        let mut patch = description.build(registry)?;
+        patch
+            .graph
+            .allocate(self.graph.sample_rate(), self.graph.block_size());
+
+        let pairs = diff
+            .kept
+            .iter()
+            .map(|id| (self.nodes[id], patch.nodes[id]))
+            .collect::<Vec<_>>();
+        patch.graph.migrate_nodes(&mut self.graph, &pairs);
+
+        *self = patch;
+        Ok(diff)
+    }
+}
+
+impl PatchDescription {
+    pub fn from_toml(text: &str) -> Result<Self, PatchError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, PatchError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, PatchError> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, PatchError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Compares the nodes of this description with those of `new`.
+    pub fn diff(&self, new: &PatchDescription) -> PatchDiff {
+        let same_stft = self.fft_length == new.fft_length
+            && self.hop_length == new.hop_length
+            && self.window == new.window;
+
+        let mut diff = PatchDiff::default();
+        for node in &new.nodes {
+            match self.nodes.iter().find(|old| old.id == node.id) {
+                Some(old) if same_stft && old.kind == node.kind && old.params == node.params => {
+                    diff.kept.push(node.id.clone())
+                }
+                Some(_) => diff.changed.push(node.id.clone()),
+                None => diff.added.push(node.id.clone()),
+            }
+        }
+        for node in &self.nodes {
+            if !new.nodes.iter().any(|new| new.id == node.id) {
+                diff.removed.push(node.id.clone());
+            }
+        }
+        diff
+    }
+
+    pub fn window_fn(&self) -> Result<WindowFunction, PatchError> {
+        WindowFunction::from_name(&self.window)
+            .ok_or_else(|| PatchError::UnknownWindow(self.window.clone()))
+    }
+
+    /// Builds the patch into a new graph, creating processors with `registry`.
+    pub fn build<F: Fft>(
+        &self,
+        registry: &ProcessorRegistry<F>,
+    ) -> Result<Patch<FftGraph<F>>, FftError> {
+        if self.fft_length != F::N_FFT {
+            return Err(PatchError::FftLengthMismatch {
+                expected: F::N_FFT,
+                found: self.fft_length,
+            }
+            .into());
+        }
+
+        let mut graph = FftGraph::<F>::try_new(self.hop_length, self.window_fn()?)?;
+        graph.set_registry(registry.clone());
+        let nodes = self.build_into(&mut graph)?;
+
+        Ok(Patch {
+            graph,
+            nodes,
+            description: self.clone(),
+        })
+    }
+
+    /// Builds the patch into a graph whose FFT length is chosen at runtime, using the builtin
+    /// processors.
+    pub fn build_dyn(&self) -> Result<Patch<Box<dyn DynFftGraph>>, FftError> {
+        validate_stft(self.fft_length, self.hop_length)?;
+        let mut graph = dyn_graph(self.fft_length, self.hop_length, self.window_fn()?).map_err(
+            |e| match e {
+                FftError::UnsupportedFftLength(length) => {
+                    PatchError::UnsupportedFftLength(length).into()
+                }
+                e => e,
+            },
+        )?;
+        let nodes = self.build_into(&mut *graph)?;
+
+        Ok(Patch {
+            graph,
+            nodes,
+            description: self.clone(),
+        })
+    }
+
+    /// Adds the nodes and edges to `graph`, creating processors with its registry.
+    fn build_into(
+        &self,
+        graph: &mut dyn DynFftGraph,
+    ) -> Result<BTreeMap<String, NodeIndex>, PatchError> {
+        graph.set_seed(self.seed);
+        let mut nodes = BTreeMap::new();
+
+        for node in &self.nodes {
+            if nodes.contains_key(&node.id) {
+                return Err(PatchError::DuplicateNode(node.id.clone()));
+            }
+
+            let index = match node.kind.as_str() {
+                AUDIO_INPUT => graph.add_audio_input(),
+                SIDECHAIN_INPUT => graph.add_sidechain_input(),
+                AUDIO_OUTPUT => graph.add_audio_output(),
+                kind => {
+                    let params = ProcessorParams::from(node.params.clone());
+                    graph
+                        .add_builtin(kind, &params)
+                        .ok_or_else(|| PatchError::UnknownProcessor(kind.to_string()))?
+                }
+            };
+            nodes.insert(node.id.clone(), index);
+        }
+
+        for edge in &self.edges {
+            let source = *nodes
+                .get(&edge.from)
+                .ok_or_else(|| PatchError::UnknownNode(edge.from.clone()))?;
+            let target = *nodes
+                .get(&edge.to)
+                .ok_or_else(|| PatchError::UnknownNode(edge.to.clone()))?;
+
+            let connected = if edge.feedback {
+                graph.connect_feedback(source, edge.output, target, edge.input)
+            } else {
+                graph.connect(source, edge.output, target, edge.input)
+            };
+            connected.map_err(|e| PatchError::Connection {
+                from: edge.from.clone(),
+                to: edge.to.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+
+        Ok(nodes)
+    }
+}
@@ -0,0 +1,297 @@
+//! Finding spectral peaks and estimating the frequency, magnitude and phase of the sinusoids
+//! behind them more precisely than the bin spacing allows.
+//!
+//! A sinusoid's energy spreads over a few bins around its frequency. Fitting a parabola through
+//! the peak bin and its neighbors locates the top of the main lobe to a fraction of a bin; the fit
+//! is best on log magnitudes, where the main lobe of common windows is close to a parabola.
+//! Frequencies are given in fractional bins throughout, multiply by `sample_rate / fft_length` for
+//! Hz.
+
+use alloc::vec::Vec;
+use core::{f32::consts::PI, ops::Range};
+
+use crate::{
+    core::{Complex32, math},
+    units,
+};
+
+/// A spectral peak with its interpolated parameters.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Peak {
+    /// The bin the peak was found at.
+    pub bin: usize,
+    /// The interpolated frequency, in fractional bins.
+    pub frequency: f32,
+    /// The interpolated magnitude.
+    pub magnitude: f32,
+    /// The interpolated phase, in radians.
+    pub phase: f32,
+}
+
+/// Fits a parabola through three equally spaced values, the middle one at offset 0.
+///
+/// Returns the offset of the vertex, clamped to half a step either way, and the value there. If
+/// the values are collinear, returns the middle one at offset 0.
+pub fn parabolic(before: f32, at: f32, after: f32) -> (f32, f32) {
+    let denom = before - 2.0 * at + after;
+    if denom.abs() <= f32::EPSILON {
+        return (0.0, at);
+    }
+    let offset = (0.5 * (before - after) / denom).clamp(-0.5, 0.5);
+    (offset, at - 0.25 * (before - after) * offset)
+}
+
+/// Returns whether `bin` is a local maximum of `magnitudes`, at least as large as the bin below
+/// and larger than the bin above. DC and Nyquist are never peaks.
+pub fn is_peak(magnitudes: &[f32], bin: usize) -> bool {
+    bin > 0
+        && bin + 1 < magnitudes.len()
+        && magnitudes[bin] >= magnitudes[bin - 1]
+        && magnitudes[bin] > magnitudes[bin + 1]
+}
+
+/// Returns the magnitude `db` decibels below the largest of `magnitudes`, for thresholding peaks
+/// relative to the loudest one.
+pub fn threshold_below_max(magnitudes: &[f32], db: f32) -> f32 {
+    let loudest = magnitudes.iter().copied().fold(0.0, f32::max);
+    loudest * units::db_to_magnitude(-db)
+}
+
+/// Refines the peak at `bin` by fitting a parabola to the magnitudes themselves.
+///
+/// Returns the frequency in fractional bins and the interpolated magnitude. `bin` must have
+/// neighbors on both sides.
+pub fn interpolate_peak(magnitudes: &[f32], bin: usize) -> (f32, f32) {
+    let (offset, magnitude) = parabolic(magnitudes[bin - 1], magnitudes[bin], magnitudes[bin + 1]);
+    (bin as f32 + offset, magnitude)
+}
+
+/// Refines the peak at `bin` by fitting a parabola to the log magnitudes, which is more accurate
+/// for windowed sinusoids than [`interpolate_peak`].
+///
+/// Returns the frequency in fractional bins and the interpolated magnitude. `bin` must have
+/// neighbors on both sides.
+pub fn interpolate_peak_log(magnitudes: &[f32], bin: usize) -> (f32, f32) {
+    let log = |bin: usize| math::lnf(magnitudes[bin] + f32::EPSILON);
+    let (offset, log_magnitude) = parabolic(log(bin - 1), log(bin), log(bin + 1));
+    (bin as f32 + offset, math::expf(log_magnitude))
+}
+
+/// Interpolates the phase of `spectrum` at the fractional bin `frequency`, e.g. of a peak found
+/// with [`interpolate_peak_log`].
+///
+/// Frames are assumed to be windowed like this crate's STFT does, with the window's peak at the
+/// start of the frame (see [`stft_window`](crate::core::stft_window)), which turns bin `k` of a
+/// sinusoid at `frequency` by `π (frequency - k)`, give or take a half cycle. That turn is
+/// removed from the two nearest bins before interpolating between them, so the result is the
+/// phase of the sinusoid at the start of the frame.
+pub fn interpolate_phase(spectrum: &[Complex32], frequency: f32) -> f32 {
+    let last = spectrum.len().saturating_sub(1);
+    let low = (frequency.max(0.0) as usize).min(last);
+    let high = (low + 1).min(last);
+    let fraction = (frequency - low as f32).clamp(0.0, 1.0);
+
+    let derotated = |bin: usize| spectrum[bin].arg() - PI * (frequency - bin as f32);
+    let (near, far, distance) = if fraction <= 0.5 {
+        (low, high, fraction)
+    } else {
+        (high, low, 1.0 - fraction)
+    };
+    let near_phase = derotated(near);
+    // the farther bin can be off by a half cycle, so only its difference modulo π counts
+    let delta = wrap_phase(derotated(far) - near_phase);
+    let delta = if delta > PI / 2.0 {
+        delta - PI
+    } else if delta < -PI / 2.0 {
+        delta + PI
+    } else {
+        delta
+    };
+    wrap_phase(near_phase + distance * delta)
+}
+
+/// Wraps `phase` to (-π, π].
+pub fn wrap_phase(phase: f32) -> f32 {
+    let wrapped = phase - 2.0 * PI * math::roundf(phase / (2.0 * PI));
+    if wrapped <= -PI {
+        wrapped + 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+/// Returns the largest bin of `magnitudes` within `range`, leaving room for a neighbor on both
+/// sides, or `None` if the range is empty.
+pub fn max_bin_in(magnitudes: &[f32], range: Range<usize>) -> Option<usize> {
+    let start = range.start.max(1);
+    let end = range.end.min(magnitudes.len().saturating_sub(1));
+    (start..end).max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))
+}
+
+/// Appends the bins of all peaks of `magnitudes` above `threshold` to `out`, in ascending order.
+pub fn find_peaks(magnitudes: &[f32], threshold: f32, out: &mut Vec<usize>) {
+    out.extend(
+        (1..magnitudes.len().saturating_sub(1))
+            .filter(|&bin| magnitudes[bin] > threshold && is_peak(magnitudes, bin)),
+    );
+}
+
+/// Appends all peaks of `spectrum` above `threshold` to `out`, in ascending order, with their
+/// frequencies and magnitudes from [`interpolate_peak_log`] and their phases from
+/// [`interpolate_phase`]. `magnitudes` must hold the magnitudes of `spectrum`.
+pub fn pick_peaks(spectrum: &[Complex32], magnitudes: &[f32], threshold: f32, out: &mut Vec<Peak>) {
+    for bin in 1..magnitudes.len().saturating_sub(1) {
+        if magnitudes[bin] > threshold && is_peak(magnitudes, bin) {
+            let (frequency, magnitude) = interpolate_peak_log(magnitudes, bin);
+            out.push(Peak {
+                bin,
+                frequency,
+                magnitude,
+                phase: interpolate_phase(spectrum, frequency),
+            });
+        }
+    }
+}
+
+/// Sorts `peaks` from the loudest to the quietest.
+pub fn sort_by_magnitude(peaks: &mut [Peak]) {
+    peaks.sort_unstable_by(|a, b| b.magnitude.total_cmp(&a.magnitude));
+}
+
+/// Sorts `peaks` by ascending frequency.
+pub fn sort_by_frequency(peaks: &mut [Peak]) {
+    peaks.sort_unstable_by(|a, b| a.frequency.total_cmp(&b.frequency));
+}
+
+/// Keeps only the `count` loudest of `peaks`, sorted from the loudest.
+pub fn keep_loudest(peaks: &mut Vec<Peak>, count: usize) {
+    sort_by_magnitude(peaks);
+    peaks.truncate(count);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::core::{WindowFunction, stft_window};
+
+    const N: usize = 256;
+
+    /// Returns the one-sided spectrum of a sinusoid of `frequency` bins, starting at `phase`,
+    /// multiplied by `window`.
+    fn windowed_sinusoid(
+        frequency: f32,
+        phase: f32,
+        amplitude: f32,
+        window: &[f32],
+    ) -> Vec<Complex32> {
+        let signal: Vec<f64> = window
+            .iter()
+            .enumerate()
+            .map(|(n, &w)| {
+                let t = core::f64::consts::TAU * frequency as f64 * n as f64 / N as f64;
+                amplitude as f64 * (t + phase as f64).cos() * w as f64
+            })
+            .collect();
+        (0..=N / 2)
+            .map(|bin| {
+                let (mut re, mut im) = (0.0, 0.0);
+                for (n, x) in signal.iter().enumerate() {
+                    let t = core::f64::consts::TAU * (bin * n) as f64 / N as f64;
+                    re += x * t.cos();
+                    im -= x * t.sin();
+                }
+                Complex32::new(re as f32, im as f32)
+            })
+            .collect()
+    }
+
+    fn magnitudes(spectrum: &[Complex32]) -> Vec<f32> {
+        spectrum.iter().map(|x| x.norm()).collect()
+    }
+
+    #[test]
+    fn parabolic_finds_the_vertex() {
+        let parabola = |x: f32| 2.0 - (x - 0.3) * (x - 0.3);
+        let (offset, value) = parabolic(parabola(-1.0), parabola(0.0), parabola(1.0));
+        assert!((offset - 0.3).abs() < 1e-6);
+        assert!((value - 2.0).abs() < 1e-6);
+        assert_eq!(parabolic(1.0, 1.0, 1.0), (0.0, 1.0));
+    }
+
+    #[test]
+    fn interpolates_frequency_and_magnitude_of_sinusoids() {
+        let window = WindowFunction::Hann.generate(N);
+        let expected_magnitude = 0.8 * window.iter().sum::<f32>() / 2.0;
+        for frequency in [20.0, 20.1, 20.25, 20.5, 20.75, 33.6] {
+            let magnitudes = magnitudes(&windowed_sinusoid(frequency, 0.3, 0.8, &window));
+            let bin = max_bin_in(&magnitudes, 0..N / 2).unwrap();
+            let (estimate, magnitude) = interpolate_peak_log(&magnitudes, bin);
+            assert!(
+                (estimate - frequency).abs() < 0.02,
+                "{estimate} for a sinusoid at {frequency}"
+            );
+            assert!(
+                (magnitude / expected_magnitude - 1.0).abs() < 0.05,
+                "{magnitude} for a sinusoid at {frequency}, expected {expected_magnitude}"
+            );
+        }
+    }
+
+    #[test]
+    fn interpolates_the_phase_at_the_start_of_stft_frames() {
+        let window = stft_window(WindowFunction::Hann, N, N / 4);
+        for frequency in [20.0, 20.1, 20.25, 20.5, 20.75, 33.6] {
+            for phase in [0.7, -2.0, 3.0] {
+                let spectrum = windowed_sinusoid(frequency, phase, 1.0, &window);
+                let estimate = interpolate_phase(&spectrum, frequency);
+                assert!(
+                    wrap_phase(estimate - phase).abs() < 0.05,
+                    "{estimate} for a sinusoid at {frequency} starting at {phase}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn picks_and_sorts_peaks() {
+        let window = WindowFunction::Hann.generate(N);
+        let spectrum: Vec<Complex32> = windowed_sinusoid(20.3, 0.0, 0.5, &window)
+            .iter()
+            .zip(&windowed_sinusoid(61.0, 1.0, 1.0, &window))
+            .map(|(a, b)| a + b)
+            .collect();
+        let magnitudes = magnitudes(&spectrum);
+
+        let mut bins = vec![];
+        find_peaks(
+            &magnitudes,
+            threshold_below_max(&magnitudes, 20.0),
+            &mut bins,
+        );
+        assert_eq!(bins, [20, 61]);
+
+        let mut peaks = vec![];
+        pick_peaks(
+            &spectrum,
+            &magnitudes,
+            threshold_below_max(&magnitudes, 20.0),
+            &mut peaks,
+        );
+        assert_eq!(peaks.len(), 2);
+        assert!((peaks[0].frequency - 20.3).abs() < 0.02);
+        assert!((peaks[1].frequency - 61.0).abs() < 0.02);
+
+        keep_loudest(&mut peaks, 1);
+        assert_eq!(peaks.len(), 1);
+        assert_eq!(peaks[0].bin, 61);
+    }
+
+    #[test]
+    fn wraps_phases() {
+        assert!((wrap_phase(3.0 * PI) - PI).abs() < 1e-5);
+        assert_eq!(wrap_phase(-PI), PI);
+        assert!((wrap_phase(0.5 - 4.0 * PI) - 0.5).abs() < 1e-5);
+    }
+}
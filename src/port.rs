@@ -0,0 +1,227 @@
+//! Typed handles to the inputs and outputs of nodes in an [`FftGraphBuilder`].
+//!
+//! Node ports are otherwise addressed by index, and connecting a spectrum output to an audio
+//! block input is only caught when the connection is made or the graph is validated. A port
+//! handle carries its signal type, checked once when the handle is created, so connecting handles
+//! of different types doesn't compile:
+//!
+//! ```ignore
+//! let fft_graph = FftGraphBuilder::<Fft1024>::new(256, WindowFunction::Hann);
+//! let input: SpectralPort<Fft1024> = fft_graph.add_spectral_input();
+//! let tilt = fft_graph.add_processor(SpectralTilt::default());
+//! input.connect(&fft_graph.input_port(&tilt, 0)?)?;
+//! fft_graph
+//!     .output_port(&tilt, 0)?
+//!     .connect(&fft_graph.add_spectral_output())?;
+//! ```
+
+use std::marker::PhantomData;
+
+use raug::prelude::*;
+use raug_graph::{
+    graph::{AbstractGraph, NodeIndex},
+    prelude::{GraphBuilder, NodeBuilder},
+};
+
+use crate::{
+    FftError,
+    graph::{FftGraph, FftGraphBuilder},
+    node::FftProcessorNode,
+    signal::Fft,
+};
+
+/// An output of a node producing signals of type `S`.
+pub struct OutputPort<F: Fft, S> {
+    graph: GraphBuilder<FftGraph<F>>,
+    node: NodeIndex,
+    index: u32,
+    _signal: PhantomData<fn() -> S>,
+}
+
+/// An input of a node taking signals of type `S`.
+pub struct InputPort<F: Fft, S> {
+    node: NodeIndex,
+    index: u32,
+    _graph: PhantomData<fn() -> F>,
+    _signal: PhantomData<fn() -> S>,
+}
+
+/// An output producing spectra.
+pub type SpectralPort<F> = OutputPort<F, <F as Fft>::RealFft>;
+
+/// An output producing time-domain audio blocks.
+pub type AudioPort<F> = OutputPort<F, <F as Fft>::AudioBlock>;
+
+/// An output producing control values.
+pub type ControlPort<F> = OutputPort<F, f32>;
+
+/// An output producing gates or triggers.
+pub type GatePort<F> = OutputPort<F, bool>;
+
+impl<F: Fft, S> OutputPort<F, S> {
+    pub fn node(&self) -> NodeIndex {
+        self.node
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Connects the output to `target`, replacing whatever `target` was connected to.
+    pub fn connect(&self, target: &InputPort<F, S>) -> Result<(), FftError> {
+        self.graph.with_inner(|graph| {
+            graph
+                .graph_mut()
+                .connect(self.node, self.index, target.node, target.index)
+                .map(|_| ())
+                .map_err(|e| FftError::Graph(format!("{e:?}")))
+        })
+    }
+
+    /// Connects the output to `target` with a delay of one hop, see
+    /// [`FftGraph::connect_feedback`].
+    pub fn connect_feedback(&self, target: &InputPort<F, S>) -> Result<(), FftError> {
+        self.graph.with_inner(|graph| {
+            graph.connect_feedback(self.node, self.index, target.node, target.index)
+        })
+    }
+}
+
+impl<F: Fft, S> Clone for OutputPort<F, S> {
+    fn clone(&self) -> Self {
+        Self {
+            graph: self.graph.clone(),
+            node: self.node,
+            index: self.index,
+            _signal: PhantomData,
+        }
+    }
+}
+
+impl<F: Fft, S> InputPort<F, S> {
+    pub fn node(&self) -> NodeIndex {
+        self.node
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl<F: Fft, S> Clone for InputPort<F, S> {
+    fn clone(&self) -> Self {
+        Self {
+            node: self.node,
+            index: self.index,
+            _graph: PhantomData,
+            _signal: PhantomData,
+        }
+    }
+}
+
+/// Checks that `specs[index]` carries signals of type `S`.
+fn check_port<S: Signal>(
+    node: &FftProcessorNode,
+    specs: &[SignalSpec],
+    kind: &str,
+    index: u32,
+) -> Result<(), FftError> {
+    let Some(spec) = specs.get(index as usize) else {
+        return Err(FftError::Graph(format!(
+            "{} has no {kind} {index}",
+            node.name()
+        )));
+    };
+    if spec.signal_type != S::signal_type() {
+        return Err(FftError::TypeMismatch {
+            node: node.name().to_string(),
+            port: index,
+            expected: std::any::type_name::<S>(),
+        });
+    }
+    Ok(())
+}
+
+impl<F: Fft> FftGraphBuilder<F> {
+    /// Returns a handle to output `index` of `node`, failing if it doesn't produce signals of
+    /// type `S`.
+    pub fn output_port<S: Signal>(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        index: u32,
+    ) -> Result<OutputPort<F, S>, FftError> {
+        self.with_inner(|graph| {
+            let node = &graph.graph()[node.id()];
+            check_port::<S>(node, node.output_spec(), "output", index)
+        })?;
+        Ok(OutputPort {
+            graph: (**self).clone(),
+            node: node.id(),
+            index,
+            _signal: PhantomData,
+        })
+    }
+
+    /// Returns a handle to the output of `node` named `name`, see
+    /// [`output_port`](Self::output_port).
+    pub fn output_port_named<S: Signal>(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        name: &str,
+    ) -> Result<OutputPort<F, S>, FftError> {
+        let index = self.with_inner(|graph| graph.graph()[node.id()].output_index(name))?;
+        self.output_port(node, index)
+    }
+
+    /// Returns a handle to input `index` of `node`, failing if it doesn't take signals of type
+    /// `S`.
+    pub fn input_port<S: Signal>(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        index: u32,
+    ) -> Result<InputPort<F, S>, FftError> {
+        self.with_inner(|graph| {
+            let node = &graph.graph()[node.id()];
+            check_port::<S>(node, node.input_spec(), "input", index)
+        })?;
+        Ok(InputPort {
+            node: node.id(),
+            index,
+            _graph: PhantomData,
+            _signal: PhantomData,
+        })
+    }
+
+    /// Returns a handle to the input of `node` named `name`, see
+    /// [`input_port`](Self::input_port).
+    pub fn input_port_named<S: Signal>(
+        &self,
+        node: &NodeBuilder<FftGraph<F>>,
+        name: &str,
+    ) -> Result<InputPort<F, S>, FftError> {
+        let index = self.with_inner(|graph| graph.graph()[node.id()].input_index(name))?;
+        self.input_port(node, index)
+    }
+
+    /// Adds an audio input, returning the output carrying its spectrum.
+    pub fn add_spectral_input(&self) -> SpectralPort<F> {
+        let node = self.add_audio_input();
+        OutputPort {
+            graph: (**self).clone(),
+            node: node.id(),
+            index: 0,
+            _signal: PhantomData,
+        }
+    }
+
+    /// Adds an audio output, returning the input taking the spectrum to resynthesize.
+    pub fn add_spectral_output(&self) -> InputPort<F, F::RealFft> {
+        let node = self.add_audio_output();
+        InputPort {
+            node: node.id(),
+            index: 0,
+            _graph: PhantomData,
+            _signal: PhantomData,
+        }
+    }
+}
@@ -1,11 +1,270 @@
-use raug::prelude::*;
+use alloc::{boxed::Box, string::String, vec::Vec};
 
+use crate::{
+    FftError,
+    core::math,
+    io::{AnyBuffer, ProcResult, ProcessorInputs, ProcessorOutputs, SignalSpec},
+    scratch::{Scratch, ScratchPool, ScratchSize},
+    state::{StateReader, StateValue, StateWriter},
+};
+
+/// Timing information about the frame an [`FftGraph`](crate::graph::FftGraph) is processing.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameInfo {
+    pub sample_rate: f32,
+    pub fft_length: usize,
+    pub hop_length: usize,
+    /// The number of frames processed since the graph was allocated.
+    pub frame_index: u64,
+    /// Whether processors should flush subnormal values out of their state, see
+    /// [`flush_denormals`](crate::denormal::flush_denormals).
+    pub flush_denormals: bool,
+    /// The host's musical time at this frame.
+    pub transport: Transport,
+    /// The seed for the processor's random numbers at this frame, derived from the graph's seed,
+    /// the node and the frame index, see [`random`](crate::random).
+    pub seed: u64,
+}
+
+impl FrameInfo {
+    /// Returns the time between two consecutive frames, in seconds.
+    #[inline]
+    pub fn hop_seconds(&self) -> f32 {
+        if self.sample_rate > 0.0 {
+            self.hop_length as f32 / self.sample_rate
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the number of frames processed per second.
+    #[inline]
+    pub fn frame_rate(&self) -> f32 {
+        if self.hop_length > 0 {
+            self.sample_rate / self.hop_length as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Converts a duration in beats to hops at the current tempo.
+    #[inline]
+    pub fn beats_to_hops(&self, beats: f32) -> f32 {
+        self.transport.beats_to_seconds(beats) * self.frame_rate()
+    }
+}
+
+/// The musical time of the host running an [`FftGraph`](crate::graph::FftGraph), see
+/// [`FftGraph::set_transport`](crate::graph::FftGraph::set_transport).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transport {
+    pub bpm: f32,
+    /// The position in beats (quarter notes) since the start of the host's timeline.
+    pub beat: f64,
+    pub playing: bool,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self {
+            bpm: 120.0,
+            beat: 0.0,
+            playing: false,
+        }
+    }
+}
+
+impl Transport {
+    /// Returns the length of one beat, in seconds.
+    #[inline]
+    pub fn beat_seconds(&self) -> f32 {
+        if self.bpm > 0.0 { 60.0 / self.bpm } else { 0.0 }
+    }
+
+    #[inline]
+    pub fn beats_to_seconds(&self, beats: f32) -> f32 {
+        beats * self.beat_seconds()
+    }
+
+    #[inline]
+    pub fn seconds_to_beats(&self, seconds: f32) -> f32 {
+        seconds * self.bpm / 60.0
+    }
+
+    /// Moves the position forward by `seconds` if the transport is playing.
+    pub fn advance(&mut self, seconds: f32) {
+        if self.playing {
+            self.beat += self.seconds_to_beats(seconds) as f64;
+        }
+    }
+}
+
+impl StateValue for Transport {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.bpm);
+        state.write(&self.beat);
+        state.write(&self.playing);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.bpm)?;
+        state.read(&mut self.beat)?;
+        state.read(&mut self.playing)
+    }
+}
+
+/// The unit of a parameter, for display and for choosing how to map controls onto it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParamUnit {
+    /// A plain number, such as a ratio, a mix amount or a normalized position.
+    #[default]
+    None,
+    Decibels,
+    DecibelsPerOctave,
+    Hertz,
+    Seconds,
+    Milliseconds,
+    Beats,
+    Bpm,
+    Semitones,
+    Octaves,
+    Degrees,
+    Bins,
+    Frames,
+    /// Off at 0 and on at any other value.
+    Toggle,
+    /// An index into a list of choices, e.g.
+    /// [`LfoShape::ALL`](crate::builtins::control::LfoShape::ALL).
+    Choice,
+}
+
+impl ParamUnit {
+    /// Returns a short suffix for displaying values, empty for units without one.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Decibels => "dB",
+            Self::DecibelsPerOctave => "dB/oct",
+            Self::Hertz => "Hz",
+            Self::Seconds => "s",
+            Self::Milliseconds => "ms",
+            Self::Beats => "beats",
+            Self::Bpm => "BPM",
+            Self::Semitones => "st",
+            Self::Octaves => "oct",
+            Self::Degrees => "°",
+            Self::Bins => "bins",
+            Self::Frames => "frames",
+            Self::None | Self::Toggle | Self::Choice => "",
+        }
+    }
+}
+
+/// How changes to a parameter should be smoothed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParamSmoothing {
+    /// Whoever sets the parameter should smooth changes to it to avoid zipper noise.
+    #[default]
+    Preferred,
+    /// The processor smooths changes itself.
+    Internal,
+    /// The parameter is discrete or a setting that is read when it matters, so changes shouldn't
+    /// be smoothed.
+    None,
+}
+
+/// Describes a parameter of a processor, see [`FftProcessor::param_spec`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamSpec {
+    pub name: String,
+    pub unit: ParamUnit,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    /// Whether the parameter only takes whole values.
+    pub stepped: bool,
+    pub smoothing: ParamSmoothing,
+}
+
+impl ParamSpec {
+    /// Describes a continuous parameter, or a stepped one if `unit` is [`ParamUnit::Toggle`] or
+    /// [`ParamUnit::Choice`].
+    pub fn new(name: impl Into<String>, unit: ParamUnit, min: f32, max: f32, default: f32) -> Self {
+        let spec = Self {
+            name: name.into(),
+            unit,
+            min,
+            max,
+            default,
+            stepped: false,
+            smoothing: ParamSmoothing::Preferred,
+        };
+        if matches!(unit, ParamUnit::Toggle | ParamUnit::Choice) {
+            spec.stepped()
+        } else {
+            spec
+        }
+    }
+
+    /// Makes the parameter take only whole values, which aren't smoothed.
+    pub fn stepped(mut self) -> Self {
+        self.stepped = true;
+        self.smoothing = ParamSmoothing::None;
+        self
+    }
+
+    pub fn smoothing(mut self, smoothing: ParamSmoothing) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Returns whether `value` is a valid value of the parameter: within the range, and whole if
+    /// the parameter is stepped.
+    pub fn contains(&self, value: f32) -> bool {
+        (self.min..=self.max).contains(&value) && (!self.stepped || math::roundf(value) == value)
+    }
+
+    /// Clamps `value` to the range, rounding it if the parameter is stepped.
+    pub fn clamp(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        if self.stepped {
+            math::roundf(value)
+        } else {
+            value
+        }
+    }
+
+    /// Maps `value` from the range onto `0..=1`.
+    pub fn normalize(&self, value: f32) -> f32 {
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Maps a normalized value (`0..=1`) back onto the range, rounding it if the parameter is
+    /// stepped.
+    pub fn denormalize(&self, normalized: f32) -> f32 {
+        self.clamp(self.min + (self.max - self.min) * normalized)
+    }
+}
+
+/// A processor running once per frame of an [`FftGraph`](crate::graph::FftGraph).
+///
+/// Processors exchange spectra (`F::RealFft`), time-domain frames (`F::AudioBlock`), scalar
+/// control values (`f32`) and gates or triggers (`bool`), one of each per frame. Control inputs are
+/// usually optional and override a parameter while connected; control outputs can come from other
+/// processors such as the sources in [`builtins::control`](crate::builtins::control). See
+/// [`builtins::trigger`](crate::builtins::trigger) for how `bool` signals are used.
 pub trait FftProcessor
 where
     Self: Send + 'static,
 {
     fn name(&self) -> &str {
-        raug::util::interned_short_type_name::<Self>()
+        #[cfg(feature = "raug")]
+        return raug::util::interned_short_type_name::<Self>();
+        #[cfg(not(feature = "raug"))]
+        return core::any::type_name::<Self>();
     }
 
     fn input_spec(&self) -> Vec<SignalSpec>;
@@ -13,10 +272,188 @@ where
 
     fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer>;
 
+    /// Returns the number of frames by which the processor delays its output.
+    fn latency_frames(&self) -> usize {
+        0
+    }
+
     #[allow(unused)]
     fn allocate(&mut self, sample_rate: f32) {}
     #[allow(unused)]
     fn resize_buffers(&mut self, sample_rate: f32) {}
 
+    /// Called when the graph's sample rate changes from `old` to `new`, before
+    /// [`allocate`](Self::allocate) or [`resize_buffers`](Self::resize_buffers) is called with the
+    /// new rate. Processors with frequency-dependent state, such as filter curves or band layouts,
+    /// recompute it here. Not called when the graph is first allocated.
+    #[allow(unused)]
+    fn sample_rate_changed(&mut self, old: f32, new: f32) {}
+
+    /// Called with the graph's look-ahead in hops, see
+    /// [`FftGraph::set_lookahead_frames`](crate::graph::FftGraph::set_lookahead_frames), when it's
+    /// set and when the processor is added to a graph. Processors that look ahead in time look at
+    /// least this many hops ahead, delaying their output by as much and reporting it from
+    /// [`latency_frames`](Self::latency_frames). Called off the audio thread, so it may allocate.
+    #[allow(unused)]
+    fn set_graph_lookahead(&mut self, frames: usize) {}
+
+    /// Called before every call to [`process`](FftProcessor::process) with the current frame's
+    /// timing.
+    #[allow(unused)]
+    fn set_frame_info(&mut self, info: &FrameInfo) {}
+
     fn process(&mut self, inputs: ProcessorInputs, outputs: ProcessorOutputs) -> ProcResult<()>;
+
+    /// Describes the processor's parameters, for generating UIs and validating values set from
+    /// outside, e.g. through [`midi`](crate::midi). Parameters are the processor's public fields
+    /// (or its [`builder`](crate::builder)'s setters); many can also be overridden by a control
+    /// input of the same name. Empty by default.
+    fn param_spec(&self) -> Vec<ParamSpec> {
+        Vec::new()
+    }
+
+    /// Sets the parameter `name` described by [`param_spec`](Self::param_spec) to `value`,
+    /// returning whether the processor has such a parameter. The value isn't clamped to the
+    /// parameter's range. Called between frames, so it must not allocate.
+    #[allow(unused)]
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        false
+    }
+
+    /// Writes the state the processor has accumulated while processing, such as envelopes,
+    /// histories and phases, for [`FftGraph::save_state`](crate::graph::FftGraph::save_state).
+    /// Usually implemented with [`processor_state!`](crate::processor_state). Writes nothing by
+    /// default.
+    #[allow(unused)]
+    fn save_state(&self, state: &mut StateWriter) {}
+
+    /// Restores state written by [`save_state`](Self::save_state), reading exactly what it wrote.
+    /// Called off the audio thread, after the processor was allocated, so it may allocate.
+    #[allow(unused)]
+    fn load_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        Ok(())
+    }
+
+    /// Returns a copy of the processor with the same configuration, for
+    /// [`FftGraph::clone_template`](crate::graph::FftGraph::clone_template), or `None` if it can't
+    /// be copied (the default). Usually implemented with
+    /// [`processor_clone!`](crate::processor_clone) by processors implementing `Clone`.
+    ///
+    /// Whatever state the copy carries over is reset when it's allocated.
+    fn clone_boxed(&self) -> Option<Box<dyn FftProcessor>> {
+        None
+    }
+
+    /// Returns how much scratch space [`process_with_scratch`](Self::process_with_scratch) takes,
+    /// see [`scratch`](crate::scratch). Called whenever the processor is allocated.
+    fn scratch_size(&self) -> ScratchSize {
+        ScratchSize::default()
+    }
+
+    /// Processes like [`process`](Self::process), with temporary buffers taken from `scratch`
+    /// instead of held by the processor. Graphs always call this; the default calls `process`.
+    ///
+    /// Processors overriding this can implement `process` with [`process_with_own_scratch`].
+    #[allow(unused)]
+    fn process_with_scratch(
+        &mut self,
+        inputs: ProcessorInputs,
+        outputs: ProcessorOutputs,
+        scratch: &mut Scratch,
+    ) -> ProcResult<()> {
+        self.process(inputs, outputs)
+    }
+}
+
+/// Implements [`FftProcessor::input_spec`], [`FftProcessor::output_spec`] and
+/// [`FftProcessor::create_output_buffers`] inside an `impl FftProcessor` block, from the names and
+/// types of the processor's inputs and outputs:
+///
+/// ```ignore
+/// impl<F: Fft> FftProcessor for Gain<F> {
+///     fft_processor_io! {
+///         inputs { input: F::RealFft, gain: f32 }
+///         outputs { output: F::RealFft }
+///     }
+///
+///     fn process(
+///         &mut self,
+///         inputs: ProcessorInputs,
+///         mut outputs: ProcessorOutputs,
+///     ) -> ProcResult<()> {
+///         // ...
+///     }
+/// }
+/// ```
+///
+/// Names that aren't identifiers, such as keywords, can be given as string literals. Processors
+/// whose signals depend on how they were constructed implement the methods by hand.
+#[macro_export]
+macro_rules! fft_processor_io {
+    (
+        inputs { $($input:tt: $input_ty:ty),* $(,)? }
+        outputs { $($output:tt: $output_ty:ty),* $(,)? }
+    ) => {
+        fn input_spec(&self) -> $crate::__private::Vec<$crate::__private::SignalSpec> {
+            $crate::__private::vec![$(
+                $crate::__private::SignalSpec::new(
+                    $crate::__signal_name!($input),
+                    <$input_ty as $crate::__private::Signal>::signal_type(),
+                )
+            ),*]
+        }
+
+        fn output_spec(&self) -> $crate::__private::Vec<$crate::__private::SignalSpec> {
+            $crate::__private::vec![$(
+                $crate::__private::SignalSpec::new(
+                    $crate::__signal_name!($output),
+                    <$output_ty as $crate::__private::Signal>::signal_type(),
+                )
+            ),*]
+        }
+
+        fn create_output_buffers(
+            &self,
+            size: usize,
+        ) -> $crate::__private::Vec<$crate::__private::AnyBuffer> {
+            $crate::__private::vec![$($crate::__private::AnyBuffer::zeros::<$output_ty>(size)),*]
+        }
+    };
+}
+
+/// Implements [`FftProcessor::clone_boxed`] inside an `impl FftProcessor` block, for processors
+/// implementing `Clone`.
+#[macro_export]
+macro_rules! processor_clone {
+    () => {
+        fn clone_boxed(
+            &self,
+        ) -> ::core::option::Option<$crate::__private::Box<dyn $crate::processor::FftProcessor>> {
+            ::core::option::Option::Some($crate::__private::Box::new(::core::clone::Clone::clone(
+                self,
+            )))
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __signal_name {
+    ($name:ident) => {
+        stringify!($name)
+    };
+    ($name:literal) => {
+        $name
+    };
+}
+
+/// Runs [`FftProcessor::process_with_scratch`] with a scratch pool allocated for the call, for
+/// processors called outside of a graph.
+pub fn process_with_own_scratch<P: FftProcessor + ?Sized>(
+    processor: &mut P,
+    inputs: ProcessorInputs,
+    outputs: ProcessorOutputs,
+) -> ProcResult<()> {
+    let mut pool = ScratchPool::new(processor.scratch_size());
+    processor.process_with_scratch(inputs, outputs, &mut pool.scratch())
 }
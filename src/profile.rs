@@ -0,0 +1,78 @@
+use std::{fmt, time::Duration};
+
+use raug_graph::graph::NodeIndex;
+
+/// Processing time statistics of a single node, one sample per hop.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NodeTiming {
+    pub hops: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl NodeTiming {
+    pub fn mean(&self) -> Duration {
+        if self.hops == 0 {
+            Duration::ZERO
+        } else {
+            self.total.div_f64(self.hops as f64)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, elapsed: Duration) {
+        if self.hops == 0 || elapsed < self.min {
+            self.min = elapsed;
+        }
+        self.max = self.max.max(elapsed);
+        self.total += elapsed;
+        self.hops += 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileEntry {
+    pub node: NodeIndex,
+    pub name: String,
+    pub timing: NodeTiming,
+}
+
+/// A snapshot of the per-node timings of an [`FftGraph`](crate::graph::FftGraph), sorted by
+/// mean processing time, slowest first.
+///
+/// The [`Display`](fmt::Display) implementation prints a table of the entries.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub entries: Vec<ProfileEntry>,
+}
+
+impl ProfileReport {
+    /// Returns the sum of the mean processing times of all nodes, i.e. the average cost of a hop.
+    pub fn mean_hop_time(&self) -> Duration {
+        self.entries.iter().map(|entry| entry.timing.mean()).sum()
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6}  {:<24} {:>8} {:>12} {:>12} {:>12}",
+            "node", "name", "hops", "mean", "min", "max"
+        )?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{:>6}  {:<24} {:>8} {:>12?} {:>12?} {:>12?}",
+                entry.node.index(),
+                entry.name,
+                entry.timing.hops,
+                entry.timing.mean(),
+                entry.timing.min,
+                entry.timing.max,
+            )?;
+        }
+        write!(f, "mean hop time: {:?}", self.mean_hop_time())
+    }
+}
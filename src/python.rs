@@ -0,0 +1,126 @@
+//! Python bindings for building FFT graphs and rendering numpy arrays through them.
+//!
+//! Enable the `python` feature and build the extension module with `maturin` (see
+//! `pyproject.toml`). Graphs built from Python run the same processors as an [`FftGraph`] built in
+//! Rust, so a chain prototyped against numpy arrays can be rebuilt node for node in Rust.
+//!
+//! [`FftGraph`]: crate::graph::FftGraph
+
+use std::collections::BTreeMap;
+
+use numpy::{PyArray2, PyReadonlyArray2};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use raug_graph::graph::NodeIndex;
+
+use crate::{
+    WindowFunction,
+    graph::{DynFftGraph, dyn_graph},
+    registry::ProcessorParams,
+};
+
+/// An FFT graph whose nodes are referred to by integer ids.
+#[pyclass(name = "FftGraph", module = "raug_fft", unsendable)]
+pub struct PyFftGraph {
+    graph: Box<dyn DynFftGraph>,
+}
+
+#[pymethods]
+impl PyFftGraph {
+    #[new]
+    #[pyo3(signature = (fft_length, hop_length, window = "hann"))]
+    fn new(fft_length: usize, hop_length: usize, window: &str) -> PyResult<Self> {
+        let window_fn = WindowFunction::from_name(window)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown window function {window:?}")))?;
+        let graph = dyn_graph(fft_length, hop_length, window_fn)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { graph })
+    }
+
+    #[getter]
+    fn fft_length(&self) -> usize {
+        self.graph.fft_length()
+    }
+
+    #[getter]
+    fn hop_length(&self) -> usize {
+        self.graph.hop_length()
+    }
+
+    #[getter]
+    fn latency_samples(&self) -> usize {
+        self.graph.latency_samples()
+    }
+
+    fn add_audio_input(&mut self) -> usize {
+        self.graph.add_audio_input().index()
+    }
+
+    fn add_audio_output(&mut self) -> usize {
+        self.graph.add_audio_output().index()
+    }
+
+    /// Adds a builtin processor by name, with keyword arguments overriding its parameters.
+    #[pyo3(signature = (name, **params))]
+    fn add_processor(&mut self, name: &str, params: Option<&Bound<'_, PyDict>>) -> PyResult<usize> {
+        let params = params
+            .map(|params| params.extract::<BTreeMap<String, f32>>())
+            .transpose()?
+            .map(ProcessorParams::from)
+            .unwrap_or_default();
+        self.graph
+            .add_builtin(name, &params)
+            .map(|node| node.index())
+            .ok_or_else(|| PyValueError::new_err(format!("unknown processor {name:?}")))
+    }
+
+    fn connect(
+        &mut self,
+        source: usize,
+        source_output: u32,
+        target: usize,
+        target_input: u32,
+    ) -> PyResult<()> {
+        self.graph
+            .connect(
+                NodeIndex::new(source),
+                source_output,
+                NodeIndex::new(target),
+                target_input,
+            )
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Renders a `(channels, samples)` array through the graph, latency compensated.
+    #[pyo3(signature = (inputs, sample_rate, block_size = 512))]
+    fn render<'py>(
+        &mut self,
+        py: Python<'py>,
+        inputs: PyReadonlyArray2<'py, f32>,
+        sample_rate: f32,
+        block_size: usize,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let inputs = inputs
+            .as_array()
+            .outer_iter()
+            .map(|channel| channel.to_vec())
+            .collect::<Vec<_>>();
+        let inputs = inputs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        let rendered = self
+            .graph
+            .render(&inputs, sample_rate, block_size)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+
+        Ok(PyArray2::from_vec2(py, &rendered)?)
+    }
+}
+
+#[pymodule]
+fn raug_fft(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyFftGraph>()?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Reproducible random numbers for stochastic processors.
+//!
+//! An [`FftGraph`](crate::graph::FftGraph) has a seed (see
+//! [`FftGraph::set_seed`](crate::graph::FftGraph::set_seed)), from which it derives a seed for
+//! every node and frame, passed to the processor as [`FrameInfo::seed`]. A processor drawing
+//! random numbers creates a [`FrameRng`] from it, mixed with its own `seed` parameter if it has
+//! one:
+//!
+//! ```ignore
+//! fn set_frame_info(&mut self, info: &FrameInfo) {
+//!     self.rng = FrameRng::new(stream_seed(info.seed, self.seed as u64));
+//! }
+//! ```
+//!
+//! The numbers then only depend on the graph's seed, the node and the frame index, so rendering
+//! the same graph twice with the same seed gives the same output, however the host splits its
+//! input into blocks, while no two nodes draw the same numbers.
+//!
+//! [`FrameInfo::seed`]: crate::processor::FrameInfo::seed
+
+/// Derives an independent seed for stream `stream` of `seed`, e.g. one per node of a graph.
+#[inline]
+pub fn stream_seed(seed: u64, stream: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(stream))
+}
+
+/// The finalizer of SplitMix64, scrambling every bit of `x` into every bit of the result.
+#[inline]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A small, fast generator of uniformly distributed numbers (SplitMix64), see the
+/// [module docs](self). Not suitable for cryptography.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRng {
+    state: u64,
+}
+
+impl FrameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        let value = splitmix64(self.state);
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        value
+    }
+
+    #[inline]
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Returns a value between 0 (inclusive) and 1 (exclusive).
+    #[inline]
+    pub fn next_unit(&mut self) -> f32 {
+        // the top 24 bits fill the mantissa exactly
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns a value between -1 (inclusive) and 1 (exclusive).
+    #[inline]
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_unit() * 2.0 - 1.0
+    }
+
+    /// Returns a value between `min` (inclusive) and `max` (exclusive).
+    #[inline]
+    pub fn next_range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_unit()
+    }
+}
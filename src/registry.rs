@@ -0,0 +1,338 @@
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
+
+use crate::{
+    builder::ParamValue,
+    builtins::{
+        analysis::{
+            BeatTracker, ContinuousWaveletTransform, ContinuousWaveletTransformBuilder, DTMF_HZ,
+            DiscreteWaveletTransform, DiscreteWaveletTransformBuilder, GoertzelBank, KeyDetect,
+            KeyDetectBuilder, KeyDetectMode, OctaveAnalyzer, SpectralCentroid, SpectralFlux,
+            SpectrumAverage, Tuner, Vad, WelchPsd,
+        },
+        control::{BeatClock, FrameEnvelope, FrameLfo, FrameRandom},
+        dynamics::{AutoGain, SpectralDuck, SpectralLimiter, Unmask},
+        enhance::Exciter,
+        filter::{
+            AdaptiveBand, AdaptiveGain, AdaptiveGainBuilder, FeatureCurve, LinearPhaseEq,
+            SpectralTilt,
+        },
+        restoration::{Declip, DeclipBuilder, HumRemove, HumRemoveBuilder},
+        routing::{SpectralCrossover, SpectralMix},
+        sampler::{SpectralSampler, SpectralSamplerBuilder},
+        shift::FreqShift,
+        spatial::SpectralRotate,
+        stereo::{MidSide, StereoWidth},
+        synthesis::PhaseReconstruct,
+        transforms::{InverseRealFft, RealFft},
+        trigger::{Counter, EdgeDetector, Latch},
+        util::Null,
+    },
+    processor::FftProcessor,
+    signal::Fft,
+};
+
+/// Named numeric parameters used to construct a processor.
+///
+/// Parameters that aren't set fall back to the processor's defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessorParams(BTreeMap<String, f32>);
+
+impl ProcessorParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>, value: f32) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: f32) {
+        self.0.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.0.get(name).copied()
+    }
+
+    pub fn get_or(&self, name: &str, default: f32) -> f32 {
+        self.get(name).unwrap_or(default)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+}
+
+impl From<BTreeMap<String, f32>> for ProcessorParams {
+    fn from(params: BTreeMap<String, f32>) -> Self {
+        Self(params)
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, f32)> for ProcessorParams {
+    fn from_iter<I: IntoIterator<Item = (S, f32)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(name, value)| (name.into(), value))
+                .collect(),
+        )
+    }
+}
+
+/// Builds a boxed processor from its parameters.
+pub type ProcessorFactory = dyn Fn(&ProcessorParams) -> Box<dyn FftProcessor> + Send + Sync;
+
+/// Maps processor names to factories, so graphs can be built from text.
+///
+/// [`ProcessorRegistry::default`] contains every builtin that can be constructed from numeric
+/// parameters alone, under its snake-case name (e.g. `"spectral_tilt"`). More entries can be added
+/// with [`register`](Self::register).
+pub struct ProcessorRegistry<F: Fft> {
+    // shared, so cloning a registry (e.g. with the graph holding it) is cheap
+    factories: BTreeMap<String, Arc<ProcessorFactory>>,
+    _f: PhantomData<fn() -> F>,
+}
+
+impl<F: Fft> Clone for ProcessorRegistry<F> {
+    fn clone(&self) -> Self {
+        Self {
+            factories: self.factories.clone(),
+            _f: PhantomData,
+        }
+    }
+}
+
+impl<F: Fft> Default for ProcessorRegistry<F> {
+    fn default() -> Self {
+        Self::with_builtins()
+    }
+}
+
+impl<F: Fft> ProcessorRegistry<F> {
+    /// Creates a registry with no entries.
+    pub fn empty() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+            _f: PhantomData,
+        }
+    }
+
+    /// Creates a registry containing the builtin processors.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register("null", |_| Box::new(Null::<F>::new()));
+        registry.register("real_fft", |_| Box::new(RealFft::<F>::new()));
+        registry.register("inverse_real_fft", |_| Box::new(InverseRealFft::<F>::new()));
+        registry.register("declip", |params| {
+            let declip = Declip::<F>::new(params.get_or("radius", 2.0) as usize);
+            Box::new(DeclipBuilder::from(declip).params(params).build())
+        });
+        registry.register("hum_remove", |params| {
+            let hum = HumRemove::<F>::new(
+                params.get_or("mains_hz", 60.0),
+                params.get_or("harmonics", 8.0) as usize,
+            );
+            Box::new(HumRemoveBuilder::from(hum).params(params).build())
+        });
+        registry.register("linear_phase_eq", |_| {
+            Box::new(LinearPhaseEq::<F>::default())
+        });
+        registry.register("spectral_tilt", |params| {
+            Box::new(SpectralTilt::<F>::builder().params(params).build())
+        });
+        registry.register("adaptive_gain", |params| {
+            let gain = match params.get("bands") {
+                Some(num_bands) => {
+                    let bands = (0..num_bands as usize)
+                        .map(|i| {
+                            AdaptiveBand::new(
+                                params
+                                    .get_or(&format!("center{i}"), 1000.0 * 2.0f32.powi(i as i32)),
+                                params.get_or(&format!("low_db{i}"), 0.0),
+                                params.get_or(&format!("high_db{i}"), 0.0),
+                            )
+                            .with_curve(FeatureCurve::from_param(
+                                params.get_or(&format!("curve{i}"), 0.0),
+                            ))
+                        })
+                        .collect::<Vec<_>>();
+                    AdaptiveGain::<F>::new(bands)
+                }
+                None => AdaptiveGain::<F>::brightness(
+                    params.get_or("high_hz", 4000.0),
+                    params.get_or("range_db", 6.0),
+                ),
+            };
+            Box::new(AdaptiveGainBuilder::from(gain).params(params).build())
+        });
+        registry.register("exciter", |params| {
+            Box::new(Exciter::<F>::builder().params(params).build())
+        });
+        registry.register("freq_shift", |params| {
+            Box::new(FreqShift::<F>::builder().params(params).build())
+        });
+        registry.register("mid_side_encode", |_| Box::new(MidSide::<F>::encode()));
+        registry.register("mid_side_decode", |_| Box::new(MidSide::<F>::decode()));
+        registry.register("stereo_width", |params| {
+            Box::new(StereoWidth::<F>::builder().params(params).build())
+        });
+        registry.register("spectral_mix", |params| {
+            let num_inputs = params.get_or("inputs", 2.0) as usize;
+            let gains = (0..num_inputs)
+                .map(|i| params.get_or(&format!("gain{i}"), 1.0))
+                .collect();
+            Box::new(SpectralMix::<F>::with_gains(gains))
+        });
+        registry.register("spectral_crossover", |params| {
+            let num_crossovers = params.get_or("crossovers", 2.0) as usize;
+            let crossovers_hz = (0..num_crossovers)
+                .map(|i| params.get_or(&format!("crossover{i}"), 200.0 * 10.0f32.powi(i as i32)))
+                .collect();
+            Box::new(SpectralCrossover::<F>::new(
+                crossovers_hz,
+                params.get_or("overlap_octaves", 0.0),
+            ))
+        });
+        registry.register("spectral_rotate", |params| {
+            Box::new(SpectralRotate::<F>::builder().params(params).build())
+        });
+        registry.register("frame_lfo", |params| {
+            Box::new(FrameLfo::<F>::builder().params(params).build())
+        });
+        registry.register("frame_envelope", |params| {
+            Box::new(FrameEnvelope::<F>::builder().params(params).build())
+        });
+        registry.register("frame_random", |params| {
+            Box::new(FrameRandom::<F>::builder().params(params).build())
+        });
+        registry.register("beat_clock", |params| {
+            Box::new(BeatClock::<F>::builder().params(params).build())
+        });
+        registry.register("edge_detector", |params| {
+            Box::new(EdgeDetector::<F>::builder().params(params).build())
+        });
+        registry.register("latch", |_| Box::new(Latch::<F>::new()));
+        registry.register("counter", |params| {
+            Box::new(Counter::<F>::builder().params(params).build())
+        });
+        registry.register("vad", |params| {
+            Box::new(Vad::<F>::builder().params(params).build())
+        });
+        registry.register("spectral_flux", |params| {
+            Box::new(SpectralFlux::<F>::builder().params(params).build())
+        });
+        registry.register("beat_tracker", |params| {
+            Box::new(BeatTracker::<F>::builder().params(params).build())
+        });
+        registry.register("key_detect", |params| {
+            let mode = if params.get_or("chords", 0.0) != 0.0 {
+                KeyDetectMode::Chord
+            } else {
+                KeyDetectMode::Key
+            };
+            Box::new(
+                KeyDetectBuilder::from(KeyDetect::<F>::new(mode))
+                    .params(params)
+                    .build(),
+            )
+        });
+        registry.register("tuner", |params| {
+            Box::new(Tuner::<F>::builder().params(params).build())
+        });
+        registry.register("spectral_centroid", |params| {
+            Box::new(SpectralCentroid::<F>::builder().params(params).build())
+        });
+        registry.register("spectrum_average", |params| {
+            Box::new(SpectrumAverage::<F>::builder().params(params).build())
+        });
+        registry.register("octave_analyzer", |params| {
+            Box::new(OctaveAnalyzer::<F>::builder().params(params).build())
+        });
+        registry.register("goertzel_bank", |params| {
+            let num_frequencies = params.get_or("frequencies", DTMF_HZ.len() as f32) as usize;
+            let frequencies_hz = (0..num_frequencies)
+                .map(|i| {
+                    params.get_or(
+                        &format!("freq{i}"),
+                        DTMF_HZ.get(i).copied().unwrap_or(1000.0),
+                    )
+                })
+                .collect();
+            Box::new(GoertzelBank::<F>::new(frequencies_hz))
+        });
+        registry.register("cwt", |params| {
+            let cwt = ContinuousWaveletTransform::<F>::new(params.get_or("scales", 16.0) as usize);
+            Box::new(
+                ContinuousWaveletTransformBuilder::from(cwt)
+                    .params(params)
+                    .build(),
+            )
+        });
+        registry.register("dwt", |params| {
+            let dwt = DiscreteWaveletTransform::<F>::new(params.get_or("levels", 6.0) as usize);
+            Box::new(
+                DiscreteWaveletTransformBuilder::from(dwt)
+                    .params(params)
+                    .build(),
+            )
+        });
+        registry.register("welch_psd", |params| {
+            Box::new(WelchPsd::<F>::builder().params(params).build())
+        });
+        registry.register("spectral_duck", |params| {
+            let mut duck = SpectralDuck::<F>::builder().params(params).build();
+            duck.set_lookahead_frames(params.get_or("lookahead_frames", 0.0) as usize);
+            Box::new(duck)
+        });
+        registry.register("unmask", |params| {
+            Box::new(Unmask::<F>::builder().params(params).build())
+        });
+        registry.register("auto_gain", |params| {
+            Box::new(AutoGain::<F>::builder().params(params).build())
+        });
+        registry.register("spectral_limiter", |params| {
+            Box::new(SpectralLimiter::<F>::builder().params(params).build())
+        });
+        registry.register("phase_reconstruct", |params| {
+            Box::new(PhaseReconstruct::<F>::builder().params(params).build())
+        });
+        registry.register("spectral_sampler", |params| {
+            let sampler = SpectralSampler::<F>::with_capacity(
+                params.get_or("frames", 256.0).max(1.0) as usize,
+            );
+            Box::new(SpectralSamplerBuilder::from(sampler).params(params).build())
+        });
+
+        registry
+    }
+
+    /// Adds an entry, replacing any existing entry with the same name.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&ProcessorParams) -> Box<dyn FftProcessor> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Arc::new(factory));
+    }
+
+    /// Removes an entry, returning whether it existed.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.factories.remove(name).is_some()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Returns the registered names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Constructs the processor registered under `name`, or returns `None` if there isn't one.
+    pub fn create(&self, name: &str, params: &ProcessorParams) -> Option<Box<dyn FftProcessor>> {
+        self.factories.get(name).map(|factory| factory(params))
+    }
+}
@@ -0,0 +1,235 @@
+//! Sample rate conversion in the frequency domain.
+//!
+//! An [`FftResample`] converts a stream between two sample rates, e.g. so a graph running at
+//! 48 kHz can feed a 44.1 kHz host path without an external resampler:
+//!
+//! ```ignore
+//! let mut resample = FftResample::new(48_000, 44_100)?;
+//! graph.process_block(&[input], &mut [&mut block])?;
+//! resample.process(&block, &mut host_samples)?;
+//! ```
+//!
+//! The stream is cut into overlapping Hann-windowed frames, whose spectra are zero-padded (going
+//! up) or truncated (going down) to the length of a frame at the new rate, then resynthesized and
+//! overlap-added at the new hop length. Truncation drops everything above the lower Nyquist
+//! frequency, so downsampling doesn't alias.
+
+use alloc::sync::Arc;
+
+use crate::{
+    FftError,
+    core::{Complex32, FftPlanner},
+};
+
+/// The shortest frame an [`FftResample`] analyzes at the input rate, in samples.
+pub const RESAMPLE_MIN_FRAME: usize = 1024;
+
+/// The number of frames overlapping every sample.
+const OVERLAP: usize = 4;
+
+/// Converts a stream of samples between two sample rates, see the [module docs](self).
+///
+/// The frame lengths at both rates are the same multiple of the rates divided by their greatest
+/// common divisor, so that both hop lengths are whole samples. Rates without a large common
+/// divisor therefore need long frames, e.g. 88200 samples to go from 44100 to 44101 Hz.
+pub struct FftResample {
+    input_rate: u32,
+    output_rate: u32,
+    input_length: usize,
+    output_length: usize,
+    forward: Arc<dyn realfft::RealToComplex<f32>>,
+    inverse: Arc<dyn realfft::ComplexToReal<f32>>,
+    window: Vec<f32>,
+    // samples that haven't been analyzed yet, starting with the end of the last frame
+    pending: Vec<f32>,
+    frame: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    resampled: Vec<Complex32>,
+    synthesized: Vec<f32>,
+    forward_scratch: Vec<Complex32>,
+    inverse_scratch: Vec<Complex32>,
+    overlap_add: Vec<f32>,
+    frames: u64,
+    input_samples: u64,
+    output_samples: u64,
+}
+
+impl FftResample {
+    /// Creates a converter from `input_rate` to `output_rate` (in Hz).
+    pub fn new(input_rate: u32, output_rate: u32) -> Result<Self, FftError> {
+        if input_rate == 0 || output_rate == 0 {
+            return Err(FftError::Transform("sample rates must be positive"));
+        }
+        let divisor = gcd(input_rate, output_rate);
+        let (input_step, output_step) = (
+            (input_rate / divisor) as usize,
+            (output_rate / divisor) as usize,
+        );
+        // a multiple of the overlap, so both hops are whole samples
+        let multiple = RESAMPLE_MIN_FRAME
+            .div_ceil(input_step)
+            .next_multiple_of(OVERLAP);
+        let input_length = input_step * multiple;
+        let output_length = output_step * multiple;
+
+        let planner = FftPlanner::global();
+        let forward = planner.plan_forward(input_length);
+        let inverse = planner.plan_inverse(output_length);
+        let mut resample = Self {
+            input_rate,
+            output_rate,
+            input_length,
+            output_length,
+            window: periodic_hann(input_length),
+            pending: Vec::with_capacity(input_length * 2),
+            frame: forward.make_input_vec(),
+            spectrum: forward.make_output_vec(),
+            resampled: inverse.make_input_vec(),
+            synthesized: inverse.make_output_vec(),
+            forward_scratch: forward.make_scratch_vec(),
+            inverse_scratch: inverse.make_scratch_vec(),
+            overlap_add: vec![0.0; output_length],
+            forward,
+            inverse,
+            frames: 0,
+            input_samples: 0,
+            output_samples: 0,
+        };
+        resample.reset();
+        Ok(resample)
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Returns the number of output samples per input sample.
+    pub fn ratio(&self) -> f64 {
+        self.output_rate as f64 / self.input_rate as f64
+    }
+
+    /// Returns the length of the frames at the input and the output rate.
+    pub fn frame_lengths(&self) -> (usize, usize) {
+        (self.input_length, self.output_length)
+    }
+
+    /// Returns how many more input samples at most have to arrive before the output covering a
+    /// sample is complete. The output itself isn't delayed: its first sample lines up with the
+    /// first input sample.
+    pub fn latency_samples(&self) -> usize {
+        self.input_length
+    }
+
+    /// Clears the buffered samples, starting a new stream.
+    pub fn reset(&mut self) {
+        // the first frame ends one hop after the start of the stream
+        self.pending.clear();
+        self.pending
+            .resize(self.input_length - self.input_length / OVERLAP, 0.0);
+        self.overlap_add.fill(0.0);
+        self.frames = 0;
+        self.input_samples = 0;
+        self.output_samples = 0;
+    }
+
+    /// Converts `input`, appending every output sample that's complete to `output`.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) -> Result<(), FftError> {
+        self.input_samples += input.len() as u64;
+        self.pending.extend_from_slice(input);
+        let input_hop = self.input_length / OVERLAP;
+        let mut start = 0;
+        while self.pending.len() - start >= self.input_length {
+            self.process_frame(start, output)?;
+            start += input_hop;
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+
+    /// Appends the rest of the output to `output`, up to the length of the input converted to the
+    /// output rate, and starts a new stream.
+    pub fn flush(&mut self, output: &mut Vec<f32>) -> Result<(), FftError> {
+        let expected = (self.input_samples * self.output_rate as u64)
+            .div_ceil(self.input_rate as u64) as usize;
+        let start = output.len();
+        let produced = self.output_samples as usize;
+        let input_samples = self.input_samples;
+
+        let silence = vec![0.0; self.input_length];
+        self.process(&silence, output)?;
+        output.truncate(start + expected.saturating_sub(produced));
+
+        self.input_samples = input_samples;
+        self.reset();
+        Ok(())
+    }
+
+    /// Resamples the frame starting at `pending[start]`, appending the hop of output it completes.
+    fn process_frame(&mut self, start: usize, output: &mut Vec<f32>) -> Result<(), FftError> {
+        let samples = &self.pending[start..start + self.input_length];
+        for ((frame, &sample), &window) in self.frame.iter_mut().zip(samples).zip(&self.window) {
+            *frame = sample * window;
+        }
+        self.forward.process_with_scratch(
+            &mut self.frame,
+            &mut self.spectrum,
+            &mut self.forward_scratch,
+        )?;
+
+        // the inverse isn't normalized, and the windows overlapping every sample add up to half
+        // the overlap
+        let scale = 2.0 / (self.input_length * OVERLAP) as f32;
+        self.resampled.fill(Complex32::ZERO);
+        for (resampled, &bin) in self.resampled.iter_mut().zip(&self.spectrum) {
+            *resampled = bin * scale;
+        }
+        if self.output_length > self.input_length {
+            // the input's Nyquist bin stands for both of its mirror images
+            self.resampled[self.spectrum.len() - 1] *= 0.5;
+        }
+        let last = self.resampled.len() - 1;
+        self.resampled[0].im = 0.0;
+        self.resampled[last].im = 0.0;
+        self.inverse.process_with_scratch(
+            &mut self.resampled,
+            &mut self.synthesized,
+            &mut self.inverse_scratch,
+        )?;
+
+        for (sum, &sample) in self.overlap_add.iter_mut().zip(&self.synthesized) {
+            *sum += sample;
+        }
+        let output_hop = self.output_length / OVERLAP;
+        // the first frames only complete samples before the start of the stream
+        if self.frames >= OVERLAP as u64 - 1 {
+            output.extend_from_slice(&self.overlap_add[..output_hop]);
+            self.output_samples += output_hop as u64;
+        }
+        self.overlap_add.copy_within(output_hop.., 0);
+        let end = self.overlap_add.len();
+        self.overlap_add[end - output_hop..].fill(0.0);
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Returns a Hann window whose shifted copies add up to a constant at any overlap of two or more.
+fn periodic_hann(length: usize) -> Vec<f32> {
+    (0..length)
+        .map(|n| {
+            let phase = 2.0 * core::f32::consts::PI * n as f32 / length as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect()
+}
@@ -0,0 +1,509 @@
+//! Processing the same audio at two FFT lengths.
+//!
+//! Short frames resolve transients, long frames resolve closely spaced partials, and no single
+//! FFT length does both. An [`FftMultiResolution`] runs a graph of each length on the same audio
+//! inputs and sums their audio outputs, so e.g. the short graph can handle everything above a
+//! crossover and the long graph everything below it:
+//!
+//! ```ignore
+//! let mut short = FftGraph::<Fft512>::new(128, WindowFunction::Hann);
+//! let mut long = FftGraph::<Fft4096>::new(128, WindowFunction::Hann);
+//! // ... add the same audio inputs and outputs to both, and their processors ...
+//! let flux = short.add_frame_output();
+//! let gate = long.add_frame_input();
+//! let mut graph = FftMultiResolution::new(short, long)?;
+//! // feed the short graph's transient spectrum to the long graph every hop
+//! graph.bridge_to_long(0, 0)?;
+//! ```
+//!
+//! Both graphs take frames every hop, with the frames of the same hop centered on the same
+//! sample. Spectra pass between the graphs through bridges from the frame outputs of one to the
+//! frame inputs of the other (see [`FftGraph::add_frame_output`]), converted to the other
+//! resolution with [`resample_spectrum`]. Within a single graph,
+//! [`FrameResample`](crate::builtins::transforms::FrameResample) converts spectra the same way,
+//! e.g. for a model expecting another FFT length.
+
+use raug::prelude::*;
+
+use crate::{
+    FftError, core::Complex32, denormal::DenormalGuard, graph::FftGraph, processor::Transport,
+    random::stream_seed, signal::Fft,
+};
+
+/// Converts a real spectrum to another number of bins covering the same frequencies, e.g. from
+/// the spectrum of a 512-point FFT to that of a 4096-point one.
+///
+/// Magnitudes are scaled by the ratio of the FFT lengths, so a sinusoid keeps its amplitude.
+/// Going to more bins, magnitudes are interpolated linearly between the nearest source bins, with
+/// the phase of the closest one. Going to fewer bins, every target bin takes the loudest source
+/// bin within its width, phase included, so tonal peaks survive. Phases don't carry over between
+/// resolutions in a meaningful way, so converted spectra are best used for analysis, masks and
+/// gains rather than resynthesized as they are.
+pub fn resample_spectrum(source: &[Complex32], target: &mut [Complex32]) {
+    if source.len() == target.len() {
+        target.copy_from_slice(source);
+        return;
+    }
+    if source.len() < 2 || target.len() < 2 {
+        let value = source.first().copied().unwrap_or(Complex32::ZERO);
+        target.fill(value);
+        return;
+    }
+
+    let source_last = source.len() - 1;
+    // source bins per target bin
+    let ratio = source_last as f32 / (target.len() - 1) as f32;
+    let scale = ratio.recip();
+
+    for (bin, value) in target.iter_mut().enumerate() {
+        let position = bin as f32 * ratio;
+        if ratio <= 1.0 {
+            let below = (position as usize).min(source_last);
+            let above = (below + 1).min(source_last);
+            let fraction = position - below as f32;
+            let magnitude =
+                source[below].norm() + (source[above].norm() - source[below].norm()) * fraction;
+            let nearest = if fraction < 0.5 { below } else { above };
+            *value = Complex32::from_polar(magnitude * scale, source[nearest].arg());
+        } else {
+            let start = (position - ratio * 0.5).ceil().max(0.0) as usize;
+            let end = ((position + ratio * 0.5).floor() as usize).min(source_last);
+            let loudest = (start..=end)
+                .max_by(|&a, &b| source[a].norm_sqr().total_cmp(&source[b].norm_sqr()))
+                .unwrap_or(start);
+            *value = source[loudest] * scale;
+        }
+    }
+}
+
+/// A frame output of one graph of an [`FftMultiResolution`] feeding a frame input of the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bridge {
+    to_long: bool,
+    source_output: usize,
+    target_input: usize,
+}
+
+/// A graph with short frames and a graph with long frames processing the same audio, see the
+/// [module docs](self).
+///
+/// Audio input `i` feeds audio input `i` of both graphs (if they have one), and audio output `i`
+/// is the sum of audio output `i` of both graphs. Both graphs must share their hop length. The
+/// graphs are processed in lockstep, the short one first, so a bridge to the long graph carries
+/// the short graph's spectrum of the same hop, while a bridge to the short graph carries the long
+/// graph's spectrum of the previous hop.
+///
+/// The latency is that of the long graph, plus half the difference of the FFT lengths to center
+/// the frames, plus the processor latency of whichever graph has more. It's fixed when the graph
+/// is allocated, so the processors should be added before.
+pub struct FftMultiResolution<S: Fft, L: Fft> {
+    short: FftGraph<S>,
+    long: FftGraph<L>,
+    bridges: Vec<Bridge>,
+    latency: usize,
+    // the raug processor's inputs and outputs, and the output of one graph before summing
+    silence: Vec<f32>,
+    scratch: Vec<f32>,
+    summed: Vec<f32>,
+}
+
+impl<S: Fft, L: Fft> FftMultiResolution<S, L> {
+    /// Combines a graph with short frames and one with long frames, failing if the long graph's
+    /// frames are shorter or the hop lengths differ.
+    pub fn new(short: FftGraph<S>, long: FftGraph<L>) -> Result<Self, FftError> {
+        let reason = if L::N_FFT < S::N_FFT {
+            "the long graph's FFT length must not be shorter than the short graph's"
+        } else if short.hop_length() != long.hop_length() {
+            "the short and long graphs must share their hop length"
+        } else {
+            let mut graph = Self {
+                short,
+                long,
+                bridges: Vec::new(),
+                latency: 0,
+                silence: Vec::new(),
+                scratch: Vec::new(),
+                summed: Vec::new(),
+            };
+            graph.latency = graph.compute_latency();
+            graph.set_seed(0);
+            return Ok(graph);
+        };
+        Err(FftError::InvalidStft {
+            fft_length: L::N_FFT,
+            hop_length: long.hop_length(),
+            reason,
+        })
+    }
+
+    pub fn short(&self) -> &FftGraph<S> {
+        &self.short
+    }
+
+    pub fn short_mut(&mut self) -> &mut FftGraph<S> {
+        &mut self.short
+    }
+
+    pub fn long(&self) -> &FftGraph<L> {
+        &self.long
+    }
+
+    pub fn long_mut(&mut self) -> &mut FftGraph<L> {
+        &mut self.long
+    }
+
+    pub fn hop_length(&self) -> usize {
+        self.long.hop_length()
+    }
+
+    pub fn num_audio_inputs(&self) -> usize {
+        self.short
+            .num_audio_inputs()
+            .max(self.long.num_audio_inputs())
+    }
+
+    pub fn num_audio_outputs(&self) -> usize {
+        self.short
+            .num_audio_outputs()
+            .max(self.long.num_audio_outputs())
+    }
+
+    /// Returns the delay in samples between the audio inputs and outputs, as of the last
+    /// allocation.
+    pub fn latency_samples(&self) -> usize {
+        self.latency
+    }
+
+    /// Passes the spectrum at frame output `short_output` of the short graph to frame input
+    /// `long_input` of the long graph every hop, converted with [`resample_spectrum`].
+    pub fn bridge_to_long(
+        &mut self,
+        short_output: usize,
+        long_input: usize,
+    ) -> Result<(), FftError> {
+        self.check_ports(
+            short_output,
+            self.short.frame_outputs().len(),
+            long_input,
+            self.long.frame_inputs().len(),
+        )?;
+        self.add_bridge(Bridge {
+            to_long: true,
+            source_output: short_output,
+            target_input: long_input,
+        });
+        Ok(())
+    }
+
+    /// Passes the spectrum at frame output `long_output` of the long graph to frame input
+    /// `short_input` of the short graph every hop, converted with [`resample_spectrum`]. The
+    /// short graph receives it one hop late, since it's processed first.
+    pub fn bridge_to_short(
+        &mut self,
+        long_output: usize,
+        short_input: usize,
+    ) -> Result<(), FftError> {
+        self.check_ports(
+            long_output,
+            self.long.frame_outputs().len(),
+            short_input,
+            self.short.frame_inputs().len(),
+        )?;
+        self.add_bridge(Bridge {
+            to_long: false,
+            source_output: long_output,
+            target_input: short_input,
+        });
+        Ok(())
+    }
+
+    fn check_ports(
+        &self,
+        source_output: usize,
+        num_outputs: usize,
+        target_input: usize,
+        num_inputs: usize,
+    ) -> Result<(), FftError> {
+        if source_output >= num_outputs {
+            return Err(FftError::Graph(format!(
+                "no frame output {source_output} to bridge from ({num_outputs} in total)"
+            )));
+        }
+        if target_input >= num_inputs {
+            return Err(FftError::Graph(format!(
+                "no frame input {target_input} to bridge to ({num_inputs} in total)"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Adds `bridge`, replacing any bridge already feeding its frame input.
+    fn add_bridge(&mut self, bridge: Bridge) {
+        self.bridges.retain(|other| {
+            other.to_long != bridge.to_long || other.target_input != bridge.target_input
+        });
+        self.bridges.push(bridge);
+    }
+
+    /// Sets the transport of both graphs, see [`FftGraph::set_transport`].
+    pub fn set_transport(&mut self, transport: Transport) {
+        self.short.set_transport(transport);
+        self.long.set_transport(transport);
+    }
+
+    /// Seeds the random numbers of both graphs from `seed`, giving each its own stream, see
+    /// [`FftGraph::set_seed`].
+    pub fn set_seed(&mut self, seed: u64) {
+        self.short.set_seed(stream_seed(seed, 0));
+        self.long.set_seed(stream_seed(seed, 1));
+    }
+
+    /// The silence buffered ahead of the long graph's inputs, centering its frames on the short
+    /// graph's.
+    fn long_padding() -> usize {
+        (L::N_FFT - S::N_FFT) / 2
+    }
+
+    fn compute_latency(&self) -> usize {
+        let hop_length = self.hop_length();
+        let short = S::N_FFT - 1 + self.short.latency_frames() * hop_length;
+        let long = L::N_FFT - 1 + Self::long_padding() + self.long.latency_frames() * hop_length;
+        short.max(long)
+    }
+
+    pub fn allocate(&mut self, sample_rate: f32, block_size: usize) {
+        self.short.allocate(sample_rate, block_size);
+        self.long.allocate(sample_rate, block_size);
+
+        // line both graphs' outputs up with the later one
+        let hop_length = self.hop_length();
+        self.latency = self.compute_latency();
+        self.long.pad_inputs(Self::long_padding());
+        self.short.delay_outputs(
+            self.latency - (S::N_FFT - 1 + self.short.latency_frames() * hop_length),
+        );
+        self.long.delay_outputs(
+            self.latency
+                - (L::N_FFT - 1 + Self::long_padding() + self.long.latency_frames() * hop_length),
+        );
+
+        self.silence = vec![0.0; block_size];
+        self.scratch = vec![0.0; block_size];
+        self.summed = vec![0.0; block_size];
+    }
+
+    pub fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        self.short.resize_buffers(sample_rate, block_size);
+        self.long.resize_buffers(sample_rate, block_size);
+    }
+
+    /// Processes every hop both graphs have buffered enough samples for.
+    fn process_frames(&mut self) -> ProcResult<()> {
+        self.short.start_frames();
+        self.long.start_frames();
+
+        let _denormal_guard = (self.short.denormal_protection() || self.long.denormal_protection())
+            .then(DenormalGuard::new);
+
+        while self.short.frame_ready() && self.long.frame_ready() {
+            self.short.process_frame()?;
+            self.pass_bridges(true);
+            self.long.process_frame()?;
+            self.pass_bridges(false);
+        }
+
+        Ok(())
+    }
+
+    /// Converts the frame outputs of one graph to the frame inputs of the other.
+    fn pass_bridges(&mut self, to_long: bool) {
+        for bridge in self
+            .bridges
+            .iter()
+            .filter(|bridge| bridge.to_long == to_long)
+        {
+            if to_long {
+                let (Some(source), Some(target)) = (
+                    self.short.frame_output(bridge.source_output),
+                    self.long.frame_input_mut(bridge.target_input),
+                ) else {
+                    continue;
+                };
+                resample_spectrum(source, target);
+            } else {
+                let (Some(source), Some(target)) = (
+                    self.long.frame_output(bridge.source_output),
+                    self.short.frame_input_mut(bridge.target_input),
+                ) else {
+                    continue;
+                };
+                resample_spectrum(source, target);
+            }
+        }
+    }
+
+    /// Sums the audio output at `channel` of both graphs into `output`, zero-filling whatever
+    /// isn't available yet. `scratch` must be at least as long as `output`.
+    fn read_output(
+        short: &mut FftGraph<S>,
+        long: &mut FftGraph<L>,
+        scratch: &mut [f32],
+        channel: usize,
+        output: &mut [f32],
+    ) {
+        let scratch = &mut scratch[..output.len()];
+        output.fill(0.0);
+        if channel < short.num_audio_outputs() {
+            short.read_output(channel, scratch);
+            output
+                .iter_mut()
+                .zip(scratch.iter())
+                .for_each(|(out, x)| *out += x);
+        }
+        if channel < long.num_audio_outputs() {
+            long.read_output(channel, scratch);
+            output
+                .iter_mut()
+                .zip(scratch.iter())
+                .for_each(|(out, x)| *out += x);
+        }
+    }
+
+    /// Processes a block of audio, see [`FftGraph::process_block`]. Blocks must not be longer
+    /// than the block size the graph was allocated for.
+    pub fn process_block(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+    ) -> ProcResult<()> {
+        for (channel, input) in inputs.iter().enumerate() {
+            self.short.push_input(channel, input);
+            self.long.push_input(channel, input);
+        }
+
+        self.process_frames()?;
+
+        for (channel, output) in outputs.iter_mut().enumerate() {
+            Self::read_output(
+                &mut self.short,
+                &mut self.long,
+                &mut self.scratch,
+                channel,
+                output,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Renders whole signals offline, returning one signal per audio output, see
+    /// [`FftGraph::render`].
+    pub fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        sample_rate: f32,
+        block_size: usize,
+    ) -> ProcResult<Vec<Vec<f32>>> {
+        let block_size = block_size.max(1);
+        self.allocate(sample_rate, block_size);
+
+        let length = inputs.iter().map(|input| input.len()).max().unwrap_or(0);
+        let total = length + self.latency;
+        let num_outputs = self.num_audio_outputs();
+        let mut rendered = vec![Vec::with_capacity(total); num_outputs];
+
+        let mut input_blocks = vec![vec![0.0; block_size]; inputs.len()];
+        let mut output_blocks = vec![vec![0.0; block_size]; num_outputs];
+        for start in (0..total).step_by(block_size) {
+            let len = block_size.min(total - start);
+            for (block, input) in input_blocks.iter_mut().zip(inputs) {
+                block.fill(0.0);
+                let end = (start + len).min(input.len());
+                if start < end {
+                    block[..end - start].copy_from_slice(&input[start..end]);
+                }
+            }
+
+            let input_refs: Vec<&[f32]> = input_blocks.iter().map(|block| &block[..len]).collect();
+            let mut output_refs: Vec<&mut [f32]> = output_blocks
+                .iter_mut()
+                .map(|block| &mut block[..len])
+                .collect();
+            self.process_block(&input_refs, &mut output_refs)?;
+
+            for (output, block) in rendered.iter_mut().zip(&output_blocks) {
+                output.extend_from_slice(&block[..len]);
+            }
+        }
+
+        for output in rendered.iter_mut() {
+            output.drain(..self.latency.min(output.len()));
+        }
+
+        Ok(rendered)
+    }
+}
+
+impl<S: Fft, L: Fft> Processor for FftMultiResolution<S, L> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        (0..self.num_audio_inputs())
+            .map(|i| SignalSpec::new(i.to_string(), f32::signal_type()))
+            .collect()
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        (0..self.num_audio_outputs())
+            .map(|i| SignalSpec::new(i.to_string(), f32::signal_type()))
+            .collect()
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        (0..self.num_audio_outputs())
+            .map(|_| AnyBuffer::zeros::<f32>(size))
+            .collect()
+    }
+
+    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
+        FftMultiResolution::allocate(self, sample_rate, max_block_size);
+    }
+
+    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        FftMultiResolution::resize_buffers(self, sample_rate, block_size);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> Result<(), ProcessorError> {
+        let block_size = inputs.block_size();
+
+        for channel in 0..self.num_audio_inputs() {
+            // unconnected inputs are silent
+            let samples = inputs
+                .input_as::<f32>(channel)
+                .and_then(|samples| samples.get(..block_size))
+                .unwrap_or(&self.silence[..block_size]);
+            self.short.push_input(channel, samples);
+            self.long.push_input(channel, samples);
+        }
+
+        self.process_frames()?;
+
+        for channel in 0..self.num_audio_outputs() {
+            let summed = &mut self.summed[..block_size];
+            Self::read_output(
+                &mut self.short,
+                &mut self.long,
+                &mut self.scratch,
+                channel,
+                summed,
+            );
+            for (sample_index, sample) in summed.iter().enumerate() {
+                outputs.set_output_as::<f32>(channel, sample_index, sample)?;
+            }
+        }
+
+        Ok(())
+    }
+}
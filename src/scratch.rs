@@ -0,0 +1,122 @@
+//! Temporary buffers for processors, preallocated once per graph instead of once per processor.
+//!
+//! Many processors need a few bin-sized buffers (magnitudes, gains, features) that only live
+//! while a frame is processed. Instead of holding them as private `Vec`s, a processor declares
+//! how much space it needs with [`FftProcessor::scratch_size`] and takes it from the [`Scratch`]
+//! passed to [`FftProcessor::process_with_scratch`]. A graph keeps a single [`ScratchPool`] large
+//! enough for its hungriest processor and lends all of it to every processor in turn, so taking
+//! scratch space never allocates.
+//!
+//! [`FftProcessor::scratch_size`]: crate::processor::FftProcessor::scratch_size
+//! [`FftProcessor::process_with_scratch`]: crate::processor::FftProcessor::process_with_scratch
+
+use alloc::{vec, vec::Vec};
+
+use crate::core::Complex32;
+
+/// How many real and complex values a processor takes from its [`Scratch`] per call.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScratchSize {
+    pub reals: usize,
+    pub complexes: usize,
+}
+
+impl ScratchSize {
+    pub fn new(reals: usize, complexes: usize) -> Self {
+        Self { reals, complexes }
+    }
+
+    /// Returns the larger of the two sizes in each kind of value.
+    pub fn max(self, other: Self) -> Self {
+        Self {
+            reals: self.reals.max(other.reals),
+            complexes: self.complexes.max(other.complexes),
+        }
+    }
+}
+
+/// The storage behind [`Scratch`], owned by a graph.
+#[derive(Debug, Default, Clone)]
+pub struct ScratchPool {
+    reals: Vec<f32>,
+    complexes: Vec<Complex32>,
+}
+
+impl ScratchPool {
+    pub fn new(size: ScratchSize) -> Self {
+        Self {
+            reals: vec![0.0; size.reals],
+            complexes: vec![Complex32::ZERO; size.complexes],
+        }
+    }
+
+    pub fn size(&self) -> ScratchSize {
+        ScratchSize::new(self.reals.len(), self.complexes.len())
+    }
+
+    /// Grows the pool to hold at least `size`. This allocates, so it must not be called while
+    /// processing.
+    pub fn reserve(&mut self, size: ScratchSize) {
+        let size = self.size().max(size);
+        self.reals.resize(size.reals, 0.0);
+        self.complexes.resize(size.complexes, Complex32::ZERO);
+    }
+
+    /// Lends the whole pool out for one call.
+    pub fn scratch(&mut self) -> Scratch<'_> {
+        Scratch {
+            reals: &mut self.reals,
+            complexes: &mut self.complexes,
+        }
+    }
+}
+
+/// Scratch space for one call of a processor, handed out front to back.
+///
+/// Every buffer taken stays borrowed until the call ends, so several can be used at once. Taking
+/// more than the processor declared in its [`ScratchSize`] is a bug and panics.
+pub struct Scratch<'a> {
+    reals: &'a mut [f32],
+    complexes: &'a mut [Complex32],
+}
+
+impl<'a> Scratch<'a> {
+    /// Takes `len` real values, zeroed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `len` real values are left.
+    pub fn reals(&mut self, len: usize) -> &'a mut [f32] {
+        assert!(
+            len <= self.reals.len(),
+            "scratch space exhausted: {len} reals requested, {} left",
+            self.reals.len()
+        );
+        let (taken, rest) = core::mem::take(&mut self.reals).split_at_mut(len);
+        self.reals = rest;
+        taken.fill(0.0);
+        taken
+    }
+
+    /// Takes `len` complex values, zeroed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than `len` complex values are left.
+    pub fn complexes(&mut self, len: usize) -> &'a mut [Complex32] {
+        assert!(
+            len <= self.complexes.len(),
+            "scratch space exhausted: {len} complex values requested, {} left",
+            self.complexes.len()
+        );
+        let (taken, rest) = core::mem::take(&mut self.complexes).split_at_mut(len);
+        self.complexes = rest;
+        taken.fill(Complex32::ZERO);
+        taken
+    }
+
+    /// Returns how much space is left.
+    pub fn remaining(&self) -> ScratchSize {
+        ScratchSize::new(self.reals.len(), self.complexes.len())
+    }
+}
@@ -1,23 +1,63 @@
-use std::ops::{Deref, DerefMut};
+use core::ops::{Deref, DerefMut};
 
-use raug::signal::Signal;
-pub use realfft::num_complex::Complex32;
+pub use crate::core::Complex32;
+
+use crate::{
+    FftError,
+    core::math,
+    io::Signal,
+    state::{StateReader, StateValue, StateWriter},
+};
 
 mod sealed {
     pub trait Sealed {}
 }
 
-pub trait Fft: sealed::Sealed + Send + 'static {
+pub trait Fft: sealed::Sealed + Clone + Copy + Send + 'static {
     const N_FFT: usize;
     const N_REAL_BINS: usize = Self::N_FFT / 2 + 1;
-    type AudioBlock: Signal + Clone + Default + Deref<Target = [f32]> + DerefMut;
-    type RealFft: Signal + Clone + Default + Deref<Target = [Complex32]> + DerefMut;
-    type ComplexFft: Signal + Clone + Default + Deref<Target = [Complex32]> + DerefMut;
+    type AudioBlock: Signal + Clone + Default + Deref<Target = [f32]> + DerefMut + StateValue;
+    type RealFft: Signal + Clone + Default + Deref<Target = [Complex32]> + DerefMut + StateValue;
+    type ComplexFft: Signal + Clone + Default + Deref<Target = [Complex32]> + DerefMut + StateValue;
+
+    /// Returns the spacing between bin center frequencies in Hz at `sample_rate`.
+    fn bin_bandwidth(sample_rate: f32) -> f32 {
+        sample_rate / Self::N_FFT as f32
+    }
+
+    /// Returns the center frequency of `bin` in Hz at `sample_rate`.
+    fn bin_to_hz(bin: usize, sample_rate: f32) -> f32 {
+        bin as f32 * Self::bin_bandwidth(sample_rate)
+    }
+
+    /// Returns the fractional bin of the frequency `hz` at `sample_rate`, or 0 while the sample
+    /// rate is unknown (not above zero).
+    fn hz_to_bin(hz: f32, sample_rate: f32) -> f32 {
+        if sample_rate > 0.0 {
+            hz * Self::N_FFT as f32 / sample_rate
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the bin whose center is nearest to `hz` at `sample_rate`, clamped to the bins of
+    /// a real spectrum.
+    fn nearest_bin(hz: f32, sample_rate: f32) -> usize {
+        (math::roundf(Self::hz_to_bin(hz, sample_rate)).max(0.0) as usize)
+            .min(Self::N_REAL_BINS - 1)
+    }
+
+    /// Returns every bin of a real spectrum with its center frequency in Hz at `sample_rate`.
+    fn bin_frequencies(sample_rate: f32) -> impl Iterator<Item = (usize, f32)> {
+        let bandwidth = Self::bin_bandwidth(sample_rate);
+        (0..Self::N_REAL_BINS).map(move |bin| (bin, bin as f32 * bandwidth))
+    }
 }
 
 macro_rules! impl_fft_frame {
     ($($n:literal => $frame:ident, $audio_block:ident, $real:ident, $complex:ident),* $(,)?) => {
         $(
+            #[derive(Clone, Copy)]
             pub struct $frame;
 
             impl sealed::Sealed for $frame {}
@@ -56,6 +96,16 @@ macro_rules! impl_fft_frame {
                 }
             }
 
+            impl StateValue for $audio_block {
+                fn save(&self, state: &mut StateWriter) {
+                    state.write(&self.0);
+                }
+
+                fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+                    state.read(&mut self.0)
+                }
+            }
+
             #[derive(Clone, Copy)]
             #[repr(transparent)]
             pub struct $real([Complex32; $n / 2 + 1]);
@@ -82,6 +132,15 @@ macro_rules! impl_fft_frame {
                 }
             }
 
+            impl StateValue for $real {
+                fn save(&self, state: &mut StateWriter) {
+                    state.write(&self.0);
+                }
+
+                fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+                    state.read(&mut self.0)
+                }
+            }
 
             #[derive(Clone, Copy)]
             #[repr(transparent)]
@@ -108,6 +167,16 @@ macro_rules! impl_fft_frame {
                     &mut self.0
                 }
             }
+
+            impl StateValue for $complex {
+                fn save(&self, state: &mut StateWriter) {
+                    state.write(&self.0);
+                }
+
+                fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+                    state.read(&mut self.0)
+                }
+            }
         )*
     };
 }
@@ -0,0 +1,174 @@
+use crate::{
+    FftError,
+    core::math,
+    state::{StateReader, StateValue, StateWriter},
+};
+
+/// How a [`SmoothedParam`] moves towards a new target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Jumps to the target on the next hop.
+    None,
+    /// Approaches the target exponentially, with the given time constant in seconds.
+    OnePole(f32),
+    /// Ramps linearly to the target over the given number of seconds.
+    Linear(f32),
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self::OnePole(0.02)
+    }
+}
+
+/// A parameter that glides towards its target once per hop instead of jumping, so parameter
+/// changes don't zipper at the frame rate.
+///
+/// Until the hop duration is known (see [`set_hop_seconds`](Self::set_hop_seconds)), changes are
+/// applied immediately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmoothedParam {
+    smoothing: Smoothing,
+    hop_seconds: f32,
+    value: f32,
+    target: f32,
+    coeff: f32,
+    step: f32,
+    remaining: usize,
+}
+
+impl SmoothedParam {
+    pub fn new(value: f32) -> Self {
+        Self {
+            smoothing: Smoothing::default(),
+            hop_seconds: 0.0,
+            value,
+            target: value,
+            coeff: 0.0,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    pub fn with_smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.set_smoothing(smoothing);
+        self
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+        self.update_coeff();
+    }
+
+    /// Sets the duration of one hop, which the smoothing times are converted with.
+    pub fn set_hop_seconds(&mut self, hop_seconds: f32) {
+        if self.hop_seconds != hop_seconds {
+            self.hop_seconds = hop_seconds;
+            self.update_coeff();
+        }
+    }
+
+    /// Returns the current (smoothed) value.
+    #[inline]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    #[inline]
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Returns whether the value has reached its target.
+    #[inline]
+    pub fn is_settled(&self) -> bool {
+        self.value == self.target
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        if self.target == target {
+            return;
+        }
+        self.target = target;
+        if let Smoothing::Linear(seconds) = self.smoothing
+            && self.hop_seconds > 0.0
+        {
+            self.remaining = math::ceilf(seconds / self.hop_seconds).max(1.0) as usize;
+            self.step = (target - self.value) / self.remaining as f32;
+        }
+    }
+
+    /// Jumps to `value` without smoothing.
+    pub fn reset(&mut self, value: f32) {
+        self.value = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    /// Advances by one hop and returns the new value.
+    #[inline]
+    pub fn advance(&mut self) -> f32 {
+        if self.is_settled() {
+            return self.value;
+        }
+
+        match self.smoothing {
+            Smoothing::OnePole(seconds) if self.hop_seconds > 0.0 && seconds > 0.0 => {
+                self.value = self.target + (self.value - self.target) * self.coeff;
+                // snap once the remaining distance is negligible, so the value settles exactly
+                if (self.value - self.target).abs() <= 1e-5 * self.target.abs().max(1.0) {
+                    self.value = self.target;
+                }
+            }
+            Smoothing::Linear(_) if self.remaining > 0 => {
+                self.remaining -= 1;
+                if self.remaining == 0 {
+                    self.value = self.target;
+                } else {
+                    self.value += self.step;
+                }
+            }
+            _ => self.value = self.target,
+        }
+
+        self.value
+    }
+
+    fn update_coeff(&mut self) {
+        self.coeff = match self.smoothing {
+            Smoothing::OnePole(seconds) if self.hop_seconds > 0.0 && seconds > 0.0 => {
+                math::expf(-self.hop_seconds / seconds)
+            }
+            _ => 0.0,
+        };
+    }
+}
+
+/// Settled at 0.
+impl Default for SmoothedParam {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+/// The glide in progress. The smoothing and the hop duration are configuration, so they aren't
+/// part of the state.
+impl StateValue for SmoothedParam {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.value);
+        state.write(&self.target);
+        state.write(&self.step);
+        state.write(&self.remaining);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.value)?;
+        state.read(&mut self.target)?;
+        state.read(&mut self.step)?;
+        state.read(&mut self.remaining)
+    }
+}
@@ -0,0 +1,407 @@
+//! Snapshots of the runtime state of processors and graphs.
+//!
+//! [`FftGraph::save_state`](crate::graph::FftGraph::save_state) captures everything a graph has
+//! accumulated while processing (its ring buffers, every processor's envelopes, histories and
+//! phases), and [`FftGraph::load_state`](crate::graph::FftGraph::load_state) puts it back, so a
+//! host can implement undo, A/B comparisons or session restore:
+//!
+//! ```ignore
+//! let a = fft_graph.save_state();
+//! // ... process with other settings ...
+//! fft_graph.load_state(&a)?;
+//! std::fs::write("session.state", a.as_bytes())?;
+//! ```
+//!
+//! Processors take part through [`FftProcessor::save_state`] and [`FftProcessor::load_state`],
+//! usually implemented with [`processor_state!`](crate::processor_state) from the fields holding
+//! their state. Parameters and anything derived from them, such as filter curves, aren't part of
+//! the state. Every value is written as little-endian bytes with [`StateValue`], so a snapshot can
+//! only be loaded into a graph built the same way as the one it was taken from.
+//!
+//! [`FftProcessor::save_state`]: crate::processor::FftProcessor::save_state
+//! [`FftProcessor::load_state`]: crate::processor::FftProcessor::load_state
+
+use alloc::{boxed::Box, collections::VecDeque, format, string::String, vec::Vec};
+
+use crate::{FftError, core::Complex32};
+
+/// A snapshot of the runtime state of an [`FftGraph`](crate::graph::FftGraph), see
+/// [`FftGraph::save_state`](crate::graph::FftGraph::save_state).
+///
+/// The bytes can be stored and turned back into a snapshot with [`from_bytes`](Self::from_bytes),
+/// e.g. to restore a session. They're only meant to be loaded by the same version of this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphState {
+    bytes: Vec<u8>,
+}
+
+impl GraphState {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Appends state to a snapshot.
+#[derive(Debug, Default, Clone)]
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn write<T: StateValue + ?Sized>(&mut self, value: &T) {
+        value.save(self);
+    }
+
+    /// Appends `bytes` as they are, without their length.
+    #[inline]
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Appends `string` like a [`String`].
+    pub fn write_str(&mut self, string: &str) {
+        self.write_len(string.len());
+        self.write_bytes(string.as_bytes());
+    }
+
+    /// Appends the length of a sequence, for [`StateReader::read_len`].
+    #[inline]
+    pub fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64));
+    }
+
+    /// Returns the number of bytes written so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads state back from a snapshot, in the order it was written.
+#[derive(Debug, Clone)]
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    #[inline]
+    pub fn read<T: StateValue + ?Sized>(&mut self, value: &mut T) -> Result<(), FftError> {
+        value.load(self)
+    }
+
+    /// Reads a value that doesn't need an existing one to load into.
+    #[inline]
+    pub fn read_new<T: StateValue + Default>(&mut self) -> Result<T, FftError> {
+        let mut value = T::default();
+        value.load(self)?;
+        Ok(value)
+    }
+
+    /// Takes the next `len` bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], FftError> {
+        if self.bytes.len() < len {
+            return Err(FftError::InvalidState(format!(
+                "expected {len} more bytes, found {}",
+                self.bytes.len()
+            )));
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    /// Reads the length of a sequence written with [`StateWriter::write_len`].
+    pub fn read_len(&mut self) -> Result<usize, FftError> {
+        let len = self.read_new::<u64>()?;
+        usize::try_from(len)
+            .map_err(|_| FftError::InvalidState(format!("sequence of {len} values is too long")))
+    }
+
+    /// Returns the number of bytes left to read.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// A value that can be saved to and loaded from a snapshot.
+///
+/// Loading overwrites an existing value, so buffers that are already allocated are reused where
+/// they have the right size.
+pub trait StateValue {
+    fn save(&self, state: &mut StateWriter);
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError>;
+}
+
+macro_rules! impl_state_value_le {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl StateValue for $ty {
+                #[inline]
+                fn save(&self, state: &mut StateWriter) {
+                    state.write_bytes(&self.to_le_bytes());
+                }
+
+                #[inline]
+                fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+                    let bytes = state.read_bytes(size_of::<$ty>())?;
+                    // `read_bytes` returned exactly as many bytes as asked for
+                    *self = <$ty>::from_le_bytes(bytes.try_into().unwrap_or_default());
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_state_value_le!(u8, u32, u64, i64, f32, f64);
+
+/// Written as a `u64`.
+impl StateValue for usize {
+    fn save(&self, state: &mut StateWriter) {
+        state.write_len(*self);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        *self = state.read_len()?;
+        Ok(())
+    }
+}
+
+impl StateValue for bool {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&u8::from(*self));
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        *self = match state.read_new::<u8>()? {
+            0 => false,
+            1 => true,
+            other => return Err(FftError::InvalidState(format!("invalid bool {other}"))),
+        };
+        Ok(())
+    }
+}
+
+impl StateValue for Complex32 {
+    #[inline]
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.re);
+        state.write(&self.im);
+    }
+
+    #[inline]
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        state.read(&mut self.re)?;
+        state.read(&mut self.im)
+    }
+}
+
+/// Written as UTF-8 bytes after their length.
+impl StateValue for String {
+    fn save(&self, state: &mut StateWriter) {
+        state.write_str(self);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        let len = state.read_len()?;
+        let bytes = state.read_bytes(len)?;
+        let string = core::str::from_utf8(bytes)
+            .map_err(|e| FftError::InvalidState(format!("invalid string: {e}")))?;
+        self.clear();
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+/// Written with its length, which must match when loading.
+impl<T: StateValue> StateValue for [T] {
+    fn save(&self, state: &mut StateWriter) {
+        state.write_len(self.len());
+        for value in self {
+            state.write(value);
+        }
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        let len = state.read_len()?;
+        if len != self.len() {
+            return Err(FftError::InvalidState(format!(
+                "expected {} values, found {len}",
+                self.len()
+            )));
+        }
+        for value in self {
+            state.read(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: StateValue, const N: usize> StateValue for [T; N] {
+    fn save(&self, state: &mut StateWriter) {
+        self.as_slice().save(state);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        self.as_mut_slice().load(state)
+    }
+}
+
+/// Resized to the saved length when loading.
+impl<T: StateValue + Default> StateValue for Vec<T> {
+    fn save(&self, state: &mut StateWriter) {
+        self.as_slice().save(state);
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        let len = state.read_len()?;
+        if len > state.remaining() {
+            // every value takes at least a byte
+            return Err(FftError::InvalidState(format!(
+                "sequence of {len} values is longer than the state"
+            )));
+        }
+        self.resize_with(len, T::default);
+        for value in self {
+            state.read(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resized to the saved length when loading.
+impl<T: StateValue + Default> StateValue for VecDeque<T> {
+    fn save(&self, state: &mut StateWriter) {
+        state.write_len(self.len());
+        for value in self {
+            state.write(value);
+        }
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        let len = state.read_len()?;
+        if len > state.remaining() {
+            return Err(FftError::InvalidState(format!(
+                "sequence of {len} values is longer than the state"
+            )));
+        }
+        self.resize_with(len, T::default);
+        for value in self {
+            state.read(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: StateValue + Default> StateValue for Option<T> {
+    fn save(&self, state: &mut StateWriter) {
+        state.write(&self.is_some());
+        if let Some(value) = self {
+            state.write(value);
+        }
+    }
+
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        if state.read_new::<bool>()? {
+            state.read(self.get_or_insert_with(T::default))
+        } else {
+            *self = None;
+            Ok(())
+        }
+    }
+}
+
+impl<T: StateValue + ?Sized> StateValue for Box<T> {
+    #[inline]
+    fn save(&self, state: &mut StateWriter) {
+        (**self).save(state);
+    }
+
+    #[inline]
+    fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        (**self).load(state)
+    }
+}
+
+macro_rules! impl_state_value_tuple {
+    ($(($($name:ident: $index:tt),*)),* $(,)?) => {
+        $(
+            impl<$($name: StateValue),*> StateValue for ($($name,)*) {
+                fn save(&self, state: &mut StateWriter) {
+                    $(state.write(&self.$index);)*
+                }
+
+                fn load(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+                    $(state.read(&mut self.$index)?;)*
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_state_value_tuple!((A: 0, B: 1), (A: 0, B: 1, C: 2));
+
+/// Implements [`FftProcessor::save_state`] and [`FftProcessor::load_state`] inside an
+/// `impl FftProcessor` block, from the fields holding the processor's state, which must all
+/// implement [`StateValue`]:
+///
+/// ```ignore
+/// impl<F: Fft> FftProcessor for Envelope<F> {
+///     processor_state!(level, smoothed_gain);
+///
+///     // ...
+/// }
+/// ```
+///
+/// [`FftProcessor::save_state`]: crate::processor::FftProcessor::save_state
+/// [`FftProcessor::load_state`]: crate::processor::FftProcessor::load_state
+#[macro_export]
+macro_rules! processor_state {
+    ($($field:ident),* $(,)?) => {
+        fn save_state(&self, state: &mut $crate::state::StateWriter) {
+            $(state.write(&self.$field);)*
+        }
+
+        fn load_state(
+            &mut self,
+            state: &mut $crate::state::StateReader<'_>,
+        ) -> ::core::result::Result<(), $crate::FftError> {
+            $(state.read(&mut self.$field)?;)*
+            Ok(())
+        }
+    };
+}
@@ -0,0 +1,227 @@
+//! Nesting [`FftGraph`]s inside each other.
+//!
+//! An [`FftSubgraph`] packages a graph of frame processors as a single processor, so reusable
+//! spectral macros (e.g. a denoising chain) can be built once and instantiated in any number of
+//! enclosing graphs with the same FFT length. The inner graph exchanges spectra with the enclosing
+//! graph through its frame inputs and outputs
+//! ([`FftGraph::add_frame_input`]/[`FftGraph::add_frame_output`]) rather than audio, and runs one
+//! hop for every frame of the enclosing graph, following its sample rate, hop length and frame
+//! index.
+
+use std::marker::PhantomData;
+
+use raug::prelude::*;
+
+use crate::{
+    FftError,
+    graph::FftGraph,
+    processor::{FftProcessor, FrameInfo},
+    processor_clone,
+    signal::{Complex32, Fft},
+    state::{StateReader, StateWriter},
+};
+
+/// An [`FftGraph`] used as a processor of another graph.
+///
+/// The processor has one spectral input per frame input of the inner graph and one spectral output
+/// per frame output, in the order they were added. Its latency is the largest latency of the inner
+/// graph's processors along any path to a frame output. Audio inputs and outputs of the inner
+/// graph are not processed.
+pub struct FftSubgraph<F: Fft> {
+    graph: FftGraph<F>,
+    name: String,
+    frame: FrameInfo,
+}
+
+impl<F: Fft> FftSubgraph<F> {
+    pub fn new(graph: FftGraph<F>) -> Self {
+        Self::with_name(graph, "FftSubgraph")
+    }
+
+    /// Creates a subgraph reported under `name`, e.g. in error messages and profiles.
+    pub fn with_name(graph: FftGraph<F>, name: impl Into<String>) -> Self {
+        Self {
+            graph,
+            name: name.into(),
+            frame: FrameInfo::default(),
+        }
+    }
+
+    pub fn graph(&self) -> &FftGraph<F> {
+        &self.graph
+    }
+
+    pub fn graph_mut(&mut self) -> &mut FftGraph<F> {
+        &mut self.graph
+    }
+
+    pub fn into_inner(self) -> FftGraph<F> {
+        self.graph
+    }
+}
+
+impl<F: Fft> FftProcessor for FftSubgraph<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        (0..self.graph.frame_inputs().len())
+            .map(|i| SignalSpec::new(format!("input{i}"), F::RealFft::signal_type()))
+            .collect()
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        (0..self.graph.frame_outputs().len())
+            .map(|i| SignalSpec::new(format!("output{i}"), F::RealFft::signal_type()))
+            .collect()
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        (0..self.graph.frame_outputs().len())
+            .map(|_| AnyBuffer::zeros::<F::RealFft>(size))
+            .collect()
+    }
+
+    fn latency_frames(&self) -> usize {
+        self.graph.latency_frames()
+    }
+
+    fn allocate(&mut self, sample_rate: f32) {
+        // the inner graph only ever processes single frames
+        self.graph.allocate(sample_rate, 1);
+    }
+
+    fn resize_buffers(&mut self, sample_rate: f32) {
+        self.graph.resize_buffers(sample_rate, 1);
+    }
+
+    fn set_graph_lookahead(&mut self, frames: usize) {
+        self.graph.set_lookahead_frames(frames);
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame = *info;
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        self.graph.write_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<'_>) -> Result<(), FftError> {
+        self.graph.read_state(state)
+    }
+
+    fn clone_boxed(&self) -> Option<Box<dyn FftProcessor>> {
+        let graph = self.graph.clone_template().ok()?;
+        Some(Box::new(Self::with_name(graph, self.name.clone())))
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        let num_inputs = self.graph.frame_inputs().len();
+        let num_frames = (0..num_inputs)
+            .filter_map(|port| inputs.input_as::<F::RealFft>(port))
+            .map(|spectra| spectra.len())
+            .max()
+            .unwrap_or(1);
+
+        for i in 0..num_frames {
+            for port in 0..num_inputs {
+                let spectrum = inputs
+                    .input_as::<F::RealFft>(port)
+                    .and_then(|spectra| spectra.get(i));
+                if let Some(target) = self.graph.frame_input_mut(port) {
+                    match spectrum {
+                        Some(spectrum) => target.copy_from_slice(spectrum),
+                        None => target.fill(Complex32::ZERO),
+                    }
+                }
+            }
+
+            self.graph.process_subgraph_hop(&self.frame)?;
+
+            for port in 0..self.graph.frame_outputs().len() {
+                if let Some(spectrum) = self.graph.frame_output(port) {
+                    outputs.set_output_as::<F::RealFft>(port, i, spectrum)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The nodes carrying spectra into and out of a nested graph.
+///
+/// A frame input has no inputs, and its output is written by the [`FftSubgraph`]. A frame output
+/// passes its input through, so the spectrum can be read from its output.
+#[derive(Clone)]
+pub(crate) struct FramePort<F: Fft> {
+    is_output: bool,
+    _f: PhantomData<F>,
+}
+
+impl<F: Fft> FramePort<F> {
+    pub(crate) fn input() -> Self {
+        Self {
+            is_output: false,
+            _f: PhantomData,
+        }
+    }
+
+    pub(crate) fn output() -> Self {
+        Self {
+            is_output: true,
+            _f: PhantomData,
+        }
+    }
+}
+
+impl<F: Fft> FftProcessor for FramePort<F> {
+    processor_clone!();
+
+    fn name(&self) -> &str {
+        if self.is_output {
+            "FrameOutput"
+        } else {
+            "FrameInput"
+        }
+    }
+
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        if self.is_output {
+            vec![SignalSpec::new("input", F::RealFft::signal_type())]
+        } else {
+            vec![]
+        }
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        vec![SignalSpec::new("output", F::RealFft::signal_type())]
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        vec![AnyBuffer::zeros::<F::RealFft>(size)]
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        if !self.is_output {
+            return Ok(());
+        }
+
+        let input = inputs.input_as::<F::RealFft>(0).unwrap();
+        for (i, input) in input.iter().enumerate() {
+            outputs.set_output_as::<F::RealFft>(0, i, input)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+//! Golden-file regression checks of the builtin processors.
+//!
+//! A [`GoldenCase`] renders a builtin over a [`Fixture`] signal and reduces the output to an
+//! averaged magnitude spectrum, which [`GoldenCase::check`] compares against a golden file. A
+//! missing golden file is an error, so a case can't pass without stored data. Setting the
+//! [`BLESS_ENV`] environment variable writes the golden files instead, for new cases or after an
+//! intended change in output.
+//!
+//! Golden files are plain text: a `#` header line describing the case, followed by one magnitude
+//! in dB per line.
+
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use super::white_noise;
+use crate::{
+    FftError, WindowFunction,
+    core::{FrameTransform, RealFftTransform},
+    graph::dyn_graph,
+    registry::ProcessorParams,
+};
+
+/// Setting this environment variable makes [`GoldenCase::check`] write the golden files instead
+/// of comparing against them.
+pub const BLESS_ENV: &str = "RAUG_FFT_BLESS";
+
+pub const SAMPLE_RATE: f32 = 48_000.0;
+/// The length of the rendered fixtures, in samples.
+pub const FIXTURE_LENGTH: usize = 1 << 15;
+/// The FFT length of the spectra stored in golden files.
+pub const ANALYSIS_LENGTH: usize = 1024;
+/// Magnitudes are clamped to this level, so differences in numerical noise are ignored.
+pub const NOISE_FLOOR_DB: f32 = -100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixture {
+    /// A 1 kHz sine at -6 dBFS.
+    Sine,
+    /// An exponential sweep from 20 Hz to 20 kHz.
+    Sweep,
+    /// Deterministic white noise.
+    Noise,
+    /// A single unit impulse in the middle of the signal.
+    Impulse,
+}
+
+impl Fixture {
+    pub const ALL: [Fixture; 4] = [Self::Sine, Self::Sweep, Self::Noise, Self::Impulse];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sine => "sine",
+            Self::Sweep => "sweep",
+            Self::Noise => "noise",
+            Self::Impulse => "impulse",
+        }
+    }
+
+    pub fn generate(&self) -> Vec<f32> {
+        let tau = core::f32::consts::TAU;
+        match self {
+            Self::Sine => (0..FIXTURE_LENGTH)
+                .map(|i| 0.5 * (tau * 1000.0 * i as f32 / SAMPLE_RATE).sin())
+                .collect(),
+            Self::Sweep => {
+                let duration = FIXTURE_LENGTH as f32 / SAMPLE_RATE;
+                let (start, end) = (20.0_f32, 20_000.0_f32);
+                let rate = (end / start).ln() / duration;
+                (0..FIXTURE_LENGTH)
+                    .map(|i| {
+                        let t = i as f32 / SAMPLE_RATE;
+                        let phase = tau * start * ((rate * t).exp() - 1.0) / rate;
+                        0.5 * phase.sin()
+                    })
+                    .collect()
+            }
+            Self::Noise => white_noise(FIXTURE_LENGTH)
+                .into_iter()
+                .map(|x| 0.5 * x)
+                .collect(),
+            Self::Impulse => {
+                let mut signal = vec![0.0; FIXTURE_LENGTH];
+                signal[FIXTURE_LENGTH / 2] = 1.0;
+                signal
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GoldenError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Fft(#[from] FftError),
+    #[error("unsupported FFT length {0}")]
+    UnsupportedFftLength(usize),
+    #[error("unknown processor type {0:?}")]
+    UnknownProcessor(String),
+    #[error("processing failed: {0}")]
+    Processing(String),
+    #[error("golden file {} is missing, set {BLESS_ENV} to write it", .0.display())]
+    Missing(PathBuf),
+    #[error("invalid golden file, line {line}: {text:?}")]
+    Parse { line: usize, text: String },
+    #[error("golden file has {expected} bins, but the spectrum has {found}")]
+    LengthMismatch { expected: usize, found: usize },
+    #[error(
+        "bin {bin} ({frequency:.1} Hz) is {found:.2} dB, expected {expected:.2} dB (tolerance {tolerance_db} dB)"
+    )]
+    Mismatch {
+        bin: usize,
+        frequency: f32,
+        expected: f32,
+        found: f32,
+        tolerance_db: f32,
+    },
+}
+
+/// A builtin processor rendered over a fixture signal, with the input connected to the
+/// processor's first input and its first output connected to the graph output.
+#[derive(Debug, Clone)]
+pub struct GoldenCase {
+    pub processor: String,
+    pub params: ProcessorParams,
+    pub fixture: Fixture,
+    pub fft_length: usize,
+    pub hop_length: usize,
+    pub window_fn: WindowFunction,
+}
+
+impl GoldenCase {
+    /// Creates a case with default parameters, an FFT length of 1024 and a Hann window at 75%
+    /// overlap.
+    pub fn new(processor: impl Into<String>, fixture: Fixture) -> Self {
+        Self {
+            processor: processor.into(),
+            params: ProcessorParams::new(),
+            fixture,
+            fft_length: 1024,
+            hop_length: 256,
+            window_fn: WindowFunction::Hann,
+        }
+    }
+
+    pub fn with_params(mut self, params: ProcessorParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_stft(
+        mut self,
+        fft_length: usize,
+        hop_length: usize,
+        window_fn: WindowFunction,
+    ) -> Self {
+        self.fft_length = fft_length;
+        self.hop_length = hop_length;
+        self.window_fn = window_fn;
+        self
+    }
+
+    /// Returns the name of the golden file, which is unique per processor, fixture, parameters
+    /// and STFT settings.
+    pub fn file_name(&self) -> String {
+        let mut name = format!("{}-{}", self.processor, self.fixture.name());
+        for (param, value) in self.params.iter() {
+            let _ = write!(name, "-{param}={value}");
+        }
+        let _ = write!(
+            name,
+            "-{}-{}-{}.txt",
+            self.fft_length,
+            self.hop_length,
+            self.window_fn.name()
+        );
+        name
+    }
+
+    /// Renders the fixture through the processor.
+    pub fn render(&self) -> Result<Vec<f32>, GoldenError> {
+        let mut graph =
+            dyn_graph(self.fft_length, self.hop_length, self.window_fn).map_err(|e| match e {
+                FftError::UnsupportedFftLength(length) => GoldenError::UnsupportedFftLength(length),
+                e => e.into(),
+            })?;
+        let input = graph.add_audio_input();
+        let processor = graph
+            .add_builtin(&self.processor, &self.params)
+            .ok_or_else(|| GoldenError::UnknownProcessor(self.processor.clone()))?;
+        let output = graph.add_audio_output();
+        graph.connect(input, 0, processor, 0)?;
+        graph.connect(processor, 0, output, 0)?;
+
+        let fixture = self.fixture.generate();
+        let mut rendered = graph
+            .render(&[&fixture], SAMPLE_RATE, self.hop_length)
+            .map_err(|e| GoldenError::Processing(e.to_string()))?;
+        Ok(rendered.pop().unwrap_or_default())
+    }
+
+    /// Renders the fixture and returns the averaged magnitude spectrum of the output.
+    pub fn spectrum(&self) -> Result<Vec<f32>, GoldenError> {
+        magnitude_spectrum(&self.render()?)
+    }
+
+    /// Compares the spectrum with the golden file in `dir`, allowing each bin to differ by up to
+    /// `tolerance_db`.
+    ///
+    /// The golden file is written instead if [`BLESS_ENV`] is set, and [`GoldenError::Missing`]
+    /// is returned if it isn't and the file doesn't exist.
+    pub fn check(&self, dir: &Path, tolerance_db: f32) -> Result<(), GoldenError> {
+        let spectrum = self.spectrum()?;
+        let path = dir.join(self.file_name());
+
+        if std::env::var_os(BLESS_ENV).is_some() {
+            fs::create_dir_all(dir)?;
+            fs::write(&path, self.to_golden(&spectrum))?;
+            return Ok(());
+        }
+        if !path.exists() {
+            return Err(GoldenError::Missing(path));
+        }
+
+        let golden = parse_golden(&fs::read_to_string(&path)?)?;
+        compare_spectra(&golden, &spectrum, tolerance_db)
+    }
+
+    fn to_golden(&self, spectrum: &[f32]) -> String {
+        let mut text = format!(
+            "# {} {} {:?} fft_length={} hop_length={} window={}\n",
+            self.processor,
+            self.fixture.name(),
+            self.params,
+            self.fft_length,
+            self.hop_length,
+            self.window_fn.name(),
+        );
+        for magnitude in spectrum {
+            let _ = writeln!(text, "{magnitude:.3}");
+        }
+        text
+    }
+}
+
+/// Returns the magnitude spectrum of `signal` in dB, averaged over Hann-windowed frames of
+/// [`ANALYSIS_LENGTH`] samples at 50% overlap and clamped to [`NOISE_FLOOR_DB`].
+pub fn magnitude_spectrum(signal: &[f32]) -> Result<Vec<f32>, GoldenError> {
+    let window = WindowFunction::Hann.cached(ANALYSIS_LENGTH);
+    let mut transform = RealFftTransform::new(ANALYSIS_LENGTH);
+    let mut frame = vec![0.0; ANALYSIS_LENGTH];
+    let mut spectrum = vec![Default::default(); ANALYSIS_LENGTH / 2 + 1];
+    let mut power = vec![0.0_f32; ANALYSIS_LENGTH / 2 + 1];
+
+    let mut frames = 0;
+    for start in (0..signal.len().saturating_sub(ANALYSIS_LENGTH - 1)).step_by(ANALYSIS_LENGTH / 2)
+    {
+        for ((out, sample), w) in frame
+            .iter_mut()
+            .zip(&signal[start..start + ANALYSIS_LENGTH])
+            .zip(window.iter())
+        {
+            *out = sample * w;
+        }
+        transform.forward(&mut frame, &mut spectrum)?;
+        for (power, bin) in power.iter_mut().zip(&spectrum) {
+            *power += bin.norm_sqr();
+        }
+        frames += 1;
+    }
+
+    let scale = 1.0 / (frames.max(1) as f32 * ANALYSIS_LENGTH as f32);
+    Ok(power
+        .into_iter()
+        .map(|power| (10.0 * (power * scale).log10()).max(NOISE_FLOOR_DB))
+        .collect())
+}
+
+/// Compares two spectra from [`magnitude_spectrum`] bin by bin.
+pub fn compare_spectra(
+    expected: &[f32],
+    found: &[f32],
+    tolerance_db: f32,
+) -> Result<(), GoldenError> {
+    if expected.len() != found.len() {
+        return Err(GoldenError::LengthMismatch {
+            expected: expected.len(),
+            found: found.len(),
+        });
+    }
+
+    for (bin, (&expected, &found)) in expected.iter().zip(found).enumerate() {
+        if (expected - found).abs() > tolerance_db {
+            return Err(GoldenError::Mismatch {
+                bin,
+                frequency: bin as f32 * SAMPLE_RATE / ANALYSIS_LENGTH as f32,
+                expected,
+                found,
+                tolerance_db,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn parse_golden(text: &str) -> Result<Vec<f32>, GoldenError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|(index, line)| {
+            line.trim().parse().map_err(|_| GoldenError::Parse {
+                line: index + 1,
+                text: line.to_string(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,230 @@
+//! Helpers for checking that an [`FftGraph`] reconstructs its input.
+//!
+//! A processor that passes its input through unchanged must not disturb the perfect
+//! reconstruction of the STFT. [`assert_passthrough`] checks this for a processor at every window
+//! and hop length the engine reconstructs with, and [`assert_identity_reconstruction`] checks the
+//! engine itself at every FFT length. [`assert_block_size_independent`] checks that a graph's
+//! output doesn't depend on how the host splits its input into blocks. The [`golden`] module
+//! checks the output of the builtins against stored spectra.
+
+pub mod golden;
+
+use raug::prelude::ProcResult;
+
+use crate::{
+    WindowFunction,
+    graph::{DynFftGraph, FftGraph, dyn_graph},
+    processor::FftProcessor,
+    signal::Fft,
+};
+
+/// The windows that reconstruct, i.e. those with a [`WindowFunction::min_overlap`].
+pub const RECONSTRUCTING_WINDOWS: [WindowFunction; 5] = [
+    WindowFunction::Rectangular,
+    WindowFunction::Hann,
+    WindowFunction::Hamming,
+    WindowFunction::Blackman,
+    WindowFunction::Nuttall,
+];
+
+/// The FFT lengths of the builtin [`Fft`] sizes.
+pub const FFT_LENGTHS: [usize; 8] = [64, 128, 256, 512, 1024, 2048, 4096, 8192];
+
+/// The host block sizes [`assert_block_size_independent`] compares: single samples, sizes that
+/// don't divide any hop length, powers of two and a size larger than every FFT length.
+pub const TEST_BLOCK_SIZES: [usize; 6] = [1, 7, 64, 100, 4096, 10_000];
+
+/// The largest sample error tolerated by the `assert_*` functions.
+///
+/// The windows are symmetric rather than periodic, so reconstruction is only exact up to an
+/// error that shrinks with the FFT length; this bound covers the smallest supported length.
+pub const DEFAULT_TOLERANCE: f32 = 1e-3;
+
+const SAMPLE_RATE: f32 = 48_000.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ReconstructionError {
+    pub max: f32,
+    pub rms: f32,
+}
+
+/// Returns the hop lengths to test for `window_fn`: the largest that reconstructs, and half of it.
+pub fn test_hop_lengths(fft_length: usize, window_fn: WindowFunction) -> Vec<usize> {
+    let Some(min_overlap) = window_fn.min_overlap() else {
+        return Vec::new();
+    };
+    let hop_length = fft_length / min_overlap;
+    [hop_length, hop_length / 2]
+        .into_iter()
+        .filter(|&hop_length| hop_length > 0)
+        .collect()
+}
+
+/// Generates a deterministic test signal of noise mixed with a few sines.
+pub fn test_signal(length: usize) -> Vec<f32> {
+    white_noise(length)
+        .into_iter()
+        .enumerate()
+        .map(|(i, noise)| {
+            let t = i as f32 / SAMPLE_RATE;
+            let sines = [110.0, 1234.5, 9876.0]
+                .iter()
+                .map(|hz| (core::f32::consts::TAU * hz * t).sin())
+                .sum::<f32>();
+            0.25 * noise + 0.2 * sines
+        })
+        .collect()
+}
+
+/// Generates deterministic white noise in `[-1, 1]`.
+pub(crate) fn white_noise(length: usize) -> Vec<f32> {
+    let mut state = 0x2545_f491_u32;
+    (0..length)
+        .map(|_| {
+            // xorshift
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 * 2.0 - 1.0
+        })
+        .collect()
+}
+
+/// Renders a test signal through the first audio input and output of `graph` and compares the
+/// output with the input.
+///
+/// The first and last `fft_length` samples are ignored, since fewer frames overlap there.
+pub fn reconstruction_error(graph: &mut dyn DynFftGraph) -> ProcResult<ReconstructionError> {
+    let fft_length = graph.fft_length();
+    let input = test_signal(fft_length * 16);
+    let output = graph.render(&[&input], SAMPLE_RATE, graph.hop_length())?;
+    let output = output.first().map(Vec::as_slice).unwrap_or(&[]);
+
+    let range = fft_length..input.len() - fft_length;
+    let mut error = ReconstructionError::default();
+    let mut sum_sq = 0.0;
+    for i in range.clone() {
+        let diff = (output.get(i).copied().unwrap_or(0.0) - input[i]).abs();
+        error.max = error.max.max(diff);
+        sum_sq += diff * diff;
+    }
+    error.rms = (sum_sq / range.len() as f32).sqrt();
+    Ok(error)
+}
+
+/// Asserts that an `audio input -> processor -> audio output` graph reconstructs its input within
+/// `tolerance`, for every reconstructing window and hop length at `F`'s FFT length.
+///
+/// `make_processor` is called once per configuration. Only the processor's first input and
+/// output are connected, so it should be configured to pass its input through unchanged.
+///
+/// # Panics
+///
+/// Panics with the offending configuration if the error exceeds `tolerance` or processing fails.
+pub fn assert_passthrough<F: Fft, P: FftProcessor>(make_processor: impl Fn() -> P, tolerance: f32) {
+    for window_fn in RECONSTRUCTING_WINDOWS {
+        for hop_length in test_hop_lengths(F::N_FFT, window_fn) {
+            let mut graph = FftGraph::<F>::new(hop_length, window_fn);
+            let input = graph.add_audio_input();
+            let processor = graph.add_processor(make_processor());
+            let output = graph.add_audio_output();
+            DynFftGraph::connect(&mut graph, input, 0, processor, 0).unwrap();
+            DynFftGraph::connect(&mut graph, processor, 0, output, 0).unwrap();
+
+            check(&mut graph, window_fn, tolerance);
+        }
+    }
+}
+
+/// Asserts that an `audio input -> audio output` graph reconstructs its input within `tolerance`
+/// for every FFT length, reconstructing window and hop length.
+///
+/// # Panics
+///
+/// Panics with the offending configuration if the error exceeds `tolerance` or processing fails.
+pub fn assert_identity_reconstruction(tolerance: f32) {
+    for fft_length in FFT_LENGTHS {
+        for window_fn in RECONSTRUCTING_WINDOWS {
+            for hop_length in test_hop_lengths(fft_length, window_fn) {
+                let mut graph = dyn_graph(fft_length, hop_length, window_fn).unwrap();
+                let input = graph.add_audio_input();
+                let output = graph.add_audio_output();
+                graph.connect(input, 0, output, 0).unwrap();
+
+                check(&mut *graph, window_fn, tolerance);
+            }
+        }
+    }
+}
+
+fn check(graph: &mut dyn DynFftGraph, window_fn: WindowFunction, tolerance: f32) {
+    let (fft_length, hop_length) = (graph.fft_length(), graph.hop_length());
+    let error = reconstruction_error(graph).unwrap_or_else(|e| {
+        panic!("processing failed (fft_length {fft_length}, hop_length {hop_length}, {window_fn:?}): {e}")
+    });
+    assert!(
+        error.max <= tolerance,
+        "reconstruction error {error:?} exceeds {tolerance} \
+         (fft_length {fft_length}, hop_length {hop_length}, {window_fn:?})"
+    );
+}
+
+/// Processes `input` through every audio input of `graph` with
+/// [`process_block`](DynFftGraph::process_block) in blocks of `block_size` samples, returning one
+/// signal per audio output.
+pub fn process_in_blocks(
+    graph: &mut dyn DynFftGraph,
+    input: &[f32],
+    block_size: usize,
+) -> ProcResult<Vec<Vec<f32>>> {
+    graph.allocate(SAMPLE_RATE, block_size);
+    let mut outputs = vec![vec![0.0; input.len()]; graph.num_audio_outputs()];
+    for start in (0..input.len()).step_by(block_size.max(1)) {
+        let end = (start + block_size).min(input.len());
+        let inputs = vec![&input[start..end]; graph.num_audio_inputs()];
+        let mut blocks: Vec<&mut [f32]> = outputs
+            .iter_mut()
+            .map(|output| &mut output[start..end])
+            .collect();
+        graph.process_block(&inputs, &mut blocks)?;
+    }
+    Ok(outputs)
+}
+
+/// Asserts that `graph` produces exactly the same output whatever size the host's blocks are,
+/// processing a test signal with [`process_in_blocks`] for each of the [`TEST_BLOCK_SIZES`].
+///
+/// The graph is reallocated for every block size, so its processors must reset their state in
+/// [`allocate`](FftProcessor::allocate).
+///
+/// # Panics
+///
+/// Panics with the first differing sample if the outputs differ or processing fails.
+pub fn assert_block_size_independent(graph: &mut dyn DynFftGraph) {
+    let (fft_length, hop_length) = (graph.fft_length(), graph.hop_length());
+    let input = test_signal(fft_length * 16);
+    let mut process = |block_size| {
+        process_in_blocks(graph, &input, block_size).unwrap_or_else(|e| {
+            panic!(
+                "processing failed (fft_length {fft_length}, hop_length {hop_length}, \
+                 block_size {block_size}): {e}"
+            )
+        })
+    };
+
+    let [first_size, sizes @ ..] = TEST_BLOCK_SIZES;
+    let expected = process(first_size);
+    for block_size in sizes {
+        let outputs = process(block_size);
+        for (channel, (expected, output)) in expected.iter().zip(&outputs).enumerate() {
+            if let Some(index) = expected.iter().zip(output).position(|(a, b)| a != b) {
+                panic!(
+                    "output {channel} differs at sample {index} between blocks of {first_size} \
+                     and {block_size} samples: {} != {} (fft_length {fft_length}, hop_length \
+                     {hop_length})",
+                    expected[index], output[index],
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Converting between linear magnitudes or powers and decibels, and between complex bins and
+//! polar form, for single values or whole slices.
+//!
+//! Levels are clamped at [`MIN_DB`] on the way to decibels, so silence (and anything that isn't a
+//! valid level, like NaN) gives a finite number instead of -inf, which would otherwise poison
+//! smoothing and averaging downstream. The slice versions are plain loops without branches over
+//! slices of equal length, which the compiler can unroll and vectorize; they convert
+//! `min(input.len(), out.len())` values.
+
+use crate::core::{Complex32, math};
+
+/// The lowest level the conversions to decibels return, standing in for silence.
+pub const MIN_DB: f32 = -200.0;
+
+/// `ln(10) / 20`, the factor from decibels to the natural logarithm of a magnitude.
+const DB_TO_LN_MAGNITUDE: f32 = core::f32::consts::LN_10 / 20.0;
+
+/// Converts a linear magnitude (or gain) to decibels, at least [`MIN_DB`].
+#[inline]
+pub fn magnitude_to_db(magnitude: f32) -> f32 {
+    (20.0 * math::log10f(magnitude)).max(MIN_DB)
+}
+
+/// Converts decibels to a linear magnitude (or gain).
+#[inline]
+pub fn db_to_magnitude(db: f32) -> f32 {
+    math::expf(db * DB_TO_LN_MAGNITUDE)
+}
+
+/// Converts a power (a squared magnitude) to decibels, at least [`MIN_DB`].
+#[inline]
+pub fn power_to_db(power: f32) -> f32 {
+    (10.0 * math::log10f(power)).max(MIN_DB)
+}
+
+/// Converts decibels to a power (a squared magnitude).
+#[inline]
+pub fn db_to_power(db: f32) -> f32 {
+    math::expf(db * 2.0 * DB_TO_LN_MAGNITUDE)
+}
+
+/// Converts every magnitude of `magnitudes` to decibels with [`magnitude_to_db`].
+pub fn magnitudes_to_db(magnitudes: &[f32], out: &mut [f32]) {
+    for (out, &magnitude) in out.iter_mut().zip(magnitudes) {
+        *out = magnitude_to_db(magnitude);
+    }
+}
+
+/// Converts every level of `db` to a magnitude with [`db_to_magnitude`].
+pub fn db_to_magnitudes(db: &[f32], out: &mut [f32]) {
+    for (out, &db) in out.iter_mut().zip(db) {
+        *out = db_to_magnitude(db);
+    }
+}
+
+/// Converts every power of `powers` to decibels with [`power_to_db`].
+pub fn powers_to_db(powers: &[f32], out: &mut [f32]) {
+    for (out, &power) in out.iter_mut().zip(powers) {
+        *out = power_to_db(power);
+    }
+}
+
+/// Converts every level of `db` to a power with [`db_to_power`].
+pub fn db_to_powers(db: &[f32], out: &mut [f32]) {
+    for (out, &db) in out.iter_mut().zip(db) {
+        *out = db_to_power(db);
+    }
+}
+
+/// Writes the magnitude of every bin of `spectrum` to `out`.
+pub fn magnitudes(spectrum: &[Complex32], out: &mut [f32]) {
+    for (out, bin) in out.iter_mut().zip(spectrum) {
+        *out = bin.norm();
+    }
+}
+
+/// Writes the power (the squared magnitude) of every bin of `spectrum` to `out`.
+pub fn powers(spectrum: &[Complex32], out: &mut [f32]) {
+    for (out, bin) in out.iter_mut().zip(spectrum) {
+        *out = bin.norm_sqr();
+    }
+}
+
+/// Splits every bin of `spectrum` into its magnitude and phase (in radians, in (-π, π]).
+pub fn to_polar(spectrum: &[Complex32], magnitudes: &mut [f32], phases: &mut [f32]) {
+    for ((magnitude, phase), bin) in magnitudes.iter_mut().zip(phases).zip(spectrum) {
+        *magnitude = bin.norm();
+        *phase = bin.arg();
+    }
+}
+
+/// Builds every bin of `out` from a magnitude and a phase (in radians).
+pub fn from_polar(magnitudes: &[f32], phases: &[f32], out: &mut [Complex32]) {
+    for ((out, &magnitude), &phase) in out.iter_mut().zip(magnitudes).zip(phases) {
+        *out = Complex32::from_polar(magnitude, phase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!(
+            (a - b).abs() <= tolerance,
+            "{a} != {b} (tolerance {tolerance})"
+        );
+    }
+
+    #[test]
+    fn converts_known_levels() {
+        assert_close(magnitude_to_db(1.0), 0.0, 1e-6);
+        assert_close(magnitude_to_db(10.0), 20.0, 1e-5);
+        assert_close(magnitude_to_db(0.5), -6.0206, 1e-4);
+        assert_close(power_to_db(10.0), 10.0, 1e-5);
+        assert_close(power_to_db(0.5), -3.0103, 1e-4);
+        assert_close(db_to_magnitude(-20.0), 0.1, 1e-7);
+        assert_close(db_to_power(-20.0), 0.01, 1e-8);
+    }
+
+    #[test]
+    fn round_trips_levels() {
+        for db in [-120.0, -60.0, -6.0, 0.0, 3.0, 24.0] {
+            assert_close(magnitude_to_db(db_to_magnitude(db)), db, 1e-3);
+            assert_close(power_to_db(db_to_power(db)), db, 1e-3);
+        }
+        for magnitude in [1e-5, 0.25, 1.0, 7.0] {
+            assert_close(
+                db_to_magnitude(magnitude_to_db(magnitude)),
+                magnitude,
+                magnitude * 1e-5,
+            );
+        }
+    }
+
+    #[test]
+    fn clamps_silence_and_invalid_levels() {
+        for level in [0.0, -1.0, f32::NAN, f32::MIN_POSITIVE * 1e-10] {
+            assert_eq!(magnitude_to_db(level), MIN_DB);
+            assert_eq!(power_to_db(level), MIN_DB);
+        }
+        assert!(db_to_magnitude(MIN_DB) > 0.0);
+    }
+
+    #[test]
+    fn slices_match_single_values() {
+        let levels = [0.0, 1e-3, 0.5, 1.0, 2.0];
+        let mut db = [0.0; 5];
+        let mut back = [0.0; 5];
+
+        magnitudes_to_db(&levels, &mut db);
+        assert_eq!(db, levels.map(magnitude_to_db));
+        db_to_magnitudes(&db, &mut back);
+        assert_eq!(back, db.map(db_to_magnitude));
+
+        powers_to_db(&levels, &mut db);
+        assert_eq!(db, levels.map(power_to_db));
+        db_to_powers(&db, &mut back);
+        assert_eq!(back, db.map(db_to_power));
+    }
+
+    #[test]
+    fn converts_only_as_many_values_as_both_slices_hold() {
+        let mut out = [1.0; 3];
+        magnitudes_to_db(&[1.0, 1.0], &mut out);
+        assert_eq!(out, [0.0, 0.0, 1.0]);
+
+        let mut short = [1.0; 1];
+        db_to_magnitudes(&[0.0, -20.0], &mut short);
+        assert_eq!(short, [1.0]);
+    }
+
+    #[test]
+    fn round_trips_polar_form() {
+        let spectrum = [
+            Complex32::new(1.0, 0.0),
+            Complex32::new(0.0, -2.0),
+            Complex32::new(-3.0, 4.0),
+            Complex32::new(-1.0, 0.0),
+            Complex32::new(0.0, 0.0),
+        ];
+        let mut magnitudes = [0.0; 5];
+        let mut phases = [0.0; 5];
+        to_polar(&spectrum, &mut magnitudes, &mut phases);
+        assert_eq!(magnitudes, [1.0, 2.0, 5.0, 1.0, 0.0]);
+        assert_close(phases[1], -core::f32::consts::FRAC_PI_2, 1e-6);
+        assert_close(phases[3], core::f32::consts::PI, 1e-6);
+
+        let mut power = [0.0; 5];
+        powers(&spectrum, &mut power);
+        assert_eq!(power, [1.0, 4.0, 25.0, 1.0, 0.0]);
+        let mut norms = [0.0; 5];
+        self::magnitudes(&spectrum, &mut norms);
+        assert_eq!(norms, magnitudes);
+
+        let mut rebuilt = [Complex32::default(); 5];
+        from_polar(&magnitudes, &phases, &mut rebuilt);
+        for (rebuilt, bin) in rebuilt.iter().zip(&spectrum) {
+            assert!((rebuilt - bin).norm() < 1e-5, "{rebuilt} != {bin}");
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Per-voice copies of an [`FftGraph`], the way synth hosts instantiate an effect for every note.
+
+use raug::prelude::*;
+
+use crate::{
+    WindowFunction, builtins::transforms::FftPlans, graph::FftGraph, random::stream_seed,
+    signal::Fft,
+};
+
+/// A fixed number of identically built [`FftGraph`]s ("voices"), each with its own processor
+/// state but all sharing one set of FFT plans.
+///
+/// As a raug processor, the inputs and outputs are those of every voice in turn: with two voices
+/// of a stereo graph, inputs 0 and 1 feed the first voice and inputs 2 and 3 the second.
+pub struct FftVoices<F: Fft> {
+    voices: Vec<FftGraph<F>>,
+    sample_rate: f32,
+    block_size: usize,
+    // per-voice buffers for the raug processor interface
+    silence: Vec<f32>,
+    scratch: Vec<Vec<f32>>,
+}
+
+impl<F: Fft> FftVoices<F> {
+    /// Creates `voices` graphs, calling `build` once for each to add its nodes. `build` must build
+    /// the same inputs and outputs every time.
+    pub fn new(
+        voices: usize,
+        hop_length: usize,
+        window_fn: WindowFunction,
+        mut build: impl FnMut(&mut FftGraph<F>),
+    ) -> Self {
+        let plans = FftPlans::new(F::N_FFT);
+        let voices = (0..voices)
+            .map(|_| {
+                let mut graph = FftGraph::new(hop_length, window_fn);
+                graph.set_plans(plans.clone());
+                build(&mut graph);
+                graph
+            })
+            .collect();
+
+        let mut voices = Self {
+            voices,
+            sample_rate: 0.0,
+            block_size: 0,
+            silence: Vec::new(),
+            scratch: Vec::new(),
+        };
+        voices.set_seed(0);
+        voices
+    }
+
+    /// Seeds the random numbers of every voice from `seed`, giving each voice its own stream,
+    /// see [`FftGraph::set_seed`].
+    pub fn set_seed(&mut self, seed: u64) {
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            voice.set_seed(stream_seed(seed, index as u64));
+        }
+    }
+
+    pub fn num_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn voice(&self, index: usize) -> &FftGraph<F> {
+        &self.voices[index]
+    }
+
+    pub fn voice_mut(&mut self, index: usize) -> &mut FftGraph<F> {
+        &mut self.voices[index]
+    }
+
+    pub fn voices_mut(&mut self) -> impl Iterator<Item = &mut FftGraph<F>> {
+        self.voices.iter_mut()
+    }
+
+    /// Clears the state of one voice, e.g. when it's assigned to a new note.
+    pub fn reset_voice(&mut self, index: usize) {
+        self.voices[index].allocate(self.sample_rate, self.block_size);
+    }
+
+    fn channels(&self) -> (usize, usize) {
+        self.voices.first().map_or((0, 0), |voice| {
+            (voice.num_audio_inputs(), voice.num_audio_outputs())
+        })
+    }
+
+    pub fn allocate(&mut self, sample_rate: f32, block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.block_size = block_size;
+        for voice in self.voices.iter_mut() {
+            voice.allocate(sample_rate, block_size);
+        }
+        let (_, num_outputs) = self.channels();
+        self.silence = vec![0.0; block_size];
+        self.scratch = vec![vec![0.0; block_size]; num_outputs];
+    }
+
+    pub fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.block_size = block_size;
+        for voice in self.voices.iter_mut() {
+            voice.resize_buffers(sample_rate, block_size);
+        }
+    }
+
+    /// Processes a block of audio for every voice, see [`FftGraph::process_block`].
+    ///
+    /// `inputs` and `outputs` hold the channels of every voice in turn.
+    pub fn process_block(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+    ) -> ProcResult<()> {
+        let (num_inputs, num_outputs) = self.channels();
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            let inputs = inputs
+                .get(index * num_inputs..(index + 1) * num_inputs)
+                .unwrap_or(&[]);
+            let outputs = outputs
+                .get_mut(index * num_outputs..(index + 1) * num_outputs)
+                .unwrap_or(&mut []);
+            voice.process_block(inputs, outputs)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: Fft> FftGraph<F> {
+    /// Builds `voices` copies of a graph with shared FFT plans, see [`FftVoices`].
+    pub fn voices(
+        voices: usize,
+        hop_length: usize,
+        window_fn: WindowFunction,
+        build: impl FnMut(&mut FftGraph<F>),
+    ) -> FftVoices<F> {
+        FftVoices::new(voices, hop_length, window_fn, build)
+    }
+}
+
+impl<F: Fft> Processor for FftVoices<F> {
+    fn input_spec(&self) -> Vec<SignalSpec> {
+        let (num_inputs, _) = self.channels();
+        (0..self.voices.len() * num_inputs)
+            .map(|i| SignalSpec::new(i.to_string(), f32::signal_type()))
+            .collect()
+    }
+
+    fn output_spec(&self) -> Vec<SignalSpec> {
+        let (_, num_outputs) = self.channels();
+        (0..self.voices.len() * num_outputs)
+            .map(|i| SignalSpec::new(i.to_string(), f32::signal_type()))
+            .collect()
+    }
+
+    fn create_output_buffers(&self, size: usize) -> Vec<AnyBuffer> {
+        let (_, num_outputs) = self.channels();
+        (0..self.voices.len() * num_outputs)
+            .map(|_| AnyBuffer::zeros::<f32>(size))
+            .collect()
+    }
+
+    fn allocate(&mut self, sample_rate: f32, max_block_size: usize) {
+        FftVoices::allocate(self, sample_rate, max_block_size);
+    }
+
+    fn resize_buffers(&mut self, sample_rate: f32, block_size: usize) {
+        FftVoices::resize_buffers(self, sample_rate, block_size);
+    }
+
+    fn process(
+        &mut self,
+        inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> Result<(), ProcessorError> {
+        let (num_inputs, num_outputs) = self.channels();
+        let block_size = inputs.block_size();
+
+        for (index, voice) in self.voices.iter_mut().enumerate() {
+            for channel in 0..num_inputs {
+                // unconnected inputs are silent
+                let samples = inputs
+                    .input_as::<f32>(index * num_inputs + channel)
+                    .and_then(|samples| samples.get(..block_size))
+                    .unwrap_or(&self.silence[..block_size]);
+                voice.push_input(channel, samples);
+            }
+            // voices of generators only are clocked by the block instead
+            voice.advance_clock(block_size);
+
+            voice.process_frames()?;
+
+            for (channel, scratch) in self.scratch.iter_mut().enumerate() {
+                let scratch = &mut scratch[..block_size];
+                voice.read_output(channel, scratch);
+                for (sample_index, sample) in scratch.iter().enumerate() {
+                    outputs.set_output_as::<f32>(
+                        index * num_outputs + channel,
+                        sample_index,
+                        sample,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! JavaScript bindings for running an [`FftGraph`] inside a web-audio worklet.
+//!
+//! Enable the `wasm` feature and build for `wasm32-unknown-unknown` with `wasm-bindgen`. The crate
+//! doesn't spawn threads, so no extra configuration is needed for single-threaded worklets. The
+//! `wasm-simd` feature additionally enables rustfft's WebAssembly SIMD kernels.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    WindowFunction,
+    graph::{DynFftGraph, FftGraph, dyn_graph},
+    signal::Fft,
+};
+
+/// A single-channel FFT graph processing `Float32Array`s.
+#[wasm_bindgen]
+pub struct WasmFftGraph {
+    graph: Box<dyn DynFftGraph>,
+}
+
+impl WasmFftGraph {
+    /// Wraps an already built single-input, single-output graph.
+    pub fn from_graph<F: Fft>(mut graph: FftGraph<F>, sample_rate: f32, block_size: usize) -> Self {
+        graph.allocate(sample_rate, block_size);
+        Self {
+            graph: Box::new(graph),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl WasmFftGraph {
+    /// Creates a pass-through graph with one audio input connected to one audio output.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        fft_length: usize,
+        hop_length: usize,
+        sample_rate: f32,
+        block_size: usize,
+    ) -> Result<WasmFftGraph, JsError> {
+        let mut graph = dyn_graph(fft_length, hop_length, WindowFunction::Hann)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let input = graph.add_audio_input();
+        let output = graph.add_audio_output();
+        graph.connect(input, 0, output, 0)?;
+        graph.allocate(sample_rate, block_size);
+        Ok(Self { graph })
+    }
+
+    /// Reallocates the graph for a new sample rate or block size.
+    pub fn allocate(&mut self, sample_rate: f32, block_size: usize) {
+        self.graph.allocate(sample_rate, block_size);
+    }
+
+    /// Processes one block of samples from `input` into `output`.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> Result<(), JsError> {
+        self.graph
+            .process_block(&[input], &mut [output])
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn latency_samples(&self) -> usize {
+        self.graph.latency_samples()
+    }
+}
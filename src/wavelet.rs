@@ -0,0 +1,273 @@
+//! Mother wavelets and the building blocks of wavelet transforms.
+//!
+//! The STFT analyzes every frequency with the same window, so it resolves either time or
+//! frequency well, but not both. A wavelet transform scales one mother wavelet instead, so high
+//! frequencies are analyzed with short wavelets that place transients precisely, and low
+//! frequencies with long ones that separate close pitches.
+//!
+//! The continuous transform (CWT) correlates a signal with a [`ContinuousWavelet`] at any set of
+//! scales, usually spaced logarithmically between two frequencies (see [`cwt_frequencies_hz`]).
+//! The wavelets are defined by their frequency responses, normalized to unit gain at the center
+//! frequency of each scale, so the transform is a bank of constant-Q bandpass filters.
+//!
+//! The discrete transform (DWT) splits a signal into a half-rate lowpass approximation and
+//! highpass detail with the orthogonal filters of a [`DiscreteWavelet`] ([`dwt_step`]), then
+//! splits the approximation again, for one octave band per level. [`idwt_step`] undoes a step
+//! exactly, so the decomposition can be processed and resynthesized:
+//!
+//! ```ignore
+//! let mut approximation = vec![0.0; frame.len() / 2];
+//! let mut detail = vec![0.0; frame.len() / 2];
+//! dwt_step(DiscreteWavelet::Daubechies4, &frame, &mut approximation, &mut detail);
+//! idwt_step(DiscreteWavelet::Daubechies4, &approximation, &detail, &mut frame);
+//! ```
+//!
+//! Both transforms treat their input as periodic, like the FFT.
+
+use alloc::vec::Vec;
+
+use crate::core::math;
+
+/// The center frequency of the [`Morlet`](ContinuousWavelet::Morlet) wavelet in radians per unit
+/// of scale, the usual compromise between time and frequency resolution.
+pub const MORLET_OMEGA0: f32 = 6.0;
+
+/// The order of the [`Paul`](ContinuousWavelet::Paul) wavelet.
+pub const PAUL_ORDER: i32 = 4;
+
+/// A mother wavelet of the continuous wavelet transform, see the [module docs](self).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ContinuousWavelet {
+    /// A complex sinusoid under a Gaussian envelope, about 0.6 octaves wide at half power. The
+    /// default, for balanced time and frequency resolution.
+    #[default]
+    Morlet,
+    /// The (real) second derivative of a Gaussian, about 1.2 octaves wide at half power, for
+    /// locating transients and edges.
+    MexicanHat,
+    /// A complex wavelet with a sharper onset than the Morlet wavelet and a wider passband, for
+    /// locating transients.
+    Paul,
+}
+
+impl ContinuousWavelet {
+    pub const ALL: [ContinuousWavelet; 3] = [Self::Morlet, Self::MexicanHat, Self::Paul];
+
+    /// Looks up a wavelet by its lowercase name (e.g. `"mexican_hat"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|wavelet| wavelet.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Morlet => "morlet",
+            Self::MexicanHat => "mexican_hat",
+            Self::Paul => "paul",
+        }
+    }
+
+    /// Returns whether the wavelet is complex, with no response to negative frequencies, so the
+    /// magnitude of its coefficients is a smooth envelope. The coefficients of real wavelets
+    /// oscillate with the signal.
+    pub fn is_analytic(&self) -> bool {
+        !matches!(self, Self::MexicanHat)
+    }
+
+    /// Returns the response of the wavelet at `ratio` times the center frequency of its scale,
+    /// which peaks at 1 for a ratio of 1. Analytic wavelets respond to positive frequencies only,
+    /// real ones symmetrically.
+    pub fn response(&self, ratio: f32) -> f32 {
+        if ratio <= 0.0 && self.is_analytic() {
+            return 0.0;
+        }
+        match self {
+            Self::Morlet => {
+                let offset = MORLET_OMEGA0 * (ratio - 1.0);
+                math::expf(-0.5 * offset * offset)
+            }
+            Self::MexicanHat => ratio * ratio * math::expf(1.0 - ratio * ratio),
+            Self::Paul => {
+                let order = PAUL_ORDER as f32;
+                math::powf(ratio, order) * math::expf(order * (1.0 - ratio))
+            }
+        }
+    }
+}
+
+/// Returns `scales` center frequencies spaced logarithmically from `min_hz` to `max_hz`,
+/// ascending, for a constant-Q continuous wavelet transform.
+pub fn cwt_frequencies_hz(scales: usize, min_hz: f32, max_hz: f32) -> Vec<f32> {
+    (0..scales)
+        .map(|scale| cwt_frequency_hz(scale, scales, min_hz, max_hz))
+        .collect()
+}
+
+/// Returns entry `scale` of [`cwt_frequencies_hz`] without allocating.
+pub fn cwt_frequency_hz(scale: usize, scales: usize, min_hz: f32, max_hz: f32) -> f32 {
+    let min_hz = min_hz.max(f32::MIN_POSITIVE);
+    let max_hz = max_hz.max(min_hz);
+    let position = if scales > 1 {
+        scale as f32 / (scales - 1) as f32
+    } else {
+        0.0
+    };
+    min_hz * math::powf(max_hz / min_hz, position)
+}
+
+/// An orthogonal wavelet of the discrete wavelet transform, see the [module docs](self).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiscreteWavelet {
+    /// Two taps: the sharpest in time, but with the most leakage between levels.
+    #[default]
+    Haar,
+    /// The Daubechies wavelet of four taps (two vanishing moments).
+    Daubechies2,
+    /// The Daubechies wavelet of eight taps (four vanishing moments), for smoother signals.
+    Daubechies4,
+}
+
+impl DiscreteWavelet {
+    pub const ALL: [DiscreteWavelet; 3] = [Self::Haar, Self::Daubechies2, Self::Daubechies4];
+
+    /// Looks up a wavelet by its lowercase name (`"haar"`, `"db2"` or `"db4"`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|wavelet| wavelet.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Haar => "haar",
+            Self::Daubechies2 => "db2",
+            Self::Daubechies4 => "db4",
+        }
+    }
+
+    /// Returns the lowpass (scaling) filter, whose taps sum to `√2`. The highpass filter is its
+    /// quadrature mirror.
+    pub fn lowpass(&self) -> &'static [f32] {
+        match self {
+            Self::Haar => &[
+                core::f32::consts::FRAC_1_SQRT_2,
+                core::f32::consts::FRAC_1_SQRT_2,
+            ],
+            Self::Daubechies2 => &[0.482_962_9, 0.836_516_3, 0.224_143_87, -0.129_409_52],
+            Self::Daubechies4 => &[
+                0.230_377_81,
+                0.714_846_6,
+                0.630_880_8,
+                -0.027_983_77,
+                -0.187_034_81,
+                0.030_841_38,
+                0.032_883_01,
+                -0.010_597_4,
+            ],
+        }
+    }
+
+    /// Returns tap `index` of the highpass (wavelet) filter.
+    fn highpass(&self, index: usize) -> f32 {
+        let lowpass = self.lowpass();
+        let tap = lowpass[lowpass.len() - 1 - index];
+        if index.is_multiple_of(2) { tap } else { -tap }
+    }
+}
+
+/// Returns the number of levels a DWT of `length` samples can be decomposed into, halving the
+/// length each time as long as it's even.
+pub fn max_dwt_levels(length: usize) -> usize {
+    if length == 0 {
+        0
+    } else {
+        length.trailing_zeros() as usize
+    }
+}
+
+/// Splits `input` (of even length) into its `approximation` and `detail` coefficients, each half
+/// as long, with the filters of `wavelet`.
+pub fn dwt_step(
+    wavelet: DiscreteWavelet,
+    input: &[f32],
+    approximation: &mut [f32],
+    detail: &mut [f32],
+) {
+    let length = input.len();
+    let lowpass = wavelet.lowpass();
+    for (k, (approximation, detail)) in approximation
+        .iter_mut()
+        .zip(detail.iter_mut())
+        .enumerate()
+        .take(length / 2)
+    {
+        let (mut low, mut high) = (0.0, 0.0);
+        for (tap, &h) in lowpass.iter().enumerate() {
+            let x = input[(2 * k + tap) % length];
+            low += h * x;
+            high += wavelet.highpass(tap) * x;
+        }
+        *approximation = low;
+        *detail = high;
+    }
+}
+
+/// Merges `approximation` and `detail` coefficients back into `output` (twice as long), the
+/// inverse of [`dwt_step`].
+pub fn idwt_step(
+    wavelet: DiscreteWavelet,
+    approximation: &[f32],
+    detail: &[f32],
+    output: &mut [f32],
+) {
+    let length = output.len();
+    output.fill(0.0);
+    let lowpass = wavelet.lowpass();
+    for (k, (&approximation, &detail)) in approximation
+        .iter()
+        .zip(detail)
+        .enumerate()
+        .take(length / 2)
+    {
+        for (tap, &h) in lowpass.iter().enumerate() {
+            output[(2 * k + tap) % length] += h * approximation + wavelet.highpass(tap) * detail;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continuous_wavelets_peak_at_their_center_frequency() {
+        for wavelet in ContinuousWavelet::ALL {
+            assert!((wavelet.response(1.0) - 1.0).abs() < 1e-6, "{wavelet:?}");
+            assert!(wavelet.response(0.8) < 1.0 && wavelet.response(1.25) < 1.0);
+        }
+        assert_eq!(ContinuousWavelet::Morlet.response(-1.0), 0.0);
+        assert_eq!(ContinuousWavelet::MexicanHat.response(-1.0), 1.0);
+    }
+
+    #[test]
+    fn dwt_step_is_orthogonal_and_inverted_by_idwt_step() {
+        let input: Vec<f32> = (0..64)
+            .map(|i| (i as f32 * 0.3).sin() + 0.25 * (i as f32 * 2.1).cos())
+            .collect();
+        for wavelet in DiscreteWavelet::ALL {
+            let mut approximation = [0.0; 32];
+            let mut detail = [0.0; 32];
+            dwt_step(wavelet, &input, &mut approximation, &mut detail);
+
+            let energy = |values: &[f32]| values.iter().map(|x| x * x).sum::<f32>();
+            let split = energy(&approximation) + energy(&detail);
+            assert!((split - energy(&input)).abs() < 1e-3, "{wavelet:?}");
+
+            let mut output = [0.0; 64];
+            idwt_step(wavelet, &approximation, &detail, &mut output);
+            for (output, input) in output.iter().zip(&input) {
+                assert!(
+                    (output - input).abs() < 1e-5,
+                    "{wavelet:?}: {output} != {input}"
+                );
+            }
+        }
+    }
+}
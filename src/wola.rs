@@ -0,0 +1,330 @@
+//! Weighted overlap-add (WOLA) filterbanks, an alternative front and back end to the windowed
+//! STFT for subband processing.
+//!
+//! A WOLA filterbank analyzes frames longer than its FFT: `taps * fft_length` samples are
+//! weighted by a lowpass prototype filter and folded (time-aliased) into a single FFT frame.
+//! Resynthesized frames are extended periodically to the same length, weighted by a synthesis
+//! prototype and overlap-added. The long prototype gives every bin a narrower band with far
+//! steeper skirts than a window of one FFT length, so processing a band on its own (e.g. with
+//! subband dynamics or a codec's quantization) leaks much less into its neighbours.
+//!
+//! [`WolaPrototype::new`] designs the prototypes, and graphs use them with
+//! [`StftEngine::Wola`](crate::graph::StftEngine::Wola).
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    FftError,
+    core::{WindowFunction, math},
+};
+
+/// The largest number of FFT lengths a prototype can span.
+pub const WOLA_MAX_TAPS: usize = 8;
+
+/// Returns a Hann-windowed root-raised-cosine lowpass of `taps` FFT lengths (clamped to
+/// `1..=WOLA_MAX_TAPS`), a prototype whose bands each span two bins and cross over halfway
+/// between them.
+///
+/// The squared response of neighbouring bands sums to a constant, so the filterbank is close to
+/// reconstructing its input on its own, and more so the more taps it has.
+pub fn root_raised_cosine(fft_length: usize, taps: usize) -> Vec<f32> {
+    let length = fft_length * taps.clamp(1, WOLA_MAX_TAPS);
+    let mut prototype = WindowFunction::Hann.generate(length);
+    let center = length as f64 / 2.0;
+    for (index, x) in prototype.iter_mut().enumerate() {
+        // in symbol periods of one FFT length, with a rolloff of 1
+        let t = (index as f64 - center) / fft_length as f64;
+        let denominator = 1.0 - 16.0 * t * t;
+        let y = if denominator.abs() < 1e-9 {
+            // the limit at t = ±1/4
+            core::f64::consts::FRAC_PI_4
+        } else {
+            math::cos(2.0 * core::f64::consts::PI * t) / denominator
+        };
+        *x *= y as f32;
+    }
+    prototype
+}
+
+/// A pair of analysis and synthesis prototype filters for a WOLA filterbank, see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct WolaPrototype {
+    fft_length: usize,
+    hop_length: usize,
+    analysis: Vec<f32>,
+    synthesis: Vec<f32>,
+}
+
+impl WolaPrototype {
+    /// Designs prototypes of `taps` FFT lengths for frames taken every `hop_length` samples,
+    /// analyzing with a [`root_raised_cosine`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fft_length` is zero, see [`from_analysis`](Self::from_analysis).
+    pub fn new(fft_length: usize, hop_length: usize, taps: usize) -> Self {
+        Self::from_analysis(root_raised_cosine(fft_length, taps), fft_length, hop_length)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Uses a custom analysis prototype, whose length is rounded down to a multiple of
+    /// `fft_length`, and solves for the synthesis prototype closest to it that reconstructs the
+    /// input.
+    ///
+    /// Reconstruction is exact (up to rounding) for hops of up to half the FFT length; with
+    /// longer hops there aren't enough overlapping frames, and the synthesis prototype is left
+    /// the same as the analysis one. The analysis prototype is scaled like the graph's windows,
+    /// so spectra read the same levels as with the windowed STFT.
+    ///
+    /// Fails with [`FftError::InvalidStft`] if `fft_length` is zero or the prototype is shorter
+    /// than it.
+    pub fn from_analysis(
+        mut analysis: Vec<f32>,
+        fft_length: usize,
+        hop_length: usize,
+    ) -> Result<Self, FftError> {
+        let invalid = |reason| FftError::InvalidStft {
+            fft_length,
+            hop_length,
+            reason,
+        };
+        if fft_length == 0 {
+            return Err(invalid("the FFT length is zero"));
+        }
+        if analysis.len() < fft_length {
+            return Err(invalid("the WOLA prototype is shorter than the FFT"));
+        }
+        let hop_length = hop_length.clamp(1, fft_length);
+        analysis.truncate(analysis.len() / fft_length * fft_length);
+
+        // like `stft_window`, so the squares overlap-add to 1 / fft_length on average
+        let energy: f32 = analysis.iter().map(|x| x * x).sum();
+        let scale = math::sqrtf(energy * fft_length as f32 / hop_length as f32);
+        if scale > 0.0 {
+            analysis.iter_mut().for_each(|x| *x /= scale);
+        }
+
+        let synthesis = if hop_length <= fft_length / 2 {
+            solve_synthesis(&analysis, fft_length, hop_length)
+        } else {
+            analysis.clone()
+        };
+        Ok(Self {
+            fft_length,
+            hop_length,
+            analysis,
+            synthesis,
+        })
+    }
+
+    pub fn fft_length(&self) -> usize {
+        self.fft_length
+    }
+
+    pub fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+
+    /// Returns the number of FFT lengths the prototypes span.
+    pub fn taps(&self) -> usize {
+        self.analysis.len() / self.fft_length
+    }
+
+    /// Returns the number of samples every frame is analyzed from.
+    pub fn frame_length(&self) -> usize {
+        self.analysis.len()
+    }
+
+    pub fn analysis(&self) -> &[f32] {
+        &self.analysis
+    }
+
+    pub fn synthesis(&self) -> &[f32] {
+        &self.synthesis
+    }
+
+    /// Returns the largest deviation of the filterbank from reconstructing its input, as a
+    /// linear gain summed over the distortion and every aliased copy of the input.
+    pub fn reconstruction_error(&self) -> f32 {
+        (0..self.hop_length)
+            .map(|residue| {
+                let mut error = 0.0;
+                for fold in self.folds() {
+                    let gain = fold_gain(
+                        &self.analysis,
+                        &self.synthesis,
+                        self.fft_length,
+                        self.hop_length,
+                        residue,
+                        fold,
+                    );
+                    error += if fold == 0 {
+                        (gain - 1.0).abs()
+                    } else {
+                        gain.abs()
+                    };
+                }
+                error as f32
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns the largest response of a band at least `bins` bins from its center, in decibels
+    /// relative to its center, e.g. how much a band lets through of its second neighbour at
+    /// `bins = 2`.
+    ///
+    /// Evaluates the analysis prototype at eight frequencies per bin, so it takes time
+    /// proportional to `taps * fft_length²`.
+    pub fn rejection_db(&self, bins: f32) -> f32 {
+        let response = |frequency: f64| {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (index, &x) in self.analysis.iter().enumerate() {
+                let phase = 2.0 * core::f64::consts::PI * frequency * index as f64;
+                re += x as f64 * math::cos(phase);
+                im -= x as f64 * math::sin(phase);
+            }
+            re * re + im * im
+        };
+        let center = response(0.0);
+        let steps = self.fft_length * 4;
+        let start = (bins.max(0.0) as f64 * 8.0) as usize;
+        let peak = (start..=steps)
+            .map(|step| response(step as f64 / (8 * self.fft_length) as f64))
+            .fold(0.0, f64::max);
+        10.0 * math::log10f((peak / center) as f32)
+    }
+
+    /// Returns the offsets, in FFT lengths, at which a frame's samples fold onto each other.
+    fn folds(&self) -> core::ops::RangeInclusive<isize> {
+        let taps = self.taps() as isize;
+        -(taps - 1)..=taps - 1
+    }
+}
+
+/// Returns `fft_length` times the sum of `synthesis[m] * analysis[m + fold * fft_length]` over
+/// the positions `m` of every frame overlapping an output sample `residue` samples into a hop.
+fn fold_gain(
+    analysis: &[f32],
+    synthesis: &[f32],
+    fft_length: usize,
+    hop_length: usize,
+    residue: usize,
+    fold: isize,
+) -> f64 {
+    let offset = fold * fft_length as isize;
+    let sum: f64 = (residue..synthesis.len())
+        .step_by(hop_length)
+        .filter_map(|m| {
+            let other = usize::try_from(m as isize + offset).ok()?;
+            Some(synthesis[m] as f64 * *analysis.get(other)? as f64)
+        })
+        .sum();
+    sum * fft_length as f64
+}
+
+/// Finds the synthesis prototype closest to `analysis` for which every output sample gets a
+/// gain of 1 from its input sample and 0 from the samples folded onto it.
+///
+/// The conditions for the samples of the synthesis prototype a hop apart are linear and
+/// independent of the others, so each such set is corrected separately by the least change
+/// meeting them.
+fn solve_synthesis(analysis: &[f32], fft_length: usize, hop_length: usize) -> Vec<f32> {
+    let taps = (analysis.len() / fft_length) as isize;
+    let folds: Vec<isize> = (-(taps - 1)..=taps - 1).collect();
+    let mut synthesis = analysis.to_vec();
+
+    for residue in 0..hop_length.min(analysis.len()) {
+        let positions: Vec<usize> = (residue..analysis.len()).step_by(hop_length).collect();
+        // one row per fold, giving its gain as a function of the synthesis samples
+        let rows: Vec<Vec<f64>> = folds
+            .iter()
+            .map(|&fold| {
+                positions
+                    .iter()
+                    .map(|&m| {
+                        let other = m as isize + fold * fft_length as isize;
+                        usize::try_from(other)
+                            .ok()
+                            .and_then(|other| analysis.get(other))
+                            .map_or(0.0, |&x| x as f64 * fft_length as f64)
+                    })
+                    .collect()
+            })
+            .collect();
+        let residual: Vec<f64> = folds
+            .iter()
+            .zip(&rows)
+            .map(|(&fold, row)| {
+                let target = if fold == 0 { 1.0 } else { 0.0 };
+                let gain: f64 = row
+                    .iter()
+                    .zip(&positions)
+                    .map(|(a, &m)| a * analysis[m] as f64)
+                    .sum();
+                target - gain
+            })
+            .collect();
+
+        // the least change is `rows^T * y` with `rows * rows^T * y = residual`
+        let mut gram: Vec<Vec<f64>> = rows
+            .iter()
+            .map(|a| {
+                rows.iter()
+                    .map(|b| a.iter().zip(b).map(|(a, b)| a * b).sum())
+                    .collect()
+            })
+            .collect();
+        let y = solve(&mut gram, residual);
+        for (k, &m) in positions.iter().enumerate() {
+            let change: f64 = rows.iter().zip(&y).map(|(row, y)| row[k] * y).sum();
+            synthesis[m] += change as f32;
+        }
+    }
+
+    synthesis
+}
+
+/// Solves `matrix * x = rhs` by Gaussian elimination with partial pivoting, leaving out the
+/// equations that depend on the others.
+fn solve(matrix: &mut [Vec<f64>], mut rhs: Vec<f64>) -> Vec<f64> {
+    let n = rhs.len();
+    let scale = (0..n).map(|i| matrix[i][i].abs()).fold(0.0, f64::max);
+    let mut pivots = vec![false; n];
+    for col in 0..n {
+        let Some(pivot) =
+            (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))
+        else {
+            break;
+        };
+        if matrix[pivot][col].abs() <= scale * 1e-12 {
+            continue;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+        pivots[col] = true;
+        let pivot_row = matrix[col].clone();
+        for row in 0..n {
+            if row == col {
+                continue;
+            }
+            let factor = matrix[row][col] / pivot_row[col];
+            if factor == 0.0 {
+                continue;
+            }
+            for (x, pivot) in matrix[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+    (0..n)
+        .map(|i| {
+            if pivots[i] {
+                rhs[i] / matrix[i][i]
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,66 @@
+//! The output of a graph must not depend on how the host splits its input into blocks.
+
+use raug_fft::{prelude::*, test_util::assert_block_size_independent};
+
+const FFT_LENGTHS: [usize; 2] = [256, 1024];
+
+/// Builtins from spectrum to spectrum that run with their default parameters.
+const SPECTRAL_BUILTINS: [&str; 7] = [
+    "declip",
+    "hum_remove",
+    "linear_phase_eq",
+    "spectral_tilt",
+    "exciter",
+    "freq_shift",
+    "spectral_limiter",
+];
+
+fn graph(fft_length: usize) -> Box<dyn DynFftGraph> {
+    dyn_graph(fft_length, fft_length / 4, WindowFunction::Hann).unwrap()
+}
+
+#[test]
+fn identity_is_block_size_independent() {
+    for fft_length in FFT_LENGTHS {
+        let mut graph = graph(fft_length);
+        let input = graph.add_audio_input();
+        let output = graph.add_audio_output();
+        graph.connect(input, 0, output, 0).unwrap();
+
+        assert_block_size_independent(&mut *graph);
+    }
+}
+
+#[test]
+fn builtins_are_block_size_independent() {
+    for fft_length in FFT_LENGTHS {
+        for name in SPECTRAL_BUILTINS {
+            let mut graph = graph(fft_length);
+            let input = graph.add_audio_input();
+            let processor = graph
+                .add_builtin(name, &ProcessorParams::new())
+                .unwrap_or_else(|| panic!("no builtin named {name}"));
+            let output = graph.add_audio_output();
+            graph.connect(input, 0, processor, 0).unwrap();
+            graph.connect(processor, 0, output, 0).unwrap();
+
+            assert_block_size_independent(&mut *graph);
+        }
+    }
+}
+
+#[test]
+fn sidechained_graphs_are_block_size_independent() {
+    let mut graph = graph(512);
+    let input = graph.add_audio_input();
+    let sidechain = graph.add_sidechain_input();
+    let duck = graph
+        .add_builtin("spectral_duck", &ProcessorParams::new())
+        .unwrap();
+    let output = graph.add_audio_output();
+    graph.connect(input, 0, duck, 0).unwrap();
+    graph.connect(sidechain, 0, duck, 1).unwrap();
+    graph.connect(duck, 0, output, 0).unwrap();
+
+    assert_block_size_independent(&mut *graph);
+}
@@ -0,0 +1,66 @@
+//! The C API returns errors instead of panicking across the FFI boundary.
+
+use std::ptr;
+
+use raug_fft::capi::*;
+
+#[test]
+fn rejects_invalid_stft_settings() {
+    // SAFETY: a null window selects the default
+    unsafe {
+        assert!(raug_fft_graph_new(1024, 0, ptr::null()).is_null());
+        assert!(raug_fft_graph_new(1024, 2048, ptr::null()).is_null());
+        assert!(raug_fft_graph_new(1000, 250, ptr::null()).is_null());
+        assert!(raug_fft_graph_new(1024, 256, c"no_such_window".as_ptr()).is_null());
+    }
+}
+
+#[test]
+fn processes_a_valid_graph() {
+    // SAFETY: the graph is live until it's freed at the end, and the buffers hold one channel of
+    // `BLOCK_SIZE` frames
+    unsafe {
+        const BLOCK_SIZE: usize = 64;
+        let graph = raug_fft_graph_new(1024, 1024, c"hann".as_ptr());
+        assert!(!graph.is_null());
+        let (mut input, mut output) = (0, 0);
+        assert_eq!(
+            raug_fft_graph_add_audio_input(graph, &mut input),
+            RaugFftStatus::Ok
+        );
+        assert_eq!(
+            raug_fft_graph_add_audio_output(graph, &mut output),
+            RaugFftStatus::Ok
+        );
+        assert_eq!(
+            raug_fft_graph_connect(graph, input, 0, output, 0),
+            RaugFftStatus::Ok
+        );
+        assert_eq!(
+            raug_fft_graph_allocate(graph, 48_000.0, BLOCK_SIZE),
+            RaugFftStatus::Ok
+        );
+
+        let samples = [0.5; BLOCK_SIZE];
+        let mut out = [0.0; BLOCK_SIZE];
+        assert_eq!(
+            raug_fft_graph_process_interleaved(
+                graph,
+                samples.as_ptr(),
+                out.as_mut_ptr(),
+                BLOCK_SIZE
+            ),
+            RaugFftStatus::Ok
+        );
+        assert_eq!(
+            raug_fft_graph_process_interleaved(
+                graph,
+                samples.as_ptr(),
+                out.as_mut_ptr(),
+                BLOCK_SIZE + 1
+            ),
+            RaugFftStatus::BlockTooLarge
+        );
+        raug_fft_graph_free(graph);
+    }
+}
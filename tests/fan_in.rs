@@ -0,0 +1,32 @@
+//! Nodes can have any number of inputs.
+
+use raug_fft::{
+    builtins::routing::SpectralMix,
+    prelude::*,
+    test_util::{DEFAULT_TOLERANCE, reconstruction_error},
+};
+
+#[test]
+fn mixes_64_inputs() {
+    const INPUTS: usize = 64;
+
+    let mut graph = FftGraph::<Fft1024>::new(256, WindowFunction::Hann);
+    let input = graph.add_audio_input();
+    // with a gain input per spectrum, the mixer has 128 inputs
+    let mix = graph.add_processor(SpectralMix::<Fft1024>::with_gains(vec![
+        1.0 / INPUTS as f32;
+        INPUTS
+    ]));
+    let output = graph.add_audio_output();
+    for port in 0..INPUTS as u32 {
+        DynFftGraph::connect(&mut graph, input, 0, mix, port).unwrap();
+    }
+    DynFftGraph::connect(&mut graph, mix, 0, output, 0).unwrap();
+
+    // the copies only sum back to the input if every one of them was mixed in
+    let error = reconstruction_error(&mut graph).unwrap();
+    assert!(
+        error.max <= DEFAULT_TOLERANCE,
+        "reconstruction error {error:?} exceeds {DEFAULT_TOLERANCE}"
+    );
+}
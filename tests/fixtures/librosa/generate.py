@@ -0,0 +1,206 @@
+"""Generates the fixtures `LibrosaStft` is tested against.
+
+    python3 generate.py [--backend librosa|reference]
+
+The `librosa` backend calls librosa and numpy themselves and is the one to trust. The
+`reference` backend follows the same steps in plain Python, in double precision, for machines
+without them: the periodic scipy window centered in `n_fft` samples, `numpy.pad` of `n_fft // 2`
+samples for centered frames, unnormalized real FFTs of the windowed frames, and an inverse that
+overlap-adds the windowed inverse FFTs and divides by the overlap-added squared window wherever
+it exceeds the smallest normal float32. Every file records the backend that wrote it.
+"""
+
+import argparse
+import cmath
+import math
+import os
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+TINY = 1.1754944e-38
+
+
+def test_signal(length):
+    # two sines and deterministic noise, so no bin is empty
+    signal = []
+    state = 12345
+    for n in range(length):
+        state = (state * 1103515245 + 12345) % 2**31
+        noise = state / 2**30 - 1.0
+        signal.append(
+            0.6 * math.sin(2 * math.pi * 0.037 * n)
+            + 0.3 * math.sin(2 * math.pi * 0.21 * n + 0.5)
+            + 0.1 * noise
+        )
+    return signal
+
+
+class Reference:
+    name = "reference"
+
+    @staticmethod
+    def window(window, win_length, n_fft):
+        coefficients = {"hann": (0.5, 0.5), "hamming": (0.54, 0.46)}[window]
+        a, b = coefficients
+        periodic = [a - b * math.cos(2 * math.pi * n / win_length) for n in range(win_length)]
+        offset = (n_fft - win_length) // 2
+        return [0.0] * offset + periodic + [0.0] * (n_fft - win_length - offset)
+
+    @staticmethod
+    def pad(signal, padding, mode):
+        if mode == "constant":
+            return [0.0] * padding + signal + [0.0] * padding
+        if mode == "edge":
+            return [signal[0]] * padding + signal + [signal[-1]] * padding
+        if mode == "reflect":
+            return signal[padding:0:-1] + signal + signal[-2 : -2 - padding : -1]
+        raise ValueError(mode)
+
+    @classmethod
+    def stft(cls, signal, n_fft, hop_length, win_length, window, center, pad_mode):
+        w = cls.window(window, win_length, n_fft)
+        if center:
+            signal = cls.pad(signal, n_fft // 2, pad_mode)
+        frames = 1 + (len(signal) - n_fft) // hop_length
+        spectra = []
+        for t in range(frames):
+            frame = [x * w[n] for n, x in enumerate(signal[t * hop_length : t * hop_length + n_fft])]
+            spectra.append(
+                [
+                    sum(x * cmath.exp(-2j * math.pi * k * n / n_fft) for n, x in enumerate(frame))
+                    for k in range(n_fft // 2 + 1)
+                ]
+            )
+        return spectra
+
+    @classmethod
+    def istft(cls, spectra, n_fft, hop_length, win_length, window, center, length):
+        w = cls.window(window, win_length, n_fft)
+        total = n_fft + hop_length * (len(spectra) - 1)
+        signal = [0.0] * total
+        window_sum = [0.0] * total
+        for t, spectrum in enumerate(spectra):
+            for n in range(n_fft):
+                # the inverse real FFT, ignoring the imaginary parts at DC and Nyquist
+                x = spectrum[0].real + spectrum[-1].real * (-1) ** n
+                for k in range(1, n_fft // 2):
+                    x += 2 * (spectrum[k] * cmath.exp(2j * math.pi * k * n / n_fft)).real
+                signal[t * hop_length + n] += x / n_fft * w[n]
+                window_sum[t * hop_length + n] += w[n] * w[n]
+        signal = [x / s if s > TINY else x for x, s in zip(signal, window_sum)]
+        start = n_fft // 2 if center else 0
+        if length is None:
+            return signal[start : len(signal) - start]
+        signal = signal[start : start + length]
+        return signal + [0.0] * (length - len(signal))
+
+
+class Librosa:
+    name = "librosa"
+
+    def __init__(self):
+        import librosa
+        import numpy
+
+        self.librosa = librosa
+        self.np = numpy
+
+    def window(self, window, win_length, n_fft):
+        w = self.librosa.filters.get_window(window, win_length, fftbins=True)
+        return list(self.librosa.util.pad_center(w, size=n_fft))
+
+    def pad(self, signal, padding, mode):
+        return list(self.np.pad(self.np.asarray(signal, dtype=self.np.float32), padding, mode=mode))
+
+    def stft(self, signal, n_fft, hop_length, win_length, window, center, pad_mode):
+        spectra = self.librosa.stft(
+            self.np.asarray(signal, dtype=self.np.float32),
+            n_fft=n_fft,
+            hop_length=hop_length,
+            win_length=win_length,
+            window=window,
+            center=center,
+            pad_mode=pad_mode,
+        )
+        return [list(frame) for frame in spectra.T]
+
+    def istft(self, spectra, n_fft, hop_length, win_length, window, center, length):
+        signal = self.librosa.istft(
+            self.np.asarray(spectra, dtype=self.np.complex64).T,
+            n_fft=n_fft,
+            hop_length=hop_length,
+            win_length=win_length,
+            window=window,
+            center=center,
+            length=length,
+        )
+        return list(signal)
+
+
+def write(backend, name, call, rows):
+    with open(os.path.join(HERE, name + ".txt"), "w") as file:
+        file.write(f"# {call}\n# backend: {backend.name}\n")
+        for row in rows:
+            values = []
+            for x in row:
+                if isinstance(x, complex) or type(x).__name__.startswith("complex"):
+                    values += [complex(x).real, complex(x).imag]
+                else:
+                    values.append(float(x))
+            file.write(" ".join(f"{x:.9g}" for x in values) + "\n")
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--backend", choices=["librosa", "reference"], default="librosa")
+    backend = Librosa() if parser.parse_args().backend == "librosa" else Reference()
+
+    signal = [float(f"{x:.9g}") for x in test_signal(200)]
+    write(backend, "signal", "the test signal, 200 samples", [signal])
+
+    for window, win_length in [("hann", 48), ("hamming", 64)]:
+        write(
+            backend,
+            f"window_{window}_64_{win_length}",
+            f"pad_center(get_window({window!r}, {win_length}, fftbins=True), size=64)",
+            [backend.window(window, win_length, 64)],
+        )
+
+    for mode in ["constant", "reflect", "edge"]:
+        write(
+            backend,
+            f"pad_{mode}_32",
+            f"numpy.pad(y, 32, mode={mode!r})",
+            [backend.pad(signal, 32, mode)],
+        )
+
+    cases = [
+        ("hann", 64, 16, 64, True, "constant"),
+        ("hann", 64, 16, 48, True, "reflect"),
+        ("hamming", 64, 20, 64, True, "edge"),
+        ("hann", 64, 16, 64, False, "constant"),
+    ]
+    for window, n_fft, hop_length, win_length, center, pad_mode in cases:
+        suffix = f"{window}_{n_fft}_{hop_length}_{win_length}_" + (pad_mode if center else "nocenter")
+        args = (n_fft, hop_length, win_length, window, center)
+        spectra = backend.stft(signal, *args[:4], center, pad_mode)
+        write(
+            backend,
+            "stft_" + suffix,
+            f"librosa.stft(y, n_fft={n_fft}, hop_length={hop_length}, win_length={win_length}, "
+            f"window={window!r}, center={center}, pad_mode={pad_mode!r}).T",
+            spectra,
+        )
+        # resynthesized from the spectra as stored, to the signal's length if centered
+        stored = [[complex(float(f"{x.real:.9g}"), float(f"{x.imag:.9g}")) for x in frame] for frame in spectra]
+        length = len(signal) if center else None
+        write(
+            backend,
+            "istft_" + suffix,
+            f"librosa.istft(S.T, n_fft={n_fft}, hop_length={hop_length}, "
+            f"win_length={win_length}, window={window!r}, center={center}, length={length})",
+            [backend.istft(stored, *args, length)],
+        )
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,35 @@
+//! Spectral regression checks of the builtins against the golden files in `tests/golden`.
+//!
+//! Run with `RAUG_FFT_BLESS=1` to rewrite the golden files after an intended change in output.
+
+use std::path::Path;
+
+use raug_fft::test_util::golden::{Fixture, GoldenCase};
+
+const BUILTINS: [&str; 7] = [
+    "declip",
+    "hum_remove",
+    "linear_phase_eq",
+    "spectral_tilt",
+    "exciter",
+    "freq_shift",
+    "spectral_limiter",
+];
+
+const TOLERANCE_DB: f32 = 0.1;
+
+#[test]
+fn builtins_match_golden_spectra() {
+    let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"));
+    let failures: Vec<String> = BUILTINS
+        .into_iter()
+        .flat_map(|name| Fixture::ALL.map(|fixture| GoldenCase::new(name, fixture)))
+        .filter_map(|case| {
+            case.check(dir, TOLERANCE_DB)
+                .err()
+                .map(|e| format!("{}: {e}", case.file_name()))
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
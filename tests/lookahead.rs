@@ -0,0 +1,68 @@
+//! The graph's look-ahead is taken up by the processors that look ahead, and reported as latency.
+
+use raug_fft::{
+    builtins::{dynamics::SpectralDuck, restoration::Declip},
+    prelude::*,
+};
+
+const HOP_LENGTH: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+fn sine() -> Vec<f32> {
+    (0..1024 * 32)
+        .map(|i| 0.5 * (core::f32::consts::TAU * 440.0 * i as f32 / SAMPLE_RATE).sin())
+        .collect()
+}
+
+fn assert_aligned(graph: &mut FftGraph<Fft1024>) {
+    let input = sine();
+    let output = graph.render(&[&input], SAMPLE_RATE, HOP_LENGTH).unwrap();
+    let range = 1024..input.len() - 1024;
+    let error = range
+        .map(|i| (output[0][i] - input[i]).abs())
+        .fold(0.0, f32::max);
+    assert!(error < 1e-3, "the output is off by up to {error}");
+}
+
+#[test]
+fn declip_looks_ahead_by_the_graph_lookahead() {
+    let mut graph = FftGraph::<Fft1024>::new(HOP_LENGTH, WindowFunction::Hann);
+    let input = graph.add_audio_input();
+    let mut declip = Declip::<Fft1024>::new(2);
+    // the onset of the sine would be detected as clipping
+    declip.detect = false;
+    let declip = graph.add_processor(declip);
+    let output = graph.add_audio_output();
+    DynFftGraph::connect(&mut graph, input, 0, declip, 0).unwrap();
+    DynFftGraph::connect(&mut graph, declip, 0, output, 0).unwrap();
+    let stft_latency = graph.latency_samples() - 2 * HOP_LENGTH;
+
+    // less than the declipper's own look-ahead changes nothing
+    graph.set_lookahead_frames(1);
+    assert_eq!(graph.latency_samples(), stft_latency + 2 * HOP_LENGTH);
+
+    graph.set_lookahead_frames(5);
+    assert_eq!(graph.lookahead_frames(), 5);
+    assert_eq!(graph.latency_samples(), stft_latency + 5 * HOP_LENGTH);
+    assert_aligned(&mut graph);
+}
+
+#[test]
+fn processors_added_later_look_ahead_too() {
+    let mut graph = FftGraph::<Fft1024>::new(HOP_LENGTH, WindowFunction::Hann);
+    graph.set_lookahead_frames(3);
+    let input = graph.add_audio_input();
+    let sidechain = graph.add_sidechain_input();
+    let mut duck = SpectralDuck::<Fft1024>::default();
+    duck.set_lookahead_frames(1);
+    let duck = graph.add_processor(duck);
+    let output = graph.add_audio_output();
+    DynFftGraph::connect(&mut graph, input, 0, duck, 0).unwrap();
+    DynFftGraph::connect(&mut graph, sidechain, 0, duck, 1).unwrap();
+    DynFftGraph::connect(&mut graph, duck, 0, output, 0).unwrap();
+
+    // the duck's own look-ahead of 1 frame applies again once the graph's is lifted
+    let latency = graph.latency_samples();
+    graph.set_lookahead_frames(0);
+    assert_eq!(graph.latency_samples(), latency - 2 * HOP_LENGTH);
+}
@@ -0,0 +1,66 @@
+//! The STFT reconstructs its input, and the [`test_util`](raug_fft::test_util) assertions catch
+//! processors that don't.
+
+use raug_fft::{
+    FftError,
+    builtins::{routing::SpectralMix, util::Probe},
+    core::StftPipeline,
+    prelude::*,
+    test_util::{
+        DEFAULT_TOLERANCE, RECONSTRUCTING_WINDOWS, assert_identity_reconstruction,
+        assert_passthrough, test_hop_lengths, test_signal,
+    },
+};
+
+#[test]
+fn identity_reconstructs_at_every_fft_length() {
+    assert_identity_reconstruction(DEFAULT_TOLERANCE);
+}
+
+#[test]
+fn passthrough_processors_reconstruct() {
+    assert_passthrough::<Fft256, _>(|| SpectralMix::<Fft256>::new(1), DEFAULT_TOLERANCE);
+    assert_passthrough::<Fft1024, _>(|| Probe::<Fft1024>::new(1).0, DEFAULT_TOLERANCE);
+}
+
+#[test]
+#[should_panic(expected = "reconstruction error")]
+fn catches_processors_that_change_the_signal() {
+    assert_passthrough::<Fft256, _>(
+        || SpectralMix::<Fft256>::with_gains(vec![0.5]),
+        DEFAULT_TOLERANCE,
+    );
+}
+
+#[test]
+fn tests_the_largest_reconstructing_hop_and_half_of_it() {
+    assert_eq!(test_hop_lengths(1024, WindowFunction::Hann), [256, 128]);
+    for window_fn in RECONSTRUCTING_WINDOWS {
+        let min_overlap = window_fn.min_overlap().unwrap();
+        assert_eq!(test_hop_lengths(1024, window_fn)[0], 1024 / min_overlap);
+    }
+    assert!(test_hop_lengths(1024, WindowFunction::Triangular).is_empty());
+}
+
+#[test]
+fn stft_pipeline_rejects_invalid_hop_lengths() {
+    for hop_length in [0, 2048] {
+        assert!(matches!(
+            StftPipeline::new(1024, hop_length, WindowFunction::Hann),
+            Err(FftError::InvalidStft { .. })
+        ));
+    }
+}
+
+#[test]
+fn stft_pipeline_writes_the_samples_available() {
+    let mut pipeline = StftPipeline::new(1024, 256, WindowFunction::Hann).unwrap();
+    let input = test_signal(1024);
+    let mut output = vec![1.0; 1024];
+
+    // the first frame finishes one hop, and the rest of the block is zero-filled
+    let read = pipeline.process(&input, &mut output, |_| {}).unwrap();
+    assert_eq!(read, 256);
+    assert!(output[..read].iter().any(|&sample| sample != 0.0));
+    assert!(output[read..].iter().all(|&sample| sample == 0.0));
+}
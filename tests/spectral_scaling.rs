@@ -0,0 +1,79 @@
+//! Every [`SpectralScaling`] scales the spectra the graph's processors see, and resynthesizes the
+//! same audio.
+
+use raug_fft::{
+    builtins::util::Probe,
+    core::{SpectralScaling, stft_window},
+    prelude::*,
+    test_util::{DEFAULT_TOLERANCE, reconstruction_error},
+};
+
+const HOP_LENGTH: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+/// A sine centered on this bin, so no energy leaks between frames of different phase.
+const BIN: usize = 32;
+const AMPLITUDE: f32 = 0.5;
+
+/// Renders a sine through `input -> probe -> output` and returns the magnitude of [`BIN`] in a
+/// spectrum from the middle of the signal.
+fn probed_magnitude(scaling: SpectralScaling) -> f32 {
+    let mut graph = FftGraph::<Fft1024>::new(HOP_LENGTH, WindowFunction::Hann);
+    graph.set_spectral_scaling(scaling);
+    let input = graph.add_audio_input();
+    let (probe, reader) = Probe::<Fft1024>::new(1024);
+    let probe = graph.add_processor(probe);
+    let output = graph.add_audio_output();
+    DynFftGraph::connect(&mut graph, input, 0, probe, 0).unwrap();
+    DynFftGraph::connect(&mut graph, probe, 0, output, 0).unwrap();
+
+    let hz = BIN as f32 * SAMPLE_RATE / 1024.0;
+    let sine: Vec<f32> = (0..1024 * 16)
+        .map(|i| AMPLITUDE * (core::f32::consts::TAU * hz * i as f32 / SAMPLE_RATE).sin())
+        .collect();
+    graph.render(&[&sine], SAMPLE_RATE, HOP_LENGTH).unwrap();
+
+    let mut magnitude = None;
+    reader.drain(|frame| {
+        if frame.hop == 32 {
+            magnitude = Some(frame.spectrum[BIN].norm());
+        }
+    });
+    magnitude.unwrap()
+}
+
+#[test]
+fn spectra_are_scaled_by_the_convention() {
+    let window = stft_window(WindowFunction::Hann, 1024, HOP_LENGTH);
+    let unscaled = probed_magnitude(SpectralScaling::None);
+    for scaling in SpectralScaling::ALL {
+        let expected = unscaled * scaling.factor(&window);
+        let found = probed_magnitude(scaling);
+        assert!(
+            (found - expected).abs() <= 1e-3 * expected,
+            "{scaling:?}: bin {BIN} is {found}, expected {expected}"
+        );
+    }
+
+    let compensated = probed_magnitude(SpectralScaling::WindowCompensated);
+    assert!(
+        (compensated - AMPLITUDE).abs() < 1e-3,
+        "a window-compensated sine reads {compensated}, expected {AMPLITUDE}"
+    );
+}
+
+#[test]
+fn every_scaling_round_trips() {
+    for scaling in SpectralScaling::ALL {
+        let mut graph = FftGraph::<Fft1024>::new(HOP_LENGTH, WindowFunction::Hann);
+        graph.set_spectral_scaling(scaling);
+        let input = graph.add_audio_input();
+        let output = graph.add_audio_output();
+        DynFftGraph::connect(&mut graph, input, 0, output, 0).unwrap();
+
+        let error = reconstruction_error(&mut graph).unwrap();
+        assert!(
+            error.max <= DEFAULT_TOLERANCE,
+            "{scaling:?}: reconstruction error {error:?} exceeds {DEFAULT_TOLERANCE}"
+        );
+    }
+}
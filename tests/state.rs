@@ -0,0 +1,95 @@
+//! A snapshot of a graph restores it to exactly where it was, and only fits the graph it was taken
+//! from.
+
+use raug_fft::{
+    FftError,
+    builtins::{dynamics::SpectralLimiter, restoration::HumRemove},
+    prelude::*,
+    test_util::test_signal,
+};
+
+const BLOCK_SIZE: usize = 100;
+const SAMPLE_RATE: f32 = 48_000.0;
+
+/// Builds `input -> hum_remove -> spectral_limiter -> output`, with `extra` processors after the
+/// limiter, allocated for blocks of [`BLOCK_SIZE`].
+fn build<F: Fft>(hop_length: usize, extra: usize) -> FftGraph<F> {
+    let mut graph = FftGraph::<F>::new(hop_length, WindowFunction::Hann);
+    let input = graph.add_audio_input();
+    let mut previous = graph.add_processor(HumRemove::<F>::default());
+    DynFftGraph::connect(&mut graph, input, 0, previous, 0).unwrap();
+    for _ in 0..=extra {
+        let limiter = graph.add_processor(SpectralLimiter::<F>::default());
+        DynFftGraph::connect(&mut graph, previous, 0, limiter, 0).unwrap();
+        previous = limiter;
+    }
+    let output = graph.add_audio_output();
+    DynFftGraph::connect(&mut graph, previous, 0, output, 0).unwrap();
+    graph.allocate(SAMPLE_RATE, BLOCK_SIZE);
+    graph
+}
+
+/// Processes `input` in blocks of [`BLOCK_SIZE`], returning the output.
+fn process<F: Fft>(graph: &mut FftGraph<F>, input: &[f32]) -> Vec<f32> {
+    let mut output = vec![0.0; input.len()];
+    for (input, output) in input.chunks(BLOCK_SIZE).zip(output.chunks_mut(BLOCK_SIZE)) {
+        graph.process_block(&[input], &mut [output]).unwrap();
+    }
+    output
+}
+
+#[test]
+fn restores_the_graph_to_the_snapshot() {
+    let signal = test_signal(BLOCK_SIZE * 120);
+    let (before, after) = signal.split_at(BLOCK_SIZE * 50);
+    let mut graph = build::<Fft512>(128, 0);
+    process(&mut graph, before);
+
+    let state = graph.save_state();
+    let expected = process(&mut graph, after);
+    assert!(expected.iter().any(|&sample| sample != 0.0));
+
+    // processing something else in between must not leak into the restored output
+    process(&mut graph, &test_signal(BLOCK_SIZE * 7));
+    graph.load_state(&state).unwrap();
+    assert_eq!(process(&mut graph, after), expected);
+
+    // a fresh graph built the same way picks up where the snapshot left off
+    let mut copy = build::<Fft512>(128, 0);
+    copy.load_state(&state).unwrap();
+    assert_eq!(process(&mut copy, after), expected);
+}
+
+#[test]
+fn rejects_snapshots_of_other_graphs() {
+    let signal = test_signal(BLOCK_SIZE * 60);
+    let mut source = build::<Fft512>(128, 0);
+    process(&mut source, &signal);
+    let state = source.save_state();
+
+    // other FFT settings
+    let mut other_hop = build::<Fft512>(256, 0);
+    assert!(matches!(
+        other_hop.load_state(&state),
+        Err(FftError::InvalidState(_))
+    ));
+    let mut other_length = build::<Fft1024>(128, 0);
+    assert!(matches!(
+        other_length.load_state(&state),
+        Err(FftError::InvalidState(_))
+    ));
+
+    // another topology, which must be left as it was
+    let mut other_nodes = build::<Fft512>(128, 1);
+    let mut untouched = build::<Fft512>(128, 1);
+    process(&mut other_nodes, &signal);
+    process(&mut untouched, &signal);
+    assert!(matches!(
+        other_nodes.load_state(&state),
+        Err(FftError::InvalidState(_))
+    ));
+    assert_eq!(
+        process(&mut other_nodes, &signal),
+        process(&mut untouched, &signal)
+    );
+}
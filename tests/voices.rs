@@ -0,0 +1,55 @@
+//! Every voice of an `FftVoices` runs, including voices of generators without audio inputs.
+
+use raug_fft::prelude::*;
+
+/// Outputs a constant DC spectrum, with no inputs.
+struct Dc {
+    level: f32,
+    spectrum: RealFft256,
+}
+
+impl FftProcessor for Dc {
+    fft_processor_io! {
+        inputs {}
+        outputs { output: RealFft256 }
+    }
+
+    fn process(
+        &mut self,
+        _inputs: ProcessorInputs,
+        mut outputs: ProcessorOutputs,
+    ) -> ProcResult<()> {
+        self.spectrum[0] = Complex32::new(self.level, 0.0);
+        outputs.set_output_as::<RealFft256>(0, 0, &self.spectrum)
+    }
+}
+
+#[test]
+fn runs_generator_voices() {
+    let mut level = 0.0;
+    let mut voices = FftGraph::<Fft256>::voices(2, 64, WindowFunction::Hann, |graph| {
+        level += 1.0;
+        let dc = graph.add_processor(Dc {
+            level,
+            spectrum: RealFft256::default(),
+        });
+        let output = graph.add_audio_output();
+        DynFftGraph::connect(graph, dc, 0, output, 0).unwrap();
+    });
+
+    const BLOCK_SIZE: usize = 100;
+    voices.allocate(48_000.0, BLOCK_SIZE);
+    let mut outputs = vec![vec![0.0; BLOCK_SIZE]; 2];
+    for _ in 0..20 {
+        let mut blocks: Vec<&mut [f32]> = outputs.iter_mut().map(Vec::as_mut_slice).collect();
+        voices.process_block(&[], &mut blocks).unwrap();
+    }
+
+    // once the overlap-add has settled, each voice holds its own constant level
+    let [first, second] = [&outputs[0], &outputs[1]].map(|output| output[BLOCK_SIZE - 1]);
+    assert!(first > 0.0, "the first voice is silent");
+    assert!(
+        (second - 2.0 * first).abs() <= 1e-3 * second,
+        "{second} is not twice {first}"
+    );
+}
@@ -0,0 +1,123 @@
+//! The wavelet transforms write one frame of scales per hop, which block processors read as a
+//! scale×time matrix.
+
+use std::sync::{Arc, Mutex};
+
+use raug_fft::{
+    builtins::analysis::{ContinuousWaveletTransform, DiscreteWaveletTransform},
+    prelude::*,
+};
+
+const HOP_LENGTH: usize = 256;
+const SAMPLE_RATE: f32 = 48_000.0;
+const BLOCK_FRAMES: usize = 4;
+
+/// The frame whose block the [`Recorder`] keeps, well inside the input.
+const RECORDED_FRAME: u64 = 32;
+
+/// Keeps the magnitudes of the block ending at [`RECORDED_FRAME`], time×scale, and outputs
+/// silence.
+struct Recorder {
+    frame_index: u64,
+    magnitudes: Arc<Mutex<Vec<Vec<f32>>>>,
+}
+
+impl FftBlockProcessor<Fft1024> for Recorder {
+    fn block_frames(&self) -> usize {
+        BLOCK_FRAMES
+    }
+
+    fn set_frame_info(&mut self, info: &FrameInfo) {
+        self.frame_index = info.frame_index;
+    }
+
+    fn process(
+        &mut self,
+        block: FrameBlock<'_, Fft1024>,
+        output: &mut RealFft1024,
+    ) -> ProcResult<()> {
+        if self.frame_index == RECORDED_FRAME {
+            *self.magnitudes.lock().unwrap() = (0..block.len())
+                .map(|time| {
+                    (0..block.num_bins())
+                        .map(|scale| block.get(time, scale).norm())
+                        .collect()
+                })
+                .collect();
+        }
+        *output = RealFft1024::default();
+        Ok(())
+    }
+}
+
+fn sine(hz: f32) -> Vec<f32> {
+    (0..1024 * 16)
+        .map(|i| 0.5 * (core::f32::consts::TAU * hz * i as f32 / SAMPLE_RATE).sin())
+        .collect()
+}
+
+/// Renders `input` through `transform`, fed with the spectra of an audio input or, if
+/// `time_domain`, with its windowed frames, and returns a block of the frames it wrote,
+/// time×scale.
+fn render(transform: impl FftProcessor, time_domain: bool, input: &[f32]) -> Vec<Vec<f32>> {
+    let mut graph = FftGraph::<Fft1024>::new(HOP_LENGTH, WindowFunction::Hann);
+    let audio_input = graph.add_audio_input();
+    let source = if time_domain {
+        graph.audio_input_frames(audio_input).unwrap()
+    } else {
+        audio_input
+    };
+    let transform = graph.add_processor(transform);
+    let magnitudes = Arc::new(Mutex::new(Vec::new()));
+    let recorder = graph.add_block_processor(Recorder {
+        frame_index: 0,
+        magnitudes: magnitudes.clone(),
+    });
+    let output = graph.add_audio_output();
+    DynFftGraph::connect(&mut graph, source, 0, transform, 0).unwrap();
+    DynFftGraph::connect(&mut graph, transform, 0, recorder, 0).unwrap();
+    DynFftGraph::connect(&mut graph, recorder, 0, output, 0).unwrap();
+
+    graph.render(&[input], SAMPLE_RATE, HOP_LENGTH).unwrap();
+    let magnitudes = magnitudes.lock().unwrap().clone();
+    assert_eq!(magnitudes.len(), BLOCK_FRAMES);
+    magnitudes
+}
+
+fn loudest(column: &[f32]) -> usize {
+    column
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap()
+        .0
+}
+
+#[test]
+fn cwt_peaks_at_the_scale_of_a_sine() {
+    let scales = 16;
+    let frequencies_hz = ContinuousWaveletTransform::<Fft1024>::new(scales).frequencies_hz();
+    for scale in [8, 12, 14] {
+        let cwt = ContinuousWaveletTransform::<Fft1024>::new(scales);
+        let block = render(cwt, false, &sine(frequencies_hz[scale]));
+        for column in &block {
+            assert_eq!(loudest(column), scale, "{column:?}");
+            assert!(column[scale] > 2.0 * column[scale - 1].max(column[scale + 1]));
+            assert!(column[scales..].iter().all(|&magnitude| magnitude == 0.0));
+        }
+    }
+}
+
+#[test]
+fn dwt_peaks_at_the_level_of_a_sine() {
+    // level `j` holds `sample_rate / 2^(j + 1)` to `sample_rate / 2^j`
+    for (hz, level) in [(18_000.0, 1), (4_500.0, 3), (1_000.0, 5)] {
+        let mut dwt = DiscreteWaveletTransform::<Fft1024>::new(6);
+        dwt.wavelet = DiscreteWavelet::Daubechies4;
+        let block = render(dwt, true, &sine(hz));
+        for column in &block {
+            assert_eq!(loudest(column), level - 1, "{hz} Hz: {column:?}");
+            assert!(column[7..].iter().all(|&magnitude| magnitude == 0.0));
+        }
+    }
+}